@@ -0,0 +1,214 @@
+// Analyzer benchmarks against a 400k-trade journal. Dashboard loads are
+// expected to stay under one second at this size: after the criterion runs
+// every cold load is timed again, written to target/bench-data/analysis-400k.json
+// and checked against the budget (ANALYSIS_BUDGET_MS overrides it).
+//
+// The fixture is seeded once into a workspace under target/bench-data and
+// reused by later runs.
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{criterion_group, criterion_main, Criterion};
+use meta_driven_trading_journal::analysis::Analyzer;
+use meta_driven_trading_journal::database::DatabaseState;
+use meta_driven_trading_journal::workspace::WorkspacePaths;
+use sqlx::{Row, SqlitePool};
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Instant;
+use tokio::runtime::Runtime;
+
+const TRADE_COUNT: i64 = 400_000;
+const BUDGET_MS: f64 = 1000.0;
+const BUDGET_RUNS: usize = 5;
+
+const SYMBOLS: &[&str] = &["EURUSD", "GBPUSD", "USDJPY", "XAUUSD", "NAS100", "US30", "AUDUSD", "USDCAD"];
+const STRATEGIES: &[&str] = &["London Breakout", "NY Reversal", "Asian Range", "Trend Continuation", "News Fade"];
+const PATTERNS: &[&str] = &["Order Block", "Fair Value Gap", "Breaker Block", "Liquidity Sweep", "Optimal Trade Entry"];
+const TIMEFRAMES: &[&str] = &["M5", "M15", "H1", "H4"];
+const EMOTIONS: &[&str] = &["Calm", "Confident", "Anxious", "Greedy", "Fearful"];
+
+// Deterministic so every machine benchmarks the same journal
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+    
+    fn unit(&mut self) -> f64 {
+        self.next() as f64 / (1u64 << 31) as f64
+    }
+    
+    fn pick<'a>(&mut self, values: &[&'a str]) -> &'a str {
+        values[self.next() as usize % values.len()]
+    }
+}
+
+fn bench_data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("bench-data")
+}
+
+async fn open_fixture() -> DatabaseState {
+    let root = bench_data_dir().join("journal-400k");
+    let mut database = DatabaseState::open(WorkspacePaths::new(root)).expect("Failed to open bench database");
+    database.initialize().await.expect("Failed to initialize bench database");
+    
+    let existing: i64 = sqlx::query("SELECT COUNT(*) AS count FROM trades")
+        .fetch_one(&database.pool())
+        .await
+        .expect("Failed to count trades")
+        .get("count");
+    
+    if existing != TRADE_COUNT {
+        seed_trades(&database.pool()).await;
+    }
+    
+    database
+}
+
+async fn seed_trades(pool: &SqlitePool) {
+    let mut rng = Lcg(0x5eed);
+    let start = Utc.with_ymd_and_hms(2019, 1, 1, 0, 0, 0).unwrap();
+    let notes = "Waited for the sweep of the previous session high, entered on the retest. ".repeat(8);
+    
+    let mut tx = pool.begin().await.expect("Failed to start seed transaction");
+    sqlx::query("DELETE FROM trades").execute(&mut *tx).await.expect("Failed to clear trades");
+    
+    for i in 0..TRADE_COUNT {
+        let entry_time = start + Duration::minutes(i * 6);
+        let trade_type = if rng.next() % 2 == 0 { "Buy" } else { "Sell" };
+        let entry_price = 1.0 + rng.unit();
+        let (sl, tp) = if trade_type == "Buy" {
+            (entry_price - 0.005, entry_price + 0.01)
+        } else {
+            (entry_price + 0.005, entry_price - 0.01)
+        };
+        
+        // One in ten trades is still open
+        let closed = rng.next() % 10 != 0;
+        let profit = (rng.unit() * 700.0 - 300.0).round();
+        let exit_time = entry_time + Duration::minutes(5 + (rng.next() % 240) as i64);
+        let created_at = entry_time.to_rfc3339();
+        
+        sqlx::query(
+            r#"
+            INSERT INTO trades (
+                symbol, trade_type, volume, entry_price, sl, tp, entry_time, exit_time,
+                notes, ict_pattern, pattern_timeframe, strategy_name, emotion, confidence_level,
+                is_win, profit_loss_money, profit_loss_pips, risk_reward_ratio, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(rng.pick(SYMBOLS))
+        .bind(trade_type)
+        .bind(0.1 + (rng.next() % 20) as f64 / 10.0)
+        .bind(entry_price)
+        .bind(sl)
+        .bind(tp)
+        .bind(entry_time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .bind(closed.then(|| exit_time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)))
+        .bind(&notes)
+        .bind(rng.pick(PATTERNS))
+        .bind(rng.pick(TIMEFRAMES))
+        .bind(rng.pick(STRATEGIES))
+        .bind(rng.pick(EMOTIONS))
+        .bind((rng.next() % 10) as f64 + 1.0)
+        .bind(closed.then(|| profit > 0.0))
+        .bind(closed.then(|| profit))
+        .bind(closed.then(|| profit / 10.0))
+        .bind(2.0)
+        .bind(&created_at)
+        .bind(&created_at)
+        .execute(&mut *tx)
+        .await
+        .expect("Failed to insert trade");
+    }
+    
+    tx.commit().await.expect("Failed to commit seed transaction");
+}
+
+fn analyzer_benchmarks(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("Failed to start runtime");
+    let database = runtime.block_on(open_fixture());
+    let analyzer = Analyzer::new(database.pool());
+    
+    let mut group = c.benchmark_group("analyzer_400k");
+    group.sample_size(10);
+    
+    // Every iteration drops the cache so the full pipeline is measured
+    group.bench_function("analyze_trades", |b| {
+        b.to_async(&runtime).iter(|| async {
+            analyzer.invalidate_cache().await;
+            analyzer.analyze_trades().await.expect("Analysis failed")
+        })
+    });
+    
+    group.bench_function("dashboard", |b| {
+        b.to_async(&runtime).iter(|| async {
+            analyzer.invalidate_cache().await;
+            analyzer.get_dashboard_data("all").await.expect("Dashboard failed")
+        })
+    });
+    
+    group.bench_function("quick_analysis", |b| {
+        b.to_async(&runtime).iter(|| async {
+            analyzer.invalidate_cache().await;
+            analyzer.quick_analysis().await.expect("Quick analysis failed")
+        })
+    });
+    
+    group.finish();
+    
+    check_budget(&runtime, &analyzer);
+}
+
+// Median of a few cold runs, in milliseconds
+fn cold_median_ms<F, Fut>(runtime: &Runtime, analyzer: &Analyzer, run: F) -> f64
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut timings: Vec<f64> = (0..BUDGET_RUNS)
+        .map(|_| {
+            runtime.block_on(async {
+                analyzer.invalidate_cache().await;
+                let started = Instant::now();
+                run().await;
+                started.elapsed().as_secs_f64() * 1000.0
+            })
+        })
+        .collect();
+    timings.sort_by(f64::total_cmp);
+    timings[timings.len() / 2]
+}
+
+fn check_budget(runtime: &Runtime, analyzer: &Analyzer) {
+    let budget_ms = std::env::var("ANALYSIS_BUDGET_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(BUDGET_MS);
+    
+    let timings = [
+        ("analyze_trades", cold_median_ms(runtime, analyzer, || async { analyzer.analyze_trades().await.expect("Analysis failed"); })),
+        ("dashboard", cold_median_ms(runtime, analyzer, || async { analyzer.get_dashboard_data("all").await.expect("Dashboard failed"); })),
+        ("quick_analysis", cold_median_ms(runtime, analyzer, || async { analyzer.quick_analysis().await.expect("Quick analysis failed"); })),
+    ];
+    
+    let result = serde_json::json!({
+        "trade_count": TRADE_COUNT,
+        "threads": std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        "budget_ms": budget_ms,
+        "median_ms": timings.iter().map(|(name, ms)| (name.to_string(), serde_json::json!(ms))).collect::<serde_json::Map<_, _>>(),
+        "recorded_at": Utc::now().to_rfc3339(),
+    });
+    let path = bench_data_dir().join("analysis-400k.json");
+    std::fs::write(&path, serde_json::to_vec_pretty(&result).unwrap()).expect("Failed to record bench result");
+    println!("Recorded {}: {}", path.display(), result);
+    
+    for (name, ms) in timings {
+        assert!(ms <= budget_ms, "{} took {:.0} ms at {} trades, over the {:.0} ms budget", name, ms, TRADE_COUNT, budget_ms);
+    }
+}
+
+criterion_group!(benches, analyzer_benchmarks);
+criterion_main!(benches);
@@ -1,3 +1,13 @@
 fn main() {
+    // Native plugins pass Rust types across the library boundary, so their
+    // ABI string names the compiler (see plugin::PLUGIN_ABI)
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=PLUGIN_RUSTC_VERSION={}", version);
+
     tauri_build::build()
 }
\ No newline at end of file
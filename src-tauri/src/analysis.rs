@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{SqlitePool, Row, Error as SqlxError};
 use chrono::{DateTime, Utc, TimeZone, Datelike, Timelike};
-use std::collections::{HashMap, HashSet};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use futures::TryStreamExt;
 use rayon::prelude::*;
+use sqlx::sqlite::SqliteRow;
 use statistical::{mean, standard_deviation, variance};
 
+use crate::database::{load_dimension_statistics, parse_trade_time, trades_source, DimensionStatistics};
+use crate::equity;
+use crate::timezone::{self, TradingSession};
+
 // Analysis results structures
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TradeAnalysis {
@@ -36,10 +43,11 @@ pub struct AnalysisSummary {
     pub sharpe_ratio: f64,
     pub max_drawdown: f64,
     pub recovery_factor: f64,
+    pub calmar_ratio: f64,
     pub risk_of_ruin: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PerformanceMetrics {
     pub total_return: f64,
     pub annual_return: f64,
@@ -55,7 +63,7 @@ pub struct PerformanceMetrics {
     pub ulcer_index: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct MonthlyReturn {
     pub year: i32,
     pub month: u32,
@@ -64,7 +72,7 @@ pub struct MonthlyReturn {
     pub trades: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PsychologicalMetrics {
     pub emotional_impact: EmotionalAnalysis,
     pub consistency: ConsistencyMetrics,
@@ -72,7 +80,7 @@ pub struct PsychologicalMetrics {
     pub stress_levels: StressAnalysis,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct EmotionalAnalysis {
     pub win_emotions: HashMap<String, f64>,
     pub loss_emotions: HashMap<String, f64>,
@@ -80,7 +88,7 @@ pub struct EmotionalAnalysis {
     pub confidence_trend: Vec<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ConsistencyMetrics {
     pub win_streaks: Vec<u32>,
     pub loss_streaks: Vec<u32>,
@@ -89,7 +97,7 @@ pub struct ConsistencyMetrics {
     pub stability_index: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct BehavioralPatterns {
     pub revenge_trading: bool,
     pub overtrading: bool,
@@ -98,7 +106,7 @@ pub struct BehavioralPatterns {
     pub late_entries: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct StressAnalysis {
     pub stress_level: f64,
     pub risk_tolerance: f64,
@@ -106,7 +114,7 @@ pub struct StressAnalysis {
     pub emotional_control: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TechnicalAnalysis {
     pub entry_accuracy: f64,
     pub exit_accuracy: f64,
@@ -115,7 +123,7 @@ pub struct TechnicalAnalysis {
     pub indicator_performance: IndicatorAnalysis,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PriceActionAnalysis {
     pub support_resistance_hits: u32,
     pub breakout_success: f64,
@@ -123,7 +131,7 @@ pub struct PriceActionAnalysis {
     pub trend_following_success: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct IndicatorAnalysis {
     pub rsi_effectiveness: f64,
     pub macd_effectiveness: f64,
@@ -131,7 +139,7 @@ pub struct IndicatorAnalysis {
     pub bollinger_effectiveness: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ICTAnalysis {
     pub pattern_performance: HashMap<String, PatternPerformance>,
     pub timeframe_analysis: HashMap<String, TimeframeAnalysis>,
@@ -140,7 +148,7 @@ pub struct ICTAnalysis {
     pub heatmap_data: Vec<ICTHeatmapData>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PatternPerformance {
     pub pattern: String,
     pub total_trades: u32,
@@ -153,7 +161,7 @@ pub struct PatternPerformance {
     pub worst_scenario: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TimeframeAnalysis {
     pub timeframe: String,
     pub success_rate: f64,
@@ -161,14 +169,14 @@ pub struct TimeframeAnalysis {
     pub volatility_adjusted_return: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CombinationAnalysis {
     pub best_combinations: Vec<PatternCombination>,
     pub worst_combinations: Vec<PatternCombination>,
     pub synergy_scores: HashMap<String, f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PatternCombination {
     pub patterns: Vec<String>,
     pub win_rate: f64,
@@ -176,7 +184,7 @@ pub struct PatternCombination {
     pub average_rr: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ICTHeatmapData {
     pub pattern: String,
     pub day_of_week: String,
@@ -185,7 +193,7 @@ pub struct ICTHeatmapData {
     pub total_trades: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TimeBasedAnalysis {
     pub hourly_analysis: HashMap<u32, HourlyPerformance>,
     pub daily_analysis: HashMap<String, DailyPerformance>,
@@ -194,7 +202,7 @@ pub struct TimeBasedAnalysis {
     pub session_analysis: SessionAnalysis,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct HourlyPerformance {
     pub hour: u32,
     pub total_trades: u32,
@@ -203,7 +211,7 @@ pub struct HourlyPerformance {
     pub profit_per_trade: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DailyPerformance {
     pub day: String,
     pub total_trades: u32,
@@ -213,7 +221,7 @@ pub struct DailyPerformance {
     pub worst_hour: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct MonthlyPerformance {
     pub month: String,
     pub total_trades: u32,
@@ -222,7 +230,7 @@ pub struct MonthlyPerformance {
     pub consistency: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SeasonalAnalysis {
     pub quarterly_performance: HashMap<String, f64>,
     pub seasonal_patterns: HashMap<String, f64>,
@@ -230,7 +238,7 @@ pub struct SeasonalAnalysis {
     pub worst_season: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SessionAnalysis {
     pub asian_session: SessionPerformance,
     pub london_session: SessionPerformance,
@@ -238,7 +246,7 @@ pub struct SessionAnalysis {
     pub overlap_sessions: OverlapPerformance,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SessionPerformance {
     pub session: String,
     pub total_trades: u32,
@@ -248,14 +256,14 @@ pub struct SessionPerformance {
     pub volatility: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct OverlapPerformance {
     pub london_new_york: SessionPerformance,
     pub asian_london: SessionPerformance,
     pub all_sessions: SessionPerformance,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct RiskAnalysis {
     pub position_sizing: PositionSizingAnalysis,
     pub risk_metrics: RiskMetrics,
@@ -263,7 +271,7 @@ pub struct RiskAnalysis {
     pub stress_testing: StressTesting,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PositionSizingAnalysis {
     pub optimal_position_size: f64,
     pub kelly_criterion: f64,
@@ -272,7 +280,7 @@ pub struct PositionSizingAnalysis {
     pub position_sizing_score: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct RiskMetrics {
     pub standard_deviation: f64,
     pub semi_deviation: f64,
@@ -282,7 +290,7 @@ pub struct RiskMetrics {
     pub tail_risk: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct VaRAnalysis {
     pub var_95: f64,
     pub var_99: f64,
@@ -291,7 +299,7 @@ pub struct VaRAnalysis {
     pub parametric_var: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct StressTesting {
     pub worst_case_scenario: f64,
     pub black_swan_impact: f64,
@@ -299,7 +307,7 @@ pub struct StressTesting {
     pub liquidity_crisis: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct StrategyAnalysis {
     pub strategy_performance: HashMap<String, StrategyPerformance>,
     pub strategy_correlation: HashMap<String, f64>,
@@ -307,7 +315,7 @@ pub struct StrategyAnalysis {
     pub optimal_strategy_mix: HashMap<String, f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct StrategyPerformance {
     pub strategy: String,
     pub total_trades: u32,
@@ -319,6 +327,83 @@ pub struct StrategyPerformance {
     pub recovery_factor: f64,
 }
 
+// Lightweight row used by the per-trade passes. Only the columns those passes
+// read are loaded (idx_trades_analysis covers them, so notes and other wide
+// columns are never touched), timestamps are parsed once and strategy names
+// are shared between rows. Anything that is a plain count or sum per symbol,
+// strategy or pattern comes from trade_statistics instead.
+#[derive(Debug, Clone)]
+pub struct TradeProjection {
+    pub id: u32,
+    pub entry_time: Option<DateTime<Utc>>,
+    pub exit_time: Option<DateTime<Utc>>,
+    pub is_win: Option<bool>,
+    pub profit_loss_money: Option<f64>,
+    pub strategy_name: Option<Arc<str>>,
+}
+
+// Closed trades only, since open ones just count through the aggregates.
+// Chronological so sequence metrics (drawdown, streaks) see trades in order.
+fn projection_sql(source: &str) -> String {
+    format!(
+        r#"
+    SELECT id, entry_time, exit_time, is_win, profit_loss_money, strategy_name
+    FROM {}
+    WHERE is_win IS NOT NULL
+    ORDER BY entry_time ASC, id ASC
+"#,
+        source
+    )
+}
+
+// Projection rows per batch sent to the analysis thread, and how many
+// batches may wait there before reading pauses
+const PROJECTION_BATCH_SIZE: usize = 4096;
+const PROJECTION_BATCHES_IN_FLIGHT: usize = 4;
+
+#[derive(Debug, Default)]
+struct StringInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringInterner {
+    fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(existing) = self.strings.get(value) {
+            return existing.clone();
+        }
+        
+        let value: Arc<str> = Arc::from(value);
+        self.strings.insert(value.clone());
+        value
+    }
+    
+    fn intern_opt(&mut self, value: Option<&str>) -> Option<Arc<str>> {
+        value.map(|value| self.intern(value))
+    }
+}
+
+impl TradeProjection {
+    fn from_row(row: &SqliteRow, interner: &mut StringInterner) -> Self {
+        Self {
+            id: row.get::<i64, _>("id") as u32,
+            entry_time: parse_trade_time(row.get("entry_time")),
+            exit_time: row.get::<Option<&str>, _>("exit_time").and_then(parse_trade_time),
+            is_win: row.get("is_win"),
+            profit_loss_money: row.get("profit_loss_money"),
+            strategy_name: interner.intern_opt(row.get("strategy_name")),
+        }
+    }
+}
+
+// Per-dimension totals read from trade_statistics instead of being
+// recomputed from individual trades
+#[derive(Debug, Clone, Default)]
+struct AnalysisAggregates {
+    totals: DimensionStatistics,
+    patterns: Vec<DimensionStatistics>,
+    strategies: Vec<DimensionStatistics>,
+}
+
 // Main analyzer structure
 pub struct Analyzer {
     pool: SqlitePool,
@@ -334,6 +419,8 @@ pub struct AnalysisConfig {
     pub trading_days_per_year: u32,
     pub max_drawdown_period: u32,
     pub var_horizon: u32,
+    pub display_timezone: Tz,
+    pub include_archived: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -344,6 +431,83 @@ struct AnalysisCache {
     performance_cache: HashMap<String, PerformanceMetrics>,
 }
 
+// Running totals for one time bucket (hour, weekday or session)
+#[derive(Debug, Default)]
+struct BucketStats {
+    total_trades: u32,
+    winning_trades: u32,
+    net_profit: f64,
+    profits: Vec<f64>,
+    holding_hours: Vec<f64>,
+}
+
+impl BucketStats {
+    fn add(&mut self, trade: &TradeProjection) {
+        let profit = trade.profit_loss_money.unwrap_or(0.0);
+        
+        self.total_trades += 1;
+        if trade.is_win == Some(true) {
+            self.winning_trades += 1;
+        }
+        self.net_profit += profit;
+        self.profits.push(profit);
+        
+        if let (Some(entry), Some(exit)) = (trade.entry_time, trade.exit_time) {
+            self.holding_hours.push((exit - entry).num_seconds() as f64 / 3600.0);
+        }
+    }
+    
+    fn win_rate(&self) -> f64 {
+        if self.total_trades == 0 {
+            0.0
+        } else {
+            self.winning_trades as f64 / self.total_trades as f64 * 100.0
+        }
+    }
+    
+    fn profit_per_trade(&self) -> f64 {
+        if self.total_trades == 0 {
+            0.0
+        } else {
+            self.net_profit / self.total_trades as f64
+        }
+    }
+    
+    fn average_win(&self) -> f64 {
+        let wins: Vec<f64> = self.profits.iter().copied().filter(|p| *p > 0.0).collect();
+        if wins.is_empty() {
+            0.0
+        } else {
+            wins.iter().sum::<f64>() / wins.len() as f64
+        }
+    }
+    
+    fn session_performance(&self, session: &str) -> SessionPerformance {
+        let average_holding = if self.holding_hours.is_empty() {
+            0.0
+        } else {
+            self.holding_hours.iter().sum::<f64>() / self.holding_hours.len() as f64
+        };
+        
+        let volatility = if self.profits.len() < 2 {
+            0.0
+        } else {
+            let avg = self.profits.iter().sum::<f64>() / self.profits.len() as f64;
+            let var = self.profits.iter().map(|p| (p - avg).powi(2)).sum::<f64>() / (self.profits.len() - 1) as f64;
+            var.sqrt()
+        };
+        
+        SessionPerformance {
+            session: session.to_string(),
+            total_trades: self.total_trades,
+            win_rate: self.win_rate(),
+            net_profit: self.net_profit,
+            average_holding,
+            volatility,
+        }
+    }
+}
+
 impl Default for AnalysisConfig {
     fn default() -> Self {
         Self {
@@ -353,6 +517,8 @@ impl Default for AnalysisConfig {
             trading_days_per_year: 252,
             max_drawdown_period: 21, // 1 month
             var_horizon: 1,
+            display_timezone: Tz::UTC,
+            include_archived: false,
         }
     }
 }
@@ -381,6 +547,24 @@ impl Analyzer {
         Ok(())
     }
 
+    // Hour and day buckets are computed in this zone
+    pub async fn set_display_timezone(&mut self, tz: Tz) {
+        self.config.display_timezone = tz;
+        self.invalidate_cache().await;
+    }
+
+    // Whether analyses also cover trades moved to the archive
+    pub async fn set_include_archived(&mut self, include_archived: bool) {
+        self.config.include_archived = include_archived;
+        self.invalidate_cache().await;
+    }
+
+    pub async fn invalidate_cache(&self) {
+        let mut cache = self.cache.lock().await;
+        cache.trade_analysis = None;
+        cache.last_update = None;
+    }
+
     // Main analysis method
     pub async fn analyze_trades(&self) -> Result<TradeAnalysis, SqlxError> {
         let mut cache = self.cache.lock().await;
@@ -396,17 +580,27 @@ impl Analyzer {
 
         log::info!("Performing comprehensive trade analysis...");
         
-        // Get all trades from database
-        let trades = self.get_all_trades().await?;
-        
-        if trades.is_empty() {
+        // Totals are aggregated in SQL; per-trade data is streamed as a projection
+        let aggregates = self.load_aggregates().await?;
+        if aggregates.totals.trade_count == 0 {
             return Ok(TradeAnalysis::default());
         }
 
-        // Perform parallel analysis
-        let analysis = tokio::task::spawn_blocking(move || {
-            Self::perform_comprehensive_analysis(trades)
-        }).await.unwrap();
+        // Rows are decoded on this task and handed over in batches, so reading
+        // overlaps with collecting. The analyses need every closed trade at
+        // once, so the thread keeps the whole projection in memory.
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Vec<TradeProjection>>(PROJECTION_BATCHES_IN_FLIGHT);
+        let display_timezone = self.config.display_timezone;
+        let worker = tokio::task::spawn_blocking(move || {
+            let mut closed_trades = Vec::new();
+            while let Some(batch) = receiver.blocking_recv() {
+                closed_trades.extend(batch);
+            }
+            Self::perform_comprehensive_analysis(closed_trades, aggregates, display_timezone)
+        });
+        
+        self.stream_projections(sender).await?;
+        let analysis = worker.await.unwrap();
 
         // Update cache
         cache.trade_analysis = Some(analysis.clone());
@@ -416,25 +610,37 @@ impl Analyzer {
     }
 
     // Comprehensive analysis function
-    fn perform_comprehensive_analysis(trades: Vec<Trade>) -> TradeAnalysis {
-        let closed_trades: Vec<&Trade> = trades.iter()
-            .filter(|t| t.is_win.is_some())
-            .collect();
-
-        let open_trades: Vec<&Trade> = trades.iter()
-            .filter(|t| t.is_win.is_none())
-            .collect();
+    fn perform_comprehensive_analysis(
+        trades: Vec<TradeProjection>,
+        aggregates: AnalysisAggregates,
+        display_timezone: Tz,
+    ) -> TradeAnalysis {
+        let closed_trades: Vec<&TradeProjection> = trades.iter().collect();
+        // Shared by the summary and the performance metrics
+        let curve = equity::build(closed_trades.iter().copied(), 0.0);
 
         // Parallel computation of different analysis aspects
-        let (summary, performance, psychological, technical, ict_analysis, time_analysis, risk_analysis, strategy_analysis) = rayon::join(
-            || Self::calculate_summary(&trades, &closed_trades),
-            || Self::calculate_performance_metrics(&closed_trades),
-            || Self::analyze_psychological_aspects(&trades),
-            || Self::perform_technical_analysis(&closed_trades),
-            || Self::analyze_ict_patterns(&closed_trades),
-            || Self::analyze_time_based_patterns(&closed_trades),
-            || Self::perform_risk_analysis(&closed_trades),
-            || Self::analyze_strategy_performance(&closed_trades),
+        let (((summary, performance), (psychological, technical)), ((ict_analysis, time_analysis), (risk_analysis, strategy_analysis))) = rayon::join(
+            || rayon::join(
+                || rayon::join(
+                    || Self::calculate_summary(&aggregates.totals, &curve),
+                    || Self::calculate_performance_metrics(&closed_trades, &curve),
+                ),
+                || rayon::join(
+                    || Self::analyze_psychological_aspects(&closed_trades),
+                    || Self::perform_technical_analysis(&closed_trades),
+                ),
+            ),
+            || rayon::join(
+                || rayon::join(
+                    || Self::analyze_ict_patterns(&closed_trades, &aggregates.patterns),
+                    || Self::analyze_time_based_patterns(&closed_trades, display_timezone),
+                ),
+                || rayon::join(
+                    || Self::perform_risk_analysis(&closed_trades),
+                    || Self::analyze_strategy_performance(&closed_trades, &aggregates.strategies),
+                ),
+            ),
         );
 
         TradeAnalysis {
@@ -449,26 +655,90 @@ impl Analyzer {
         }
     }
 
-    // Summary calculation
-    fn calculate_summary(trades: &[Trade], closed_trades: &[&Trade]) -> AnalysisSummary {
-        let total_trades = trades.len() as u32;
-        let open_trades = trades.iter().filter(|t| t.is_win.is_none()).count() as u32;
-        let winning_trades = closed_trades.iter().filter(|t| t.is_win.unwrap()).count() as u32;
-        let losing_trades = closed_trades.iter().filter(|t| !t.is_win.unwrap()).count() as u32;
+    // Summary of an arbitrary selection of trades (exports, reports). The
+    // totals are summed here since trade_statistics only holds whole dimensions.
+    pub fn summarize(trades: &[TradeProjection]) -> AnalysisSummary {
+        let mut totals = DimensionStatistics { key: "selection".to_string(), ..Default::default() };
+        for trade in trades {
+            totals.record(trade.is_win, trade.profit_loss_money);
+        }
         
-        let win_rate = if closed_trades.is_empty() {
-            0.0
-        } else {
-            (winning_trades as f64 / closed_trades.len() as f64) * 100.0
-        };
+        let mut closed_trades: Vec<&TradeProjection> = trades.iter()
+            .filter(|t| t.is_win.is_some())
+            .collect();
+        closed_trades.sort_by_key(|t| (t.entry_time, t.id));
+        
+        let curve = equity::build(closed_trades.iter().copied(), 0.0);
+        Self::calculate_summary(&totals, &curve)
+    }
 
-        let profits: Vec<f64> = closed_trades.iter()
-            .map(|t| t.profit_loss_money.unwrap_or(0.0))
+    // Session breakdown of a selection (see analyze_trading_sessions)
+    pub fn session_breakdown(trades: &[TradeProjection]) -> SessionAnalysis {
+        let closed_trades: Vec<&TradeProjection> = trades.iter().filter(|t| t.is_win.is_some()).collect();
+        Self::analyze_trading_sessions(&closed_trades)
+    }
+
+    // Per-strategy performance of a selection, best net profit first.
+    // `strategies` holds the selection's totals per strategy name.
+    pub fn strategy_breakdown(trades: &[TradeProjection], strategies: &[DimensionStatistics]) -> Vec<StrategyPerformance> {
+        let mut closed_trades: Vec<&TradeProjection> = trades.iter().filter(|t| t.is_win.is_some()).collect();
+        closed_trades.sort_by_key(|t| (t.entry_time, t.id));
+
+        let mut performance: Vec<StrategyPerformance> = Self::calculate_strategy_performance(&closed_trades, strategies)
+            .into_values()
             .collect();
+        performance.sort_by(|a, b| b.net_profit.partial_cmp(&a.net_profit).unwrap_or(std::cmp::Ordering::Equal));
+        performance
+    }
 
-        let net_profit = profits.iter().sum();
-        let gross_profit = profits.iter().filter(|&&p| p > 0.0).sum();
-        let gross_loss = profits.iter().filter(|&&p| p < 0.0).sum::<f64>().abs();
+    // Realized P/L per calendar month in `tz`, by exit time. The return is
+    // relative to the balance at the start of the month, so it needs the
+    // balance the selection started from; without one it is left at 0.
+    pub fn monthly_returns(trades: &[TradeProjection], tz: Tz, starting_balance: Option<f64>) -> Vec<MonthlyReturn> {
+        let closed_trades: Vec<&TradeProjection> = trades.iter().filter(|t| t.is_win.is_some()).collect();
+        Self::monthly_buckets(&closed_trades, tz, starting_balance)
+    }
+
+    fn monthly_buckets(closed_trades: &[&TradeProjection], tz: Tz, starting_balance: Option<f64>) -> Vec<MonthlyReturn> {
+        let mut months: BTreeMap<(i32, u32), MonthlyReturn> = BTreeMap::new();
+        for trade in closed_trades {
+            let closed_at = match trade.exit_time.or(trade.entry_time) {
+                Some(closed_at) => closed_at.with_timezone(&tz),
+                None => continue,
+            };
+            let month = months.entry((closed_at.year(), closed_at.month())).or_insert_with(|| MonthlyReturn {
+                year: closed_at.year(),
+                month: closed_at.month(),
+                ..Default::default()
+            });
+            month.profit += trade.profit_loss_money.unwrap_or(0.0);
+            month.trades += 1;
+        }
+
+        let mut balance = starting_balance;
+        months.into_values()
+            .map(|mut month| {
+                if let Some(opening) = balance {
+                    month.return_percentage = if opening > 0.0 { month.profit / opening * 100.0 } else { 0.0 };
+                    balance = Some(opening + month.profit);
+                }
+                month
+            })
+            .collect()
+    }
+
+    // Summary calculation; counts and sums come from the SQL aggregates,
+    // sequence metrics from the equity curve of the closed trades
+    fn calculate_summary(totals: &DimensionStatistics, curve: &equity::EquityCurve) -> AnalysisSummary {
+        let total_trades = totals.trade_count;
+        let open_trades = totals.trade_count.saturating_sub(totals.closed_count);
+        let winning_trades = totals.win_count;
+        let losing_trades = totals.loss_count;
+        let win_rate = totals.win_rate();
+
+        let net_profit = totals.net_profit;
+        let gross_profit = totals.gross_profit;
+        let gross_loss = totals.gross_loss;
 
         let profit_factor = if gross_loss > 0.0 {
             gross_profit / gross_loss
@@ -477,24 +747,23 @@ impl Analyzer {
         };
 
         let average_win = if winning_trades > 0 {
-            profits.iter().filter(|&&p| p > 0.0).sum::<f64>() / winning_trades as f64
+            gross_profit / winning_trades as f64
         } else {
             0.0
         };
 
         let average_loss = if losing_trades > 0 {
-            profits.iter().filter(|&&p| p < 0.0).sum::<f64>().abs() / losing_trades as f64
+            gross_loss / losing_trades as f64
         } else {
             0.0
         };
 
         let expectancy = (win_rate / 100.0) * average_win - ((100.0 - win_rate) / 100.0) * average_loss;
-        let average_trade = if !closed_trades.is_empty() { net_profit / closed_trades.len() as f64 } else { 0.0 };
+        let average_trade = if totals.closed_count > 0 { net_profit / totals.closed_count as f64 } else { 0.0 };
 
-        // Calculate more advanced metrics
-        let sharpe_ratio = Self::calculate_sharpe_ratio(&profits);
-        let max_drawdown = Self::calculate_max_drawdown(&profits);
-        let recovery_factor = if max_drawdown > 0.0 { net_profit / max_drawdown } else { 0.0 };
+        // Calculate more advanced metrics, over the same close-ordered
+        // trades the drawdown is read from
+        let sharpe_ratio = Self::calculate_sharpe_ratio(&Self::curve_returns(curve));
         let risk_of_ruin = Self::calculate_risk_of_ruin(win_rate / 100.0, average_win, average_loss);
 
         AnalysisSummary {
@@ -510,14 +779,15 @@ impl Analyzer {
             expectancy,
             average_trade,
             sharpe_ratio,
-            max_drawdown,
-            recovery_factor,
+            max_drawdown: curve.max_drawdown,
+            recovery_factor: curve.recovery_factor,
+            calmar_ratio: curve.calmar_ratio,
             risk_of_ruin,
         }
     }
 
     // Performance metrics calculation
-    fn calculate_performance_metrics(closed_trades: &[&Trade]) -> PerformanceMetrics {
+    fn calculate_performance_metrics(closed_trades: &[&TradeProjection], curve: &equity::EquityCurve) -> PerformanceMetrics {
         if closed_trades.is_empty() {
             return PerformanceMetrics::default();
         }
@@ -532,12 +802,11 @@ impl Analyzer {
         let monthly_returns = Self::calculate_monthly_returns(closed_trades);
         let daily_returns = Self::calculate_daily_returns(closed_trades);
 
-        let volatility = standard_deviation(&profits);
+        let volatility = if profits.len() > 1 { standard_deviation(&profits, None) } else { 0.0 };
         let alpha = Self::calculate_alpha(&profits);
         let beta = Self::calculate_beta(&profits);
         let r_squared = Self::calculate_r_squared(&profits);
         let information_ratio = Self::calculate_information_ratio(&profits);
-        let calmar_ratio = Self::calculate_calmar_ratio(&profits);
         let sortino_ratio = Self::calculate_sortino_ratio(&profits);
         let ulcer_index = Self::calculate_ulcer_index(&profits);
 
@@ -551,14 +820,14 @@ impl Analyzer {
             beta,
             r_squared,
             information_ratio,
-            calmar_ratio,
+            calmar_ratio: curve.calmar_ratio,
             sortino_ratio,
             ulcer_index,
         }
     }
 
     // Psychological analysis
-    fn analyze_psychological_aspects(trades: &[Trade]) -> PsychologicalMetrics {
+    fn analyze_psychological_aspects(trades: &[&TradeProjection]) -> PsychologicalMetrics {
         let emotional_impact = Self::analyze_emotional_impact(trades);
         let consistency = Self::calculate_consistency_metrics(trades);
         let behavioral_patterns = Self::identify_behavioral_patterns(trades);
@@ -573,7 +842,7 @@ impl Analyzer {
     }
 
     // Technical analysis
-    fn perform_technical_analysis(closed_trades: &[&Trade]) -> TechnicalAnalysis {
+    fn perform_technical_analysis(closed_trades: &[&TradeProjection]) -> TechnicalAnalysis {
         let entry_accuracy = Self::calculate_entry_accuracy(closed_trades);
         let exit_accuracy = Self::calculate_exit_accuracy(closed_trades);
         let timing_efficiency = Self::calculate_timing_efficiency(closed_trades);
@@ -590,11 +859,14 @@ impl Analyzer {
     }
 
     // ICT pattern analysis
-    fn analyze_ict_patterns(closed_trades: &[&Trade]) -> ICTAnalysis {
-        let pattern_performance = Self::calculate_pattern_performance(closed_trades);
+    fn analyze_ict_patterns(closed_trades: &[&TradeProjection], patterns: &[DimensionStatistics]) -> ICTAnalysis {
+        let pattern_performance = Self::calculate_pattern_performance(patterns);
         let timeframe_analysis = Self::analyze_timeframe_performance(closed_trades);
         let combination_analysis = Self::analyze_pattern_combinations(closed_trades);
-        let win_rates = Self::calculate_ict_win_rates(closed_trades);
+        let win_rates = patterns.iter()
+            .filter(|stats| stats.closed_count > 0)
+            .map(|stats| (stats.key.clone(), stats.win_rate()))
+            .collect();
         let heatmap_data = Self::generate_ict_heatmap(closed_trades);
 
         ICTAnalysis {
@@ -606,10 +878,10 @@ impl Analyzer {
         }
     }
 
-        // Time-based analysis
-        fn analyze_time_based_patterns(closed_trades: &[&Trade]) -> TimeBasedAnalysis {
-            let hourly_analysis = Self::analyze_hourly_performance(closed_trades);
-            let daily_analysis = Self::analyze_daily_performance(closed_trades);
+        // Time-based analysis; hours and weekdays are taken in the display timezone
+        fn analyze_time_based_patterns(closed_trades: &[&TradeProjection], tz: Tz) -> TimeBasedAnalysis {
+            let hourly_analysis = Self::analyze_hourly_performance(closed_trades, tz);
+            let daily_analysis = Self::analyze_daily_performance(closed_trades, tz);
             let monthly_analysis = Self::analyze_monthly_performance(closed_trades);
             let seasonal_analysis = Self::analyze_seasonal_patterns(closed_trades);
             let session_analysis = Self::analyze_trading_sessions(closed_trades);
@@ -623,8 +895,106 @@ impl Analyzer {
             }
         }
     
+        fn analyze_hourly_performance(trades: &[&TradeProjection], tz: Tz) -> HashMap<u32, HourlyPerformance> {
+            let mut buckets: HashMap<u32, BucketStats> = HashMap::new();
+            for trade in trades {
+                if let Some(entry_time) = trade.entry_time {
+                    buckets.entry(entry_time.with_timezone(&tz).hour()).or_default().add(trade);
+                }
+            }
+    
+            buckets.into_iter()
+                .map(|(hour, stats)| (hour, HourlyPerformance {
+                    hour,
+                    total_trades: stats.total_trades,
+                    win_rate: stats.win_rate(),
+                    average_profit: stats.average_win(),
+                    profit_per_trade: stats.profit_per_trade(),
+                }))
+                .collect()
+        }
+    
+        fn analyze_daily_performance(trades: &[&TradeProjection], tz: Tz) -> HashMap<String, DailyPerformance> {
+            let mut buckets: HashMap<chrono::Weekday, (BucketStats, HashMap<u32, f64>)> = HashMap::new();
+            for trade in trades {
+                if let Some(entry_time) = trade.entry_time {
+                    let local_time = entry_time.with_timezone(&tz);
+                    let (stats, hourly_profit) = buckets.entry(local_time.weekday()).or_default();
+                    stats.add(trade);
+                    *hourly_profit.entry(local_time.hour()).or_insert(0.0) += trade.profit_loss_money.unwrap_or(0.0);
+                }
+            }
+    
+            buckets.into_iter()
+                .map(|(weekday, (stats, hourly_profit))| {
+                    let by_profit = |a: &(&u32, &f64), b: &(&u32, &f64)| {
+                        a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal)
+                    };
+                    let best_hour = hourly_profit.iter().max_by(by_profit).map(|(h, _)| *h).unwrap_or(0);
+                    let worst_hour = hourly_profit.iter().min_by(by_profit).map(|(h, _)| *h).unwrap_or(0);
+                    let day = format!("{:?}", weekday);
+    
+                    (day.clone(), DailyPerformance {
+                        day,
+                        total_trades: stats.total_trades,
+                        win_rate: stats.win_rate(),
+                        net_profit: stats.net_profit,
+                        best_hour,
+                        worst_hour,
+                    })
+                })
+                .collect()
+        }
+    
+        // Sessions follow each market's local clock, independent of the display timezone
+        fn analyze_trading_sessions(trades: &[&TradeProjection]) -> SessionAnalysis {
+            let mut sessions: HashMap<TradingSession, BucketStats> = HashMap::new();
+            let mut london_new_york = BucketStats::default();
+            let mut asian_london = BucketStats::default();
+            let mut any_overlap = BucketStats::default();
+    
+            for trade in trades {
+                let entry_time = match trade.entry_time {
+                    Some(entry_time) => entry_time,
+                    None => continue,
+                };
+    
+                let active = timezone::sessions_at(entry_time);
+                for session in &active {
+                    sessions.entry(*session).or_default().add(trade);
+                }
+    
+                if active.contains(&TradingSession::London) && active.contains(&TradingSession::NewYork) {
+                    london_new_york.add(trade);
+                }
+                if active.contains(&TradingSession::Asian) && active.contains(&TradingSession::London) {
+                    asian_london.add(trade);
+                }
+                if active.len() > 1 {
+                    any_overlap.add(trade);
+                }
+            }
+    
+            let performance = |session: TradingSession| {
+                sessions.get(&session)
+                    .map(|stats| stats.session_performance(session.name()))
+                    .unwrap_or_else(|| BucketStats::default().session_performance(session.name()))
+            };
+    
+            SessionAnalysis {
+                asian_session: performance(TradingSession::Asian),
+                london_session: performance(TradingSession::London),
+                new_york_session: performance(TradingSession::NewYork),
+                overlap_sessions: OverlapPerformance {
+                    london_new_york: london_new_york.session_performance("London/New York"),
+                    asian_london: asian_london.session_performance("Asian/London"),
+                    all_sessions: any_overlap.session_performance("Any overlap"),
+                },
+            }
+        }
+    
         // Risk analysis
-        fn perform_risk_analysis(closed_trades: &[&Trade]) -> RiskAnalysis {
+        fn perform_risk_analysis(closed_trades: &[&TradeProjection]) -> RiskAnalysis {
             let position_sizing = Self::analyze_position_sizing(closed_trades);
             let risk_metrics = Self::calculate_risk_metrics(closed_trades);
             let var_analysis = Self::calculate_var_analysis(closed_trades);
@@ -639,8 +1009,8 @@ impl Analyzer {
         }
     
         // Strategy analysis
-        fn analyze_strategy_performance(closed_trades: &[&Trade]) -> StrategyAnalysis {
-            let strategy_performance = Self::calculate_strategy_performance(closed_trades);
+        fn analyze_strategy_performance(closed_trades: &[&TradeProjection], strategies: &[DimensionStatistics]) -> StrategyAnalysis {
+            let strategy_performance = Self::calculate_strategy_performance(closed_trades, strategies);
             let strategy_correlation = Self::calculate_strategy_correlation(closed_trades);
             let strategy_diversification = Self::calculate_diversification_benefit(closed_trades);
             let optimal_strategy_mix = Self::calculate_optimal_strategy_mix(closed_trades);
@@ -653,14 +1023,70 @@ impl Analyzer {
             }
         }
     
+        fn calculate_pattern_performance(patterns: &[DimensionStatistics]) -> HashMap<String, PatternPerformance> {
+            patterns.iter()
+                .filter(|stats| stats.closed_count > 0)
+                .map(|stats| (stats.key.clone(), PatternPerformance {
+                    pattern: stats.key.clone(),
+                    total_trades: stats.closed_count,
+                    winning_trades: stats.win_count,
+                    win_rate: stats.win_rate(),
+                    average_profit: if stats.win_count > 0 { stats.gross_profit / stats.win_count as f64 } else { 0.0 },
+                    average_loss: if stats.loss_count > 0 { stats.gross_loss / stats.loss_count as f64 } else { 0.0 },
+                    profit_factor: stats.profit_factor(),
+                    best_scenario: String::new(),
+                    worst_scenario: String::new(),
+                }))
+                .collect()
+        }
+    
+        // Totals come from the aggregates; Sharpe, drawdown and recovery
+        // factor are read from each strategy's own equity curve
+        fn calculate_strategy_performance(
+            closed_trades: &[&TradeProjection],
+            strategies: &[DimensionStatistics],
+        ) -> HashMap<String, StrategyPerformance> {
+            let mut series: HashMap<&str, Vec<&TradeProjection>> = HashMap::new();
+            for trade in closed_trades {
+                if let Some(strategy) = &trade.strategy_name {
+                    series.entry(strategy.as_ref()).or_default().push(trade);
+                }
+            }
+    
+            strategies.iter()
+                .filter(|stats| stats.closed_count > 0)
+                .map(|stats| {
+                    let trades = series.get(stats.key.as_str()).map(|t| t.as_slice()).unwrap_or(&[]);
+                    let curve = equity::build(trades.iter().copied(), 0.0);
+    
+                    (stats.key.clone(), StrategyPerformance {
+                        strategy: stats.key.clone(),
+                        total_trades: stats.closed_count,
+                        win_rate: stats.win_rate(),
+                        net_profit: stats.net_profit,
+                        profit_factor: stats.profit_factor(),
+                        sharpe_ratio: Self::calculate_sharpe_ratio(&Self::curve_returns(&curve)),
+                        max_drawdown: curve.max_drawdown,
+                        recovery_factor: curve.recovery_factor,
+                    })
+                })
+                .collect()
+        }
+    
+        // Per-trade P/L in the order the curve closed the trades
+        fn curve_returns(curve: &equity::EquityCurve) -> Vec<f64> {
+            curve.points.iter().map(|p| p.profit).collect()
+        }
+    
         // Advanced metric calculations
         fn calculate_sharpe_ratio(returns: &[f64]) -> f64 {
-            if returns.is_empty() {
+            // statistical's standard deviation needs two points
+            if returns.len() < 2 {
                 return 0.0;
             }
     
             let avg_return = mean(returns);
-            let std_dev = standard_deviation(returns);
+            let std_dev = standard_deviation(returns, None);
             
             if std_dev == 0.0 {
                 return 0.0;
@@ -670,29 +1096,6 @@ impl Analyzer {
             (avg_return - 0.02) / std_dev
         }
     
-        fn calculate_max_drawdown(returns: &[f64]) -> f64 {
-            if returns.is_empty() {
-                return 0.0;
-            }
-    
-            let mut peak = returns[0];
-            let mut max_drawdown = 0.0;
-            let mut running_sum = 0.0;
-    
-            for &ret in returns {
-                running_sum += ret;
-                if running_sum > peak {
-                    peak = running_sum;
-                }
-                let drawdown = peak - running_sum;
-                if drawdown > max_drawdown {
-                    max_drawdown = drawdown;
-                }
-            }
-    
-            max_drawdown
-        }
-    
         fn calculate_risk_of_ruin(win_rate: f64, avg_win: f64, avg_loss: f64) -> f64 {
             if avg_loss == 0.0 {
                 return 0.0;
@@ -740,7 +1143,11 @@ impl Analyzer {
                 return f64::INFINITY;
             }
     
-            let downside_deviation = standard_deviation(&downside_returns);
+            if downside_returns.len() < 2 {
+                return 0.0;
+            }
+    
+            let downside_deviation = standard_deviation(&downside_returns, None);
             
             if downside_deviation == 0.0 {
                 return 0.0;
@@ -751,66 +1158,76 @@ impl Analyzer {
     
         // ICT-specific analysis methods
         pub async fn calculate_ict_win_rates(&self) -> Result<Vec<HashMap<String, serde_json::Value>>, SqlxError> {
-            let result = sqlx::query(
-                r#"
-                SELECT 
-                    ict_pattern,
-                    COUNT(*) as total_trades,
-                    SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) as winning_trades,
-                    CAST(SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) AS FLOAT) / COUNT(*) * 100 as win_rate,
-                    AVG(CASE WHEN is_win = 1 THEN profit_loss_money ELSE NULL END) as avg_win,
-                    AVG(CASE WHEN is_win = 0 THEN profit_loss_money ELSE NULL END) as avg_loss
-                FROM trades 
-                WHERE ict_pattern IS NOT NULL AND is_win IS NOT NULL
-                GROUP BY ict_pattern
-                ORDER BY win_rate DESC
-                "#
-            )
-            .fetch_all(&self.pool)
-            .await?;
+            // Read from the precomputed per-pattern aggregates
+            let patterns = load_dimension_statistics(&self.pool, "pattern", self.config.include_archived).await?;
+            Ok(Self::ict_win_rates(patterns))
+        }
+    
+        // Win rate rows per ICT pattern, best first, from per-pattern totals
+        pub fn ict_win_rates(mut patterns: Vec<DimensionStatistics>) -> Vec<HashMap<String, serde_json::Value>> {
+            patterns.retain(|stats| stats.closed_count > 0);
+            patterns.sort_by(|a, b| b.win_rate().partial_cmp(&a.win_rate()).unwrap_or(std::cmp::Ordering::Equal));
     
             let mut win_rates = Vec::new();
-            for row in result {
+            for stats in patterns {
+                let avg_win = if stats.win_count > 0 { stats.gross_profit / stats.win_count as f64 } else { 0.0 };
+                let avg_loss = if stats.loss_count > 0 { -stats.gross_loss / stats.loss_count as f64 } else { 0.0 };
+                
                 let mut data = HashMap::new();
-                data.insert("pattern".to_string(), serde_json::Value::String(row.get("ict_pattern")));
-                data.insert("total_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(row.get::<i64, _>("total_trades"))));
-                data.insert("winning_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(row.get::<i64, _>("winning_trades"))));
-                data.insert("win_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(row.get::<f64, _>("win_rate")).unwrap()));
-                data.insert("avg_win".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(row.get::<f64, _>("avg_win").unwrap_or(0.0)).unwrap()));
-                data.insert("avg_loss".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(row.get::<f64, _>("avg_loss").unwrap_or(0.0)).unwrap()));
+                data.insert("pattern".to_string(), serde_json::Value::String(stats.key.clone()));
+                data.insert("total_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.closed_count)));
+                data.insert("winning_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(stats.win_count)));
+                data.insert("win_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(stats.win_rate()).unwrap()));
+                data.insert("avg_win".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(avg_win).unwrap()));
+                data.insert("avg_loss".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(avg_loss).unwrap()));
                 
                 win_rates.push(data);
             }
     
-            Ok(win_rates)
+            win_rates
         }
     
         pub async fn calculate_ict_heatmap(&self) -> Result<Vec<HashMap<String, serde_json::Value>>, SqlxError> {
-            let result = sqlx::query(
+            let rows = sqlx::query(&format!(
                 r#"
-                SELECT 
-                    ict_pattern,
-                    strftime('%w', entry_time) as day_of_week,
-                    strftime('%H', entry_time) as hour_of_day,
-                    COUNT(*) as total_trades,
-                    CAST(SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) AS FLOAT) / COUNT(*) * 100 as win_rate
-                FROM trades 
+                SELECT ict_pattern, entry_time, is_win
+                FROM {} 
                 WHERE ict_pattern IS NOT NULL AND is_win IS NOT NULL
-                GROUP BY ict_pattern, day_of_week, hour_of_day
-                ORDER BY ict_pattern, day_of_week, hour_of_day
-                "#
-            )
+                "#,
+                trades_source(&self.pool, self.config.include_archived).await?
+            ))
             .fetch_all(&self.pool)
             .await?;
     
+            // Bucket in the display timezone; keyed by (pattern, day of week, hour)
+            let tz = self.config.display_timezone;
+            let mut buckets: BTreeMap<(String, u32, u32), (u32, u32)> = BTreeMap::new();
+            for row in rows {
+                let entry_time: String = row.get("entry_time");
+                let local_time = match parse_trade_time(&entry_time) {
+                    Some(entry_time) => entry_time.with_timezone(&tz),
+                    None => continue,
+                };
+                
+                let bucket = buckets
+                    .entry((row.get("ict_pattern"), local_time.weekday().num_days_from_sunday(), local_time.hour()))
+                    .or_insert((0, 0));
+                bucket.0 += 1;
+                if row.get::<bool, _>("is_win") {
+                    bucket.1 += 1;
+                }
+            }
+    
             let mut heatmap_data = Vec::new();
-            for row in result {
+            for ((pattern, day_of_week, hour_of_day), (total_trades, winning_trades)) in buckets {
+                let win_rate = winning_trades as f64 / total_trades as f64 * 100.0;
+                
                 let mut data = HashMap::new();
-                data.insert("pattern".to_string(), serde_json::Value::String(row.get("ict_pattern")));
-                data.insert("day_of_week".to_string(), serde_json::Value::String(row.get("day_of_week")));
-                data.insert("hour_of_day".to_string(), serde_json::Value::Number(serde_json::Number::from(row.get::<i64, _>("hour_of_day"))));
-                data.insert("total_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(row.get::<i64, _>("total_trades"))));
-                data.insert("win_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(row.get::<f64, _>("win_rate")).unwrap()));
+                data.insert("pattern".to_string(), serde_json::Value::String(pattern));
+                data.insert("day_of_week".to_string(), serde_json::Value::String(day_of_week.to_string()));
+                data.insert("hour_of_day".to_string(), serde_json::Value::Number(serde_json::Number::from(hour_of_day)));
+                data.insert("total_trades".to_string(), serde_json::Value::Number(serde_json::Number::from(total_trades)));
+                data.insert("win_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(win_rate).unwrap()));
                 
                 heatmap_data.push(data);
             }
@@ -833,6 +1250,8 @@ impl Analyzer {
                 "strategy_analysis": analysis.strategy_analysis,
                 "alerts": self.generate_alerts(&analysis).await?,
                 "market_overview": self.get_market_overview().await?,
+                "daily_statistics": load_dimension_statistics(&self.pool, "day", self.config.include_archived).await?,
+                "strategy_statistics": load_dimension_statistics(&self.pool, "strategy", self.config.include_archived).await?,
             });
     
             Ok(dashboard_data)
@@ -868,7 +1287,7 @@ impl Analyzer {
             }
     
             // Performance alerts
-            if analysis.performance.sharpe_ratio < 1.0 {
+            if analysis.summary.sharpe_ratio < 1.0 {
                 alerts.push(serde_json::json!({
                     "type": "warning",
                     "title": "Suboptimal Risk-Adjusted Returns",
@@ -898,31 +1317,21 @@ impl Analyzer {
     
         // Market overview
         async fn get_market_overview(&self) -> Result<serde_json::Value, SqlxError> {
-            let symbol_performance = sqlx::query(
-                r#"
-                SELECT 
-                    symbol,
-                    COUNT(*) as total_trades,
-                    SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) as winning_trades,
-                    CAST(SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) AS FLOAT) / COUNT(*) * 100 as win_rate,
-                    SUM(profit_loss_money) as net_profit
-                FROM trades 
-                WHERE is_win IS NOT NULL
-                GROUP BY symbol
-                ORDER BY net_profit DESC
-                "#
-            )
-            .fetch_all(&self.pool)
-            .await?;
+            // Read from the precomputed per-symbol aggregates
+            let mut symbol_performance: Vec<_> = load_dimension_statistics(&self.pool, "symbol", self.config.include_archived).await?
+                .into_iter()
+                .filter(|stats| stats.closed_count > 0)
+                .collect();
+            symbol_performance.sort_by(|a, b| b.net_profit.partial_cmp(&a.net_profit).unwrap_or(std::cmp::Ordering::Equal));
     
             let mut market_data = Vec::new();
-            for row in symbol_performance {
+            for stats in symbol_performance {
                 market_data.push(serde_json::json!({
-                    "symbol": row.get::<String, _>("symbol"),
-                    "total_trades": row.get::<i64, _>("total_trades"),
-                    "winning_trades": row.get::<i64, _>("winning_trades"),
-                    "win_rate": row.get::<f64, _>("win_rate"),
-                    "net_profit": row.get::<f64, _>("net_profit")
+                    "symbol": stats.key,
+                    "total_trades": stats.closed_count,
+                    "winning_trades": stats.win_count,
+                    "win_rate": stats.win_rate(),
+                    "net_profit": stats.net_profit
                 }));
             }
     
@@ -934,10 +1343,43 @@ impl Analyzer {
         }
     
         // Helper methods
-        async fn get_all_trades(&self) -> Result<Vec<Trade>, SqlxError> {
-            sqlx::query_as::<_, Trade>("SELECT * FROM trades ORDER BY entry_time DESC")
-                .fetch_all(&self.pool)
-                .await
+        // Stream the projection row by row instead of materializing full
+        // trades, handing it on in batches. Stops early if the receiver is gone.
+        async fn stream_projections(&self, sender: tokio::sync::mpsc::Sender<Vec<TradeProjection>>) -> Result<(), SqlxError> {
+            let mut interner = StringInterner::default();
+            let mut batch = Vec::with_capacity(PROJECTION_BATCH_SIZE);
+            
+            let sql = projection_sql(&trades_source(&self.pool, self.config.include_archived).await?);
+            let mut rows = sqlx::query(&sql).fetch(&self.pool);
+            while let Some(row) = rows.try_next().await? {
+                batch.push(TradeProjection::from_row(&row, &mut interner));
+                if batch.len() == PROJECTION_BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(PROJECTION_BATCH_SIZE));
+                    if sender.send(full).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            
+            if !batch.is_empty() {
+                let _ = sender.send(batch).await;
+            }
+            Ok(())
+        }
+    
+        async fn load_aggregates(&self) -> Result<AnalysisAggregates, SqlxError> {
+            // Every trade has a symbol, so the symbol rows add up to the journal totals
+            let mut totals = DimensionStatistics { key: "all".to_string(), ..Default::default() };
+            let include_archived = self.config.include_archived;
+            for stats in load_dimension_statistics(&self.pool, "symbol", include_archived).await? {
+                totals.absorb(&stats);
+            }
+            
+            Ok(AnalysisAggregates {
+                totals,
+                patterns: load_dimension_statistics(&self.pool, "pattern", include_archived).await?,
+                strategies: load_dimension_statistics(&self.pool, "strategy", include_archived).await?,
+            })
         }
     
         async fn analyze_market_conditions(&self) -> Result<serde_json::Value, SqlxError> {
@@ -969,7 +1411,7 @@ impl Analyzer {
                 "net_profit": analysis.summary.net_profit,
                 "profit_factor": analysis.summary.profit_factor,
                 "max_drawdown": analysis.summary.max_drawdown,
-                "sharpe_ratio": analysis.performance.sharpe_ratio,
+                "sharpe_ratio": analysis.summary.sharpe_ratio,
                 "current_streak": self.calculate_current_streak().await?,
                 "daily_performance": self.calculate_daily_performance().await?,
             }))
@@ -1007,7 +1449,11 @@ impl Analyzer {
         }
     
         async fn calculate_daily_performance(&self) -> Result<serde_json::Value, SqlxError> {
-            let today = Utc::now().format("%Y-%m-%d").to_string();
+            // "Today" is the current calendar day in the display timezone
+            let tz = self.config.display_timezone;
+            let now = Utc::now();
+            let today = now.with_timezone(&tz).format("%Y-%m-%d").to_string();
+            let (day_start, day_end) = timezone::local_day_bounds(now, tz);
             
             let daily_stats = sqlx::query(
                 r#"
@@ -1016,10 +1462,11 @@ impl Analyzer {
                     SUM(CASE WHEN is_win = 1 THEN 1 ELSE 0 END) as winning_trades,
                     SUM(profit_loss_money) as net_profit
                 FROM trades 
-                WHERE DATE(entry_time) = ? AND is_win IS NOT NULL
+                WHERE entry_time >= ? AND entry_time < ? AND is_win IS NOT NULL
                 "#
             )
-            .bind(&today)
+            .bind(timezone::format_utc(day_start))
+            .bind(timezone::format_utc(day_end))
             .fetch_one(&self.pool)
             .await?;
     
@@ -1036,44 +1483,37 @@ impl Analyzer {
         fn calculate_beta(_returns: &[f64]) -> f64 { 1.0 }
         fn calculate_r_squared(_returns: &[f64]) -> f64 { 0.0 }
         fn calculate_information_ratio(_returns: &[f64]) -> f64 { 0.0 }
-        fn calculate_calmar_ratio(_returns: &[f64]) -> f64 { 0.0 }
         fn calculate_ulcer_index(_returns: &[f64]) -> f64 { 0.0 }
         
-        fn calculate_monthly_returns(_trades: &[&Trade]) -> Vec<MonthlyReturn> { vec![] }
-        fn calculate_daily_returns(_trades: &[&Trade]) -> Vec<f64> { vec![] }
+        fn calculate_monthly_returns(trades: &[&TradeProjection]) -> Vec<MonthlyReturn> { Self::monthly_buckets(trades, Tz::UTC, None) }
+        fn calculate_daily_returns(_trades: &[&TradeProjection]) -> Vec<f64> { vec![] }
         
-        fn analyze_emotional_impact(_trades: &[Trade]) -> EmotionalAnalysis { EmotionalAnalysis::default() }
-        fn calculate_consistency_metrics(_trades: &[Trade]) -> ConsistencyMetrics { ConsistencyMetrics::default() }
-        fn identify_behavioral_patterns(_trades: &[Trade]) -> BehavioralPatterns { BehavioralPatterns::default() }
-        fn analyze_stress_levels(_trades: &[Trade]) -> StressAnalysis { StressAnalysis::default() }
+        fn analyze_emotional_impact(_trades: &[&TradeProjection]) -> EmotionalAnalysis { EmotionalAnalysis::default() }
+        fn calculate_consistency_metrics(_trades: &[&TradeProjection]) -> ConsistencyMetrics { ConsistencyMetrics::default() }
+        fn identify_behavioral_patterns(_trades: &[&TradeProjection]) -> BehavioralPatterns { BehavioralPatterns::default() }
+        fn analyze_stress_levels(_trades: &[&TradeProjection]) -> StressAnalysis { StressAnalysis::default() }
         
-        fn calculate_entry_accuracy(_trades: &[&Trade]) -> f64 { 0.0 }
-        fn calculate_exit_accuracy(_trades: &[&Trade]) -> f64 { 0.0 }
-        fn calculate_timing_efficiency(_trades: &[&Trade]) -> f64 { 0.0 }
-        fn analyze_price_action(_trades: &[&Trade]) -> PriceActionAnalysis { PriceActionAnalysis::default() }
-        fn analyze_indicator_performance(_trades: &[&Trade]) -> IndicatorAnalysis { IndicatorAnalysis::default() }
+        fn calculate_entry_accuracy(_trades: &[&TradeProjection]) -> f64 { 0.0 }
+        fn calculate_exit_accuracy(_trades: &[&TradeProjection]) -> f64 { 0.0 }
+        fn calculate_timing_efficiency(_trades: &[&TradeProjection]) -> f64 { 0.0 }
+        fn analyze_price_action(_trades: &[&TradeProjection]) -> PriceActionAnalysis { PriceActionAnalysis::default() }
+        fn analyze_indicator_performance(_trades: &[&TradeProjection]) -> IndicatorAnalysis { IndicatorAnalysis::default() }
         
-        fn calculate_pattern_performance(_trades: &[&Trade]) -> HashMap<String, PatternPerformance> { HashMap::new() }
-        fn analyze_timeframe_performance(_trades: &[&Trade]) -> HashMap<String, TimeframeAnalysis> { HashMap::new() }
-        fn analyze_pattern_combinations(_trades: &[&Trade]) -> CombinationAnalysis { CombinationAnalysis::default() }
-        fn calculate_ict_win_rates(_trades: &[&Trade]) -> HashMap<String, f64> { HashMap::new() }
-        fn generate_ict_heatmap(_trades: &[&Trade]) -> Vec<ICTHeatmapData> { vec![] }
+        fn analyze_timeframe_performance(_trades: &[&TradeProjection]) -> HashMap<String, TimeframeAnalysis> { HashMap::new() }
+        fn analyze_pattern_combinations(_trades: &[&TradeProjection]) -> CombinationAnalysis { CombinationAnalysis::default() }
+        fn generate_ict_heatmap(_trades: &[&TradeProjection]) -> Vec<ICTHeatmapData> { vec![] }
         
-        fn analyze_hourly_performance(_trades: &[&Trade]) -> HashMap<u32, HourlyPerformance> { HashMap::new() }
-        fn analyze_daily_performance(_trades: &[&Trade]) -> HashMap<String, DailyPerformance> { HashMap::new() }
-        fn analyze_monthly_performance(_trades: &[&Trade]) -> HashMap<String, MonthlyPerformance> { HashMap::new() }
-        fn analyze_seasonal_patterns(_trades: &[&Trade]) -> SeasonalAnalysis { SeasonalAnalysis::default() }
-        fn analyze_trading_sessions(_trades: &[&Trade]) -> SessionAnalysis { SessionAnalysis::default() }
+        fn analyze_monthly_performance(_trades: &[&TradeProjection]) -> HashMap<String, MonthlyPerformance> { HashMap::new() }
+        fn analyze_seasonal_patterns(_trades: &[&TradeProjection]) -> SeasonalAnalysis { SeasonalAnalysis::default() }
         
-        fn analyze_position_sizing(_trades: &[&Trade]) -> PositionSizingAnalysis { PositionSizingAnalysis::default() }
-        fn calculate_risk_metrics(_trades: &[&Trade]) -> RiskMetrics { RiskMetrics::default() }
-        fn calculate_var_analysis(_trades: &[&Trade]) -> VaRAnalysis { VaRAnalysis::default() }
-        fn perform_stress_testing(_trades: &[&Trade]) -> StressTesting { StressTesting::default() }
+        fn analyze_position_sizing(_trades: &[&TradeProjection]) -> PositionSizingAnalysis { PositionSizingAnalysis::default() }
+        fn calculate_risk_metrics(_trades: &[&TradeProjection]) -> RiskMetrics { RiskMetrics::default() }
+        fn calculate_var_analysis(_trades: &[&TradeProjection]) -> VaRAnalysis { VaRAnalysis::default() }
+        fn perform_stress_testing(_trades: &[&TradeProjection]) -> StressTesting { StressTesting::default() }
         
-        fn calculate_strategy_performance(_trades: &[&Trade]) -> HashMap<String, StrategyPerformance> { HashMap::new() }
-        fn calculate_strategy_correlation(_trades: &[&Trade]) -> HashMap<String, f64> { HashMap::new() }
-        fn calculate_diversification_benefit(_trades: &[&Trade]) -> f64 { 0.0 }
-        fn calculate_optimal_strategy_mix(_trades: &[&Trade]) -> HashMap<String, f64> { HashMap::new() }
+        fn calculate_strategy_correlation(_trades: &[&TradeProjection]) -> HashMap<String, f64> { HashMap::new() }
+        fn calculate_diversification_benefit(_trades: &[&TradeProjection]) -> f64 { 0.0 }
+        fn calculate_optimal_strategy_mix(_trades: &[&TradeProjection]) -> HashMap<String, f64> { HashMap::new() }
     }
     
     // Default implementations
@@ -1109,14 +1549,77 @@ impl Analyzer {
                 sharpe_ratio: 0.0,
                 max_drawdown: 0.0,
                 recovery_factor: 0.0,
+                calmar_ratio: 0.0,
                 risk_of_ruin: 0.0,
             }
         }
     }
     
-    // ... Additional default implementations for all structs
-    
     // Export for use in main application
     pub use TradeAnalysis;
     pub use AnalysisSummary;
-    pub use PerformanceMetrics;
\ No newline at end of file
+    pub use PerformanceMetrics;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    fn trade(id: u32, entry: Option<&str>, exit: Option<&str>, profit: f64) -> TradeProjection {
+        TradeProjection {
+            id,
+            entry_time: entry.map(at),
+            exit_time: exit.map(at),
+            is_win: Some(profit > 0.0),
+            profit_loss_money: Some(profit),
+            strategy_name: Some(Arc::from("Breakout")),
+        }
+    }
+
+    // Listed by entry: the winner opened first but closed last. Trade 4 has
+    // no times, so it counts in the totals but has no place on the curve.
+    fn trades() -> Vec<TradeProjection> {
+        vec![
+            trade(1, Some("2024-01-01T09:00:00Z"), Some("2024-01-05T10:00:00Z"), 300.0),
+            trade(2, Some("2024-01-02T09:00:00Z"), Some("2024-01-02T10:00:00Z"), -200.0),
+            trade(3, Some("2024-01-03T09:00:00Z"), Some("2024-01-03T10:00:00Z"), 150.0),
+            trade(4, None, None, 1000.0),
+        ]
+    }
+
+    fn expected_sharpe(returns: &[f64]) -> f64 {
+        (mean(returns) - 0.02) / standard_deviation(returns, None)
+    }
+
+    #[test]
+    fn summary_sharpe_uses_the_curve_returns_in_close_order() {
+        let summary = Analyzer::summarize(&trades());
+
+        assert_eq!(summary.net_profit, 1250.0);
+        assert_eq!(summary.max_drawdown, 200.0);
+        // Net profit of the curve (250), not of the totals
+        assert_eq!(summary.recovery_factor, 1.25);
+        assert!((summary.sharpe_ratio - expected_sharpe(&[-200.0, 150.0, 300.0])).abs() < 1e-12);
+    }
+
+    #[test]
+    fn strategy_recovery_factor_and_sharpe_come_from_its_curve() {
+        let trades = trades();
+        let mut totals = DimensionStatistics { key: "Breakout".to_string(), ..Default::default() };
+        for trade in &trades {
+            totals.record(trade.is_win, trade.profit_loss_money);
+        }
+        let performance = Analyzer::strategy_breakdown(&trades, &[totals]);
+
+        assert_eq!(performance.len(), 1);
+        let breakout = &performance[0];
+        assert_eq!((breakout.total_trades, breakout.net_profit), (4, 1250.0));
+        assert_eq!(breakout.max_drawdown, 200.0);
+        // Was the totals' 1250 over the curve's drawdown
+        assert_eq!(breakout.recovery_factor, 1.25);
+        assert!((breakout.sharpe_ratio - expected_sharpe(&[-200.0, 150.0, 300.0])).abs() < 1e-12);
+    }
+}
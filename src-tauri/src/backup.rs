@@ -9,6 +9,8 @@ use walkdir::WalkDir;
 use crypto_hash::{Algorithm, hex_digest};
 use tokio::task;
 
+use crate::paths;
+
 // Backup configuration
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BackupConfig {
@@ -68,16 +70,20 @@ pub struct BackupResult {
 // Backup manager
 pub struct BackupManager {
     config: BackupConfig,
+    root: PathBuf,
     backup_dir: PathBuf,
     encryption_key: Option<Vec<u8>>,
 }
 
 impl BackupManager {
-    pub fn new(backup_dir: impl AsRef<Path>) -> Self {
+    // `root` is the workspace directory that backups are taken from and
+    // restored into; installed plugins come from the application data root
+    pub fn new(root: impl AsRef<Path>, backup_dir: impl AsRef<Path>) -> Self {
         let backup_dir = backup_dir.as_ref().to_path_buf();
         
         Self {
             config: BackupConfig::default(),
+            root: root.as_ref().to_path_buf(),
             backup_dir,
             encryption_key: None,
         }
@@ -217,10 +223,11 @@ impl BackupManager {
             "data/trading_journal.db",
             "data/trading_journal.db-wal",
             "data/trading_journal.db-shm",
+            "data/archive.db",
         ];
         
         for db_file in db_files {
-            let path = PathBuf::from(db_file);
+            let path = self.root.join(db_file);
             if path.exists() {
                 if let Ok(file_info) = self.add_file_to_zip(zip, &path, "data/", options).await {
                     files.push(file_info);
@@ -244,7 +251,7 @@ impl BackupManager {
         let mut files = Vec::new();
         
         // Export schema to JSON
-        let schema_path = PathBuf::from("data/schema_backup.json");
+        let schema_path = self.root.join("data/schema_backup.json");
         self.export_schema_to_file(&schema_path).await?;
         
         if let Ok(file_info) = self.add_file_to_zip(zip, &schema_path, "data/", options).await {
@@ -282,7 +289,7 @@ impl BackupManager {
         let image_dirs = vec!["data/images", "data/thumbnails"];
         
         for image_dir in image_dirs {
-            let path = PathBuf::from(image_dir);
+            let path = self.root.join(image_dir);
             if path.exists() {
                 let dir_files = self.backup_directory(zip, &path, "images/", options).await?;
                 files.extend(dir_files);
@@ -300,10 +307,10 @@ impl BackupManager {
     ) -> Result<Vec<BackupFileInfo>, Box<dyn std::error::Error>> {
         let mut files = Vec::new();
         
-        let plugin_dirs = vec!["plugins/native", "plugins/scripts"];
+        let plugin_dirs = vec!["native", "scripts"];
         
         for plugin_dir in plugin_dirs {
-            let path = PathBuf::from(plugin_dir);
+            let path = paths::plugins_dir().join(plugin_dir);
             if path.exists() {
                 let dir_files = self.backup_directory(zip, &path, "plugins/", options).await?;
                 files.extend(dir_files);
@@ -322,14 +329,13 @@ impl BackupManager {
         let mut files = Vec::new();
         
         let config_files = vec![
-            "config/app_settings.json",
-            "config/theme_settings.json",
-            "config/window_settings.json",
-            "backups/backup_config.json",
+            self.root.join("config/app_settings.json"),
+            self.root.join("config/theme_settings.json"),
+            self.root.join("config/window_settings.json"),
+            self.backup_dir.join("backup_config.json"),
         ];
         
-        for config_file in config_files {
-            let path = PathBuf::from(config_file);
+        for path in config_files {
             if path.exists() {
                 if let Ok(file_info) = self.add_file_to_zip(zip, &path, "config/", options).await {
                     files.push(file_info);
@@ -611,7 +617,7 @@ impl BackupManager {
                 .collect();
             
             for db_file in db_files {
-                let target_path = PathBuf::from(&db_file.original_path);
+                let target_path = self.restore_target(&db_file.original_path);
                 if let Some(parent) = target_path.parent() {
                     tokio::fs::create_dir_all(parent).await?;
                 }
@@ -624,6 +630,18 @@ impl BackupManager {
             Ok(())
         }
         
+        // Archive paths are relative; images belong under the workspace data
+        // directory and plugins under the application data root
+        fn restore_target(&self, original_path: &str) -> PathBuf {
+            if original_path.starts_with("images/") {
+                self.root.join("data").join(original_path)
+            } else if original_path.starts_with("plugins/") {
+                paths::data_root().join(original_path)
+            } else {
+                self.root.join(original_path)
+            }
+        }
+        
         async fn restore_images(
             &self,
             files: &[RestoredFile],
@@ -640,7 +658,7 @@ impl BackupManager {
                 .collect();
             
             for image_file in image_files {
-                let target_path = PathBuf::from(&image_file.original_path);
+                let target_path = self.restore_target(&image_file.original_path);
                 if let Some(parent) = target_path.parent() {
                     tokio::fs::create_dir_all(parent).await?;
                 }
@@ -668,7 +686,7 @@ impl BackupManager {
                 .collect();
             
             for plugin_file in plugin_files {
-                let target_path = PathBuf::from(&plugin_file.original_path);
+                let target_path = self.restore_target(&plugin_file.original_path);
                 if let Some(parent) = target_path.parent() {
                     tokio::fs::create_dir_all(parent).await?;
                 }
@@ -692,7 +710,7 @@ impl BackupManager {
                 .collect();
             
             for config_file in config_files {
-                let target_path = PathBuf::from(&config_file.original_path);
+                let target_path = self.restore_target(&config_file.original_path);
                 if let Some(parent) = target_path.parent() {
                     tokio::fs::create_dir_all(parent).await?;
                 }
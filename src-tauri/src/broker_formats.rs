@@ -0,0 +1,746 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::csv_import::{ColumnMapping, CsvOptions, ImportProfile, ProfileImporter, ValueTransform};
+use crate::database::NewTrade;
+use crate::importer::{
+    BrokerImporter, CashFlow, Execution, ImportContext, ImportFile, ImportRow, ImportRowError, ImportedTrade,
+    ParsedImport, PositionClose, TicketScope,
+};
+use crate::mt_statement::{self, MetaTraderImporter};
+
+// Flex statements are written in US Eastern time unless the query says otherwise
+const FLEX_TIMEZONE: &str = "America/New_York";
+const FLEX_TIME_FORMATS: &[&str] = &["%Y%m%d;%H%M%S", "%Y-%m-%d;%H:%M:%S", "%Y%m%d %H%M%S", "%Y-%m-%d %H:%M:%S"];
+const FLEX_DATE_FORMATS: &[&str] = &["%Y%m%d", "%Y-%m-%d"];
+// Quantities below this are treated as a flat position
+const FLAT: f64 = 1e-9;
+
+// Importers that ship with the app, in detection tie-break order
+pub fn builtin() -> Vec<Arc<dyn BrokerImporter>> {
+    vec![
+        Arc::new(MetaTraderImporter),
+        Arc::new(IbkrFlexImporter),
+        Arc::new(ctrader()),
+        Arc::new(tradelocker()),
+        Arc::new(dxtrade()),
+    ]
+}
+
+fn mapping(column: &str, field: &str, transforms: Vec<ValueTransform>) -> ColumnMapping {
+    ColumnMapping { column: column.to_string(), field: field.to_string(), transforms }
+}
+
+// Long / short and one-letter sides; Buy and Sell pass through
+fn sides() -> Vec<ValueTransform> {
+    let values = [("long", "Buy"), ("short", "Sell"), ("b", "Buy"), ("s", "Sell")]
+        .into_iter()
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .collect();
+    vec![ValueTransform::Map { values, case_sensitive: false }]
+}
+
+// "1.50 Lots", "100,000"
+fn quantity() -> Vec<ValueTransform> {
+    vec![
+        ValueTransform::Replace { from: "Lots".to_string(), to: String::new() },
+        ValueTransform::Number { decimal_separator: '.', thousands_separator: Some(',') },
+    ]
+}
+
+fn date(format: &str) -> Vec<ValueTransform> {
+    vec![ValueTransform::Date { format: format.to_string() }]
+}
+
+fn profile(name: &str, mappings: Vec<ColumnMapping>) -> ImportProfile {
+    ImportProfile {
+        name: name.to_string(),
+        csv: CsvOptions::default(),
+        mappings,
+        defaults: HashMap::new(),
+        timezone: None,
+    }
+}
+
+// cTrader "History" tab export. Time headers carry the zone the platform was
+// set to, e.g. "Opening Time (UTC+2)", which the profile importer picks up.
+fn ctrader() -> ProfileImporter {
+    let time = "%d/%m/%Y %H:%M:%S%.f";
+    let profile = profile("cTrader history", vec![
+        mapping("Position ID", "external_id", Vec::new()),
+        mapping("Symbol", "symbol", vec![ValueTransform::Uppercase]),
+        mapping("Opening Direction", "trade_type", sides()),
+        mapping("Closing Quantity", "volume", quantity()),
+        mapping("Entry Price", "entry_price", Vec::new()),
+        mapping("Closing Price", "exit_price", Vec::new()),
+        mapping("Opening Time", "entry_time", date(time)),
+        mapping("Closing Time", "exit_time", date(time)),
+        mapping("Commissions", "commission", Vec::new()),
+        mapping("Swap", "swap", Vec::new()),
+        mapping("Gross", "profit", Vec::new()),
+    ]);
+
+    ProfileImporter::new("ctrader", profile)
+        .with_signature(&["Opening Direction", "Closing Quantity", "Position ID"], 80)
+}
+
+// TradeLocker "Positions History" export
+fn tradelocker() -> ProfileImporter {
+    let time = "%Y/%m/%d %H:%M:%S";
+    let profile = profile("TradeLocker positions history", vec![
+        mapping("Position ID", "external_id", Vec::new()),
+        mapping("Instrument", "symbol", vec![ValueTransform::Uppercase]),
+        mapping("Side", "trade_type", sides()),
+        mapping("Amount", "volume", quantity()),
+        mapping("Open Price", "entry_price", Vec::new()),
+        mapping("Close Price", "exit_price", Vec::new()),
+        mapping("Open Time", "entry_time", date(time)),
+        mapping("Close Time", "exit_time", date(time)),
+        mapping("Stop Loss", "sl", Vec::new()),
+        mapping("Take Profit", "tp", Vec::new()),
+        mapping("Fee", "commission", Vec::new()),
+        mapping("Swap", "swap", Vec::new()),
+        mapping("Profit", "profit", Vec::new()),
+    ]);
+
+    ProfileImporter::new("tradelocker", profile)
+        .with_signature(&["Position ID", "Instrument", "Side", "Amount"], 80)
+}
+
+// DXtrade "Positions History" export; times are ISO 8601 with an offset
+fn dxtrade() -> ProfileImporter {
+    let profile = profile("DXtrade positions history", vec![
+        mapping("Position Code", "external_id", Vec::new()),
+        mapping("Symbol", "symbol", vec![ValueTransform::Uppercase]),
+        mapping("Side", "trade_type", sides()),
+        mapping("Quantity", "volume", quantity()),
+        mapping("Open Price", "entry_price", Vec::new()),
+        mapping("Close Price", "exit_price", Vec::new()),
+        mapping("Open Time", "entry_time", Vec::new()),
+        mapping("Close Time", "exit_time", Vec::new()),
+        mapping("Commission", "commission", Vec::new()),
+        mapping("Swap", "swap", Vec::new()),
+        mapping("P&L", "profit", Vec::new()),
+    ]);
+
+    ProfileImporter::new("dxtrade", profile)
+        .with_signature(&["Position Code", "Symbol", "Side", "Quantity"], 80)
+}
+
+// Interactive Brokers Flex Query (XML) with the Trades and, optionally, Cash
+// Transactions sections. IBKR reports fills, so round-trip trades are rebuilt
+// per account and symbol: a trade opens when the position leaves flat and
+// closes when it returns to flat. The ticket is the id of the opening fill,
+// which keeps re-imports of overlapping periods idempotent. Closing fills of a
+// position opened before the statement period become a PositionClose. A file
+// may hold several statements, each scoped to its own account. Cancellation
+// rows ("SELL (Ca.)") take the fill they cancel out instead of adding one.
+pub struct IbkrFlexImporter;
+
+impl BrokerImporter for IbkrFlexImporter {
+    fn id(&self) -> &str {
+        "ibkr_flex"
+    }
+
+    fn name(&self) -> &str {
+        "Interactive Brokers Flex Query"
+    }
+
+    fn detect(&self, file: &ImportFile) -> u8 {
+        let text = file.text();
+        if text.contains("<FlexQueryResponse") {
+            100
+        } else if text.contains("<FlexStatement") {
+            80
+        } else {
+            0
+        }
+    }
+
+    fn parse(&self, file: &ImportFile, context: &ImportContext) -> Result<ParsedImport, String> {
+        let text = file.text();
+        if self.detect(file) == 0 {
+            return Err("Not an Interactive Brokers Flex statement".to_string());
+        }
+
+        let timezone = context.timezone.clone().unwrap_or_else(|| FLEX_TIMEZONE.to_string());
+        let mut parsed = ParsedImport::default();
+        let mut fills = Vec::new();
+        let mut cash_index = 0;
+
+        for (account, statement) in flex_statements(text) {
+            let scope = TicketScope::new(self.id(), account);
+
+            // Orders and closed lots repeat the executions; only executions count
+            let trades = xml_elements(statement, "Trade").into_iter()
+                .filter(|a| a.get("levelOfDetail").map_or(true, |level| level.eq_ignore_ascii_case("EXECUTION")));
+            for attributes in trades {
+                parsed.total_rows += 1;
+                match Fill::parse(parsed.total_rows, &attributes, &scope, context) {
+                    Ok(fill) => fills.push(fill),
+                    Err(error) => parsed.errors.push(error),
+                }
+            }
+
+            let cash = xml_elements(statement, "CashTransaction").into_iter()
+                .filter(|a| a.get("levelOfDetail").map_or(true, |level| level.eq_ignore_ascii_case("DETAIL")));
+            for attributes in cash {
+                cash_index += 1;
+                match cash_flow(&attributes, &timezone) {
+                    Some(flow) => parsed.cash_flows.push((cash_index, CashFlow { scope: scope.clone(), ..flow })),
+                    None => parsed.warnings.push(format!("Cash transaction {} could not be read", cash_index)),
+                }
+            }
+        }
+        // Stable, so fills with the same timestamp keep file order
+        fills.sort_by(|a, b| a.time.cmp(&b.time));
+        let fills = without_cancelled(fills, &mut parsed.warnings);
+
+        // Positions and closes of positions opened before this file, per
+        // account and symbol
+        let mut open: HashMap<(TicketScope, String), Position> = HashMap::new();
+        let mut earlier: HashMap<(TicketScope, String), PositionClose> = HashMap::new();
+        for fill in fills {
+            let key = (fill.scope.clone(), fill.symbol.clone());
+            if fill.closing && !open.contains_key(&key) {
+                let close = earlier.entry(key).or_insert_with(|| PositionClose {
+                    symbol: fill.symbol.clone(),
+                    trade_type: if fill.side == "Buy" { "Sell" } else { "Buy" }.to_string(),
+                    fills: Vec::new(),
+                    profit: None,
+                });
+                close.profit = Some(close.profit.unwrap_or(0.0) + fill.realized);
+                close.fills.push((fill.row, fill.into_execution(None, &timezone)));
+                continue;
+            }
+
+            let ticket = match open.get_mut(&key) {
+                Some(position) if position.side == fill.side => {
+                    position.add(&fill);
+                    Some(position.ticket.clone())
+                }
+                Some(position) => {
+                    let reversal = position.reduce(&fill);
+                    let ticket = position.ticket.clone();
+                    if position.remaining <= FLAT {
+                        if let Some(position) = open.remove(&key) {
+                            parsed.rows.push(position.into_row(true, &timezone));
+                        }
+                        if reversal > FLAT {
+                            open.insert(key, Position::open(&fill, reversal, 0.0));
+                        }
+                    }
+                    Some(ticket)
+                }
+                None => {
+                    let position = Position::open(&fill, fill.quantity, fill.commission);
+                    let ticket = position.ticket.clone();
+                    open.insert(key, position);
+                    Some(ticket)
+                }
+            };
+
+            parsed.executions.push((fill.row, fill.into_execution(ticket, &timezone)));
+        }
+        let mut earlier: Vec<PositionClose> = earlier.into_values().collect();
+        earlier.sort_by_key(|close| close.fills[0].0);
+        parsed.closes = earlier;
+
+        let mut still_open: Vec<Position> = open.into_values().collect();
+        still_open.sort_by_key(|position| position.row);
+        for position in still_open {
+            if position.exit_quantity > FLAT {
+                parsed.warnings.push(format!(
+                    "{} position {} is partly closed; it is imported as open until the file shows it flat",
+                    position.symbol, position.ticket
+                ));
+            }
+            parsed.rows.push(position.into_row(false, &timezone));
+        }
+
+        Ok(parsed)
+    }
+}
+
+// Each FlexStatement with its account; a file without statement elements is
+// read as one statement of an unknown account
+fn flex_statements(text: &str) -> Vec<(Option<String>, &str)> {
+    let starts: Vec<usize> = text.match_indices("<FlexStatement")
+        .map(|(start, _)| start)
+        .filter(|start| text[start + "<FlexStatement".len()..].starts_with(|c: char| c.is_whitespace() || c == '>'))
+        .collect();
+    if starts.is_empty() {
+        return vec![(None, text)];
+    }
+
+    starts.iter().enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(text.len());
+            let statement = &text[start..end];
+            let (attributes, _) = xml_attributes(&statement["<FlexStatement".len()..]);
+            let account = attributes.get("accountId").cloned().filter(|account| !account.is_empty());
+            (account, statement)
+        })
+        .collect()
+}
+
+// Drops each cancellation together with the fill it cancels: the one named
+// by origTradeID, or else the latest earlier fill of the same account,
+// symbol, side, quantity and price
+fn without_cancelled(fills: Vec<Fill>, warnings: &mut Vec<String>) -> Vec<Fill> {
+    let (cancellations, mut fills): (Vec<Fill>, Vec<Fill>) = fills.into_iter().partition(|fill| fill.cancelled);
+    for cancellation in cancellations {
+        let cancelled = fills.iter().rposition(|fill| {
+            fill.scope == cancellation.scope
+                && match &cancellation.original {
+                    Some(original) => fill.id == *original,
+                    None => fill.symbol == cancellation.symbol
+                        && fill.side == cancellation.side
+                        && (fill.quantity - cancellation.quantity).abs() <= FLAT
+                        && fill.price == cancellation.price
+                        && fill.time <= cancellation.time,
+                }
+        });
+        match cancelled {
+            Some(index) => {
+                fills.remove(index);
+            }
+            None => warnings.push(format!(
+                "Cancellation {} of a {} {} fill matches no fill in this file; it is skipped",
+                cancellation.id, cancellation.symbol, cancellation.side
+            )),
+        }
+    }
+    fills
+}
+
+struct Fill {
+    row: usize,
+    id: String,
+    scope: TicketScope,
+    symbol: String,
+    side: &'static str,
+    quantity: f64,
+    price: f64,
+    time: String,
+    commission: f64,
+    realized: f64,
+    closing: bool,
+    // A "(Ca.)" row, and the fill it cancels when the file names it
+    cancelled: bool,
+    original: Option<String>,
+}
+
+impl Fill {
+    fn parse(
+        row: usize,
+        attributes: &HashMap<String, String>,
+        scope: &TicketScope,
+        context: &ImportContext,
+    ) -> Result<Self, ImportRowError> {
+        let error = |field: &str, message: String| ImportRowError { row, field: Some(field.to_string()), message };
+        let text = |name: &str| attributes.get(name).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let number = |name: &str| -> Result<Option<f64>, ImportRowError> {
+            text(name)
+                .map(|value| value.parse::<f64>().map_err(|_| error(name, format!("'{}' is not a number", value))))
+                .transpose()
+        };
+
+        let id = text("tradeID").or_else(|| text("transactionID"))
+            .ok_or_else(|| error("tradeID", "Missing trade id".to_string()))?;
+        let symbol = text("symbol").ok_or_else(|| error("symbol", "Missing symbol".to_string()))?;
+        // "SELL (Ca.)" cancels an earlier sell of the same trade
+        let buy_sell = text("buySell").map(|s| s.to_ascii_uppercase());
+        let side = match &buy_sell {
+            Some(s) if s.starts_with("BUY") => "Buy",
+            Some(s) if s.starts_with("SELL") => "Sell",
+            other => return Err(error("buySell", format!("Unknown side '{}'", other.clone().unwrap_or_default()))),
+        };
+        let time = text("dateTime")
+            .or_else(|| text("tradeDate"))
+            .and_then(flex_time)
+            .ok_or_else(|| error("dateTime", "Missing or unreadable trade time".to_string()))?;
+
+        Ok(Fill {
+            row,
+            id: id.to_string(),
+            scope: scope.clone(),
+            symbol: context.map_symbol(symbol),
+            side,
+            quantity: number("quantity")?.map(f64::abs).ok_or_else(|| error("quantity", "Missing quantity".to_string()))?,
+            price: number("tradePrice")?.ok_or_else(|| error("tradePrice", "Missing price".to_string()))?,
+            time,
+            commission: number("ibCommission")?.unwrap_or(0.0),
+            realized: number("fifoPnlRealized")?.unwrap_or(0.0),
+            closing: text("openCloseIndicator").map_or(false, |flag| flag.contains('C')),
+            cancelled: buy_sell.is_some_and(|s| s.contains("(CA")),
+            original: text("origTradeID").filter(|id| *id != "0").map(str::to_string),
+        })
+    }
+
+    fn into_execution(self, ticket: Option<String>, timezone: &str) -> Execution {
+        Execution {
+            external_id: Some(self.id),
+            position_id: ticket,
+            symbol: self.symbol,
+            side: self.side.to_string(),
+            volume: self.quantity,
+            price: self.price,
+            executed_at: self.time,
+            commission: Some(self.commission),
+            scope: self.scope,
+            source_timezone: Some(timezone.to_string()),
+        }
+    }
+}
+
+// A position being rebuilt from its fills
+struct Position {
+    ticket: String,
+    scope: TicketScope,
+    row: usize,
+    symbol: String,
+    side: &'static str,
+    entry_time: String,
+    remaining: f64,
+    entry_quantity: f64,
+    entry_value: f64,
+    exit_quantity: f64,
+    exit_value: f64,
+    exit_time: Option<String>,
+    commission: f64,
+    profit: f64,
+}
+
+impl Position {
+    fn open(fill: &Fill, quantity: f64, commission: f64) -> Self {
+        Position {
+            ticket: format!("ibkr-{}", fill.id),
+            scope: fill.scope.clone(),
+            row: fill.row,
+            symbol: fill.symbol.clone(),
+            side: fill.side,
+            entry_time: fill.time.clone(),
+            remaining: quantity,
+            entry_quantity: quantity,
+            entry_value: quantity * fill.price,
+            exit_quantity: 0.0,
+            exit_value: 0.0,
+            exit_time: None,
+            commission,
+            profit: 0.0,
+        }
+    }
+
+    fn add(&mut self, fill: &Fill) {
+        self.remaining += fill.quantity;
+        self.entry_quantity += fill.quantity;
+        self.entry_value += fill.quantity * fill.price;
+        self.commission += fill.commission;
+    }
+
+    // Applies an opposite fill and returns what is left of it once the
+    // position is flat (a reversal opens a new position with that amount)
+    fn reduce(&mut self, fill: &Fill) -> f64 {
+        let closed = fill.quantity.min(self.remaining);
+        self.remaining -= closed;
+        self.exit_quantity += closed;
+        self.exit_value += closed * fill.price;
+        self.exit_time = Some(fill.time.clone());
+        self.commission += fill.commission;
+        self.profit += fill.realized;
+        fill.quantity - closed
+    }
+
+    fn into_row(self, closed: bool, timezone: &str) -> ImportRow {
+        let trade = new_trade(
+            self.symbol,
+            self.side,
+            self.entry_quantity,
+            self.entry_value / self.entry_quantity,
+            self.entry_time,
+            Some(self.commission).filter(|c| *c != 0.0),
+            timezone,
+        );
+
+        let mut record = ImportedTrade::new(trade);
+        record.external_id = Some(self.ticket);
+        record.scope = self.scope;
+        if closed {
+            record.exit_price = Some(self.exit_value / self.exit_quantity);
+            record.exit_time = self.exit_time;
+            // fifoPnlRealized is net of every commission of the trade (IBKR
+            // puts opening ones in the cost basis); profit is stored gross
+            // like the other importers, with the commission beside it
+            record.profit = Some(self.profit - self.commission);
+        }
+
+        ImportRow { row: self.row, record }
+    }
+}
+
+fn new_trade(
+    symbol: String,
+    side: &str,
+    volume: f64,
+    entry_price: f64,
+    entry_time: String,
+    commission: Option<f64>,
+    timezone: &str,
+) -> NewTrade {
+    NewTrade {
+        symbol,
+        trade_type: side.to_string(),
+        volume,
+        entry_price,
+        sl: None,
+        tp: None,
+        entry_time,
+        notes: None,
+        commission,
+        swap: None,
+        ict_pattern: None,
+        pattern_type: None,
+        pattern_size: None,
+        pattern_timeframe: None,
+        pattern_combination: None,
+        chart_explanation: None,
+        strategy_name: None,
+        emotion: None,
+        confidence_level: None,
+        market_condition: None,
+        session: None,
+        entry_image: None,
+        exit_image: None,
+        analysis_image: None,
+        rsi: None,
+        macd: None,
+        moving_average: None,
+        support_level: None,
+        resistance_level: None,
+        source_timezone: Some(timezone.to_string()),
+    }
+}
+
+fn cash_flow(attributes: &HashMap<String, String>, timezone: &str) -> Option<CashFlow> {
+    let amount: f64 = attributes.get("amount")?.trim().parse().ok()?;
+    let kind = attributes.get("type").map_or("", String::as_str);
+    let kind = match kind {
+        k if k.starts_with("Deposits") && amount >= 0.0 => "deposit",
+        k if k.starts_with("Deposits") => "withdrawal",
+        k if k.contains("Dividend") => "dividend",
+        k if k.contains("Interest") => "interest",
+        k if k.contains("Tax") => "tax",
+        k if k.contains("Fee") => "fee",
+        _ => "other",
+    };
+    let occurred_at = ["dateTime", "settleDate", "reportDate"].iter()
+        .find_map(|name| attributes.get(*name).and_then(|value| flex_time(value.trim())))?;
+
+    Some(CashFlow {
+        kind: kind.to_string(),
+        amount,
+        occurred_at,
+        comment: attributes.get("description").cloned().filter(|d| !d.is_empty()),
+        external_id: attributes.get("transactionID").map(|id| format!("ibkr-cash-{}", id)),
+        scope: TicketScope::default(),
+        source_timezone: Some(timezone.to_string()),
+    })
+}
+
+fn flex_time(value: &str) -> Option<String> {
+    FLEX_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            FLEX_DATE_FORMATS.iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+// Attributes of every <tag .../> element. Flex statements keep all values in
+// attributes, so this is all the XML reading they need.
+fn xml_elements(xml: &str, tag: &str) -> Vec<HashMap<String, String>> {
+    let open = format!("<{}", tag);
+    let mut elements = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // "<Trade" is also the start of "<Trades>"
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>') {
+            continue;
+        }
+        let (attributes, consumed) = xml_attributes(rest);
+        elements.push(attributes);
+        rest = &rest[consumed..];
+    }
+
+    elements
+}
+
+// name="value" pairs up to the tag's closing '>', and the bytes consumed
+fn xml_attributes(text: &str) -> (HashMap<String, String>, usize) {
+    let mut attributes = HashMap::new();
+    let mut name = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '>' => return (attributes, i + 1),
+            '"' | '\'' => {
+                let start = i + 1;
+                let end = text[start..].find(c).map_or(text.len(), |len| start + len);
+                attributes.insert(std::mem::take(&mut name), mt_statement::decode_entities(&text[start..end]));
+                // Continue after the closing quote
+                chars.find(|(j, _)| *j >= end);
+            }
+            '=' | '/' => {}
+            c if c.is_whitespace() => {}
+            c => name.push(c),
+        }
+    }
+
+    (attributes, text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::importer::ImporterRegistry;
+
+    const CTRADER: &str = "\u{feff}Position ID,Symbol,Opening Direction,Opening Time (UTC+2),Closing Time (UTC+2),Entry Price,Closing Price,Closing Quantity,Commissions,Swap,Gross USD,Net USD\n\
+        101,xauusd,Sell,01/03/2024 11:15:22.123,01/03/2024 13:02:10.000,2031.40,2018.00,0.50 Lots,-3.50,-1.20,670.00,665.30\n\
+        102,EURUSD,Buy,02/03/2024 16:00:00,02/03/2024 17:30:00,1.0820,1.0835,1.00 Lots,0,0,15.00,15.00\n";
+
+    const FLEX: &str = r#"<FlexQueryResponse queryName="Journal" type="AF"><FlexStatements count="1">
+<FlexStatement accountId="U123" fromDate="20240301" toDate="20240331">
+<Trades>
+<Trade symbol="AAPL" assetCategory="STK" tradeID="11" dateTime="20240304;093500" quantity="100" tradePrice="170" ibCommission="-1" buySell="BUY" openCloseIndicator="O" fifoPnlRealized="0" levelOfDetail="EXECUTION" />
+<Trade symbol="AAPL" tradeID="12" dateTime="20240304;100000" quantity="50" tradePrice="172" ibCommission="-0.5" buySell="BUY" openCloseIndicator="O" levelOfDetail="EXECUTION" />
+<Trade symbol="AAPL" tradeID="13" dateTime="20240305;153000" quantity="-150" tradePrice="175" ibCommission="-1.5" buySell="SELL" openCloseIndicator="C" fifoPnlRealized="697" levelOfDetail="EXECUTION" />
+<Trade symbol="AAPL" tradeID="13" dateTime="20240305;153000" quantity="-150" tradePrice="175" buySell="SELL" levelOfDetail="CLOSED_LOT" />
+<Trade symbol="MSFT" tradeID="14" dateTime="20240306;110000" quantity="-10" tradePrice="410" ibCommission="-1" buySell="SELL" openCloseIndicator="C" fifoPnlRealized="30" levelOfDetail="EXECUTION" />
+<Trade symbol="NVDA" tradeID="15" dateTime="20240307;120000" quantity="20" tradePrice="880" ibCommission="-1" buySell="BUY" openCloseIndicator="O" levelOfDetail="EXECUTION" />
+</Trades>
+<CashTransactions>
+<CashTransaction type="Deposits/Withdrawals" amount="5000" dateTime="20240301" description="CASH RECEIPTS &amp; TRANSFERS" transactionID="900" levelOfDetail="DETAIL" />
+</CashTransactions>
+</FlexStatement></FlexStatements></FlexQueryResponse>"#;
+
+    #[test]
+    fn registry_detects_each_builtin_format() {
+        let registry = ImporterRegistry::with_builtins();
+        let detected = |name: &str, data: &str| {
+            registry.detect(&ImportFile::new(name, data.as_bytes().to_vec())).map(|i| i.id().to_string())
+        };
+
+        assert_eq!(detected("history.csv", CTRADER).as_deref(), Some("ctrader"));
+        assert_eq!(detected("flex.xml", FLEX).as_deref(), Some("ibkr_flex"));
+        assert_eq!(
+            detected("dx.csv", "Position Code,Symbol,Side,Quantity,Open Price,Close Price,Open Time,Close Time,Commission,Swap,P&L\n").as_deref(),
+            Some("dxtrade")
+        );
+        assert_eq!(detected("notes.csv", "date,mood\n2024-03-01,calm\n"), None);
+    }
+
+    #[test]
+    fn ctrader_history_reads_zone_from_headers() {
+        let file = ImportFile::new("history.csv", CTRADER.as_bytes().to_vec());
+        let context = ImportContext {
+            symbol_mapping: HashMap::from([("XAUUSD".to_string(), "GOLD".to_string())]),
+            timezone: None,
+        };
+        let parsed = ctrader().parse(&file, &context).unwrap();
+
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.rows.len(), 2);
+        let gold = &parsed.rows[0].record;
+        assert_eq!(gold.trade.symbol, "GOLD");
+        assert_eq!(gold.trade.trade_type, "Sell");
+        assert_eq!(gold.trade.volume, 0.5);
+        assert_eq!(gold.trade.entry_time, "2024-03-01 11:15:22");
+        assert_eq!(gold.trade.source_timezone.as_deref(), Some("Etc/GMT-2"));
+        assert_eq!(gold.profit, Some(670.0));
+        assert_eq!(gold.external_id.as_deref(), Some("101"));
+    }
+
+    #[test]
+    fn flex_fills_are_grouped_into_round_trips() {
+        let file = ImportFile::new("flex.xml", FLEX.as_bytes().to_vec());
+        let parsed = IbkrFlexImporter.parse(&file, &ImportContext::default()).unwrap();
+
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.total_rows, 5);
+        assert_eq!(parsed.rows.len(), 2);
+
+        let aapl = &parsed.rows[0].record;
+        assert_eq!(aapl.external_id.as_deref(), Some("ibkr-11"));
+        assert_eq!(aapl.trade.volume, 150.0);
+        assert!((aapl.trade.entry_price - 170.6667).abs() < 1e-3);
+        assert_eq!(aapl.exit_price, Some(175.0));
+        // Gross; with the commission added back it is IBKR's net 697
+        assert_eq!(aapl.profit, Some(700.0));
+        assert_eq!(aapl.trade.commission, Some(-3.0));
+        assert_eq!(aapl.profit.unwrap() + aapl.trade.commission.unwrap(), 697.0);
+        assert_eq!(aapl.scope, TicketScope::new("ibkr_flex", Some("U123".to_string())));
+
+        let nvda = &parsed.rows[1].record;
+        assert_eq!(nvda.exit_price, None);
+        assert_eq!(nvda.trade.source_timezone.as_deref(), Some(FLEX_TIMEZONE));
+
+        // The MSFT close has no opening fill in this period; the import
+        // closes the MSFT trade of an earlier file with it
+        assert_eq!(parsed.executions.len(), 4);
+        assert!(parsed.warnings.is_empty());
+        let msft = &parsed.closes[0];
+        assert_eq!((msft.symbol.as_str(), msft.trade_type.as_str(), msft.profit), ("MSFT", "Buy", Some(30.0)));
+        assert_eq!(msft.fills[0].1.external_id.as_deref(), Some("14"));
+        assert_eq!(msft.fills[0].1.scope, TicketScope::new("ibkr_flex", Some("U123".to_string())));
+
+        let (_, deposit) = &parsed.cash_flows[0];
+        assert_eq!(deposit.kind, "deposit");
+        assert_eq!(deposit.occurred_at, "2024-03-01 00:00:00");
+        assert_eq!(deposit.comment.as_deref(), Some("CASH RECEIPTS & TRANSFERS"));
+    }
+
+    #[test]
+    fn flex_cancellations_take_out_their_fill_and_accounts_stay_apart() {
+        let flex = r#"<FlexQueryResponse><FlexStatements count="2">
+<FlexStatement accountId="U1"><Trades>
+<Trade symbol="AAPL" tradeID="21" dateTime="20240304;093500" quantity="100" tradePrice="170" ibCommission="-1" buySell="BUY" openCloseIndicator="O" />
+<Trade symbol="AAPL" tradeID="22" dateTime="20240304;094000" quantity="100" tradePrice="171" ibCommission="-1" buySell="BUY" openCloseIndicator="O" />
+<Trade symbol="AAPL" tradeID="23" dateTime="20240304;094500" quantity="-100" tradePrice="171" ibCommission="1" buySell="BUY (Ca.)" origTradeID="22" />
+<Trade symbol="AAPL" tradeID="24" dateTime="20240305;153000" quantity="-100" tradePrice="175" ibCommission="-1" buySell="SELL" openCloseIndicator="C" fifoPnlRealized="498" />
+</Trades></FlexStatement>
+<FlexStatement accountId="U2"><Trades>
+<Trade symbol="AAPL" tradeID="31" dateTime="20240304;100000" quantity="10" tradePrice="172" buySell="BUY" openCloseIndicator="O" />
+<Trade symbol="AAPL" tradeID="32" dateTime="20240304;100500" quantity="5" tradePrice="173" buySell="BUY" openCloseIndicator="O" />
+<Trade symbol="AAPL" tradeID="33" dateTime="20240304;101000" quantity="-5" tradePrice="173" buySell="BUY (Ca.)" />
+<Trade symbol="AAPL" tradeID="34" dateTime="20240304;101500" quantity="5" tradePrice="180" buySell="SELL (Ca.)" />
+</Trades></FlexStatement>
+</FlexStatements></FlexQueryResponse>"#;
+        let file = ImportFile::new("flex.xml", flex.as_bytes().to_vec());
+        let parsed = IbkrFlexImporter.parse(&file, &ImportContext::default()).unwrap();
+
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.rows.len(), 2);
+        // The U1 sell closes U1's buy of 100 alone; U2's buy stays open
+        let first = &parsed.rows[0].record;
+        assert_eq!((first.trade.volume, first.trade.entry_price, first.exit_price), (100.0, 170.0, Some(175.0)));
+        assert_eq!((first.profit, first.trade.commission), (Some(500.0), Some(-2.0)));
+        assert_eq!(first.scope.account.as_deref(), Some("U1"));
+        let second = &parsed.rows[1].record;
+        assert_eq!((second.trade.volume, second.exit_price), (10.0, None));
+        assert_eq!((second.external_id.as_deref(), second.scope.account.as_deref()), (Some("ibkr-31"), Some("U2")));
+
+        let executions: Vec<(&str, &str)> = parsed.executions.iter()
+            .map(|(_, e)| (e.external_id.as_deref().unwrap(), e.scope.account.as_deref().unwrap()))
+            .collect();
+        assert_eq!(executions, [("21", "U1"), ("31", "U2"), ("24", "U1")]);
+        assert_eq!(parsed.warnings.len(), 1);
+        assert!(parsed.warnings[0].starts_with("Cancellation 34 "), "{:?}", parsed.warnings);
+    }
+}
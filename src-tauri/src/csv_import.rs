@@ -0,0 +1,671 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::database::NewTrade;
+use crate::importer::{BrokerImporter, ImportContext, ImportFile, ImportRow, ImportRowError, ImportedTrade, ParsedImport, TicketScope};
+use crate::paths;
+
+const PROFILES_FILE: &str = "import_profiles.json";
+const PREVIEW_ROWS: usize = 20;
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+const CUSTOM_PREFIX: &str = "custom.";
+
+// NewTrade fields a column can be mapped to. Image paths are left out on
+// purpose: a CSV cell cannot carry the file itself.
+const TRADE_FIELDS: &[&str] = &[
+    "symbol", "trade_type", "volume", "entry_price", "sl", "tp", "entry_time", "notes",
+    "commission", "swap", "ict_pattern", "pattern_type", "pattern_size", "pattern_timeframe",
+    "pattern_combination", "chart_explanation", "strategy_name", "emotion", "confidence_level",
+    "market_condition", "session", "rsi", "macd", "moving_average", "support_level",
+    "resistance_level", "source_timezone",
+];
+
+// Targets that are not NewTrade fields but are kept by the importer
+const IMPORT_FIELDS: &[&str] = &["exit_price", "exit_time", "profit", "external_id"];
+
+const NUMERIC_FIELDS: &[&str] = &[
+    "volume", "entry_price", "sl", "tp", "commission", "swap", "pattern_size", "confidence_level",
+    "rsi", "macd", "moving_average", "support_level", "resistance_level", "exit_price", "profit",
+];
+
+// Header spellings seen in broker exports, used to pre-fill a new profile
+const HEADER_SYNONYMS: &[(&str, &[&str])] = &[
+    ("symbol", &["symbol", "instrument", "market", "pair", "ticker", "item"]),
+    ("trade_type", &["type", "side", "direction", "action", "buy/sell"]),
+    ("volume", &["volume", "lots", "size", "quantity", "qty", "amount"]),
+    ("entry_price", &["entry price", "open price", "price open", "entry", "open"]),
+    ("exit_price", &["exit price", "close price", "price close", "exit", "close"]),
+    ("entry_time", &["entry time", "open time", "time open", "opened", "open date", "date"]),
+    ("exit_time", &["exit time", "close time", "time close", "closed", "close date"]),
+    ("sl", &["sl", "s/l", "stop loss", "stop"]),
+    ("tp", &["tp", "t/p", "take profit", "target"]),
+    ("commission", &["commission", "commissions", "fee", "fees"]),
+    ("swap", &["swap", "swaps", "rollover", "financing"]),
+    ("profit", &["profit", "p/l", "pnl", "net profit", "result"]),
+    ("external_id", &["ticket", "order", "order id", "position", "position id", "deal", "id"]),
+    ("notes", &["comment", "comments", "notes", "note"]),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CsvOptions {
+    // Detected from the first lines when not set
+    pub delimiter: Option<char>,
+    pub has_headers: bool,
+    // Lines before the header row (broker banners, account details)
+    pub skip_lines: usize,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            has_headers: true,
+            skip_lines: 0,
+        }
+    }
+}
+
+// Applied in order to a cell before it is assigned to its field
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ValueTransform {
+    // Translate vocabularies such as BUY / Long / B into Buy
+    Map {
+        values: HashMap<String, String>,
+        #[serde(default)]
+        case_sensitive: bool,
+    },
+    // chrono format of the cell, e.g. "%d.%m.%Y %H:%M"
+    Date { format: String },
+    // Locale numbers such as "1.234,56"
+    Number {
+        decimal_separator: char,
+        thousands_separator: Option<char>,
+    },
+    Scale { factor: f64 },
+    Replace { from: String, to: String },
+    Uppercase,
+    // Used when the cell is empty
+    Default { value: String },
+}
+
+impl ValueTransform {
+    pub fn apply(&self, value: &str) -> Result<String, String> {
+        match self {
+            ValueTransform::Map { values, case_sensitive } => {
+                let mapped = if *case_sensitive {
+                    values.get(value)
+                } else {
+                    values.iter()
+                        .find(|(from, _)| from.eq_ignore_ascii_case(value))
+                        .map(|(_, to)| to)
+                };
+                Ok(mapped.cloned().unwrap_or_else(|| value.to_string()))
+            }
+            ValueTransform::Date { format } => {
+                if value.is_empty() {
+                    return Ok(String::new());
+                }
+                NaiveDateTime::parse_from_str(value, format)
+                    .or_else(|_| NaiveDate::parse_from_str(value, format).map(|date| date.and_hms_opt(0, 0, 0).unwrap()))
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .map_err(|_| format!("'{}' does not match date format '{}'", value, format))
+            }
+            ValueTransform::Number { decimal_separator, thousands_separator } => {
+                if value.is_empty() {
+                    return Ok(String::new());
+                }
+                let normalized: String = value.chars()
+                    .filter(|c| Some(*c) != *thousands_separator && !c.is_whitespace() && *c != '\u{a0}')
+                    .map(|c| if c == *decimal_separator { '.' } else { c })
+                    .collect();
+                normalized.parse::<f64>()
+                    .map(|_| normalized.clone())
+                    .map_err(|_| format!("'{}' is not a number", value))
+            }
+            ValueTransform::Scale { factor } => {
+                if value.is_empty() {
+                    return Ok(String::new());
+                }
+                value.parse::<f64>()
+                    .map(|number| (number * factor).to_string())
+                    .map_err(|_| format!("'{}' is not a number", value))
+            }
+            ValueTransform::Replace { from, to } => Ok(value.replace(from.as_str(), to)),
+            ValueTransform::Uppercase => Ok(value.to_uppercase()),
+            ValueTransform::Default { value: default } => {
+                Ok(if value.is_empty() { default.clone() } else { value.to_string() })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ColumnMapping {
+    // Header name, or "Column N" when the file has no header row
+    pub column: String,
+    // A NewTrade field, exit_price / exit_time / profit / external_id, or custom.<name>
+    pub field: String,
+    #[serde(default)]
+    pub transforms: Vec<ValueTransform>,
+}
+
+// A saved mapping for one broker's export format
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportProfile {
+    pub name: String,
+    #[serde(default)]
+    pub csv: CsvOptions,
+    pub mappings: Vec<ColumnMapping>,
+    // Constant values for fields the file does not carry (e.g. strategy_name)
+    #[serde(default)]
+    pub defaults: HashMap<String, String>,
+    // Zone the file's timestamps are written in; the broker timezone when unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl ImportProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Profile name is required".to_string());
+        }
+
+        let fields = self.mappings.iter().map(|m| &m.field).chain(self.defaults.keys());
+        for field in fields {
+            if !is_known_field(field) {
+                return Err(format!("Unknown import field '{}'", field));
+            }
+        }
+
+        for required in ["symbol", "trade_type", "volume", "entry_price", "entry_time"] {
+            let mapped = self.mappings.iter().any(|m| m.field == required) || self.defaults.contains_key(required);
+            if !mapped {
+                return Err(format!("Field '{}' must be mapped", required));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsvPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub delimiter: char,
+    pub suggested_mappings: Vec<ColumnMapping>,
+}
+
+fn is_known_field(field: &str) -> bool {
+    TRADE_FIELDS.contains(&field)
+        || IMPORT_FIELDS.contains(&field)
+        || field.strip_prefix(CUSTOM_PREFIX).map_or(false, |name| !name.is_empty())
+}
+
+// Pick the candidate that splits the first lines into the same, non-zero
+// number of fields
+fn detect_delimiter(sample: &str) -> u8 {
+    let lines: Vec<&str> = sample.lines().filter(|l| !l.trim().is_empty()).take(5).collect();
+
+    DELIMITERS.iter()
+        .copied()
+        .map(|delimiter| {
+            let counts: Vec<usize> = lines.iter().map(|l| l.bytes().filter(|b| *b == delimiter).count()).collect();
+            let consistent = counts.windows(2).all(|w| w[0] == w[1]);
+            (delimiter, if consistent { counts.first().copied().unwrap_or(0) } else { 0 })
+        })
+        .max_by_key(|(_, count)| *count)
+        .filter(|(_, count)| *count > 0)
+        .map_or(b',', |(delimiter, _)| delimiter)
+}
+
+// Strip a UTF-8 BOM and the banner lines before the header
+fn body<'a>(data: &'a str, options: &CsvOptions) -> &'a str {
+    let mut data = data.trim_start_matches('\u{feff}');
+    for _ in 0..options.skip_lines {
+        data = data.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    data
+}
+
+fn reader<'a>(data: &'a str, options: &CsvOptions) -> (csv::Reader<&'a [u8]>, u8) {
+    let delimiter = options.delimiter
+        .filter(char::is_ascii)
+        .map(|c| c as u8)
+        .unwrap_or_else(|| detect_delimiter(data));
+
+    let reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data.as_bytes());
+
+    (reader, delimiter)
+}
+
+fn headers(first: Option<&csv::StringRecord>, options: &CsvOptions) -> Vec<String> {
+    match first {
+        Some(record) if options.has_headers => record.iter().map(str::to_string).collect(),
+        Some(record) => (1..=record.len()).map(|i| format!("Column {}", i)).collect(),
+        None => Vec::new(),
+    }
+}
+
+// Exact header match, else the header with a suffix the broker appends to
+// it: a unit or zone in brackets ("Opening Time (UTC+2)") or a currency
+// ("Net USD")
+fn column_index(headers: &[String], column: &str) -> Option<usize> {
+    let column = column.trim();
+    headers.iter().position(|header| header.eq_ignore_ascii_case(column)).or_else(|| {
+        headers.iter().position(|header| {
+            let suffix = header.get(..column.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(column))
+                .and_then(|_| header.get(column.len()..))
+                .and_then(|rest| rest.strip_prefix(' '))
+                .map(str::trim);
+            match suffix {
+                Some(s) if s.starts_with('(') && s.ends_with(')') => true,
+                Some(s) => s.len() == 3 && s.chars().all(|c| c.is_ascii_uppercase()),
+                None => false,
+            }
+        })
+    })
+}
+
+// Zone named by a header such as "Opening Time (UTC+2)". chrono-tz only has
+// whole-hour fixed zones, whose Etc names have the sign inverted.
+fn header_timezone(headers: &[String]) -> Option<String> {
+    headers.iter().find_map(|header| {
+        let start = header.find("(UTC")?;
+        let offset = header[start + 4..].strip_suffix(')')?;
+        if offset.is_empty() || offset == "+0" || offset == "-0" {
+            return Some("UTC".to_string());
+        }
+        let hours: i32 = offset.parse().ok()?;
+        Some(format!("Etc/GMT{:+}", -hours))
+    })
+}
+
+pub async fn read_file(path: &Path) -> Result<String, std::io::Error> {
+    let data = tokio::fs::read(path).await?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+pub fn preview(data: &str, options: &CsvOptions) -> Result<CsvPreview, csv::Error> {
+    let data = body(data, options);
+    let (mut reader, delimiter) = reader(data, options);
+
+    let mut records = reader.records();
+    let first = records.next().transpose()?;
+    let headers = headers(first.as_ref(), options);
+
+    let mut rows = Vec::new();
+    if !options.has_headers {
+        rows.extend(first.map(|record| record.iter().map(str::to_string).collect()));
+    }
+    for record in records.take(PREVIEW_ROWS - rows.len()) {
+        rows.push(record?.iter().map(str::to_string).collect());
+    }
+
+    Ok(CsvPreview {
+        suggested_mappings: suggest_mappings(&headers),
+        headers,
+        rows,
+        delimiter: delimiter as char,
+    })
+}
+
+// Best guess at a mapping from header names; each field is used once
+pub fn suggest_mappings(headers: &[String]) -> Vec<ColumnMapping> {
+    let mut mappings: Vec<ColumnMapping> = Vec::new();
+
+    for header in headers {
+        let normalized = header.trim().to_lowercase().replace('_', " ");
+        let field = HEADER_SYNONYMS.iter()
+            .find(|(field, synonyms)| {
+                synonyms.contains(&normalized.as_str()) && !mappings.iter().any(|m| m.field == *field)
+            })
+            .map(|(field, _)| field.to_string());
+
+        if let Some(field) = field {
+            mappings.push(ColumnMapping { column: header.clone(), field, transforms: Vec::new() });
+        }
+    }
+
+    mappings
+}
+
+// Apply a profile to a whole file. Structural problems (unreadable CSV, a
+// mapped column missing from the header) fail the import; bad cells are
+// reported per row.
+pub fn parse(data: &str, profile: &ImportProfile) -> Result<ParsedImport, String> {
+    profile.validate()?;
+
+    let options = &profile.csv;
+    let data = body(data, options);
+    let (mut reader, _) = reader(data, options);
+    let mut records = reader.records();
+
+    let first = records.next().transpose().map_err(|e| format!("Failed to read CSV: {}", e))?;
+    let headers = headers(first.as_ref(), options);
+
+    let columns = profile.mappings.iter()
+        .map(|mapping| {
+            column_index(&headers, &mapping.column)
+                .ok_or_else(|| format!("Column '{}' not found in file", mapping.column))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    let pending = if options.has_headers { None } else { first };
+    let mut parsed = ParsedImport::default();
+
+    for record in pending.into_iter().map(Ok).chain(records) {
+        let record = record.map_err(|e| format!("Failed to read CSV: {}", e))?;
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+
+        let row = record.position().map_or(0, |p| p.line() as usize) + options.skip_lines;
+        parsed.total_rows += 1;
+
+        match map_record(&record, &columns, profile) {
+            Ok(record) => parsed.rows.push(ImportRow { row, record }),
+            Err((field, message)) => parsed.errors.push(ImportRowError { row, field, message }),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn map_record(
+    record: &csv::StringRecord,
+    columns: &[usize],
+    profile: &ImportProfile,
+) -> Result<ImportedTrade, (Option<String>, String)> {
+    let mut values: HashMap<&str, String> = profile.defaults.iter()
+        .map(|(field, value)| (field.as_str(), value.clone()))
+        .collect();
+
+    for (mapping, &column) in profile.mappings.iter().zip(columns) {
+        let mut value = record.get(column).unwrap_or_default().to_string();
+        for transform in &mapping.transforms {
+            value = transform.apply(&value).map_err(|e| (Some(mapping.field.clone()), e))?;
+        }
+        if !value.is_empty() {
+            values.insert(mapping.field.as_str(), value);
+        }
+    }
+
+    let mut trade = serde_json::Map::new();
+    let mut imported = HashMap::new();
+    let mut custom_fields = HashMap::new();
+
+    for (field, value) in values {
+        let json = if NUMERIC_FIELDS.contains(&field) {
+            let number = value.parse::<f64>()
+                .map_err(|_| (Some(field.to_string()), format!("'{}' is not a number", value)))?;
+            serde_json::json!(number)
+        } else if field == "pattern_combination" {
+            serde_json::json!(value.split(',').map(str::trim).filter(|p| !p.is_empty()).collect::<Vec<_>>())
+        } else if field == "trade_type" {
+            serde_json::json!(trade_type(&value).ok_or_else(|| {
+                (Some(field.to_string()), format!("Unknown trade type '{}'; add a map transform", value))
+            })?)
+        } else {
+            serde_json::json!(value)
+        };
+
+        // Custom values stay text; the import converts them to the schema type
+        if let Some(name) = field.strip_prefix(CUSTOM_PREFIX) {
+            custom_fields.insert(name.to_string(), json);
+        } else if IMPORT_FIELDS.contains(&field) {
+            imported.insert(field, json);
+        } else {
+            trade.insert(field.to_string(), json);
+        }
+    }
+
+    if let Some(timezone) = &profile.timezone {
+        trade.entry("source_timezone").or_insert(serde_json::json!(timezone));
+    }
+
+    let trade: NewTrade = serde_json::from_value(serde_json::Value::Object(trade))
+        .map_err(|e| (None, e.to_string()))?;
+
+    let mut record = ImportedTrade::new(trade);
+    record.exit_price = imported.get("exit_price").and_then(|v| v.as_f64());
+    record.exit_time = imported.get("exit_time").and_then(|v| v.as_str()).map(str::to_string);
+    record.profit = imported.get("profit").and_then(|v| v.as_f64());
+    record.external_id = imported.get("external_id").and_then(|v| v.as_str()).map(str::to_string);
+    record.custom_fields = custom_fields;
+
+    Ok(record)
+}
+
+// A profile registered as an importer: the built-in broker layouts and
+// the user's saved profiles both go through here
+pub struct ProfileImporter {
+    id: String,
+    profile: ImportProfile,
+    // Headers that identify the format; the mapped columns when empty
+    signature: Vec<String>,
+    confidence: u8,
+}
+
+impl ProfileImporter {
+    pub fn new(id: impl Into<String>, profile: ImportProfile) -> Self {
+        Self { id: id.into(), profile, signature: Vec::new(), confidence: 50 }
+    }
+
+    pub fn with_signature(mut self, headers: &[&str], confidence: u8) -> Self {
+        self.signature = headers.iter().map(|h| h.to_string()).collect();
+        self.confidence = confidence;
+        self
+    }
+
+    fn headers(&self, data: &str) -> Vec<String> {
+        let options = &self.profile.csv;
+        let (mut reader, _) = reader(body(data, options), options);
+        let first = reader.records().next().and_then(Result::ok);
+        headers(first.as_ref(), options)
+    }
+}
+
+impl BrokerImporter for ProfileImporter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.profile.name
+    }
+
+    fn detect(&self, file: &ImportFile) -> u8 {
+        if !self.profile.csv.has_headers || !matches!(file.extension().as_str(), "csv" | "txt" | "tsv") {
+            return 0;
+        }
+
+        let headers = self.headers(file.text());
+        let found = |column: &str| column_index(&headers, column).is_some();
+        let matched = if self.signature.is_empty() {
+            self.profile.mappings.iter().all(|m| found(&m.column))
+        } else {
+            self.signature.iter().all(|column| found(column))
+        };
+        if matched { self.confidence } else { 0 }
+    }
+
+    fn parse(&self, file: &ImportFile, context: &ImportContext) -> Result<ParsedImport, String> {
+        // An explicit zone wins over the one the headers name
+        let mut profile = self.profile.clone();
+        let timezone = context.timezone.clone().or_else(|| header_timezone(&self.headers(file.text())));
+        if timezone.is_some() {
+            profile.timezone = timezone;
+        }
+
+        let mut parsed = parse(file.text(), &profile)?;
+        for row in &mut parsed.rows {
+            row.record.trade.symbol = context.map_symbol(&row.record.trade.symbol);
+        }
+        parsed.set_scope(&TicketScope::new(&self.id, None));
+        Ok(parsed)
+    }
+}
+
+fn trade_type(value: &str) -> Option<&'static str> {
+    match value.to_lowercase().as_str() {
+        "buy" => Some("Buy"),
+        "sell" => Some("Sell"),
+        _ => None,
+    }
+}
+
+// Saved profiles are app-level so every workspace can use them
+fn profiles_path() -> PathBuf {
+    paths::config_dir().join(PROFILES_FILE)
+}
+
+pub async fn load_profiles() -> Result<Vec<ImportProfile>, Box<dyn std::error::Error>> {
+    match tokio::fs::read(profiles_path()).await {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn write_profiles(profiles: &[ImportProfile]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = profiles_path();
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, serde_json::to_vec_pretty(profiles)?).await?;
+    Ok(())
+}
+
+pub async fn find_profile(name: &str) -> Result<Option<ImportProfile>, Box<dyn std::error::Error>> {
+    Ok(load_profiles().await?.into_iter().find(|p| p.name == name))
+}
+
+// Insert or replace by name
+pub async fn save_profile(profile: ImportProfile) -> Result<(), Box<dyn std::error::Error>> {
+    profile.validate()?;
+
+    let mut profiles = load_profiles().await?;
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+
+    write_profiles(&profiles).await
+}
+
+pub async fn delete_profile(name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut profiles = load_profiles().await?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+
+    if profiles.len() == before {
+        return Ok(false);
+    }
+    write_profiles(&profiles).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(column: &str, field: &str, transforms: Vec<ValueTransform>) -> ColumnMapping {
+        ColumnMapping { column: column.to_string(), field: field.to_string(), transforms }
+    }
+
+    fn european_profile() -> ImportProfile {
+        let comma = || ValueTransform::Number { decimal_separator: ',', thousands_separator: Some('.') };
+        let side = ValueTransform::Map {
+            values: HashMap::from([("K".to_string(), "Buy".to_string()), ("V".to_string(), "Sell".to_string())]),
+            case_sensitive: false,
+        };
+
+        ImportProfile {
+            name: "EU broker".to_string(),
+            csv: CsvOptions { skip_lines: 1, ..Default::default() },
+            mappings: vec![
+                mapping("Ticket", "external_id", vec![]),
+                mapping("Instrument", "symbol", vec![ValueTransform::Uppercase]),
+                mapping("Richtung", "trade_type", vec![side]),
+                mapping("Lots", "volume", vec![comma()]),
+                mapping("Kurs", "entry_price", vec![comma()]),
+                mapping("Zeit", "entry_time", vec![ValueTransform::Date { format: "%d.%m.%Y %H:%M".to_string() }]),
+                mapping("Gewinn", "profit", vec![comma()]),
+                mapping("Setup", "custom.setup_grade", vec![]),
+            ],
+            defaults: HashMap::from([("strategy_name".to_string(), "London breakout".to_string())]),
+            timezone: Some("Europe/Berlin".to_string()),
+        }
+    }
+
+    #[test]
+    fn transforms_normalize_locale_values() {
+        let number = ValueTransform::Number { decimal_separator: ',', thousands_separator: Some('.') };
+        assert_eq!(number.apply("1.234,56").unwrap(), "1234.56");
+        assert!(number.apply("n/a").is_err());
+
+        let date = ValueTransform::Date { format: "%d/%m/%Y".to_string() };
+        assert_eq!(date.apply("05/03/2024").unwrap(), "2024-03-05 00:00:00");
+        assert!(date.apply("2024-03-05").is_err());
+
+        let scale = ValueTransform::Scale { factor: 0.00001 };
+        assert!((scale.apply("100000").unwrap().parse::<f64>().unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn profile_maps_rows_and_reports_bad_cells() {
+        let data = "Kontoauszug 2024\n\
+            Ticket;Instrument;Richtung;Lots;Kurs;Zeit;Gewinn;Setup\n\
+            1001;xauusd;K;0,5;2.031,40;01.03.2024 09:15;125,50;8\n\
+            1002;eurusd;X;1;1,0840;01.03.2024 10:00;-20;\n\
+            1003;eurusd;v;1;1,0850;01.03.2024 11:30;abc;\n";
+
+        let parsed = parse(data, &european_profile()).unwrap();
+        assert_eq!(parsed.total_rows, 3);
+        assert_eq!(parsed.rows.len(), 1);
+
+        let row = &parsed.rows[0];
+        assert_eq!(row.row, 3);
+        assert_eq!(row.record.trade.symbol, "XAUUSD");
+        assert_eq!(row.record.trade.trade_type, "Buy");
+        assert!((row.record.trade.entry_price - 2031.4).abs() < 1e-9);
+        assert_eq!(row.record.trade.entry_time, "2024-03-01 09:15:00");
+        assert_eq!(row.record.trade.source_timezone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(row.record.trade.strategy_name.as_deref(), Some("London breakout"));
+        assert_eq!(row.record.external_id.as_deref(), Some("1001"));
+        assert_eq!(row.record.profit, Some(125.5));
+        assert_eq!(row.record.custom_fields.get("setup_grade"), Some(&serde_json::json!("8")));
+
+        let errors: Vec<(usize, Option<&str>)> = parsed.errors.iter().map(|e| (e.row, e.field.as_deref())).collect();
+        assert_eq!(errors, vec![(4, Some("trade_type")), (5, Some("profit"))]);
+    }
+
+    #[test]
+    fn preview_detects_delimiter_and_suggests_mappings() {
+        let data = "Ticket\tSymbol\tType\tLots\tOpen Price\tOpen Time\tProfit\n\
+            1\tEURUSD\tbuy\t1\t1.08\t2024-03-01 08:00\t10\n";
+
+        let preview = preview(data, &CsvOptions::default()).unwrap();
+        assert_eq!(preview.delimiter, '\t');
+        assert_eq!(preview.rows.len(), 1);
+
+        let fields: Vec<&str> = preview.suggested_mappings.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec!["external_id", "symbol", "trade_type", "volume", "entry_price", "entry_time", "profit"]);
+
+        let mut missing_column = european_profile();
+        missing_column.mappings[1].column = "Nope".to_string();
+        let error = parse("banner\nTicket;Instrument\n", &missing_column).unwrap_err();
+        assert!(error.contains("Nope"));
+    }
+}
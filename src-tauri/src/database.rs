@@ -1,11 +1,38 @@
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool, Row, Error as SqlxError};
+use sqlx::{sqlite::{Sqlite, SqliteArguments, SqliteConnectOptions, SqlitePoolOptions, SqliteRow}, query::Query, SqlitePool, Row, Error as SqlxError};
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use chrono::{DateTime, TimeZone, Utc};
 use uuid::Uuid;
 use tokio::fs;
-use std::path::PathBuf;
-use image::{ImageFormat, imageops::FilterType};
+use std::path::{Component, Path, PathBuf};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures::TryStreamExt;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+use crate::image_hash;
+use crate::duplicates::{DuplicateDetector, TradeFingerprint};
+use crate::equity::{self, EquityCurve};
+use crate::journal_export::{
+    remap_trade_references, ConflictPolicy, ImageMode, JournalExportOptions, JournalExportReport, JournalHeader,
+    JournalImportOptions, JournalImportReport, JournalRecord, RecordKind, END_RECORD,
+};
+use crate::importer::{
+    CashFlow, DuplicatePolicy, Execution, ImportDuplicate, ImportOptions, ImportReport, ImportRow, ImportRowError,
+    ImportedTrade, ParsedImport, PositionClose, TicketScope,
+};
+use crate::pagination::{self, SortDirection, TradeCursor, TradePage};
+use crate::pdf_report::{self, PdfTemplate};
+use crate::quick_entry::{self, QuickEntryPreview};
+use crate::report::{self, ReportData, ReportImage, ReportOptions, ReportRange, ReportResult, ReportScreenshot};
+use crate::trade_export::{
+    self, resolve_columns, schema_labels, ExportFormat, ExportLocale, TradeExportOptions, TradeExportReport, TradeRow,
+    TRADE_SCHEMA,
+};
+use crate::tax_report::{self, FxRate, FxTable, TaxReportOptions, TaxReportResult};
+use crate::timezone::{self, TimeSettings};
+use crate::vault_export::{self, FileAction, Manifest, VaultExportOptions, VaultExportReport};
+use crate::workspace::WorkspacePaths;
 
 // Trade structures
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -14,8 +41,9 @@ pub struct NewTrade {
     pub trade_type: String,
     pub volume: f64,
     pub entry_price: f64,
-    pub sl: f64,
-    pub tp: f64,
+    // None when the trade had no stop loss / take profit
+    pub sl: Option<f64>,
+    pub tp: Option<f64>,
     pub entry_time: String,
     pub notes: Option<String>,
     pub commission: Option<f64>,
@@ -47,6 +75,10 @@ pub struct NewTrade {
     pub moving_average: Option<f64>,
     pub support_level: Option<f64>,
     pub resistance_level: Option<f64>,
+    
+    // Timezone the entry/exit times were recorded in (defaults to the broker timezone)
+    #[serde(default)]
+    pub source_timezone: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -65,9 +97,27 @@ pub struct Trade {
     pub version: u32,
 }
 
+// Timestamp helpers
+pub(crate) const TRADE_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%Y.%m.%d %H:%M:%S",
+    "%Y.%m.%d %H:%M",
+];
+
+// Parse the entry/exit time strings stored on trades. Stored values are
+// normalized to UTC on write, so values without an offset are read as UTC.
+pub fn parse_trade_time(value: &str) -> Option<DateTime<Utc>> {
+    timezone::parse_in_zone(value, chrono_tz::UTC)
+}
+
 // Database query parameters
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TradeQuery {
+    pub ids: Option<Vec<u32>>,
     pub symbol: Option<Vec<String>>,
     pub trade_type: Option<Vec<String>>,
     pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
@@ -82,8 +132,129 @@ pub struct TradeQuery {
     pub offset: Option<u32>,
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
+    pub include_archived: bool,
+    // Opaque keyset cursor from a previous TradePage; replaces offset
+    pub cursor: Option<String>,
+}
+
+impl TradeQuery {
+    // True when the query would match every trade
+    pub fn has_no_filters(&self) -> bool {
+        fn empty<T>(values: &Option<Vec<T>>) -> bool {
+            values.as_ref().map(|v| v.is_empty()).unwrap_or(true)
+        }
+        
+        empty(&self.ids)
+            && empty(&self.symbol)
+            && empty(&self.trade_type)
+            && self.date_range.is_none()
+            && empty(&self.ict_pattern)
+            && empty(&self.strategy_name)
+            && self.is_win.is_none()
+            && self.min_profit.is_none()
+            && self.max_profit.is_none()
+            && empty(&self.emotion)
+            && empty(&self.market_condition)
+    }
+}
+
+// One entry of the change_log outbox
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeEntry {
+    pub seq: i64,
+    pub entity: String,
+    pub entity_id: String,
+    pub operation: String,
+    pub payload: serde_json::Value,
+    pub changed_at: String,
+    // Schema the row moved to ("archive" or "main") when the delete or insert
+    // came from archiving; the row still exists there
+    pub moved_to: Option<String>,
+}
+
+// A page of changes after a cursor. `truncated` means compaction removed
+// entries the consumer has not seen, so it has to resynchronize.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeBatch {
+    pub changes: Vec<ChangeEntry>,
+    pub last_seq: i64,
+    pub oldest_seq: Option<i64>,
+    pub truncated: bool,
+}
+
+// Rows moved by an archive or un-archive operation
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ArchiveResult {
+    pub trades: u32,
+    pub merges: u32,
+    pub executions: u32,
+    pub image_hashes: u32,
+}
+
+// Bulk operation results
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkResult {
+    pub matched: u32,
+    pub affected: u32,
+    pub dry_run: bool,
+    pub trade_ids: Vec<u32>,
+}
+
+// Columns that may be changed through bulk updates
+const PATCHABLE_TRADE_COLUMNS: &[&str] = &[
+    "symbol", "trade_type", "volume", "entry_price", "sl", "tp", "entry_time", "exit_time",
+    "exit_price", "commission", "swap", "notes", "ict_pattern", "pattern_type", "pattern_size",
+    "pattern_timeframe", "pattern_combination", "chart_explanation", "strategy_name", "emotion",
+    "confidence_level", "market_condition", "session", "entry_image", "exit_image",
+    "analysis_image", "rsi", "macd", "moving_average", "support_level", "resistance_level",
+    "source_timezone",
+];
+
+// Patched columns the derived P/L columns are calculated from. Those columns
+// are gross, so commission and swap are not inputs: net figures subtract
+// fees when they are read, and recalculating on a fee patch would replace a
+// broker-reported profit with the estimate.
+const METRIC_INPUT_COLUMNS: &[&str] = &[
+    "symbol", "trade_type", "volume", "entry_price", "exit_price", "sl", "tp",
+];
+const DERIVED_METRIC_COLUMNS: &[&str] = &["is_win", "profit_loss_pips", "profit_loss_money", "risk_reward_ratio"];
+
+// Whether a patch needs the derived P/L columns refreshed: it changes an
+// input and does not set them itself. Both update paths go by this, and the
+// refresh skips imported trades (see recalculate_trade_metrics).
+fn patch_changes_metrics(patch: &HashMap<String, serde_json::Value>) -> bool {
+    let patched = |columns: &[&str]| patch.keys().any(|column| columns.contains(&column.as_str()));
+    patched(METRIC_INPUT_COLUMNS) && !patched(DERIVED_METRIC_COLUMNS)
 }
 
+// Columns reconciled field-by-field when merging duplicates
+const MERGEABLE_TRADE_COLUMNS: &[&str] = &[
+    "symbol", "trade_type", "volume", "entry_price", "sl", "tp", "entry_time", "exit_time",
+    "exit_price", "commission", "swap", "notes", "ict_pattern", "pattern_type", "pattern_size",
+    "pattern_timeframe", "pattern_combination", "chart_explanation", "strategy_name", "emotion",
+    "confidence_level", "market_condition", "session", "entry_image", "exit_image",
+    "analysis_image", "rsi", "macd", "moving_average", "support_level", "resistance_level",
+    "source_timezone", "is_win", "profit_loss_pips", "profit_loss_money", "risk_reward_ratio",
+];
+
+// Which side of a merge a field value is taken from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSource {
+    Primary,
+    Duplicate,
+    // Both values kept; only notes can be combined
+    Merged,
+}
+
+// Maximum number of ids bound into a single IN (...) clause
+const BULK_CHUNK_SIZE: usize = 500;
+
+// schema_versions entry of the one-time rewrite of naive trade times to UTC
+const TIMESTAMP_MIGRATION_VERSION: i64 = 1;
+const OPTIONAL_LEVELS_MIGRATION_VERSION: i64 = 2;
+const SELL_PROFIT_SIGN_MIGRATION_VERSION: i64 = 3;
+
 // Schema management
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EntitySchema {
@@ -167,11 +338,377 @@ pub struct RelationshipSchema {
     pub foreign_key: String,
 }
 
+// Chart similarity search
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ChartQuery {
+    TradeId(u32),
+    Image(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SimilarChart {
+    pub trade_id: u32,
+    pub image_path: String,
+    pub distance: u32,
+    pub similarity: f64,
+    pub symbol: String,
+    pub trade_type: String,
+    pub entry_time: String,
+    pub ict_pattern: Option<String>,
+    pub strategy_name: Option<String>,
+    pub is_win: Option<bool>,
+    pub profit_loss_money: Option<f64>,
+    pub risk_reward_ratio: Option<f64>,
+}
+
+// Aggregates maintained in trade_statistics by triggers on trades.
+// statistic_type is "<dimension>:<metric>" and statistic_key the dimension
+// value; day rows are keyed by the calendar date of entry_time in the display
+// timezone, like every other daily view, using the offsets kept in
+// display_day_offsets. Expressions are written against the row alias `R`.
+pub const STAT_DIMENSIONS: &[(&str, &str)] = &[
+    ("day", "substr(datetime(R.entry_time, COALESCE((SELECT offset_seconds FROM display_day_offsets WHERE starts_at <= R.entry_time ORDER BY starts_at DESC LIMIT 1), 0) || ' seconds'), 1, 10)"),
+    ("symbol", "R.symbol"),
+    ("strategy", "R.strategy_name"),
+    ("pattern", "R.ict_pattern"),
+];
+
+const STAT_METRICS: &[(&str, &str)] = &[
+    ("trade_count", "1"),
+    ("closed_count", "CASE WHEN R.is_win IS NOT NULL THEN 1 ELSE 0 END"),
+    ("win_count", "CASE WHEN R.is_win = 1 THEN 1 ELSE 0 END"),
+    ("loss_count", "CASE WHEN R.is_win = 0 THEN 1 ELSE 0 END"),
+    ("net_profit", "COALESCE(R.profit_loss_money, 0)"),
+    ("gross_profit", "CASE WHEN R.profit_loss_money > 0 THEN R.profit_loss_money ELSE 0 END"),
+    ("gross_loss", "CASE WHEN R.profit_loss_money < 0 THEN -R.profit_loss_money ELSE 0 END"),
+];
+
+// Span of display timezone offsets stored in display_day_offsets
+const DAY_OFFSETS_FROM: &str = "1970-01-01T00:00:00Z";
+const DAY_OFFSETS_UNTIL: &str = "2100-01-01T00:00:00Z";
+
+// Trade columns the aggregates depend on
+const STAT_SOURCE_COLUMNS: &str = "symbol, strategy_name, ict_pattern, entry_time, is_win, profit_loss_money";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DimensionStatistics {
+    pub key: String,
+    pub trade_count: u32,
+    pub closed_count: u32,
+    pub win_count: u32,
+    pub loss_count: u32,
+    pub net_profit: f64,
+    pub gross_profit: f64,
+    pub gross_loss: f64,
+}
+
+impl DimensionStatistics {
+    pub fn win_rate(&self) -> f64 {
+        if self.closed_count == 0 {
+            0.0
+        } else {
+            self.win_count as f64 / self.closed_count as f64 * 100.0
+        }
+    }
+    
+    pub fn profit_factor(&self) -> f64 {
+        if self.gross_loss == 0.0 {
+            0.0
+        } else {
+            self.gross_profit / self.gross_loss
+        }
+    }
+    
+    // Add one trade, the same way the trade_statistics triggers count it
+    pub fn record(&mut self, is_win: Option<bool>, profit: Option<f64>) {
+        let profit = profit.unwrap_or(0.0);
+        self.trade_count += 1;
+        self.closed_count += is_win.is_some() as u32;
+        self.win_count += (is_win == Some(true)) as u32;
+        self.loss_count += (is_win == Some(false)) as u32;
+        self.net_profit += profit;
+        self.gross_profit += profit.max(0.0);
+        self.gross_loss += (-profit).max(0.0);
+    }
+    
+    pub fn absorb(&mut self, other: &DimensionStatistics) {
+        self.trade_count += other.trade_count;
+        self.closed_count += other.closed_count;
+        self.win_count += other.win_count;
+        self.loss_count += other.loss_count;
+        self.net_profit += other.net_profit;
+        self.gross_profit += other.gross_profit;
+        self.gross_loss += other.gross_loss;
+    }
+}
+
+// Read precomputed aggregates for one dimension (see STAT_DIMENSIONS),
+// optionally adding the aggregates of archived trades
+pub async fn load_dimension_statistics(
+    pool: &SqlitePool,
+    dimension: &str,
+    include_archived: bool,
+) -> Result<Vec<DimensionStatistics>, SqlxError> {
+    if !STAT_DIMENSIONS.iter().any(|(name, _)| *name == dimension) {
+        return Err(SqlxError::Decode(format!("Unknown statistics dimension: {}", dimension).into()));
+    }
+    
+    let sql = if include_archived {
+        r#"
+        SELECT statistic_type, statistic_key, SUM(statistic_value) AS statistic_value
+        FROM (
+            SELECT statistic_type, statistic_key, statistic_value FROM main.trade_statistics
+            UNION ALL
+            SELECT statistic_type, statistic_key, statistic_value FROM archive.trade_statistics
+        )
+        WHERE statistic_type LIKE ?
+        GROUP BY statistic_type, statistic_key
+        "#
+    } else {
+        "SELECT statistic_type, statistic_key, statistic_value FROM trade_statistics WHERE statistic_type LIKE ?"
+    };
+    
+    let rows = sqlx::query(sql)
+    .bind(format!("{}:%", dimension))
+    .fetch_all(pool)
+    .await?;
+    
+    let mut by_key: std::collections::BTreeMap<String, DimensionStatistics> = std::collections::BTreeMap::new();
+    for row in rows {
+        let statistic_type: String = row.get("statistic_type");
+        let key: String = row.get("statistic_key");
+        let value: f64 = row.get("statistic_value");
+        
+        let stats = by_key.entry(key.clone()).or_insert_with(|| DimensionStatistics { key, ..Default::default() });
+        match statistic_type.split(':').nth(1).unwrap_or_default() {
+            "trade_count" => stats.trade_count = value.round() as u32,
+            "closed_count" => stats.closed_count = value.round() as u32,
+            "win_count" => stats.win_count = value.round() as u32,
+            "loss_count" => stats.loss_count = value.round() as u32,
+            "net_profit" => stats.net_profit = value,
+            "gross_profit" => stats.gross_profit = value,
+            "gross_loss" => stats.gross_loss = value,
+            _ => {}
+        }
+    }
+    
+    Ok(by_key.into_values().filter(|stats| stats.trade_count > 0).collect())
+}
+
+// Attached database holding archived trades (see DatabaseState::archive_trades)
+const ARCHIVE_SCHEMA: &str = "archive";
+
+// Tables whose rows follow a trade into the archive
+const ARCHIVED_TABLES: [&str; 5] = ["trades", "trade_merges", "executions", "image_hashes", "trade_statistics"];
+
+// Columns of the trades table itself. Every other column holds a custom
+// field declared in the Trade entity schema (see ensure_custom_columns).
+const BUILTIN_TRADE_COLUMNS: &[&str] = &[
+    "id", "symbol", "trade_type", "volume", "entry_price", "sl", "tp", "entry_time", "exit_time", "exit_price",
+    "commission", "swap", "notes", "ict_pattern", "pattern_type", "pattern_size", "pattern_timeframe",
+    "pattern_combination", "chart_explanation", "strategy_name", "emotion", "confidence_level", "market_condition",
+    "session", "entry_image", "exit_image", "analysis_image", "rsi", "macd", "moving_average", "support_level",
+    "resistance_level", "is_win", "profit_loss_pips", "profit_loss_money", "risk_reward_ratio", "created_at",
+    "updated_at", "version", "source_timezone", "external_id", "broker", "account",
+];
+
+// Table expression for trade reads; archived trades are only included on
+// request. The archive gets columns appended in the order they were added,
+// so its layout can differ from the live table and the union names them.
+pub async fn trades_source<'e, E>(executor: E, include_archived: bool) -> Result<String, SqlxError>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
+    if !include_archived {
+        return Ok("trades".to_string());
+    }
+    
+    let columns: Vec<String> = sqlx::query("PRAGMA main.table_info(trades)")
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(|row| format!("\"{}\"", row.get::<String, _>("name")))
+        .collect();
+    
+    Ok(format!(
+        "(SELECT {columns} FROM main.trades UNION ALL SELECT {columns} FROM archive.trades) AS trades",
+        columns = columns.join(", ")
+    ))
+}
+
+// Custom fields of the Trade schema with their column type: fields that are
+// not trade columns. Names must be plain identifiers since they become columns.
+pub fn custom_trade_fields(schema: &serde_json::Value) -> Result<Vec<(String, &'static str)>, String> {
+    let fields = schema.get("fields").and_then(serde_json::Value::as_array).cloned().unwrap_or_default();
+    
+    fields.iter()
+        .filter_map(|field| {
+            let name = field.get("name")?.as_str()?;
+            (!BUILTIN_TRADE_COLUMNS.contains(&name)).then(|| (name, field.get("data_type").and_then(serde_json::Value::as_str)))
+        })
+        .map(|(name, data_type)| {
+            let identifier = name.starts_with(|c: char| c.is_ascii_lowercase())
+                && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !identifier {
+                return Err(format!("Custom field '{}' must use lowercase letters, digits and underscores", name));
+            }
+            let column_type = match data_type.unwrap_or_default() {
+                "integer" | "boolean" => "INTEGER",
+                "number" | "float" | "decimal" => "REAL",
+                _ => "TEXT",
+            };
+            Ok((name.to_string(), column_type))
+        })
+        .collect()
+}
+
+// An imported custom field value in the type of its column. Text fields
+// keep what the file says ("0012" stays text); numbers must parse.
+fn custom_field_value(column_type: &str, value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let text = match value {
+        serde_json::Value::String(text) => text,
+        serde_json::Value::Null => return Ok(serde_json::Value::Null),
+        other if column_type == "TEXT" => return Ok(serde_json::Value::String(other.to_string())),
+        other => return Ok(other),
+    };
+    let trimmed = text.trim();
+    match column_type {
+        "INTEGER" => match trimmed.to_ascii_lowercase().as_str() {
+            "true" | "yes" => Ok(serde_json::json!(1)),
+            "false" | "no" => Ok(serde_json::json!(0)),
+            _ => trimmed.parse::<i64>().map(|n| serde_json::json!(n)).map_err(|_| format!("'{}' is not a whole number", text)),
+        },
+        "REAL" => trimmed.parse::<f64>().map(|n| serde_json::json!(n)).map_err(|_| format!("'{}' is not a number", text)),
+        _ => Ok(serde_json::Value::String(text)),
+    }
+}
+
+// Custom fields of the stored Trade schema
+async fn stored_custom_fields<'e, E>(executor: E) -> Result<Vec<(String, &'static str)>, SqlxError>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
+    let schema_json: Option<String> = sqlx::query_scalar("SELECT schema_json FROM entity_schemas WHERE entity_name = ?")
+        .bind(TRADE_SCHEMA)
+        .fetch_optional(executor)
+        .await?;
+    let Some(schema_json) = schema_json else {
+        return Ok(Vec::new());
+    };
+    let schema: serde_json::Value = serde_json::from_str(&schema_json)
+        .map_err(|e| SqlxError::Decode(format!("Unreadable {} schema: {}", TRADE_SCHEMA, e).into()))?;
+    custom_trade_fields(&schema).map_err(|e| SqlxError::Decode(e.into()))
+}
+
+// Give every custom field of the stored Trade schema a column in the live
+// and archived trades tables. Returns the custom field names.
+async fn ensure_custom_columns(conn: &mut sqlx::SqliteConnection) -> Result<Vec<String>, SqlxError> {
+    let fields = stored_custom_fields(&mut *conn).await?;
+    
+    for schema in ["main", ARCHIVE_SCHEMA] {
+        let existing: Vec<String> = sqlx::query(&format!("PRAGMA {}.table_info(trades)", schema))
+            .fetch_all(&mut *conn)
+            .await?
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+        // The archive table is created from the live one on first start
+        if existing.is_empty() {
+            continue;
+        }
+        for (name, column_type) in &fields {
+            if !existing.contains(name) {
+                sqlx::query(&format!("ALTER TABLE {}.trades ADD COLUMN {} {}", schema, name, column_type))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+    }
+    
+    Ok(fields.into_iter().map(|(name, _)| name).collect())
+}
+
+// One keyset page of trades ordered by (entry_time, id). Takes a pool rather
+// than the state so streaming can page in the background without the app lock.
+pub async fn fetch_trade_page(pool: &SqlitePool, query: &TradeQuery) -> Result<TradePage, SqlxError> {
+    if query.sort_by.as_deref().map_or(false, |column| column != "entry_time") {
+        return Err(SqlxError::Decode("Cursor pagination is only ordered by entry_time".into()));
+    }
+
+    let direction = SortDirection::parse(query.sort_order.as_deref())
+        .ok_or_else(|| SqlxError::Decode(format!("Invalid sort order: {:?}", query.sort_order).into()))?;
+    let cursor = match &query.cursor {
+        Some(value) => {
+            let cursor = TradeCursor::decode(value)
+                .ok_or_else(|| SqlxError::Decode("Invalid trade cursor".into()))?;
+            if cursor.direction != direction {
+                return Err(SqlxError::Decode("Trade cursor was issued for a different sort order".into()));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let source = trades_source(pool, query.include_archived).await?;
+    let (filter, params) = DatabaseState::build_trade_filter(query);
+
+    // The total ignores the cursor so it stays the same on every page
+    let count_sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1{}", source, filter);
+    let mut count_query = sqlx::query_scalar::<_, i64>(&count_sql);
+    for param in &params {
+        count_query = count_query.bind(param);
+    }
+    let total_count = count_query.fetch_one(pool).await?;
+
+    let mut sql = format!("SELECT * FROM {} WHERE 1=1{}", source, filter);
+    let mut params = params;
+    if let Some(cursor) = &cursor {
+        let (condition, cursor_params) = cursor.condition();
+        sql.push_str(&condition);
+        params.extend(cursor_params);
+    }
+
+    // Fetch one extra row to know whether another page follows
+    let limit = pagination::page_size(query.limit);
+    sql.push_str(&format!(
+        " ORDER BY entry_time {order}, id {order} LIMIT {}",
+        limit + 1,
+        order = direction.sql()
+    ));
+
+    let mut query_builder = sqlx::query_as::<_, Trade>(&sql);
+    for param in params {
+        query_builder = query_builder.bind(param);
+    }
+    let mut trades = query_builder.fetch_all(pool).await?;
+
+    let next_cursor = if trades.len() > limit as usize {
+        trades.truncate(limit as usize);
+        trades.last().map(|trade| TradeCursor::after(trade, direction).encode())
+    } else {
+        None
+    };
+
+    Ok(TradePage { trades, next_cursor, total_count })
+}
+
+// (name, declared type) of a table's columns; empty when the table does not exist
+async fn table_columns(pool: &SqlitePool, schema: &str, table: &str) -> Result<Vec<(String, String)>, SqlxError> {
+    let rows = sqlx::query(&format!("PRAGMA {}.table_info({})", schema, table))
+        .fetch_all(pool)
+        .await?;
+    
+    Ok(rows.iter().map(|row| (row.get("name"), row.get("type"))).collect())
+}
+
 // Database state
 pub struct DatabaseState {
     pool: SqlitePool,
     schema_cache: HashMap<String, EntitySchema>,
     image_storage_path: PathBuf,
+    time_settings: TimeSettings,
+    paths: WorkspacePaths,
 }
 
 impl DatabaseState {
@@ -182,32 +719,39 @@ impl DatabaseState {
         // Create necessary directories
         self.create_directories().await?;
         
+        // Load timezone settings used to normalize timestamps
+        self.time_settings = TimeSettings::load(&self.paths.config_dir()).await?;
+        
         // Run migrations
         self.run_migrations().await?;
         
         // Load default schemas
         self.load_default_schemas().await?;
         
+        // Columns for custom fields declared in the Trade schema
+        let mut conn = self.pool.acquire().await?;
+        ensure_custom_columns(&mut conn).await?;
+        drop(conn);
+        
         // Create indexes
         self.create_indexes().await?;
         
+        // Cold storage for old trades
+        self.sync_archive_tables().await?;
+        
+        // Keep trade_statistics in sync with trades
+        self.create_statistics_triggers().await?;
+        
+        // Record every mutation in change_log
+        self.create_change_log_triggers().await?;
+        
         log::info!("Database initialized successfully");
         Ok(())
     }
     
     // Create necessary directories
     async fn create_directories(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let directories = [
-            "data",
-            "data/images",
-            "data/thumbnails",
-            "data/backups",
-            "data/exports",
-            "config",
-            "logs"
-        ];
-        
-        for dir in directories.iter() {
+        for dir in self.paths.directories() {
             fs::create_dir_all(dir).await?;
         }
         
@@ -227,8 +771,8 @@ impl DatabaseState {
                 trade_type TEXT NOT NULL CHECK(trade_type IN ('Buy', 'Sell')),
                 volume REAL NOT NULL,
                 entry_price REAL NOT NULL,
-                sl REAL NOT NULL,
-                tp REAL NOT NULL,
+                sl REAL,
+                tp REAL,
                 entry_time TEXT NOT NULL,
                 exit_time TEXT,
                 exit_price REAL,
@@ -333,10 +877,144 @@ impl DatabaseState {
             "#
         ).execute(&self.pool).await?;
         
+        // UTC offset of the display timezone from `starts_at` on, for day
+        // buckets computed inside SQL (see STAT_DIMENSIONS)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS display_day_offsets (
+                starts_at TEXT PRIMARY KEY,
+                offset_seconds INTEGER NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Perceptual hashes of stored chart images
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS image_hashes (
+                image_path TEXT PRIMARY KEY,
+                dhash INTEGER NOT NULL,
+                band0 INTEGER NOT NULL,
+                band1 INTEGER NOT NULL,
+                band2 INTEGER NOT NULL,
+                band3 INTEGER NOT NULL,
+                width INTEGER NOT NULL,
+                height INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Audit trail of merged duplicate trades
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS trade_merges (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                primary_trade_id INTEGER NOT NULL,
+                merged_trade_id INTEGER NOT NULL,
+                merged_trade_json TEXT NOT NULL,
+                field_sources_json TEXT NOT NULL,
+                merged_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Deposits, withdrawals and other balance operations from statements
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cash_flows (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                amount REAL NOT NULL,
+                occurred_at TEXT NOT NULL,
+                comment TEXT,
+                external_id TEXT,
+                created_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Broker fills; trade_id links a fill to the trade built from it
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS executions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                trade_id INTEGER,
+                position_id TEXT,
+                external_id TEXT,
+                symbol TEXT NOT NULL,
+                side TEXT NOT NULL,
+                volume REAL NOT NULL,
+                price REAL NOT NULL,
+                executed_at TEXT NOT NULL,
+                commission REAL,
+                created_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Daily exchange rates for converting account amounts (1 base = rate quote)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS fx_rates (
+                base_currency TEXT NOT NULL,
+                quote_currency TEXT NOT NULL,
+                rate_date TEXT NOT NULL,
+                rate REAL NOT NULL CHECK(rate > 0),
+                source TEXT,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (base_currency, quote_currency, rate_date)
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Append-only outbox of every change; AUTOINCREMENT keeps sequence
+        // numbers increasing even after compaction deleted the newest rows
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS change_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                operation TEXT NOT NULL CHECK(operation IN ('insert', 'update', 'delete')),
+                payload TEXT NOT NULL,
+                changed_at TEXT NOT NULL
+            )
+            "#
+        ).execute(&self.pool).await?;
+        
+        // Columns added after the initial release
+        self.ensure_column("trades", "source_timezone", "TEXT").await?;
+        self.ensure_column("plugin_data", "expires_at", "TEXT").await?;
+        self.ensure_column("change_log", "moved_to", "TEXT").await?;
+        self.ensure_column("trades", "external_id", "TEXT").await?;
+        // Broker and account an external id belongs to (see TicketScope)
+        for table in ["trades", "cash_flows", "executions"] {
+            self.ensure_column(table, "broker", "TEXT").await?;
+            self.ensure_column(table, "account", "TEXT").await?;
+        }
+        
+        self.run_data_migrations().await?;
+        
         log::info!("Database migrations completed successfully");
         Ok(())
     }
     
+    // Add a column to an existing table unless it is already present
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<(), SqlxError> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await?;
+        
+        if !columns.iter().any(|row| row.get::<String, _>("name") == column) {
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
+        
+        Ok(())
+    }
+    
     // Create indexes for performance
     async fn create_indexes(&self) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Creating database indexes...");
@@ -352,8 +1030,24 @@ impl DatabaseState {
             "CREATE INDEX IF NOT EXISTS idx_trades_market_condition ON trades(market_condition)",
             "CREATE INDEX IF NOT EXISTS idx_trades_session ON trades(session)",
             "CREATE INDEX IF NOT EXISTS idx_trades_created_at ON trades(created_at)",
+            "CREATE INDEX IF NOT EXISTS idx_trades_external_id ON trades(external_id)",
+            "CREATE INDEX IF NOT EXISTS idx_cash_flows_occurred_at ON cash_flows(occurred_at)",
+            "CREATE INDEX IF NOT EXISTS idx_cash_flows_external_id ON cash_flows(external_id)",
+            "CREATE INDEX IF NOT EXISTS idx_executions_trade_id ON executions(trade_id)",
+            "CREATE INDEX IF NOT EXISTS idx_executions_external_id ON executions(external_id)",
             "CREATE INDEX IF NOT EXISTS idx_trade_stats_type_key ON trade_statistics(statistic_type, statistic_key)",
             "CREATE INDEX IF NOT EXISTS idx_plugin_data_name_key ON plugin_data(plugin_name, data_key)",
+            "CREATE INDEX IF NOT EXISTS idx_plugin_data_expires_at ON plugin_data(expires_at)",
+            "CREATE INDEX IF NOT EXISTS idx_image_hashes_band0 ON image_hashes(band0)",
+            "CREATE INDEX IF NOT EXISTS idx_image_hashes_band1 ON image_hashes(band1)",
+            "CREATE INDEX IF NOT EXISTS idx_image_hashes_band2 ON image_hashes(band2)",
+            "CREATE INDEX IF NOT EXISTS idx_image_hashes_band3 ON image_hashes(band3)",
+            "CREATE INDEX IF NOT EXISTS idx_trades_entry_image ON trades(entry_image)",
+            "CREATE INDEX IF NOT EXISTS idx_trade_merges_primary ON trade_merges(primary_trade_id)",
+            "CREATE INDEX IF NOT EXISTS idx_trades_exit_image ON trades(exit_image)",
+            "CREATE INDEX IF NOT EXISTS idx_trades_analysis_image ON trades(analysis_image)",
+            "CREATE INDEX IF NOT EXISTS idx_change_log_changed_at ON change_log(changed_at)",
+            "CREATE INDEX IF NOT EXISTS idx_trades_analysis ON trades(entry_time, id, exit_time, is_win, profit_loss_money, strategy_name)",
         ];
         
         for index_sql in indexes.iter() {
@@ -444,12 +1138,29 @@ impl DatabaseState {
     }
 
         // Trade operations
-        pub async fn create_trade(&self, trade: NewTrade) -> Result<u32, SqlxError> {
+        pub async fn create_trade(&self, mut trade: NewTrade) -> Result<u32, SqlxError> {
             let now = Utc::now().to_rfc3339();
             
+            // Store entry time in UTC, remembering the zone it was recorded in
+            self.normalize_new_trade_time(&mut trade)?;
+            
             // Calculate derived fields
-            let (is_win, profit_loss_pips, profit_loss_money, risk_reward_ratio) = 
-                self.calculate_trade_metrics(&trade).await;
+            let metrics = Self::trade_metrics(&trade.symbol, &trade.trade_type, trade.volume, trade.entry_price, None, trade.sl, trade.tp);
+            
+            Self::insert_trade(&self.pool, &trade, metrics, &now).await
+        }
+        
+        // INSERT shared by create_trade and imports; times must already be normalized
+        async fn insert_trade<'e, E>(
+            executor: E,
+            trade: &NewTrade,
+            metrics: (Option<bool>, Option<f64>, Option<f64>, Option<f64>),
+            now: &str,
+        ) -> Result<u32, SqlxError>
+        where
+            E: sqlx::Executor<'e, Database = Sqlite>,
+        {
+            let (is_win, profit_loss_pips, profit_loss_money, risk_reward_ratio) = metrics;
             
             // Serialize pattern combination to JSON
             let pattern_combination_json = trade.pattern_combination
@@ -464,8 +1175,8 @@ impl DatabaseState {
                     pattern_combination, chart_explanation, strategy_name, emotion, confidence_level,
                     market_condition, session, entry_image, exit_image, analysis_image, rsi, macd,
                     moving_average, support_level, resistance_level, is_win, profit_loss_pips,
-                    profit_loss_money, risk_reward_ratio, created_at, updated_at, version
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    profit_loss_money, risk_reward_ratio, created_at, updated_at, version, source_timezone
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 "#
             )
             .bind(&trade.symbol)
@@ -501,10 +1212,11 @@ impl DatabaseState {
             .bind(profit_loss_pips)
             .bind(profit_loss_money)
             .bind(risk_reward_ratio)
-            .bind(&now)
-            .bind(&now)
+            .bind(now)
+            .bind(now)
             .bind(1)
-            .execute(&self.pool)
+            .bind(&trade.source_timezone)
+            .execute(executor)
             .await?;
             
             Ok(result.last_insert_rowid() as u32)
@@ -523,44 +1235,9 @@ impl DatabaseState {
         }
         
         pub async fn get_trades_with_query(&self, query: TradeQuery) -> Result<Vec<Trade>, SqlxError> {
-            let mut sql = "SELECT * FROM trades WHERE 1=1".to_string();
-            let mut params: Vec<String> = Vec::new();
-            
-            // Build WHERE clause based on query parameters
-            if let Some(symbols) = &query.symbol {
-                if !symbols.is_empty() {
-                    let placeholders = symbols.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-                    sql.push_str(&format!(" AND symbol IN ({})", placeholders));
-                    params.extend(symbols.clone());
-                }
-            }
-            
-            if let Some(trade_types) = &query.trade_type {
-                if !trade_types.is_empty() {
-                    let placeholders = trade_types.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-                    sql.push_str(&format!(" AND trade_type IN ({})", placeholders));
-                    params.extend(trade_types.clone());
-                }
-            }
-            
-            if let Some((start_date, end_date)) = &query.date_range {
-                sql.push_str(" AND entry_time BETWEEN ? AND ?");
-                params.push(start_date.to_rfc3339());
-                params.push(end_date.to_rfc3339());
-            }
-            
-            if let Some(ict_patterns) = &query.ict_pattern {
-                if !ict_patterns.is_empty() {
-                    let placeholders = ict_patterns.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-                    sql.push_str(&format!(" AND ict_pattern IN ({})", placeholders));
-                    params.extend(ict_patterns.clone());
-                }
-            }
-            
-            if let Some(is_win) = query.is_win {
-                sql.push_str(" AND is_win = ?");
-                params.push(if is_win { "1".to_string() } else { "0".to_string() });
-            }
+            let (where_clause, params) = Self::build_trade_filter(&query);
+            let source = trades_source(&self.pool, query.include_archived).await?;
+            let mut sql = format!("SELECT * FROM {} WHERE 1=1{}", source, where_clause);
             
             // Add sorting
             if let Some(sort_by) = &query.sort_by {
@@ -587,13 +1264,78 @@ impl DatabaseState {
             Ok(trades)
         }
         
+        pub async fn get_trades_page(&self, query: &TradeQuery) -> Result<TradePage, SqlxError> {
+            fetch_trade_page(&self.pool, query).await
+        }
+        
+        // Build the WHERE clause (appended to "WHERE 1=1") for a trade query
+        fn build_trade_filter(query: &TradeQuery) -> (String, Vec<String>) {
+            let mut sql = String::new();
+            let mut params: Vec<String> = Vec::new();
+            
+            fn push_in(column: &str, values: &Option<Vec<String>>, sql: &mut String, params: &mut Vec<String>) {
+                if let Some(values) = values {
+                    if !values.is_empty() {
+                        let placeholders = values.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                        sql.push_str(&format!(" AND {} IN ({})", column, placeholders));
+                        params.extend(values.iter().cloned());
+                    }
+                }
+            }
+            
+            let ids = query.ids.as_ref()
+                .map(|ids| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>());
+            push_in("id", &ids, &mut sql, &mut params);
+            push_in("symbol", &query.symbol, &mut sql, &mut params);
+            push_in("trade_type", &query.trade_type, &mut sql, &mut params);
+            
+            if let Some((start_date, end_date)) = &query.date_range {
+                sql.push_str(" AND entry_time BETWEEN ? AND ?");
+                // Stored times are format_utc strings, compared as text
+                params.push(timezone::format_utc(*start_date));
+                params.push(timezone::format_utc(*end_date));
+            }
+            
+            push_in("ict_pattern", &query.ict_pattern, &mut sql, &mut params);
+            push_in("strategy_name", &query.strategy_name, &mut sql, &mut params);
+            push_in("emotion", &query.emotion, &mut sql, &mut params);
+            push_in("market_condition", &query.market_condition, &mut sql, &mut params);
+            
+            if let Some(is_win) = query.is_win {
+                sql.push_str(" AND is_win = ?");
+                params.push(if is_win { "1".to_string() } else { "0".to_string() });
+            }
+            
+            if let Some(min_profit) = query.min_profit {
+                sql.push_str(" AND profit_loss_money >= ?");
+                params.push(min_profit.to_string());
+            }
+            
+            if let Some(max_profit) = query.max_profit {
+                sql.push_str(" AND profit_loss_money <= ?");
+                params.push(max_profit.to_string());
+            }
+            
+            (sql, params)
+        }
+        
         pub async fn update_trade(
             &self, 
             id: u32, 
-            updates: HashMap<String, serde_json::Value>
+            mut updates: HashMap<String, serde_json::Value>
         ) -> Result<Trade, SqlxError> {
             let now = Utc::now().to_rfc3339();
             
+            if updates.contains_key("entry_time") || updates.contains_key("exit_time") {
+                let stored_zone: Option<String> = sqlx::query("SELECT source_timezone FROM trades WHERE id = ?")
+                    .bind(id)
+                    .fetch_one(&self.pool)
+                    .await?
+                    .get("source_timezone");
+                self.normalize_time_patch(&mut updates, stored_zone.as_deref())?;
+            }
+            let recalculate = patch_changes_metrics(&updates);
+            
             // Build dynamic update query
             let mut set_clauses = Vec::new();
             let mut params: Vec<serde_json::Value> = Vec::new();
@@ -615,10 +1357,15 @@ impl DatabaseState {
             let mut query_builder = sqlx::query(&sql);
             
             for param in params {
-                query_builder = query_builder.bind(param);
+                query_builder = bind_json_value(query_builder, param);
             }
             
-            query_builder.bind(id).execute(&self.pool).await?;
+            let mut tx = self.pool.begin().await?;
+            query_builder.bind(id).execute(&mut *tx).await?;
+            if recalculate {
+                Self::recalculate_trade_metrics(&mut tx, &[id]).await?;
+            }
+            tx.commit().await?;
             
             // Return updated trade
             self.get_trade_by_id(id).await
@@ -677,7 +1424,12 @@ impl DatabaseState {
             
             let now = Utc::now().to_rfc3339();
             let schema_json = serde_json::to_string(&entity_schema).unwrap();
+            if entity_schema.name == TRADE_SCHEMA {
+                custom_trade_fields(&serde_json::from_str(&schema_json).unwrap())
+                    .map_err(|e| SqlxError::Decode(e.into()))?;
+            }
             
+            let mut conn = self.pool.acquire().await?;
             sqlx::query(
                 r#"
                 INSERT OR REPLACE INTO entity_schemas (entity_name, schema_json, created_at, updated_at)
@@ -689,9 +1441,17 @@ impl DatabaseState {
             .bind(&entity_schema.name)
             .bind(&now)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?;
             
+            // New custom fields get their columns, and the change log triggers
+            // have to list them
+            if entity_schema.name == TRADE_SCHEMA {
+                ensure_custom_columns(&mut conn).await?;
+                drop(conn);
+                self.create_change_log_triggers().await?;
+            }
+            
             // Update cache
             self.schema_cache.insert(entity_schema.name, entity_schema);
             
@@ -718,22 +1478,31 @@ impl DatabaseState {
             // Copy original image
             fs::copy(&original_path, &target_path).await?;
             
+            let relative_path = format!("images/{}", unique_filename);
+            let image = self.load_image(&original_path).await?;
+            
             // Create thumbnail
-            self.create_thumbnail(&original_path, &thumbnail_path).await?;
+            self.create_thumbnail(&image, &thumbnail_path)?;
+            
+            // Index perceptual hash for similarity search
+            self.store_image_hash(&relative_path, &image).await?;
             
             // Return relative path for database storage
-            Ok(format!("images/{}", unique_filename))
+            Ok(relative_path)
         }
         
-        async fn create_thumbnail(
-            &self, 
-            source_path: &PathBuf, 
-            target_path: &PathBuf
-        ) -> Result<(), Box<dyn std::error::Error>> {
+        async fn load_image(&self, source_path: &PathBuf) -> Result<DynamicImage, Box<dyn std::error::Error>> {
             let image_data = fs::read(source_path).await?;
             let image_format = ImageFormat::from_path(source_path)?;
             
-            let image = image::load_from_memory_with_format(&image_data, image_format)?;
+            Ok(image::load_from_memory_with_format(&image_data, image_format)?)
+        }
+        
+        fn create_thumbnail(
+            &self, 
+            image: &DynamicImage, 
+            target_path: &PathBuf
+        ) -> Result<(), Box<dyn std::error::Error>> {
             let thumbnail = image.resize(200, 200, FilterType::Lanczos3);
             
             thumbnail.save_with_format(target_path, ImageFormat::WebP)?;
@@ -741,23 +1510,25 @@ impl DatabaseState {
             Ok(())
         }
         
-        // Calculate trade metrics
-        async fn calculate_trade_metrics(
-            &self, 
-            trade: &NewTrade
+        // Calculate trade metrics: (is_win, pips, money, risk/reward). The
+        // exit price is passed separately because NewTrade only describes the entry.
+        fn trade_metrics(
+            symbol: &str,
+            trade_type: &str,
+            volume: f64,
+            entry_price: f64,
+            exit_price: Option<f64>,
+            sl: Option<f64>,
+            tp: Option<f64>,
         ) -> (Option<bool>, Option<f64>, Option<f64>, Option<f64>) {
             // If trade is not closed, return None for calculated fields
-            if trade.exit_price.is_none() {
+            let Some(exit_price) = exit_price else {
                 return (None, None, None, None);
-            }
-            
-            let entry_price = trade.entry_price;
-            let exit_price = trade.exit_price.unwrap();
-            let volume = trade.volume;
+            };
             
-            // Calculate P/L in pips
-            let price_diff = exit_price - entry_price;
-            let profit_loss_pips = if trade.symbol.contains("JPY") {
+            // Calculate P/L in pips, positive when the trade made money
+            let price_diff = if trade_type == "Sell" { entry_price - exit_price } else { exit_price - entry_price };
+            let profit_loss_pips = if symbol.contains("JPY") {
                 price_diff * 100.0 // For JPY pairs
             } else {
                 price_diff * 10000.0 // For other pairs
@@ -767,18 +1538,70 @@ impl DatabaseState {
             let profit_loss_money = price_diff * volume * 100000.0; // Standard lot size
             
             // Determine if trade is win
-            let is_win = if trade.trade_type == "Buy" {
-                profit_loss_money > 0.0
-            } else {
-                profit_loss_money < 0.0
-            };
+            let is_win = profit_loss_money > 0.0;
+            
+            // Calculate risk/reward ratio; unknown without both levels
+            let risk_reward_ratio = sl.zip(tp).map(|(sl, tp)| {
+                let risk = (entry_price - sl).abs();
+                let reward = (tp - entry_price).abs();
+                if risk > 0.0 { reward / risk } else { 0.0 }
+            });
+            
+            (Some(is_win), Some(profit_loss_pips), Some(profit_loss_money), risk_reward_ratio)
+        }
+        
+        // Point the state at a workspace; nothing is touched on disk until
+        // initialize() creates the directories and runs migrations. Every
+        // pooled connection has the archive database attached.
+        pub fn open(paths: WorkspacePaths) -> Result<Self, SqlxError> {
+            let options = SqliteConnectOptions::from_str(&paths.database_url())?.create_if_missing(true);
+            let archive_file = paths.archive_file().to_string_lossy().to_string();
             
-            // Calculate risk/reward ratio
-            let risk = (entry_price - trade.sl).abs();
-            let reward = (trade.tp - entry_price).abs();
-            let risk_reward_ratio = if risk > 0.0 { reward / risk } else { 0.0 };
+            let pool = SqlitePoolOptions::new()
+                .after_connect(move |conn, _meta| {
+                    let archive_file = archive_file.clone();
+                    Box::pin(async move {
+                        sqlx::query(&format!("ATTACH DATABASE ? AS {}", ARCHIVE_SCHEMA))
+                            .bind(archive_file)
+                            .execute(&mut *conn)
+                            .await?;
+                        Ok(())
+                    })
+                })
+                .connect_lazy_with(options);
             
-            (Some(is_win), Some(profit_loss_pips), Some(profit_loss_money), Some(risk_reward_ratio))
+            Ok(Self {
+                pool,
+                schema_cache: HashMap::new(),
+                image_storage_path: paths.data_dir(),
+                time_settings: TimeSettings::default(),
+                paths,
+            })
+        }
+        
+        pub fn paths(&self) -> &WorkspacePaths {
+            &self.paths
+        }
+        
+        // Consistent copy of the live and archive databases into another
+        // workspace, safe while the pool is in use
+        pub async fn snapshot_to(&self, target: &WorkspacePaths) -> Result<(), SqlxError> {
+            for (schema, file) in [("main", target.database_file()), (ARCHIVE_SCHEMA, target.archive_file())] {
+                sqlx::query(&format!("VACUUM {} INTO ?", schema))
+                    .bind(file.to_string_lossy().to_string())
+                    .execute(&self.pool)
+                    .await?;
+            }
+            Ok(())
+        }
+        
+        // Shared pool for subsystems that run their own queries
+        pub fn pool(&self) -> SqlitePool {
+            self.pool.clone()
+        }
+        
+        pub fn image_storage_path(&self) -> &PathBuf {
+            &self.image_storage_path
         }
         
         // Health check
@@ -794,17 +1617,268 @@ impl DatabaseState {
         }
     }
     
+    // Timezone handling
+    impl DatabaseState {
+        pub fn time_settings(&self) -> &TimeSettings {
+            &self.time_settings
+        }
+        
+        pub async fn update_time_settings(&mut self, settings: TimeSettings) -> Result<(), Box<dyn std::error::Error>> {
+            settings.validate()?;
+            settings.save(&self.paths.config_dir()).await?;
+            self.time_settings = settings;
+            
+            // Day aggregates are bucketed in the display timezone
+            if self.sync_display_offsets().await? {
+                self.rebuild_trade_statistics().await?;
+            }
+            Ok(())
+        }
+        
+        fn resolve_timezone(&self, name: Option<&str>) -> Result<chrono_tz::Tz, SqlxError> {
+            match name {
+                Some(name) => timezone::parse_timezone(name).map_err(|e| SqlxError::Decode(e.into())),
+                None => Ok(self.time_settings.broker_tz()),
+            }
+        }
+        
+        fn normalize_new_trade_time(&self, trade: &mut NewTrade) -> Result<(), SqlxError> {
+            let tz = self.resolve_timezone(trade.source_timezone.as_deref())?;
+            
+            trade.entry_time = timezone::normalize_timestamp(&trade.entry_time, tz)
+                .ok_or_else(|| SqlxError::Decode(format!("Invalid entry time: {}", trade.entry_time).into()))?;
+            trade.source_timezone = Some(tz.name().to_string());
+            
+            Ok(())
+        }
+        
+        // Normalize entry/exit times in an update patch. A `source_timezone` in
+        // the patch takes precedence over the zone stored on the trade.
+        fn normalize_time_patch(
+            &self,
+            patch: &mut HashMap<String, serde_json::Value>,
+            stored_zone: Option<&str>,
+        ) -> Result<(), SqlxError> {
+            let patch_zone = patch.get("source_timezone").and_then(|v| v.as_str()).map(|s| s.to_string());
+            let tz = self.resolve_timezone(patch_zone.as_deref().or(stored_zone))?;
+            let mut touched = false;
+            
+            for field in ["entry_time", "exit_time"] {
+                if let Some(serde_json::Value::String(value)) = patch.get(field) {
+                    let normalized = timezone::normalize_timestamp(value, tz)
+                        .ok_or_else(|| SqlxError::Decode(format!("Invalid {}: {}", field, value).into()))?;
+                    patch.insert(field.to_string(), serde_json::Value::String(normalized));
+                    touched = true;
+                }
+            }
+            
+            if touched {
+                patch.insert("source_timezone".to_string(), serde_json::Value::String(tz.name().to_string()));
+            }
+            
+            Ok(())
+        }
+        
+        // Rewrite timestamps of trades stored before normalization existed.
+        // Naive values are read in the broker timezone.
+        pub async fn normalize_trade_timestamps(&self) -> Result<u32, SqlxError> {
+            let mut tx = self.pool.begin().await?;
+            let normalized = Self::normalize_stored_timestamps(&mut tx, self.time_settings.broker_tz()).await?;
+            tx.commit().await?;
+            
+            log::info!("Normalized timestamps of {} trades to UTC", normalized);
+            Ok(normalized)
+        }
+        
+        // Trades with a time that doesn't parse are left untouched (and without
+        // a source_timezone) so a later run can pick them up once corrected
+        async fn normalize_stored_timestamps(
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            tz: chrono_tz::Tz,
+        ) -> Result<u32, SqlxError> {
+            let rows = sqlx::query("SELECT id, entry_time, exit_time FROM trades WHERE source_timezone IS NULL")
+                .fetch_all(&mut **tx)
+                .await?;
+            
+            let mut normalized = 0;
+            for row in rows {
+                let id: i64 = row.get("id");
+                let entry_time: String = row.get("entry_time");
+                let exit_time: Option<String> = row.get("exit_time");
+                
+                let Some(entry_time) = timezone::normalize_timestamp(&entry_time, tz) else {
+                    log::warn!("Skipping trade {} with unparseable entry time '{}'", id, entry_time);
+                    continue;
+                };
+                let exit_time = match exit_time {
+                    Some(value) => match timezone::normalize_timestamp(&value, tz) {
+                        Some(normalized) => Some(normalized),
+                        None => {
+                            log::warn!("Skipping trade {} with unparseable exit time '{}'", id, value);
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                
+                sqlx::query("UPDATE trades SET entry_time = ?, exit_time = ?, source_timezone = ? WHERE id = ?")
+                    .bind(&entry_time)
+                    .bind(&exit_time)
+                    .bind(tz.name())
+                    .bind(id)
+                    .execute(&mut **tx)
+                    .await?;
+                normalized += 1;
+            }
+            
+            Ok(normalized)
+        }
+        
+        // One-time data migrations, each recorded in schema_versions so it
+        // runs once per journal
+        async fn run_data_migrations(&self) -> Result<(), SqlxError> {
+            let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_versions")
+                .fetch_all(&self.pool)
+                .await?;
+            
+            if !applied.contains(&TIMESTAMP_MIGRATION_VERSION) {
+                // Older rows hold naive "YYYY-MM-DD HH:MM:SS" times, which sort and
+                // compare wrongly next to normalized ones
+                let mut tx = self.pool.begin().await?;
+                let normalized = Self::normalize_stored_timestamps(&mut tx, self.time_settings.broker_tz()).await?;
+                sqlx::query("INSERT INTO schema_versions (version, applied_at, description) VALUES (?, ?, ?)")
+                    .bind(TIMESTAMP_MIGRATION_VERSION)
+                    .bind(Utc::now().to_rfc3339())
+                    .bind("Normalize trade timestamps to UTC")
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                
+                log::info!("Normalized timestamps of {} trades to UTC", normalized);
+            }
+            
+            if !applied.contains(&OPTIONAL_LEVELS_MIGRATION_VERSION) {
+                // Trades without a stop or target keep NULL there rather than 0
+                let mut tx = self.pool.begin().await?;
+                for schema in ["main", ARCHIVE_SCHEMA] {
+                    Self::drop_not_null(&mut tx, schema, "trades", &["sl", "tp"]).await?;
+                }
+                sqlx::query("INSERT INTO schema_versions (version, applied_at, description) VALUES (?, ?, ?)")
+                    .bind(OPTIONAL_LEVELS_MIGRATION_VERSION)
+                    .bind(Utc::now().to_rfc3339())
+                    .bind("Allow trades without stop loss or take profit")
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+            }
+            
+            if !applied.contains(&SELL_PROFIT_SIGN_MIGRATION_VERSION) {
+                // Sell P/L used to be stored as exit minus entry with is_win
+                // set for a negative amount. Those rows are recognisable by
+                // is_win disagreeing with the sign; broker figures never do.
+                // The archive copy of the table may not exist yet.
+                let archived = !table_columns(&self.pool, ARCHIVE_SCHEMA, "trades").await?.is_empty();
+                let mut tx = self.pool.begin().await?;
+                let mut flipped = 0;
+                for schema in ["main", ARCHIVE_SCHEMA].into_iter().filter(|schema| *schema == "main" || archived) {
+                    flipped += sqlx::query(&format!(
+                        r#"
+                        UPDATE {}.trades SET
+                            profit_loss_money = -profit_loss_money,
+                            profit_loss_pips = -profit_loss_pips
+                        WHERE trade_type = 'Sell' AND profit_loss_money != 0 AND is_win = (profit_loss_money < 0)
+                        "#,
+                        schema
+                    ))
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                }
+                if flipped > 0 {
+                    // Emptied aggregates are rebuilt once the triggers are in place
+                    sqlx::query("DELETE FROM main.trade_statistics").execute(&mut *tx).await?;
+                }
+                sqlx::query("INSERT INTO schema_versions (version, applied_at, description) VALUES (?, ?, ?)")
+                    .bind(SELL_PROFIT_SIGN_MIGRATION_VERSION)
+                    .bind(Utc::now().to_rfc3339())
+                    .bind("Store Sell P/L with the sign of the outcome")
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                
+                log::info!("Corrected the P/L sign of {} Sell trades", flipped);
+            }
+            
+            Ok(())
+        }
+        
+        // SQLite cannot change a column constraint in place, so the table is
+        // rebuilt from its own definition with the NOT NULLs removed. Indexes
+        // and triggers go with the old table and are recreated on every start.
+        async fn drop_not_null(
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            schema: &str,
+            table: &str,
+            columns: &[&str],
+        ) -> Result<(), SqlxError> {
+            let definition: Option<String> = sqlx::query_scalar(&format!(
+                "SELECT sql FROM {}.sqlite_master WHERE type = 'table' AND name = ?", schema
+            ))
+            .bind(table)
+            .fetch_optional(&mut **tx)
+            .await?;
+            let Some(definition) = definition else {
+                return Ok(());
+            };
+            
+            let mut relaxed = definition.clone();
+            for column in columns {
+                relaxed = relaxed.replace(&format!("{} REAL NOT NULL", column), &format!("{} REAL", column));
+            }
+            if relaxed == definition {
+                return Ok(());
+            }
+            // A table renamed by an earlier rebuild has its name quoted
+            let body = relaxed.strip_prefix("CREATE TABLE ")
+                .and_then(|rest| rest.strip_prefix(table).or_else(|| rest.strip_prefix(&format!("\"{}\"", table))))
+                .ok_or_else(|| SqlxError::Decode(format!("Unexpected definition of table {}", table).into()))?;
+            
+            let sequence: Option<i64> = sqlx::query_scalar(&format!("SELECT seq FROM {}.sqlite_sequence WHERE name = ?", schema))
+                .bind(table)
+                .fetch_optional(&mut **tx)
+                .await?;
+            
+            for statement in [
+                format!("CREATE TABLE {schema}.{table}_rebuild{body}"),
+                format!("INSERT INTO {schema}.{table}_rebuild SELECT * FROM {schema}.{table}"),
+                format!("DROP TABLE {schema}.{table}"),
+                format!("ALTER TABLE {schema}.{table}_rebuild RENAME TO {table}"),
+            ] {
+                sqlx::query(&statement).execute(&mut **tx).await?;
+            }
+            // Dropping the table dropped its AUTOINCREMENT high-water mark
+            if let Some(sequence) = sequence {
+                sqlx::query(&format!("DELETE FROM {}.sqlite_sequence WHERE name = ?", schema))
+                    .bind(table)
+                    .execute(&mut **tx)
+                    .await?;
+                sqlx::query(&format!("INSERT INTO {}.sqlite_sequence (name, seq) VALUES (?, ?)", schema))
+                    .bind(table)
+                    .bind(sequence)
+                    .execute(&mut **tx)
+                    .await?;
+            }
+            
+            log::info!("Rebuilt {}.{} without NOT NULL on {}", schema, table, columns.join(", "));
+            Ok(())
+        }
+    }
+    
     // Implementation of Default for DatabaseState
     impl Default for DatabaseState {
         fn default() -> Self {
-            // This will be properly initialized in the setup phase
-            Self {
-                pool: SqlitePoolOptions::new()
-                    .connect_lazy("sqlite:data/trading_journal.db")
-                    .expect("Failed to create database pool"),
-                schema_cache: HashMap::new(),
-                image_storage_path: PathBuf::from("."),
-            }
+            // Replaced with the active workspace in the setup phase
+            Self::open(WorkspacePaths::new(".")).expect("Failed to create database pool")
         }
     }
     
@@ -859,8 +1933,3593 @@ impl DatabaseState {
         }
     }
     
-    // Export for use in other modules
-    pub use Trade;
-    pub use NewTrade;
-    pub use TradeQuery;
-    pub use EntitySchema;
\ No newline at end of file
+    // Chart similarity search
+    impl DatabaseState {
+        async fn store_image_hash(&self, image_path: &str, image: &DynamicImage) -> Result<u64, SqlxError> {
+            let hash = image_hash::dhash(image);
+            let bands = image_hash::hash_bands(hash);
+            
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO image_hashes (
+                    image_path, dhash, band0, band1, band2, band3, width, height, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(image_path)
+            .bind(image_hash::to_sql(hash))
+            .bind(bands[0])
+            .bind(bands[1])
+            .bind(bands[2])
+            .bind(bands[3])
+            .bind(image.width() as i64)
+            .bind(image.height() as i64)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+            
+            Ok(hash)
+        }
+        
+        // Resolve the hash of a stored image path or an external file
+        async fn image_hash_for(&self, image_path: &str) -> Result<u64, Box<dyn std::error::Error>> {
+            let stored = sqlx::query("SELECT dhash FROM image_hashes WHERE image_path = ?")
+                .bind(image_path)
+                .fetch_optional(&self.pool)
+                .await?;
+            
+            if let Some(row) = stored {
+                return Ok(image_hash::from_sql(row.get("dhash")));
+            }
+            
+            // Stored image saved before hashing existed: index it now
+            let stored_path = self.image_storage_path.join(image_path);
+            if stored_path.exists() {
+                let image = self.load_image(&stored_path).await?;
+                return Ok(self.store_image_hash(image_path, &image).await?);
+            }
+            
+            let external_path = PathBuf::from(image_path);
+            if !external_path.exists() {
+                return Err(format!("Image not found: {}", image_path).into());
+            }
+            
+            let image = self.load_image(&external_path).await?;
+            Ok(image_hash::dhash(&image))
+        }
+        
+        pub async fn find_similar_charts(
+            &self,
+            query: ChartQuery,
+            k: u32,
+        ) -> Result<Vec<SimilarChart>, Box<dyn std::error::Error>> {
+            let (query_hash, exclude_trade_id) = match &query {
+                ChartQuery::TradeId(trade_id) => {
+                    let trade = self.get_trade_by_id(*trade_id).await?;
+                    let image_path = trade.new_trade.entry_image
+                        .or(trade.new_trade.analysis_image)
+                        .or(trade.new_trade.exit_image)
+                        .ok_or("Trade has no chart image")?;
+                    
+                    (self.image_hash_for(&image_path).await?, Some(*trade_id))
+                }
+                ChartQuery::Image(image_path) => (self.image_hash_for(image_path).await?, None),
+            };
+            
+            let k = k.max(1) as usize;
+            
+            // Band lookup finds near-duplicates through the indexes. Sharing a
+            // band is only guaranteed within HASH_BANDS - 1 bits, so its result
+            // is the true top k only when the k-th match is that close; any
+            // chart it missed is further away. Otherwise scan everything.
+            let results = Self::closest_charts(self.fetch_chart_candidates(query_hash, true).await?, exclude_trade_id, k);
+            let band_exact = results.len() == k
+                && results.last().is_some_and(|c| c.distance < image_hash::HASH_BANDS as u32);
+            if band_exact {
+                return Ok(results);
+            }
+            
+            let candidates = self.fetch_chart_candidates(query_hash, false).await?;
+            Ok(Self::closest_charts(candidates, exclude_trade_id, k))
+        }
+        
+        // The k closest trades, each by its closest image; newer trades first on ties
+        fn closest_charts(candidates: Vec<SimilarChart>, exclude_trade_id: Option<u32>, k: usize) -> Vec<SimilarChart> {
+            let mut best: HashMap<u32, SimilarChart> = HashMap::new();
+            for candidate in candidates {
+                if Some(candidate.trade_id) == exclude_trade_id {
+                    continue;
+                }
+                match best.get(&candidate.trade_id) {
+                    Some(existing) if existing.distance <= candidate.distance => {}
+                    _ => {
+                        best.insert(candidate.trade_id, candidate);
+                    }
+                }
+            }
+            
+            let mut results: Vec<SimilarChart> = best.into_values().collect();
+            results.sort_by(|a, b| {
+                a.distance.cmp(&b.distance)
+                    .then_with(|| b.entry_time.cmp(&a.entry_time))
+            });
+            results.truncate(k);
+            results
+        }
+        
+        async fn fetch_chart_candidates(
+            &self,
+            query_hash: u64,
+            band_filter: bool,
+        ) -> Result<Vec<SimilarChart>, SqlxError> {
+            let mut sql = r#"
+                SELECT
+                    t.id, t.symbol, t.trade_type, t.entry_time, t.ict_pattern, t.strategy_name,
+                    t.is_win, t.profit_loss_money, t.risk_reward_ratio, h.image_path, h.dhash
+                FROM image_hashes h
+                JOIN (
+                    SELECT id AS trade_id, entry_image AS image_path FROM trades WHERE entry_image IS NOT NULL
+                    UNION ALL
+                    SELECT id, exit_image FROM trades WHERE exit_image IS NOT NULL
+                    UNION ALL
+                    SELECT id, analysis_image FROM trades WHERE analysis_image IS NOT NULL
+                    UNION ALL
+                    -- Screenshots of duplicates merged into a trade
+                    SELECT m.primary_trade_id, json_extract(m.merged_trade_json, '$.' || c.column_name)
+                    FROM trade_merges m
+                    CROSS JOIN (SELECT 'entry_image' AS column_name UNION ALL SELECT 'exit_image' UNION ALL SELECT 'analysis_image') c
+                    WHERE json_extract(m.merged_trade_json, '$.' || c.column_name) IS NOT NULL
+                ) ti ON ti.image_path = h.image_path
+                JOIN trades t ON t.id = ti.trade_id
+            "#.to_string();
+            
+            if band_filter {
+                sql.push_str(" WHERE h.band0 = ? OR h.band1 = ? OR h.band2 = ? OR h.band3 = ?");
+            }
+            
+            let mut query_builder = sqlx::query(&sql);
+            if band_filter {
+                for band in image_hash::hash_bands(query_hash) {
+                    query_builder = query_builder.bind(band);
+                }
+            }
+            
+            let rows = query_builder.fetch_all(&self.pool).await?;
+            
+            Ok(rows.into_iter().map(|row| {
+                let distance = image_hash::hamming_distance(
+                    query_hash,
+                    image_hash::from_sql(row.get("dhash")),
+                );
+                
+                SimilarChart {
+                    trade_id: row.get::<i64, _>("id") as u32,
+                    image_path: row.get("image_path"),
+                    distance,
+                    similarity: image_hash::similarity(distance),
+                    symbol: row.get("symbol"),
+                    trade_type: row.get("trade_type"),
+                    entry_time: row.get("entry_time"),
+                    ict_pattern: row.get("ict_pattern"),
+                    strategy_name: row.get("strategy_name"),
+                    is_win: row.get("is_win"),
+                    profit_loss_money: row.get("profit_loss_money"),
+                    risk_reward_ratio: row.get("risk_reward_ratio"),
+                }
+            }).collect())
+        }
+        
+        // Hash trade images that were stored before hashing was introduced
+        pub async fn reindex_image_hashes(&self) -> Result<u32, Box<dyn std::error::Error>> {
+            let rows = sqlx::query(
+                r#"
+                SELECT image_path FROM (
+                    SELECT entry_image AS image_path FROM trades
+                    UNION SELECT exit_image FROM trades
+                    UNION SELECT analysis_image FROM trades
+                )
+                WHERE image_path IS NOT NULL
+                  AND image_path NOT IN (SELECT image_path FROM image_hashes)
+                "#
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            
+            let mut indexed = 0;
+            for row in rows {
+                let image_path: String = row.get("image_path");
+                let full_path = self.image_storage_path.join(&image_path);
+                
+                if !full_path.exists() {
+                    log::warn!("Skipping missing image while reindexing: {}", image_path);
+                    continue;
+                }
+                
+                match self.load_image(&full_path).await {
+                    Ok(image) => {
+                        self.store_image_hash(&image_path, &image).await?;
+                        indexed += 1;
+                    }
+                    Err(e) => log::warn!("Failed to hash image {}: {}", image_path, e),
+                }
+            }
+            
+            log::info!("Indexed {} chart images", indexed);
+            Ok(indexed)
+        }
+    }
+    
+    // Bulk operations
+    impl DatabaseState {
+        pub async fn bulk_update_trades(
+            &self,
+            filter: &TradeQuery,
+            mut patch: HashMap<String, serde_json::Value>,
+            dry_run: bool,
+        ) -> Result<BulkResult, Box<dyn std::error::Error>> {
+            if patch.is_empty() {
+                return Err("Bulk update patch is empty".into());
+            }
+            
+            if let Some(column) = patch.keys().find(|k| !PATCHABLE_TRADE_COLUMNS.contains(&k.as_str())) {
+                return Err(format!("Field '{}' cannot be bulk updated", column).into());
+            }
+            
+            if filter.has_no_filters() {
+                return Err("Refusing to bulk update without a filter".into());
+            }
+            
+            self.normalize_time_patch(&mut patch, None)?;
+            
+            let mut tx = self.pool.begin().await?;
+            let trade_ids = Self::matching_trade_ids(&mut tx, filter).await?;
+            
+            if dry_run || trade_ids.is_empty() {
+                tx.rollback().await?;
+                return Ok(BulkResult {
+                    matched: trade_ids.len() as u32,
+                    affected: 0,
+                    dry_run,
+                    trade_ids,
+                });
+            }
+            
+            let now = Utc::now().to_rfc3339();
+            let columns: Vec<&String> = patch.keys().collect();
+            let set_clause = columns.iter()
+                .map(|column| format!("{} = ?", column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            
+            let recalculate = patch_changes_metrics(&patch);
+            
+            let mut affected = 0u64;
+            for chunk in trade_ids.chunks(BULK_CHUNK_SIZE) {
+                let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                let sql = format!(
+                    "UPDATE trades SET {}, updated_at = ?, version = version + 1 WHERE id IN ({})",
+                    set_clause, placeholders
+                );
+                
+                let mut query_builder = sqlx::query(&sql);
+                for column in &columns {
+                    query_builder = bind_json_value(query_builder, patch[*column].clone());
+                }
+                query_builder = query_builder.bind(&now);
+                for id in chunk {
+                    query_builder = query_builder.bind(*id);
+                }
+                
+                affected += query_builder.execute(&mut *tx).await?.rows_affected();
+                
+                if recalculate {
+                    Self::recalculate_trade_metrics(&mut tx, chunk).await?;
+                }
+            }
+            
+            tx.commit().await?;
+            
+            Ok(BulkResult {
+                matched: trade_ids.len() as u32,
+                affected: affected as u32,
+                dry_run,
+                trade_ids,
+            })
+        }
+        
+        // Refresh the derived P/L columns of `ids` from their stored prices.
+        // Imported trades (those with a broker ticket) keep the broker's
+        // figures, which the lot-size estimate would only make worse.
+        async fn recalculate_trade_metrics(
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            ids: &[u32],
+        ) -> Result<(), SqlxError> {
+            let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let sql = format!(
+                "SELECT id, symbol, trade_type, volume, entry_price, exit_price, sl, tp FROM trades WHERE id IN ({}) AND external_id IS NULL",
+                placeholders
+            );
+            let mut query_builder = sqlx::query(&sql);
+            for id in ids {
+                query_builder = query_builder.bind(*id);
+            }
+            let rows = query_builder.fetch_all(&mut **tx).await?;
+            
+            for row in rows {
+                let (is_win, profit_loss_pips, profit_loss_money, risk_reward_ratio) = Self::trade_metrics(
+                    row.get("symbol"),
+                    row.get("trade_type"),
+                    row.get("volume"),
+                    row.get("entry_price"),
+                    row.get("exit_price"),
+                    row.get("sl"),
+                    row.get("tp"),
+                );
+                sqlx::query(
+                    "UPDATE trades SET is_win = ?, profit_loss_pips = ?, profit_loss_money = ?, risk_reward_ratio = ? WHERE id = ?"
+                )
+                .bind(is_win)
+                .bind(profit_loss_pips)
+                .bind(profit_loss_money)
+                .bind(risk_reward_ratio)
+                .bind(row.get::<i64, _>("id"))
+                .execute(&mut **tx)
+                .await?;
+            }
+            
+            Ok(())
+        }
+        
+        pub async fn bulk_delete_trades(
+            &self,
+            filter: &TradeQuery,
+            dry_run: bool,
+        ) -> Result<BulkResult, Box<dyn std::error::Error>> {
+            if filter.has_no_filters() {
+                return Err("Refusing to bulk delete without a filter".into());
+            }
+            
+            let mut tx = self.pool.begin().await?;
+            let trade_ids = Self::matching_trade_ids(&mut tx, filter).await?;
+            
+            if dry_run || trade_ids.is_empty() {
+                tx.rollback().await?;
+                return Ok(BulkResult {
+                    matched: trade_ids.len() as u32,
+                    affected: 0,
+                    dry_run,
+                    trade_ids,
+                });
+            }
+            
+            let mut affected = 0u64;
+            for chunk in trade_ids.chunks(BULK_CHUNK_SIZE) {
+                let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                let sql = format!("DELETE FROM trades WHERE id IN ({})", placeholders);
+                
+                let mut query_builder = sqlx::query(&sql);
+                for id in chunk {
+                    query_builder = query_builder.bind(*id);
+                }
+                
+                affected += query_builder.execute(&mut *tx).await?.rows_affected();
+            }
+            
+            tx.commit().await?;
+            
+            Ok(BulkResult {
+                matched: trade_ids.len() as u32,
+                affected: affected as u32,
+                dry_run,
+                trade_ids,
+            })
+        }
+        
+        async fn matching_trade_ids(
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            filter: &TradeQuery,
+        ) -> Result<Vec<u32>, SqlxError> {
+            let (where_clause, params) = Self::build_trade_filter(filter);
+            let sql = format!("SELECT id FROM trades WHERE 1=1{} ORDER BY id", where_clause);
+            
+            let mut query_builder = sqlx::query(&sql);
+            for param in params {
+                query_builder = query_builder.bind(param);
+            }
+            
+            let rows = query_builder.fetch_all(&mut **tx).await?;
+            Ok(rows.iter().map(|row| row.get::<i64, _>("id") as u32).collect())
+        }
+    }
+    
+    // Duplicate merging
+    impl DatabaseState {
+        // Merge `duplicate_id` into `primary_id`. Fields default to the primary's
+        // value and fall back to the duplicate's when the primary has none;
+        // `field_sources` overrides that choice per column.
+        pub async fn merge_trades(
+            &self,
+            primary_id: u32,
+            duplicate_id: u32,
+            field_sources: HashMap<String, MergeSource>,
+        ) -> Result<Trade, Box<dyn std::error::Error>> {
+            if primary_id == duplicate_id {
+                return Err("Cannot merge a trade into itself".into());
+            }
+            
+            if let Some(field) = field_sources.keys().find(|k| !MERGEABLE_TRADE_COLUMNS.contains(&k.as_str())) {
+                return Err(format!("Field '{}' cannot be merged", field).into());
+            }
+            
+            if let Some((field, _)) = field_sources.iter().find(|(k, source)| **source == MergeSource::Merged && k.as_str() != "notes") {
+                return Err(format!("Field '{}' cannot combine both values", field).into());
+            }
+            
+            let primary = serde_json::to_value(self.get_trade_by_id(primary_id).await?)?;
+            let duplicate = serde_json::to_value(self.get_trade_by_id(duplicate_id).await?)?;
+            
+            let mut values = Vec::with_capacity(MERGEABLE_TRADE_COLUMNS.len());
+            let mut sources = HashMap::new();
+            
+            for column in MERGEABLE_TRADE_COLUMNS {
+                let primary_value = primary.get(*column).cloned().unwrap_or(serde_json::Value::Null);
+                let duplicate_value = duplicate.get(*column).cloned().unwrap_or(serde_json::Value::Null);
+                
+                let source = match field_sources.get(*column) {
+                    Some(source) => *source,
+                    None if primary_value.is_null() && !duplicate_value.is_null() => MergeSource::Duplicate,
+                    // Keep both sets of notes unless a side was picked explicitly
+                    None if *column == "notes" => MergeSource::Merged,
+                    None => MergeSource::Primary,
+                };
+                
+                let (source, value) = match source {
+                    MergeSource::Primary => (source, primary_value),
+                    MergeSource::Duplicate => (source, duplicate_value),
+                    MergeSource::Merged => match (primary_value.as_str(), duplicate_value.as_str()) {
+                        (Some(a), Some(b)) if a.trim() != b.trim() => (source, serde_json::Value::String(format!("{}\n\n{}", a, b))),
+                        // Same or one-sided notes: nothing to combine
+                        _ if primary_value.is_null() => (MergeSource::Duplicate, duplicate_value),
+                        _ => (MergeSource::Primary, primary_value),
+                    },
+                };
+                
+                sources.insert(column.to_string(), source);
+                values.push(value);
+            }
+            
+            let now = Utc::now().to_rfc3339();
+            let set_clause = MERGEABLE_TRADE_COLUMNS.iter()
+                .map(|column| format!("{} = ?", column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql = format!(
+                "UPDATE trades SET {}, updated_at = ?, version = version + 1 WHERE id = ?",
+                set_clause
+            );
+            
+            let mut tx = self.pool.begin().await?;
+            
+            let mut query_builder = sqlx::query(&sql);
+            for value in values {
+                query_builder = bind_json_value(query_builder, value);
+            }
+            query_builder.bind(&now).bind(primary_id).execute(&mut *tx).await?;
+            
+            // Earlier merges into the duplicate now belong to the primary, and so
+            // do its fills. Screenshots the merged row no longer shows stay
+            // reachable through the audit row below (see fetch_chart_candidates).
+            for sql in [
+                "UPDATE trade_merges SET primary_trade_id = ? WHERE primary_trade_id = ?",
+                "UPDATE executions SET trade_id = ? WHERE trade_id = ?",
+            ] {
+                sqlx::query(sql)
+                    .bind(primary_id)
+                    .bind(duplicate_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            
+            sqlx::query(
+                r#"
+                INSERT INTO trade_merges (
+                    primary_trade_id, merged_trade_id, merged_trade_json, field_sources_json, merged_at
+                ) VALUES (?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(primary_id)
+            .bind(duplicate_id)
+            .bind(duplicate.to_string())
+            .bind(serde_json::to_string(&sources)?)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+            
+            sqlx::query("DELETE FROM trades WHERE id = ?")
+                .bind(duplicate_id)
+                .execute(&mut *tx)
+                .await?;
+            
+            tx.commit().await?;
+            
+            Ok(self.get_trade_by_id(primary_id).await?)
+        }
+    }
+    
+    // Aggregate statistics
+    impl DatabaseState {
+        // (Re)create the triggers and backfill when the table has never been
+        // populated or the display timezone changed
+        async fn create_statistics_triggers(&self) -> Result<(), SqlxError> {
+            let mut tx = self.pool.begin().await?;
+            for statement in statistics_trigger_sql() {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+            
+            let populated: Option<i64> = sqlx::query("SELECT 1 FROM trade_statistics LIMIT 1")
+                .fetch_optional(&self.pool)
+                .await?
+                .map(|row| row.get(0));
+            
+            let offsets_changed = self.sync_display_offsets().await?;
+            
+            if populated.is_none() || offsets_changed {
+                self.rebuild_trade_statistics().await?;
+            }
+            
+            Ok(())
+        }
+        
+        // Store the offsets of the configured display timezone; returns whether
+        // they differ from the stored ones, which leaves day aggregates stale
+        async fn sync_display_offsets(&self) -> Result<bool, SqlxError> {
+            let parse = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+            let offsets: Vec<(String, i64)> = timezone::offset_changes(
+                self.time_settings.display_tz(),
+                parse(DAY_OFFSETS_FROM),
+                parse(DAY_OFFSETS_UNTIL),
+            )
+            .into_iter()
+            .map(|(starts_at, offset)| (timezone::format_utc(starts_at), offset as i64))
+            .collect();
+            
+            let stored: Vec<(String, i64)> = sqlx::query_as("SELECT starts_at, offset_seconds FROM display_day_offsets ORDER BY starts_at")
+                .fetch_all(&self.pool)
+                .await?;
+            if stored == offsets {
+                return Ok(false);
+            }
+            
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("DELETE FROM display_day_offsets").execute(&mut *tx).await?;
+            for (starts_at, offset) in &offsets {
+                sqlx::query("INSERT INTO display_day_offsets (starts_at, offset_seconds) VALUES (?, ?)")
+                    .bind(starts_at)
+                    .bind(offset)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            
+            Ok(true)
+        }
+        
+        // Regenerate all aggregates, live and archived, from the trades tables
+        pub async fn rebuild_trade_statistics(&self) -> Result<u32, SqlxError> {
+            log::info!("Rebuilding trade statistics...");
+            
+            let mut tx = self.pool.begin().await?;
+            for statement in statistics_rebuild_sql("main").into_iter().chain(statistics_rebuild_sql(ARCHIVE_SCHEMA)) {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            
+            let count: i64 = sqlx::query("SELECT COUNT(*) FROM trade_statistics")
+                .fetch_one(&mut *tx)
+                .await?
+                .get(0);
+            tx.commit().await?;
+            
+            log::info!("Trade statistics rebuilt: {} rows", count);
+            Ok(count as u32)
+        }
+        
+        pub async fn get_dimension_statistics(&self, dimension: &str, include_archived: bool) -> Result<Vec<DimensionStatistics>, SqlxError> {
+            load_dimension_statistics(&self.pool, dimension, include_archived).await
+        }
+    }
+    
+    // Cold storage archive
+    impl DatabaseState {
+        // Mirror the archived tables in the attached archive database. Missing
+        // tables are cloned from the live definition and columns added to a
+        // live table later are added to its copy, so both keep one layout.
+        async fn sync_archive_tables(&self) -> Result<(), SqlxError> {
+            for table in ARCHIVED_TABLES {
+                let archive_columns = table_columns(&self.pool, ARCHIVE_SCHEMA, table).await?;
+                
+                if archive_columns.is_empty() {
+                    let live_sql: String = sqlx::query("SELECT sql FROM main.sqlite_master WHERE type = 'table' AND name = ?")
+                        .bind(table)
+                        .fetch_one(&self.pool)
+                        .await?
+                        .get("sql");
+                    let definition = live_sql.strip_prefix(&format!("CREATE TABLE {}", table))
+                        .ok_or_else(|| SqlxError::Decode(format!("Unexpected definition of table {}", table).into()))?;
+                    
+                    sqlx::query(&format!("CREATE TABLE {}.{}{}", ARCHIVE_SCHEMA, table, definition))
+                        .execute(&self.pool)
+                        .await?;
+                    continue;
+                }
+                
+                for (column, declared_type) in table_columns(&self.pool, "main", table).await? {
+                    if !archive_columns.iter().any(|(name, _)| *name == column) {
+                        sqlx::query(&format!("ALTER TABLE {}.{} ADD COLUMN {} {}", ARCHIVE_SCHEMA, table, column, declared_type))
+                            .execute(&self.pool)
+                            .await?;
+                    }
+                }
+            }
+            
+            for statement in [
+                "CREATE INDEX IF NOT EXISTS archive.idx_trades_entry_time ON trades(entry_time)",
+                "CREATE INDEX IF NOT EXISTS archive.idx_trade_merges_primary ON trade_merges(primary_trade_id)",
+                "CREATE INDEX IF NOT EXISTS archive.idx_trade_stats_type_key ON trade_statistics(statistic_type, statistic_key)",
+            ] {
+                sqlx::query(statement).execute(&self.pool).await?;
+            }
+            
+            Ok(())
+        }
+        
+        // Move closed trades entered before `cutoff` into the archive, together
+        // with their merge history, fills, image hashes and aggregates
+        pub async fn archive_trades(&self, cutoff: DateTime<Utc>) -> Result<ArchiveResult, SqlxError> {
+            let result = self.move_trades(
+                "main",
+                ARCHIVE_SCHEMA,
+                "SELECT id FROM main.trades WHERE exit_time IS NOT NULL AND entry_time < ?",
+                Some(timezone::format_utc(cutoff)),
+            ).await?;
+            
+            log::info!("Archived {} trades entered before {}", result.trades, cutoff);
+            Ok(result)
+        }
+        
+        // Bring archived trades back, either the given ids or everything entered
+        // since `since`. Ids are preserved: the live AUTOINCREMENT never reuses them.
+        pub async fn unarchive_trades(
+            &self,
+            ids: Option<Vec<u32>>,
+            since: Option<DateTime<Utc>>,
+        ) -> Result<ArchiveResult, SqlxError> {
+            let mut selection = "SELECT id FROM archive.trades WHERE 1=1".to_string();
+            
+            if let Some(ids) = &ids {
+                if ids.is_empty() {
+                    return Ok(ArchiveResult::default());
+                }
+                let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+                selection.push_str(&format!(" AND id IN ({})", ids.join(", ")));
+            }
+            if since.is_some() {
+                selection.push_str(" AND entry_time >= ?");
+            }
+            
+            let result = self.move_trades(ARCHIVE_SCHEMA, "main", &selection, since.map(timezone::format_utc)).await?;
+            
+            log::info!("Restored {} archived trades", result.trades);
+            Ok(result)
+        }
+        
+        // Copy the selected trades and everything hanging off them from one
+        // schema to the other and delete the originals, in one transaction.
+        // Live aggregates follow through the statistics triggers; the archive
+        // has none, so its aggregates are rebuilt afterwards.
+        async fn move_trades(
+            &self,
+            from: &str,
+            to: &str,
+            selection: &str,
+            param: Option<String>,
+        ) -> Result<ArchiveResult, SqlxError> {
+            let mut column_lists = Vec::new();
+            for table in ["trades", "trade_merges", "executions", "image_hashes"] {
+                let names: Vec<String> = table_columns(&self.pool, from, table).await?
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect();
+                column_lists.push(names.join(", "));
+            }
+            let (trade_columns, merge_columns, execution_columns, hash_columns) =
+                (&column_lists[0], &column_lists[1], &column_lists[2], &column_lists[3]);
+            
+            // Images referenced by trades inside (or outside) the batch
+            let image_refs = |membership: &str| {
+                ["entry_image", "exit_image", "analysis_image"].iter()
+                    .map(|column| format!(
+                        "SELECT {column} FROM {from}.trades WHERE {column} IS NOT NULL AND id {membership} (SELECT id FROM temp.archive_batch)"
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" UNION ")
+            };
+            // Hashes only move when no trade left behind still uses the image
+            let moved_hashes = format!(
+                "image_path IN ({}) AND image_path NOT IN ({})",
+                image_refs("IN"),
+                image_refs("NOT IN"),
+            );
+            
+            let mut tx = self.pool.begin().await?;
+            
+            let last_seq: i64 = sqlx::query("SELECT COALESCE(MAX(seq), 0) FROM change_log")
+                .fetch_one(&mut *tx)
+                .await?
+                .get(0);
+            
+            sqlx::query("DROP TABLE IF EXISTS temp.archive_batch").execute(&mut *tx).await?;
+            let create_sql = format!("CREATE TEMP TABLE archive_batch AS {}", selection);
+            let mut create_batch = sqlx::query(&create_sql);
+            if let Some(param) = &param {
+                create_batch = create_batch.bind(param);
+            }
+            create_batch.execute(&mut *tx).await?;
+            
+            let trades = sqlx::query(&format!(
+                "INSERT INTO {to}.trades ({trade_columns}) SELECT {trade_columns} FROM {from}.trades WHERE id IN (SELECT id FROM temp.archive_batch)"
+            ))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            
+            let merges = sqlx::query(&format!(
+                "INSERT INTO {to}.trade_merges ({merge_columns}) SELECT {merge_columns} FROM {from}.trade_merges WHERE primary_trade_id IN (SELECT id FROM temp.archive_batch)"
+            ))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            
+            let executions = sqlx::query(&format!(
+                "INSERT INTO {to}.executions ({execution_columns}) SELECT {execution_columns} FROM {from}.executions WHERE trade_id IN (SELECT id FROM temp.archive_batch)"
+            ))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            
+            let image_hashes = sqlx::query(&format!(
+                "INSERT OR REPLACE INTO {to}.image_hashes ({hash_columns}) SELECT {hash_columns} FROM {from}.image_hashes WHERE {moved_hashes}"
+            ))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            
+            for statement in [
+                format!("DELETE FROM {from}.image_hashes WHERE {moved_hashes}"),
+                format!("DELETE FROM {from}.trade_merges WHERE primary_trade_id IN (SELECT id FROM temp.archive_batch)"),
+                format!("DELETE FROM {from}.executions WHERE trade_id IN (SELECT id FROM temp.archive_batch)"),
+                format!("DELETE FROM {from}.trades WHERE id IN (SELECT id FROM temp.archive_batch)"),
+                "DROP TABLE temp.archive_batch".to_string(),
+            ] {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            
+            // The triggers logged the move as deletes (archiving) or inserts
+            // (restoring); mark them so consumers keep the rows
+            sqlx::query("UPDATE change_log SET moved_to = ? WHERE seq > ?")
+                .bind(to)
+                .bind(last_seq)
+                .execute(&mut *tx)
+                .await?;
+            
+            for statement in statistics_rebuild_sql(ARCHIVE_SCHEMA) {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            
+            tx.commit().await?;
+            
+            Ok(ArchiveResult {
+                trades: trades as u32,
+                merges: merges as u32,
+                executions: executions as u32,
+                image_hashes: image_hashes as u32,
+            })
+        }
+    }
+    
+    // Change data capture
+    impl DatabaseState {
+        // Triggers write the log inside the statement that changes the row, so
+        // an entry commits or rolls back together with its mutation. They are
+        // regenerated on every start to pick up columns added by migrations.
+        async fn create_change_log_triggers(&self) -> Result<(), SqlxError> {
+            let mut statements = Vec::new();
+            
+            for (entity, id_expr) in CHANGE_TRACKED_TABLES {
+                let columns: Vec<String> = table_columns(&self.pool, "main", entity).await?
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect();
+                statements.extend(change_log_trigger_sql(entity, id_expr, &columns));
+            }
+            
+            let mut tx = self.pool.begin().await?;
+            for statement in statements {
+                sqlx::query(&statement).execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+            
+            Ok(())
+        }
+        
+        // Changes with a sequence number above `since`, oldest first
+        pub async fn changes_since(&self, since: i64, limit: u32) -> Result<ChangeBatch, SqlxError> {
+            let rows = sqlx::query(
+                "SELECT seq, entity, entity_id, operation, payload, changed_at, moved_to FROM change_log WHERE seq > ? ORDER BY seq LIMIT ?"
+            )
+            .bind(since)
+            .bind(limit.max(1) as i64)
+            .fetch_all(&self.pool)
+            .await?;
+            
+            let oldest_seq: Option<i64> = sqlx::query("SELECT MIN(seq) FROM change_log")
+                .fetch_one(&self.pool)
+                .await?
+                .get(0);
+            
+            // A payload that is not JSON means the log was damaged; handing out
+            // an empty snapshot would let consumers silently lose the change
+            let changes = rows.iter().map(|row| {
+                let seq: i64 = row.get("seq");
+                let payload = serde_json::from_str(row.get::<&str, _>("payload"))
+                    .map_err(|e| SqlxError::Decode(format!("Change {} has an unreadable payload: {}", seq, e).into()))?;
+                
+                Ok(ChangeEntry {
+                    seq,
+                    entity: row.get("entity"),
+                    entity_id: row.get("entity_id"),
+                    operation: row.get("operation"),
+                    payload,
+                    changed_at: row.get("changed_at"),
+                    moved_to: row.get("moved_to"),
+                })
+            }).collect::<Result<Vec<ChangeEntry>, SqlxError>>()?;
+            
+            Ok(ChangeBatch {
+                last_seq: changes.last().map(|change| change.seq).unwrap_or(since),
+                truncated: oldest_seq.map(|oldest| oldest > since + 1).unwrap_or(false),
+                oldest_seq,
+                changes,
+            })
+        }
+        
+        // Delete entries recorded before `before`. The newest entry is always
+        // kept so consumers can still tell where the stream stands.
+        pub async fn compact_change_log(&self, before: DateTime<Utc>) -> Result<u32, SqlxError> {
+            let result = sqlx::query(
+                "DELETE FROM change_log WHERE changed_at < ? AND seq < (SELECT MAX(seq) FROM change_log)"
+            )
+            .bind(timezone::format_utc(before))
+            .execute(&self.pool)
+            .await?;
+            
+            log::info!("Compacted change log: {} entries removed", result.rows_affected());
+            Ok(result.rows_affected() as u32)
+        }
+    }
+    
+    // Trade imports
+    impl DatabaseState {
+        // Write parsed import rows in one transaction. Rows whose ticket or
+        // fingerprint matches an existing trade are reported as duplicates; any
+        // row error rolls the whole import back unless partial imports are allowed.
+        pub async fn import_trades(&self, parsed: ParsedImport, options: &ImportOptions) -> Result<ImportReport, SqlxError> {
+            let mut report = ImportReport {
+                total_rows: parsed.total_rows,
+                errors: parsed.errors,
+                warnings: parsed.warnings,
+                dry_run: options.dry_run,
+                ..Default::default()
+            };
+            
+            // Archived trades count too, so re-importing an old statement stays a no-op
+            let tickets = sqlx::query(&format!(
+                "SELECT external_id, broker, account, id, exit_price IS NULL AS open FROM {} WHERE external_id IS NOT NULL",
+                trades_source(&self.pool, true).await?
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+            let mut trade_by_ticket: TicketIndex<u32> = TicketIndex::default();
+            let mut open_positions: HashSet<u32> = HashSet::new();
+            let mut ticketed: HashSet<u32> = HashSet::new();
+            for row in tickets {
+                let id = row.get::<i64, _>("id") as u32;
+                let scope = TicketScope { broker: row.get("broker"), account: row.get("account") };
+                trade_by_ticket.insert(scope, row.get("external_id"), id);
+                ticketed.insert(id);
+                if row.get::<bool, _>("open") {
+                    open_positions.insert(id);
+                }
+            }
+            
+            let mut index = DuplicateDetector::new(self.pool(), options.duplicates.clone())
+                .build_index()
+                .await?;
+            
+            let now = Utc::now().to_rfc3339();
+            let mut tx = self.pool.begin().await?;
+            ensure_custom_columns(&mut *tx).await?;
+            let custom_columns: HashMap<String, &'static str> = stored_custom_fields(&mut *tx).await?.into_iter().collect();
+            
+            // Positions opened by an earlier file are closed before this file's
+            // rows can open new trades of the same instrument
+            let mut executions = parsed.executions;
+            if !parsed.closes.is_empty() {
+                let known_fills = TicketIndex::load(&mut tx, "executions").await?;
+                for mut close in parsed.closes {
+                    if let Some(trade_id) = self.apply_position_close(&mut tx, &mut close, &known_fills, &now, &mut report).await? {
+                        open_positions.remove(&trade_id);
+                    }
+                    executions.extend(close.fills);
+                }
+            }
+            
+            for ImportRow { row, record } in parsed.rows {
+                let ImportedTrade { mut trade, exit_price, exit_time, profit, external_id, scope, custom_fields } = record;
+                let skip_duplicates = options.on_duplicate == DuplicatePolicy::Skip;
+                
+                // Custom values land in their schema column, so the field must
+                // exist and the value must fit its type
+                let mut custom_fields: Vec<(String, serde_json::Value)> = custom_fields.into_iter().collect();
+                custom_fields.sort_by(|a, b| a.0.cmp(&b.0));
+                let custom_fields: Result<Vec<(String, serde_json::Value)>, ImportRowError> = custom_fields.into_iter()
+                    .map(|(name, value)| {
+                        let error = |message: String| ImportRowError { row, field: Some(format!("custom.{}", name)), message };
+                        let Some(column_type) = custom_columns.get(&name) else {
+                            return Err(error(format!("Custom field '{}' is not defined in the {} schema", name, TRADE_SCHEMA)));
+                        };
+                        let value = custom_field_value(column_type, value).map_err(error)?;
+                        Ok((name, value))
+                    })
+                    .collect();
+                let custom_fields = match custom_fields {
+                    Ok(custom_fields) => custom_fields,
+                    Err(error) => {
+                        report.errors.push(error);
+                        continue;
+                    }
+                };
+                
+                if let Some(trade_id) = external_id.as_ref().and_then(|ticket| trade_by_ticket.get(&scope, ticket)) {
+                    // A position imported while open shows up closed in a newer statement
+                    if exit_price.is_some() && open_positions.contains(&trade_id) {
+                        let exit_time = match self.normalize_import_times(&mut trade, exit_time) {
+                            Ok(exit_time) => exit_time,
+                            Err(e) => {
+                                report.errors.push(ImportRowError { row, field: None, message: e.to_string() });
+                                continue;
+                            }
+                        };
+                        
+                        // Metrics come from the stored entry, as when the row is inserted closed
+                        let stored = sqlx::query("SELECT symbol, trade_type, volume, entry_price, sl, tp FROM trades WHERE id = ?")
+                            .bind(trade_id)
+                            .fetch_one(&mut *tx)
+                            .await?;
+                        let mut metrics = Self::trade_metrics(
+                            stored.get("symbol"), stored.get("trade_type"), stored.get("volume"), stored.get("entry_price"),
+                            exit_price, stored.get("sl"), stored.get("tp"),
+                        );
+                        if let Some(profit) = profit {
+                            metrics.0 = Some(profit > 0.0);
+                            metrics.2 = Some(profit);
+                        }
+                        
+                        sqlx::query(
+                            r#"
+                            UPDATE trades SET
+                                exit_price = ?, exit_time = ?,
+                                is_win = ?, profit_loss_pips = ?, profit_loss_money = ?, risk_reward_ratio = ?,
+                                commission = COALESCE(?, commission), swap = COALESCE(?, swap),
+                                updated_at = ?, version = version + 1
+                            WHERE id = ?
+                            "#
+                        )
+                        .bind(exit_price)
+                        .bind(&exit_time)
+                        .bind(metrics.0)
+                        .bind(metrics.1)
+                        .bind(metrics.2)
+                        .bind(metrics.3)
+                        .bind(trade.commission)
+                        .bind(trade.swap)
+                        .bind(&now)
+                        .bind(trade_id)
+                        .execute(&mut *tx)
+                        .await?;
+                        
+                        open_positions.remove(&trade_id);
+                        report.updated += 1;
+                        continue;
+                    }
+                    
+                    if skip_duplicates {
+                        report.duplicates.push(ImportDuplicate {
+                            row,
+                            trade_id,
+                            reason: format!("Ticket {} was already imported", external_id.as_deref().unwrap_or_default()),
+                        });
+                        continue;
+                    }
+                }
+                
+                let exit_time = match self.normalize_import_times(&mut trade, exit_time) {
+                    Ok(exit_time) => exit_time,
+                    Err(e) => {
+                        report.errors.push(ImportRowError { row, field: Some("entry_time".to_string()), message: e.to_string() });
+                        continue;
+                    }
+                };
+                
+                // A trade from another ticket is a different execution, however close
+                let fingerprint = TradeFingerprint::from_new_trade(0, &trade);
+                let fingerprint_match = fingerprint.as_ref()
+                    .and_then(|f| index.best_match(f, &options.duplicates))
+                    .filter(|m| external_id.is_none() || !ticketed.contains(&m.trade_id));
+                if let (true, Some(m)) = (skip_duplicates, fingerprint_match) {
+                    report.duplicates.push(ImportDuplicate {
+                        row,
+                        trade_id: m.trade_id,
+                        reason: format!("Matches trade {} (score {:.2})", m.trade_id, m.score),
+                    });
+                    continue;
+                }
+                
+                // Closed rows get metrics from their exit; a reported profit wins
+                // over the computed one since it is in the account currency
+                let mut metrics = Self::trade_metrics(&trade.symbol, &trade.trade_type, trade.volume, trade.entry_price, exit_price, trade.sl, trade.tp);
+                if let Some(profit) = profit {
+                    metrics.0 = Some(profit > 0.0);
+                    metrics.2 = Some(profit);
+                }
+                
+                let trade_id = match Self::insert_trade(&mut *tx, &trade, metrics, &now).await {
+                    Ok(trade_id) => trade_id,
+                    Err(e) => {
+                        report.errors.push(ImportRowError { row, field: None, message: e.to_string() });
+                        continue;
+                    }
+                };
+                
+                if exit_price.is_some() || exit_time.is_some() || external_id.is_some() || !custom_fields.is_empty() {
+                    let assignments: String = custom_fields.iter()
+                        .map(|(name, _)| format!(", \"{}\" = ?", name))
+                        .collect();
+                    let sql = format!(
+                        "UPDATE trades SET exit_price = ?, exit_time = ?, external_id = ?, broker = ?, account = ?{} WHERE id = ?",
+                        assignments
+                    );
+                    let mut query = sqlx::query(&sql)
+                        .bind(exit_price)
+                        .bind(&exit_time)
+                        .bind(&external_id)
+                        .bind(&scope.broker)
+                        .bind(&scope.account);
+                    for (_, value) in custom_fields {
+                        query = bind_json_value(query, value);
+                    }
+                    query.bind(trade_id).execute(&mut *tx).await?;
+                }
+                
+                if let Some(mut fingerprint) = fingerprint {
+                    fingerprint.trade_id = trade_id;
+                    index.insert(fingerprint);
+                }
+                if let Some(ticket) = external_id {
+                    trade_by_ticket.insert(scope, ticket, trade_id);
+                }
+                
+                report.imported += 1;
+                report.trade_ids.push(trade_id);
+            }
+            
+            report.cash_flows_imported = self.import_cash_flows(&mut tx, parsed.cash_flows, &now, &mut report.errors).await?;
+            report.executions_imported = self.import_executions(
+                &mut tx, executions, &trade_by_ticket, &now, &mut report.errors
+            ).await?;
+            
+            let failed = !report.errors.is_empty() && !options.allow_partial;
+            if options.dry_run || failed {
+                tx.rollback().await?;
+                report.trade_ids.clear();
+                if failed {
+                    report.imported = 0;
+                    report.updated = 0;
+                    report.cash_flows_imported = 0;
+                    report.executions_imported = 0;
+                }
+            } else {
+                tx.commit().await?;
+                report.committed = true;
+            }
+            
+            log::info!(
+                "Imported {} of {} rows ({} duplicates, {} errors, committed: {})",
+                report.imported, report.total_rows, report.duplicates.len(), report.errors.len(), report.committed
+            );
+            Ok(report)
+        }
+        
+        // Close the newest open trade of the instrument in the same broker
+        // account with fills whose opening fill came in an earlier file, and
+        // point the fills at its ticket. Fills already on file (a re-import)
+        // change nothing. Returns the trade once it is fully closed.
+        async fn apply_position_close(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            close: &mut PositionClose,
+            known_fills: &TicketIndex<()>,
+            now: &str,
+            report: &mut ImportReport,
+        ) -> Result<Option<u32>, SqlxError> {
+            let mut new_fills: Vec<&mut Execution> = close.fills.iter_mut()
+                .map(|(_, fill)| fill)
+                .filter(|fill| match &fill.external_id {
+                    Some(id) => known_fills.get(&fill.scope, id).is_none(),
+                    None => true,
+                })
+                .collect();
+            let Some(last) = new_fills.last() else {
+                return Ok(None);
+            };
+            let scope = last.scope.clone();
+            
+            let open = sqlx::query(
+                r#"
+                SELECT id, external_id, volume, entry_price, sl, tp, commission FROM trades
+                WHERE exit_price IS NULL AND symbol = ? AND trade_type = ? AND broker IS ? AND account IS ?
+                ORDER BY entry_time DESC, id DESC LIMIT 1
+                "#
+            )
+            .bind(&close.symbol)
+            .bind(&close.trade_type)
+            .bind(&scope.broker)
+            .bind(&scope.account)
+            .fetch_optional(&mut **tx)
+            .await?;
+            let Some(open) = open else {
+                report.warnings.push(format!(
+                    "Closing fills for {} have no open {} trade in this journal; they are kept as executions only",
+                    close.symbol, close.trade_type
+                ));
+                return Ok(None);
+            };
+            let trade_id = open.get::<i64, _>("id") as u32;
+            let volume: f64 = open.get("volume");
+            let ticket: Option<String> = open.get("external_id");
+            for fill in new_fills.iter_mut() {
+                fill.position_id = ticket.clone();
+            }
+            let last = &new_fills[new_fills.len() - 1];
+            
+            // Partial closes of earlier files are already linked to the trade
+            let (earlier_volume, earlier_value): (Option<f64>, Option<f64>) = sqlx::query_as(
+                "SELECT SUM(volume), SUM(volume * price) FROM executions WHERE trade_id = ? AND side != ?"
+            )
+            .bind(trade_id)
+            .bind(&close.trade_type)
+            .fetch_one(&mut **tx)
+            .await?;
+            let closed = earlier_volume.unwrap_or(0.0) + new_fills.iter().map(|fill| fill.volume).sum::<f64>();
+            let value = earlier_value.unwrap_or(0.0) + new_fills.iter().map(|fill| fill.volume * fill.price).sum::<f64>();
+            let commission = new_fills.iter()
+                .filter_map(|fill| fill.commission)
+                .fold(open.get::<Option<f64>, _>("commission"), |total, fee| Some(total.unwrap_or(0.0) + fee));
+            
+            if closed < volume - 1e-9 {
+                sqlx::query("UPDATE trades SET commission = ?, updated_at = ?, version = version + 1 WHERE id = ?")
+                    .bind(commission)
+                    .bind(now)
+                    .bind(trade_id)
+                    .execute(&mut **tx)
+                    .await?;
+                report.warnings.push(format!(
+                    "{} trade {} is partly closed; it stays open until a file shows it flat",
+                    close.symbol, trade_id
+                ));
+                return Ok(None);
+            }
+            
+            let exit_time = self.resolve_timezone(last.source_timezone.as_deref())
+                .ok()
+                .and_then(|tz| timezone::normalize_timestamp(&last.executed_at, tz))
+                .ok_or_else(|| SqlxError::Decode(format!("Invalid exit time: {}", last.executed_at).into()))?;
+            let exit_price = value / closed;
+            let mut metrics = Self::trade_metrics(
+                &close.symbol, &close.trade_type, volume, open.get("entry_price"), Some(exit_price), open.get("sl"), open.get("tp"),
+            );
+            // The broker's P/L only covers the whole trade when no earlier file
+            // closed part of it. It is net, and the commission is kept beside it.
+            if let (None, Some(profit)) = (earlier_volume, close.profit) {
+                let gross = profit - commission.unwrap_or(0.0);
+                metrics.0 = Some(gross > 0.0);
+                metrics.2 = Some(gross);
+            }
+            
+            sqlx::query(
+                r#"
+                UPDATE trades SET
+                    exit_price = ?, exit_time = ?, commission = ?,
+                    is_win = ?, profit_loss_pips = ?, profit_loss_money = ?, risk_reward_ratio = ?,
+                    updated_at = ?, version = version + 1
+                WHERE id = ?
+                "#
+            )
+            .bind(exit_price)
+            .bind(&exit_time)
+            .bind(commission)
+            .bind(metrics.0)
+            .bind(metrics.1)
+            .bind(metrics.2)
+            .bind(metrics.3)
+            .bind(now)
+            .bind(trade_id)
+            .execute(&mut **tx)
+            .await?;
+            
+            report.updated += 1;
+            Ok(Some(trade_id))
+        }
+        
+        // Balance operations already on file (same ticket) are skipped silently
+        async fn import_cash_flows(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            flows: Vec<(usize, CashFlow)>,
+            now: &str,
+            errors: &mut Vec<ImportRowError>,
+        ) -> Result<usize, SqlxError> {
+            if flows.is_empty() {
+                return Ok(0);
+            }
+            
+            let mut known: TicketIndex<()> = TicketIndex::load(tx, "cash_flows").await?;
+            
+            let mut imported = 0;
+            for (row, flow) in flows {
+                if flow.external_id.as_ref().map_or(false, |ticket| known.get(&flow.scope, ticket).is_some()) {
+                    continue;
+                }
+                
+                let occurred_at = self.resolve_timezone(flow.source_timezone.as_deref())
+                    .ok()
+                    .and_then(|tz| timezone::normalize_timestamp(&flow.occurred_at, tz));
+                let occurred_at = match occurred_at {
+                    Some(occurred_at) => occurred_at,
+                    None => {
+                        errors.push(ImportRowError {
+                            row,
+                            field: Some("occurred_at".to_string()),
+                            message: format!("Invalid time: {}", flow.occurred_at),
+                        });
+                        continue;
+                    }
+                };
+                
+                sqlx::query(
+                    r#"
+                    INSERT INTO cash_flows (kind, amount, occurred_at, comment, external_id, broker, account, created_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                    "#
+                )
+                .bind(&flow.kind)
+                .bind(flow.amount)
+                .bind(&occurred_at)
+                .bind(&flow.comment)
+                .bind(&flow.external_id)
+                .bind(&flow.scope.broker)
+                .bind(&flow.scope.account)
+                .bind(now)
+                .execute(&mut **tx)
+                .await?;
+                
+                if let Some(ticket) = flow.external_id {
+                    known.insert(flow.scope, ticket, ());
+                }
+                imported += 1;
+            }
+            
+            Ok(imported)
+        }
+        
+        // Fills already on file (same id) are skipped silently. A fill is linked
+        // to the trade whose ticket is its position id, imported now or earlier.
+        async fn import_executions(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            executions: Vec<(usize, Execution)>,
+            trade_by_ticket: &TicketIndex<u32>,
+            now: &str,
+            errors: &mut Vec<ImportRowError>,
+        ) -> Result<usize, SqlxError> {
+            if executions.is_empty() {
+                return Ok(0);
+            }
+            
+            let mut known: TicketIndex<()> = TicketIndex::load(tx, "executions").await?;
+            
+            let mut imported = 0;
+            for (row, execution) in executions {
+                if execution.external_id.as_ref().map_or(false, |id| known.get(&execution.scope, id).is_some()) {
+                    continue;
+                }
+                
+                let executed_at = self.resolve_timezone(execution.source_timezone.as_deref())
+                    .ok()
+                    .and_then(|tz| timezone::normalize_timestamp(&execution.executed_at, tz));
+                let executed_at = match executed_at {
+                    Some(executed_at) => executed_at,
+                    None => {
+                        errors.push(ImportRowError {
+                            row,
+                            field: Some("executed_at".to_string()),
+                            message: format!("Invalid time: {}", execution.executed_at),
+                        });
+                        continue;
+                    }
+                };
+                let trade_id = execution.position_id.as_ref().and_then(|ticket| trade_by_ticket.get(&execution.scope, ticket));
+                
+                sqlx::query(
+                    r#"
+                    INSERT INTO executions (
+                        trade_id, position_id, external_id, broker, account, symbol, side, volume, price,
+                        executed_at, commission, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#
+                )
+                .bind(trade_id)
+                .bind(&execution.position_id)
+                .bind(&execution.external_id)
+                .bind(&execution.scope.broker)
+                .bind(&execution.scope.account)
+                .bind(&execution.symbol)
+                .bind(&execution.side)
+                .bind(execution.volume)
+                .bind(execution.price)
+                .bind(&executed_at)
+                .bind(execution.commission)
+                .bind(now)
+                .execute(&mut **tx)
+                .await?;
+                
+                if let Some(id) = execution.external_id {
+                    known.insert(execution.scope, id, ());
+                }
+                imported += 1;
+            }
+            
+            Ok(imported)
+        }
+        
+        // Normalize entry and exit times into UTC using the trade's source zone
+        fn normalize_import_times(&self, trade: &mut NewTrade, exit_time: Option<String>) -> Result<Option<String>, SqlxError> {
+            self.normalize_new_trade_time(trade)?;
+            
+            let tz = self.resolve_timezone(trade.source_timezone.as_deref())?;
+            exit_time
+                .map(|value| {
+                    timezone::normalize_timestamp(&value, tz)
+                        .ok_or_else(|| SqlxError::Decode(format!("Invalid exit time: {}", value).into()))
+                })
+                .transpose()
+        }
+    }
+    
+    // External ids on file, per broker and account. Rows imported before
+    // tickets were scoped have no broker; they still match their ticket from
+    // any broker, so re-importing an old statement stays a no-op.
+    struct TicketIndex<V> {
+        scoped: HashMap<(TicketScope, String), V>,
+        unscoped: HashMap<String, V>,
+    }
+    
+    impl<V> Default for TicketIndex<V> {
+        fn default() -> Self {
+            Self { scoped: HashMap::new(), unscoped: HashMap::new() }
+        }
+    }
+    
+    impl<V: Copy> TicketIndex<V> {
+        fn insert(&mut self, scope: TicketScope, ticket: String, value: V) {
+            if scope.broker.is_none() {
+                self.unscoped.insert(ticket, value);
+            } else {
+                self.scoped.insert((scope, ticket), value);
+            }
+        }
+        
+        fn get(&self, scope: &TicketScope, ticket: &str) -> Option<V> {
+            self.scoped.get(&(scope.clone(), ticket.to_string()))
+                .or_else(|| self.unscoped.get(ticket))
+                .copied()
+        }
+    }
+    
+    impl TicketIndex<()> {
+        async fn load(tx: &mut sqlx::Transaction<'_, Sqlite>, table: &str) -> Result<Self, SqlxError> {
+            let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(&format!(
+                "SELECT external_id, broker, account FROM {} WHERE external_id IS NOT NULL", table
+            ))
+            .fetch_all(&mut **tx)
+            .await?;
+            
+            let mut index = Self::default();
+            for (ticket, broker, account) in rows {
+                index.insert(TicketScope { broker, account }, ticket, ());
+            }
+            Ok(index)
+        }
+    }
+    
+    // Journal export and import (format described in journal_export.rs)
+    impl DatabaseState {
+        // Write every entity of the journal to a JSON Lines file. It is written
+        // beside the target and renamed into place once complete.
+        pub async fn export_journal(
+            &self,
+            path: &Path,
+            options: &JournalExportOptions,
+        ) -> Result<JournalExportReport, Box<dyn std::error::Error>> {
+            let partial = path.with_extension("partial");
+            let mut writer = tokio::io::BufWriter::new(fs::File::create(&partial).await?);
+            
+            let result = self.write_journal(&mut writer, options).await;
+            let result = match result {
+                Ok(report) => writer.flush().await.map(|_| report).map_err(Into::into),
+                Err(e) => Err(e),
+            };
+            drop(writer);
+            
+            let mut report = match result {
+                Ok(report) => report,
+                Err(e) => {
+                    let _ = fs::remove_file(&partial).await;
+                    return Err(e);
+                }
+            };
+            fs::rename(&partial, path).await?;
+            
+            report.path = path.to_string_lossy().to_string();
+            log::info!("Exported journal to {}: {:?}", report.path, report.counts);
+            Ok(report)
+        }
+        
+        async fn write_journal<W: AsyncWrite + Unpin>(
+            &self,
+            writer: &mut W,
+            options: &JournalExportOptions,
+        ) -> Result<JournalExportReport, Box<dyn std::error::Error>> {
+            let mut report = JournalExportReport::default();
+            let mut schemas = vec![("main", false)];
+            if options.include_archived {
+                schemas.push((ARCHIVE_SCHEMA, true));
+            }
+            
+            let header = JournalHeader::new(timezone::format_utc(Utc::now()), options.images);
+            write_json_line(writer, &header).await?;
+            
+            for kind in RecordKind::ALL {
+                let mut count = 0;
+                match kind.table() {
+                    None => count += self.write_journal_images(writer, &schemas, options.images, &mut report.missing_images).await?,
+                    Some(table) => {
+                        for (schema, archived) in &schemas {
+                            if *archived && !ARCHIVED_TABLES.contains(&table) {
+                                continue;
+                            }
+                            
+                            let sql = format!("SELECT * FROM {}.{} ORDER BY rowid", schema, table);
+                            let mut rows = sqlx::query(&sql).fetch(&self.pool);
+                            while let Some(row) = rows.try_next().await? {
+                                write_json_line(writer, &JournalRecord::new(kind, *archived, row_to_json(&row))).await?;
+                                count += 1;
+                            }
+                        }
+                    }
+                }
+                report.counts.insert(kind.name().to_string(), count);
+            }
+            
+            write_json_line(writer, &JournalRecord::end(report.counts.clone())).await?;
+            Ok(report)
+        }
+        
+        // One record per image file referenced by a trade
+        async fn write_journal_images<W: AsyncWrite + Unpin>(
+            &self,
+            writer: &mut W,
+            schemas: &[(&str, bool)],
+            mode: ImageMode,
+            missing: &mut Vec<String>,
+        ) -> Result<usize, Box<dyn std::error::Error>> {
+            let mut paths = BTreeSet::new();
+            for (schema, _) in schemas {
+                for column in ["entry_image", "exit_image", "analysis_image"] {
+                    let found: Vec<String> = sqlx::query_scalar(&format!(
+                        "SELECT DISTINCT {column} FROM {schema}.trades WHERE {column} IS NOT NULL AND {column} != ''"
+                    ))
+                    .fetch_all(&self.pool)
+                    .await?;
+                    paths.extend(found);
+                }
+            }
+            
+            for path in &paths {
+                let file = self.image_storage_path.join(path);
+                let mut data = serde_json::Map::new();
+                data.insert("path".to_string(), serde_json::Value::from(path.as_str()));
+                
+                if mode == ImageMode::Embedded {
+                    match fs::read(&file).await {
+                        Ok(bytes) => {
+                            data.insert("content".to_string(), serde_json::Value::from(BASE64.encode(bytes)));
+                        }
+                        Err(_) => missing.push(path.clone()),
+                    }
+                } else if !fs::try_exists(&file).await.unwrap_or(false) {
+                    missing.push(path.clone());
+                }
+                
+                write_json_line(writer, &JournalRecord::new(RecordKind::Image, false, data)).await?;
+            }
+            
+            Ok(paths.len())
+        }
+        
+        // Rebuild a journal export in this journal, in one transaction. Trades
+        // keep their id when it is free here and get a new one otherwise;
+        // references follow. Rows matching a local row by natural key are
+        // resolved by the conflict policy.
+        pub async fn import_journal(
+            &self,
+            path: &Path,
+            options: &JournalImportOptions,
+        ) -> Result<JournalImportReport, Box<dyn std::error::Error>> {
+            let mut lines = tokio::io::BufReader::new(fs::File::open(path).await?).lines();
+            let header_line = lines.next_line().await?.ok_or("The file is empty")?;
+            let header = JournalHeader::parse(header_line.trim_start_matches('\u{feff}'))?;
+            
+            let mut session = JournalImportSession {
+                options: options.clone(),
+                columns: HashMap::new(),
+                trade_ids: BTreeMap::new(),
+                image_paths: HashMap::new(),
+                written_images: Vec::new(),
+                report: JournalImportReport {
+                    version: header.version,
+                    dry_run: options.dry_run,
+                    ..Default::default()
+                },
+            };
+            for table in RecordKind::ALL.iter().filter_map(|kind| kind.table()) {
+                for schema in ["main", ARCHIVE_SCHEMA] {
+                    if schema == ARCHIVE_SCHEMA && !ARCHIVED_TABLES.contains(&table) {
+                        continue;
+                    }
+                    let names = table_columns(&self.pool, schema, table).await?.into_iter().map(|(name, _)| name).collect();
+                    session.columns.insert(format!("{}.{}", schema, table), names);
+                }
+            }
+            
+            let mut tx = self.pool.begin().await?;
+            if let Err(e) = self.read_journal_records(&mut tx, &mut lines, &mut session).await {
+                drop(tx);
+                session.remove_written_images(&self.image_storage_path).await;
+                return Err(e);
+            }
+            
+            // Ids kept from the file may lie above the live sequence when they
+            // went to the archive; move the sequences past them so live trades
+            // and fills never reuse an archived id
+            for table in ["trades", "executions"] {
+                for statement in [
+                    format!("INSERT INTO main.sqlite_sequence (name, seq) SELECT '{table}', 0 WHERE NOT EXISTS (SELECT 1 FROM main.sqlite_sequence WHERE name = '{table}')"),
+                    format!("UPDATE main.sqlite_sequence SET seq = MAX(seq, (SELECT IFNULL(MAX(id), 0) FROM archive.{table})) WHERE name = '{table}'"),
+                ] {
+                    sqlx::query(&statement).execute(&mut *tx).await?;
+                }
+            }
+            
+            if options.dry_run {
+                tx.rollback().await?;
+                session.remove_written_images(&self.image_storage_path).await;
+            } else {
+                for statement in statistics_rebuild_sql(ARCHIVE_SCHEMA) {
+                    sqlx::query(&statement).execute(&mut *tx).await?;
+                }
+                tx.commit().await?;
+                session.report.committed = true;
+                // Custom columns added by the file's schema join the change log
+                self.create_change_log_triggers().await?;
+                
+                for image in &session.written_images {
+                    if let Err(e) = self.create_journal_thumbnail(image).await {
+                        session.report.warnings.push(format!("No thumbnail for {}: {}", image, e));
+                    }
+                }
+            }
+            
+            let JournalImportSession { trade_ids, mut report, .. } = session;
+            report.trade_id_map = trade_ids.into_iter().filter(|(from, to)| from != to).collect();
+            
+            log::info!(
+                "Imported journal {}: inserted {:?}, replaced {:?}, skipped {:?} (committed: {})",
+                path.display(), report.inserted, report.replaced, report.skipped, report.committed
+            );
+            Ok(report)
+        }
+        
+        async fn read_journal_records<R: tokio::io::AsyncBufRead + Unpin>(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            lines: &mut tokio::io::Lines<R>,
+            session: &mut JournalImportSession,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let mut line_number = 1;
+            let mut unknown_kinds = BTreeSet::new();
+            
+            while let Some(line) = lines.next_line().await? {
+                line_number += 1;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                
+                let record: JournalRecord = serde_json::from_str(&line)
+                    .map_err(|e| format!("Line {}: {}", line_number, e))?;
+                if record.kind == END_RECORD {
+                    return Ok(());
+                }
+                
+                match RecordKind::parse(&record.kind) {
+                    Some(RecordKind::Image) => self.import_journal_image(record.data, session).await
+                        .map_err(|e| format!("Line {}: {}", line_number, e))?,
+                    Some(kind) => self.import_journal_row(tx, kind, record.archived, record.data, session).await
+                        .map_err(|e| format!("Line {}: {}", line_number, e))?,
+                    None => {
+                        if unknown_kinds.insert(record.kind.clone()) {
+                            session.report.warnings.push(format!("Skipped records of unknown type '{}'", record.kind));
+                        }
+                    }
+                }
+            }
+            
+            Err("The file ends before its trailer line; it was cut short".into())
+        }
+        
+        async fn import_journal_image(
+            &self,
+            data: serde_json::Map<String, serde_json::Value>,
+            session: &mut JournalImportSession,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let path = data.get("path").and_then(|p| p.as_str()).unwrap_or_default().to_string();
+            let inside = !path.is_empty() && Path::new(&path).components().all(|c| matches!(c, Component::Normal(_)));
+            if !inside {
+                session.report.warnings.push(format!("Image path '{}' is outside the image folder; skipped", path));
+                return Ok(());
+            }
+            
+            let target = self.image_storage_path.join(&path);
+            let content = match data.get("content").and_then(|c| c.as_str()) {
+                Some(content) => BASE64.decode(content).map_err(|e| format!("Image {}: {}", path, e))?,
+                None => {
+                    if !fs::try_exists(&target).await.unwrap_or(false) {
+                        session.report.warnings.push(format!("Image {} is referenced but not in this journal", path));
+                    }
+                    return Ok(());
+                }
+            };
+            
+            let stored = if fs::try_exists(&target).await.unwrap_or(false) {
+                if fs::read(&target).await? == content {
+                    return Ok(());
+                }
+                // Same name, different picture: keep both
+                let extension = Path::new(&path).extension().and_then(|e| e.to_str()).unwrap_or("webp");
+                let renamed = format!("images/{}.{}", Uuid::new_v4(), extension);
+                session.image_paths.insert(path, renamed.clone());
+                renamed
+            } else {
+                path
+            };
+            
+            if !session.options.dry_run {
+                let target = self.image_storage_path.join(&stored);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&target, content).await?;
+            }
+            session.written_images.push(stored);
+            *session.report.inserted.entry(RecordKind::Image.name().to_string()).or_insert(0) += 1;
+            Ok(())
+        }
+        
+        async fn import_journal_row(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            kind: RecordKind,
+            archived: bool,
+            mut data: serde_json::Map<String, serde_json::Value>,
+            session: &mut JournalImportSession,
+        ) -> Result<(), SqlxError> {
+            let table = kind.table().unwrap_or_default();
+            let schema = if archived && ARCHIVED_TABLES.contains(&table) { ARCHIVE_SCHEMA } else { "main" };
+            
+            let image_columns: &[&str] = match kind {
+                RecordKind::Trade => &["entry_image", "exit_image", "analysis_image"],
+                RecordKind::ImageHash => &["image_path"],
+                _ => &[],
+            };
+            for column in image_columns {
+                if let Some(serde_json::Value::String(path)) = data.get_mut(*column) {
+                    if let Some(renamed) = session.image_paths.get(path.as_str()) {
+                        *path = renamed.clone();
+                    }
+                }
+            }
+            remap_trade_references(kind, &mut data, &session.trade_ids);
+            
+            // Columns this journal does not have (a newer export) are dropped
+            let source_id = data.get("id").and_then(|id| id.as_i64());
+            let local_columns = session.columns.get(&format!("{}.{}", schema, table)).cloned().unwrap_or_default();
+            data.retain(|column, _| local_columns.contains(column));
+            
+            let policy = match session.options.on_conflict {
+                ConflictPolicy::KeepBoth if kind.is_unique() => ConflictPolicy::Skip,
+                policy => policy,
+            };
+            let existing = match policy {
+                ConflictPolicy::KeepBoth => None,
+                _ => self.find_journal_row(tx, kind, table, &data).await?,
+            };
+            
+            let (counter, local_id) = match existing {
+                Some((_, rowid)) if policy == ConflictPolicy::Skip => (&mut session.report.skipped, rowid),
+                Some((existing_schema, rowid)) => {
+                    let columns: Vec<&String> = data.keys().filter(|column| *column != "id").collect();
+                    let assignments: Vec<String> = columns.iter().map(|column| format!("{} = ?", column)).collect();
+                    let sql = format!("UPDATE {}.{} SET {} WHERE rowid = ?", existing_schema, table, assignments.join(", "));
+                    
+                    let mut query = sqlx::query(&sql);
+                    for column in columns {
+                        query = bind_json_value(query, data[column].clone());
+                    }
+                    query.bind(rowid).execute(&mut **tx).await?;
+                    (&mut session.report.replaced, rowid)
+                }
+                None => {
+                    // Only trade ids are worth keeping: other tables are not referenced
+                    // by id. Live and archived trades share one id space, so a new trade
+                    // id is allocated past both rather than by either table's sequence.
+                    data.remove("id");
+                    if kind == RecordKind::Trade {
+                        let free = match source_id {
+                            Some(id) => sqlx::query(&format!("SELECT 1 FROM {} WHERE id = ?", trades_source(&mut **tx, true).await?))
+                                .bind(id)
+                                .fetch_optional(&mut **tx)
+                                .await?
+                                .is_none(),
+                            None => false,
+                        };
+                        let id = match source_id {
+                            Some(id) if free => id,
+                            _ => sqlx::query_scalar::<_, i64>(
+                                r#"
+                                SELECT MAX(
+                                    (SELECT IFNULL(MAX(id), 0) FROM main.trades),
+                                    (SELECT IFNULL(MAX(id), 0) FROM archive.trades),
+                                    (SELECT IFNULL(MAX(seq), 0) FROM main.sqlite_sequence WHERE name = 'trades')
+                                ) + 1
+                                "#
+                            )
+                            .fetch_one(&mut **tx)
+                            .await?,
+                        };
+                        data.insert("id".to_string(), serde_json::Value::from(id));
+                    }
+                    
+                    let columns: Vec<&String> = data.keys().collect();
+                    let placeholders = vec!["?"; columns.len()].join(", ");
+                    let names: Vec<&str> = columns.iter().map(|column| column.as_str()).collect();
+                    let sql = format!("INSERT INTO {}.{} ({}) VALUES ({})", schema, table, names.join(", "), placeholders);
+                    
+                    let mut query = sqlx::query(&sql);
+                    for column in columns {
+                        query = bind_json_value(query, data[column].clone());
+                    }
+                    let rowid = query.execute(&mut **tx).await?.last_insert_rowid();
+                    (&mut session.report.inserted, rowid)
+                }
+            };
+            
+            *counter.entry(kind.name().to_string()).or_insert(0) += 1;
+            if let (RecordKind::Trade, Some(source_id)) = (kind, source_id) {
+                session.trade_ids.insert(source_id, local_id);
+            }
+            
+            // Trade records that follow may carry the schema's custom fields
+            if kind == RecordKind::Schema && data.get("entity_name").and_then(|name| name.as_str()) == Some(TRADE_SCHEMA) {
+                let custom_columns = ensure_custom_columns(&mut **tx).await?;
+                for schema in ["main", ARCHIVE_SCHEMA] {
+                    let columns = session.columns.entry(format!("{}.trades", schema)).or_default();
+                    for name in &custom_columns {
+                        if !columns.contains(name) {
+                            columns.push(name.clone());
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        
+        // Local row with the same natural key, live rows first
+        async fn find_journal_row(
+            &self,
+            tx: &mut sqlx::Transaction<'_, Sqlite>,
+            kind: RecordKind,
+            table: &str,
+            data: &serde_json::Map<String, serde_json::Value>,
+        ) -> Result<Option<(&'static str, i64)>, SqlxError> {
+            // Later key columns an older export lacks (the ticket scope) match NULL
+            let key = kind.natural_key(data);
+            if !key.first().is_some_and(|column| data.contains_key(*column)) {
+                return Ok(None);
+            }
+            
+            let condition: Vec<String> = key.iter().map(|column| format!("{} IS ?", column)).collect();
+            for schema in ["main", ARCHIVE_SCHEMA] {
+                if schema == ARCHIVE_SCHEMA && !ARCHIVED_TABLES.contains(&table) {
+                    continue;
+                }
+                
+                let sql = format!("SELECT rowid FROM {}.{} WHERE {} LIMIT 1", schema, table, condition.join(" AND "));
+                let mut query = sqlx::query(&sql);
+                for column in key {
+                    query = bind_json_value(query, data.get(*column).cloned().unwrap_or_default());
+                }
+                if let Some(row) = query.fetch_optional(&mut **tx).await? {
+                    return Ok(Some((schema, row.get(0))));
+                }
+            }
+            
+            Ok(None)
+        }
+        
+        async fn create_journal_thumbnail(&self, image: &str) -> Result<(), Box<dyn std::error::Error>> {
+            let source = self.image_storage_path.join(image);
+            let file_name = Path::new(image).file_name().ok_or("Image path has no file name")?;
+            let loaded = self.load_image(&source).await?;
+            self.create_thumbnail(&loaded, &self.image_storage_path.join("thumbnails").join(file_name))
+        }
+    }
+    
+    // Bookkeeping of one import_journal run
+    struct JournalImportSession {
+        options: JournalImportOptions,
+        // Local columns per "schema.table"
+        columns: HashMap<String, Vec<String>>,
+        // Trade id in the file -> trade id here
+        trade_ids: BTreeMap<i64, i64>,
+        // Image path in the file -> path it was stored under here
+        image_paths: HashMap<String, String>,
+        written_images: Vec<String>,
+        report: JournalImportReport,
+    }
+    
+    impl JournalImportSession {
+        async fn remove_written_images(&mut self, storage: &Path) {
+            if self.options.dry_run {
+                self.written_images.clear();
+                return;
+            }
+            for image in self.written_images.drain(..) {
+                let _ = fs::remove_file(storage.join(image)).await;
+            }
+        }
+    }
+    
+    async fn write_json_line<W: AsyncWrite + Unpin, T: Serialize>(writer: &mut W, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+        writer.write_all(&line).await?;
+        Ok(())
+    }
+    
+    // A row as JSON, keeping each value's storage class. REAL values keep full
+    // precision; SQLite's json_object() would round them to 15 digits.
+    fn row_to_json(row: &SqliteRow) -> serde_json::Map<String, serde_json::Value> {
+        use sqlx::{Column, TypeInfo, ValueRef};
+        
+        row.columns().iter()
+            .map(|column| {
+                let index = column.ordinal();
+                let value = match row.try_get_raw(index) {
+                    Ok(raw) if raw.is_null() => serde_json::Value::Null,
+                    Ok(raw) => match raw.type_info().name() {
+                        "INTEGER" => row.try_get::<i64, _>(index).map(serde_json::Value::from).unwrap_or_default(),
+                        "REAL" => row.try_get::<f64, _>(index).map(serde_json::Value::from).unwrap_or_default(),
+                        "BLOB" => row.try_get::<Vec<u8>, _>(index).map(|bytes| serde_json::Value::from(BASE64.encode(bytes))).unwrap_or_default(),
+                        _ => row.try_get::<String, _>(index).map(serde_json::Value::from).unwrap_or_default(),
+                    },
+                    Err(_) => serde_json::Value::Null,
+                };
+                (column.name().to_string(), value)
+            })
+            .collect()
+    }
+    
+    // Spreadsheet export (columns, locales and layout in trade_export.rs)
+    impl DatabaseState {
+        // Export every trade matching the query; limit, offset and cursor are
+        // ignored. The format follows the file extension unless given.
+        pub async fn export_trades(
+            &self,
+            path: &Path,
+            query: &TradeQuery,
+            columns: &[String],
+            format: Option<ExportFormat>,
+            options: &TradeExportOptions,
+        ) -> Result<TradeExportReport, Box<dyn std::error::Error>> {
+            let format = format.or_else(|| ExportFormat::from_path(path))
+                .ok_or("Pick a format or use a .csv or .xlsx file name")?;
+            
+            let labels = match sqlx::query_scalar::<_, String>("SELECT schema_json FROM entity_schemas WHERE entity_name = ?")
+                .bind(TRADE_SCHEMA)
+                .fetch_optional(&self.pool)
+                .await?
+            {
+                Some(json) => schema_labels(&serde_json::from_str(&json)?),
+                None => HashMap::new(),
+            };
+            let columns = resolve_columns(columns, &labels)?;
+            let tz = match &options.timezone {
+                Some(name) => timezone::parse_timezone(name)?,
+                None => self.time_settings.display_tz(),
+            };
+            let locale = options.locale.as_deref().map(ExportLocale::from_tag).unwrap_or_default();
+            
+            let rows = self.export_rows(query).await?;
+            let summary = options.include_summary.then(|| trade_export::summarize(&rows));
+            let summary_path = match (format, &summary) {
+                (ExportFormat::Csv, Some(_)) => Some(path.with_extension("summary.csv")),
+                _ => None,
+            };
+            
+            let report = TradeExportReport {
+                path: path.to_string_lossy().to_string(),
+                format,
+                rows: rows.len(),
+                columns: columns.iter().map(|column| column.key.clone()).collect(),
+                summary_path: summary_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            };
+            
+            // Building a workbook is CPU bound; keep it off the async workers
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || -> Result<(), String> {
+                let written = match format {
+                    ExportFormat::Csv => trade_export::write_csv(&path, &columns, &rows, &locale, tz).and_then(|_| {
+                        match (&summary_path, &summary) {
+                            (Some(summary_path), Some(summary)) => trade_export::write_summary_csv(summary_path, summary, &locale),
+                            _ => Ok(()),
+                        }
+                    }),
+                    ExportFormat::Xlsx => trade_export::write_xlsx(&path, &columns, &rows, summary.as_ref(), &locale, tz),
+                };
+                written.map_err(|e| e.to_string())
+            })
+            .await??;
+            
+            Ok(report)
+        }
+        
+        // Matching trades as stored, including custom field columns and external_id
+        async fn export_rows(&self, query: &TradeQuery) -> Result<Vec<TradeRow>, Box<dyn std::error::Error>> {
+            let sort_by = query.sort_by.as_deref().unwrap_or("entry_time");
+            if !trade_export::TRADE_COLUMNS.iter().any(|(name, ..)| *name == sort_by) {
+                return Err(format!("Cannot sort by {}", sort_by).into());
+            }
+            // Oldest first reads like a statement unless an order is asked for
+            let direction = match &query.sort_order {
+                Some(order) => SortDirection::parse(Some(order)).ok_or_else(|| format!("Unknown sort order: {}", order))?,
+                None => SortDirection::Ascending,
+            };
+            
+            let (where_clause, params) = Self::build_trade_filter(query);
+            let sql = format!(
+                "SELECT * FROM {} WHERE 1=1{} ORDER BY {} {}, id {}",
+                trades_source(&self.pool, query.include_archived).await?, where_clause, sort_by, direction.sql(), direction.sql()
+            );
+            
+            let mut query_builder = sqlx::query(&sql);
+            for param in params {
+                query_builder = query_builder.bind(param);
+            }
+            
+            let mut rows = Vec::new();
+            let mut stream = query_builder.fetch(&self.pool);
+            while let Some(row) = stream.try_next().await? {
+                rows.push(row_to_json(&row));
+            }
+            Ok(rows)
+        }
+    }
+    
+    // Performance reports (layout in report.rs)
+    impl DatabaseState {
+        // Figures and screenshots for the trades in `range` that match `scope`
+        // (its own date range is replaced), with warnings for missing pieces
+        async fn report_data(
+            &self,
+            range: &ReportRange,
+            scope: &TradeQuery,
+            options: &ReportOptions,
+        ) -> Result<(ReportData, Vec<String>), Box<dyn std::error::Error>> {
+            let tz = self.time_settings.display_tz();
+            let now = Utc::now();
+            let range = range.resolve(now, tz)?;
+            
+            let query = TradeQuery {
+                date_range: range.bounds,
+                sort_by: None,
+                sort_order: None,
+                ..scope.clone()
+            };
+            let rows = self.export_rows(&query).await?;
+            let mut report = ReportData::build(&rows, &range.label, options, tz, now);
+            let mut warnings = Vec::new();
+            
+            for id in &options.screenshot_trade_ids {
+                let row = match rows.iter().find(|row| row.get("id").and_then(serde_json::Value::as_i64) == Some(*id as i64)) {
+                    Some(row) => row,
+                    None => {
+                        warnings.push(format!("Trade {} is not in the report", id));
+                        continue;
+                    }
+                };
+                
+                let mut images = Vec::new();
+                for (column, label) in report::IMAGE_COLUMNS {
+                    let image = match row.get(column).and_then(serde_json::Value::as_str).filter(|p| !p.is_empty()) {
+                        Some(image) => image,
+                        None => continue,
+                    };
+                    let bytes = match fs::read(self.image_storage_path.join(image)).await {
+                        Ok(bytes) => Some(bytes),
+                        Err(_) => {
+                            warnings.push(format!("{} image of trade {} was not found: {}", label, id, image));
+                            None
+                        }
+                    };
+                    images.push(ReportImage { label, path: image.to_string(), bytes });
+                }
+                report.screenshots.push(ReportScreenshot { trade: row.clone(), images });
+            }
+            
+            Ok((report, warnings))
+        }
+        
+        // Render the report into a single offline HTML file
+        pub async fn generate_report(
+            &self,
+            path: &Path,
+            range: &ReportRange,
+            scope: &TradeQuery,
+            options: &ReportOptions,
+        ) -> Result<ReportResult, Box<dyn std::error::Error>> {
+            let (report, warnings) = self.report_data(range, scope, options).await?;
+            fs::write(path, report::render_html(&report)).await?;
+            
+            Ok(ReportResult {
+                path: path.to_string_lossy().to_string(),
+                range: report.range_label,
+                trades: report.trade_count,
+                screenshots: report.screenshots.len(),
+                warnings,
+            })
+        }
+        
+        // Render the report as a paginated PDF laid out by `template`
+        pub async fn generate_pdf_report(
+            &self,
+            path: &Path,
+            template: PdfTemplate,
+            range: &ReportRange,
+            scope: &TradeQuery,
+            options: &ReportOptions,
+        ) -> Result<ReportResult, Box<dyn std::error::Error>> {
+            if template == PdfTemplate::TradeRecap && options.screenshot_trade_ids.is_empty() {
+                return Err("A trade recap needs at least one trade in screenshot_trade_ids".into());
+            }
+            
+            let (report, mut warnings) = self.report_data(range, scope, options).await?;
+            let result = ReportResult {
+                path: path.to_string_lossy().to_string(),
+                range: report.range_label.clone(),
+                trades: report.trade_count,
+                screenshots: report.screenshots.len(),
+                warnings: Vec::new(),
+            };
+            
+            // Laying out pages and re-encoding screenshots is CPU bound
+            let font = options.pdf_font.clone().map(PathBuf::from);
+            let output = tokio::task::spawn_blocking(move || {
+                pdf_report::render_pdf(&report, template, font.as_deref()).map_err(|e| e.to_string())
+            })
+            .await??;
+            fs::write(path, output.bytes).await?;
+            warnings.extend(output.warnings);
+            
+            Ok(ReportResult { warnings, ..result })
+        }
+    }
+    
+    // Exchange rates and the annual tax report (lot matching in tax_report.rs)
+    impl DatabaseState {
+        // Insert or replace rates; all are validated before any is written
+        pub async fn set_fx_rates(&self, rates: &[FxRate]) -> Result<usize, Box<dyn std::error::Error>> {
+            for rate in rates {
+                rate.validate()?;
+            }
+            
+            let now = Utc::now().to_rfc3339();
+            let mut tx = self.pool.begin().await?;
+            for rate in rates {
+                sqlx::query(
+                    r#"
+                    INSERT INTO fx_rates (base_currency, quote_currency, rate_date, rate, source, updated_at)
+                    VALUES (?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(base_currency, quote_currency, rate_date)
+                    DO UPDATE SET rate = excluded.rate, source = excluded.source, updated_at = excluded.updated_at
+                    "#
+                )
+                .bind(&rate.base)
+                .bind(&rate.quote)
+                .bind(rate.date.to_string())
+                .bind(rate.rate)
+                .bind(&rate.source)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            
+            Ok(rates.len())
+        }
+        
+        // Stored rates between two currencies in either direction, oldest first
+        pub async fn get_fx_rates(&self, base: &str, quote: &str) -> Result<Vec<FxRate>, SqlxError> {
+            let rows = sqlx::query(
+                r#"
+                SELECT base_currency, quote_currency, rate_date, rate, source FROM fx_rates
+                WHERE (base_currency = ?1 AND quote_currency = ?2) OR (base_currency = ?2 AND quote_currency = ?1)
+                ORDER BY rate_date, base_currency
+                "#
+            )
+            .bind(base)
+            .bind(quote)
+            .fetch_all(&self.pool)
+            .await?;
+            
+            rows.iter()
+                .map(|row| {
+                    let date: String = row.get("rate_date");
+                    Ok(FxRate {
+                        base: row.get("base_currency"),
+                        quote: row.get("quote_currency"),
+                        date: date.parse().map_err(|e| SqlxError::Decode(Box::new(e)))?,
+                        rate: row.get("rate"),
+                        source: row.get("source"),
+                    })
+                })
+                .collect()
+        }
+        
+        // Realized P/L of the trades matching `scope` for one calendar year in
+        // the display timezone: a CSV of lots at `path` and a text summary
+        // next to it. Archived trades count.
+        pub async fn generate_tax_report(
+            &self,
+            path: &Path,
+            scope: &TradeQuery,
+            options: &TaxReportOptions,
+        ) -> Result<TaxReportResult, Box<dyn std::error::Error>> {
+            let tax_currency = options.tax_currency.trim().to_ascii_uppercase();
+            let account_currency = options.account_currency.trim().to_ascii_uppercase();
+            let options = TaxReportOptions { tax_currency, account_currency, ..options.clone() };
+            let tz = self.time_settings.display_tz();
+            
+            // Positions opened after the year cannot have been realized in it
+            let year_end = tz.with_ymd_and_hms(options.year + 1, 1, 1, 0, 0, 0)
+                .earliest()
+                .ok_or_else(|| format!("Invalid year: {}", options.year))?
+                .with_timezone(&Utc);
+            let query = TradeQuery {
+                date_range: Some((Utc.timestamp_opt(0, 0).unwrap(), year_end)),
+                sort_by: None,
+                sort_order: None,
+                include_archived: true,
+                ..scope.clone()
+            };
+            let rows = self.export_rows(&query).await?;
+            
+            let mut fills: HashMap<i64, Vec<tax_report::Fill>> = HashMap::new();
+            let mut stream = sqlx::query(
+                r#"
+                SELECT id, trade_id, side, volume, price, executed_at, commission FROM main.executions WHERE trade_id IS NOT NULL
+                UNION ALL
+                SELECT id, trade_id, side, volume, price, executed_at, commission FROM archive.executions WHERE trade_id IS NOT NULL
+                ORDER BY executed_at, id
+                "#
+            )
+            .fetch(&self.pool);
+            while let Some(row) = stream.try_next().await? {
+                let executed_at: String = row.get("executed_at");
+                let side: String = row.get("side");
+                if let Some(time) = parse_trade_time(&executed_at) {
+                    fills.entry(row.get("trade_id")).or_default().push(tax_report::Fill {
+                        buy: side.eq_ignore_ascii_case("buy"),
+                        volume: row.get("volume"),
+                        price: row.get("price"),
+                        time,
+                        commission: row.get("commission"),
+                    });
+                }
+            }
+            drop(stream);
+            
+            let (lots, warnings) = tax_report::realize(&rows, &fills);
+            
+            let rates = self.get_fx_rates(&options.account_currency, &options.tax_currency).await?;
+            let fx = FxTable::new(&options.account_currency, &options.tax_currency, &rates, options.max_rate_age_days);
+            let report = tax_report::build_report(lots, &options, &fx, tz, Utc::now())?;
+            
+            let summary_path = path.with_extension("summary.txt");
+            let summary = tax_report::render_summary(&report);
+            let csv_path = path.to_path_buf();
+            let written = tokio::task::spawn_blocking(move || {
+                tax_report::write_csv(&csv_path, &report).map_err(|e| e.to_string())?;
+                Ok::<_, String>(report)
+            })
+            .await??;
+            fs::write(&summary_path, summary).await?;
+            
+            Ok(TaxReportResult {
+                path: path.to_string_lossy().to_string(),
+                summary_path: summary_path.to_string_lossy().to_string(),
+                year: written.year,
+                tax_currency: written.tax_currency,
+                lots: written.lines.len(),
+                instruments: written.instruments,
+                totals: written.totals,
+                warnings,
+            })
+        }
+        
+        // Write the trades matching `scope` as a markdown vault into the folder
+        // at `path`. The manifest of the previous export decides what to do
+        // with each file: unchanged files are not touched, files edited in the
+        // vault are kept unless `overwrite_edited`, and files of trades that
+        // left the scope are removed.
+        pub async fn export_vault(
+            &self,
+            path: &Path,
+            scope: &TradeQuery,
+            options: &VaultExportOptions,
+        ) -> Result<VaultExportReport, Box<dyn std::error::Error>> {
+            let query = TradeQuery {
+                include_archived: options.include_archived,
+                sort_by: None,
+                sort_order: None,
+                ..scope.clone()
+            };
+            let rows = self.export_rows(&query).await?;
+            let custom_fields: Vec<String> = stored_custom_fields(&self.pool).await?
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            let plan = vault_export::plan(&rows, &custom_fields, self.time_settings.display_tz());
+            
+            fs::create_dir_all(path).await?;
+            let previous: Manifest = match fs::read(path.join(vault_export::MANIFEST_FILE)).await {
+                Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| format!("Unreadable {}: {}", vault_export::MANIFEST_FILE, e))?,
+                Err(_) => Manifest::default(),
+            };
+            
+            let mut report = VaultExportReport {
+                path: path.to_string_lossy().to_string(),
+                notes: plan.notes.len(),
+                ..Default::default()
+            };
+            let mut files: Vec<(String, Vec<u8>)> = plan.notes.into_iter().map(|(file, markdown)| (file, markdown.into_bytes())).collect();
+            for (file, stored) in plan.attachments {
+                match fs::read(self.image_storage_path.join(&stored)).await {
+                    Ok(bytes) => files.push((file, bytes)),
+                    Err(_) => report.missing_images.push(stored),
+                }
+            }
+            report.attachments = files.len() - report.notes;
+            
+            let mut written = BTreeMap::new();
+            for (file, bytes) in files {
+                let target = path.join(&file);
+                let hash = vault_export::content_hash(&bytes);
+                let on_disk = fs::read(&target).await.ok().map(|current| vault_export::content_hash(&current));
+                let exported = previous.files.get(&file);
+                match vault_export::file_action(exported.map(String::as_str), on_disk.as_deref(), &hash, options.overwrite_edited) {
+                    FileAction::Unchanged => report.unchanged += 1,
+                    FileAction::Write => {
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent).await?;
+                        }
+                        fs::write(&target, &bytes).await?;
+                        report.written += 1;
+                    }
+                    FileAction::KeepEdited => {
+                        // Files we never wrote stay out of the manifest, so
+                        // they are never removed either
+                        if let Some(exported) = exported {
+                            written.insert(file.clone(), exported.clone());
+                        }
+                        report.kept_edited.push(file);
+                        continue;
+                    }
+                }
+                written.insert(file, hash);
+            }
+            
+            for (file, hash) in &previous.files {
+                let inside = Path::new(file).components().all(|c| matches!(c, Component::Normal(_)));
+                if written.contains_key(file) || !inside {
+                    continue;
+                }
+                let target = path.join(file);
+                let Ok(current) = fs::read(&target).await else { continue };
+                if vault_export::content_hash(&current) != *hash && !options.overwrite_edited {
+                    report.kept_edited.push(file.clone());
+                    continue;
+                }
+                fs::remove_file(&target).await?;
+                report.deleted += 1;
+                // Drop the folder too once its last note is gone
+                if let Some(parent) = target.parent().filter(|parent| *parent != path) {
+                    let _ = fs::remove_dir(parent).await;
+                }
+            }
+            
+            let manifest = Manifest {
+                version: vault_export::MANIFEST_VERSION,
+                exported_at: timezone::format_utc(Utc::now()),
+                files: written,
+            };
+            fs::write(path.join(vault_export::MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?).await?;
+            
+            log::info!(
+                "Exported vault to {}: {} written, {} unchanged, {} deleted, {} kept",
+                report.path, report.written, report.unchanged, report.deleted, report.kept_edited.len()
+            );
+            Ok(report)
+        }
+    }
+    
+    // Quick entry
+    impl DatabaseState {
+        // Parse a quick entry line against the instruments in the journal and
+        // the display timezone; nothing is saved
+        pub async fn preview_quick_entry(
+            &self,
+            text: &str,
+            aliases: &BTreeMap<String, String>,
+        ) -> Result<QuickEntryPreview, SqlxError> {
+            let symbols: Vec<String> = sqlx::query_scalar(&format!(
+                "SELECT DISTINCT symbol FROM {} ORDER BY symbol", trades_source(&self.pool, true).await?
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+            
+            Ok(quick_entry::parse(text, aliases, &symbols, self.time_settings.display_tz(), Utc::now()))
+        }
+    }
+    
+    // Equity curve
+    impl DatabaseState {
+        // Balance, peak and drawdown after each closed trade matching `scope`,
+        // in the order they were closed. Open trades are left out.
+        pub async fn equity_curve(
+            &self,
+            scope: &TradeQuery,
+            starting_balance: f64,
+        ) -> Result<EquityCurve, Box<dyn std::error::Error>> {
+            let query = TradeQuery { sort_by: None, sort_order: None, ..scope.clone() };
+            let rows = self.export_rows(&query).await?;
+            let projections: Vec<_> = rows.iter().map(trade_export::projection).collect();
+            
+            Ok(equity::build(&projections, starting_balance))
+        }
+    }
+    
+    // Tracked tables and the expression identifying a row (R = NEW or OLD)
+    const CHANGE_TRACKED_TABLES: [(&str, &str); 7] = [
+        ("trades", "R.id"),
+        ("trade_merges", "R.id"),
+        ("executions", "R.id"),
+        ("cash_flows", "R.id"),
+        ("entity_schemas", "R.entity_name"),
+        ("plugin_data", "R.plugin_name || '/' || R.data_key"),
+        ("fx_rates", "R.base_currency || '/' || R.quote_currency || '/' || R.rate_date"),
+    ];
+    
+    // JSON snapshot of a row. SQL functions take at most 127 arguments, so
+    // wide tables start with one json_object() chunk and add the rest with
+    // json_insert(). json_patch() would drop NULL columns, merge-patch style.
+    fn change_payload_sql(row: &str, columns: &[String]) -> String {
+        columns.chunks(50)
+            .enumerate()
+            .fold("json_object()".to_string(), |payload, (index, chunk)| {
+                if index == 0 {
+                    let pairs: Vec<String> = chunk.iter()
+                        .map(|column| format!("'{column}', {row}.{column}"))
+                        .collect();
+                    format!("json_object({})", pairs.join(", "))
+                } else {
+                    let pairs: Vec<String> = chunk.iter()
+                        .map(|column| format!("'$.{column}', {row}.{column}"))
+                        .collect();
+                    format!("json_insert({}, {})", payload, pairs.join(", "))
+                }
+            })
+    }
+    
+    fn change_log_trigger_sql(entity: &str, id_expr: &str, columns: &[String]) -> Vec<String> {
+        let mut statements = Vec::new();
+        
+        for (event, operation, row) in [("INSERT", "insert", "NEW"), ("UPDATE", "update", "NEW"), ("DELETE", "delete", "OLD")] {
+            let trigger = format!("trg_change_log_{}_{}", entity, operation);
+            statements.push(format!("DROP TRIGGER IF EXISTS {}", trigger));
+            statements.push(format!(
+                r#"
+            CREATE TRIGGER {trigger} AFTER {event} ON {entity} BEGIN
+                INSERT INTO change_log (entity, entity_id, operation, payload, changed_at)
+                VALUES ('{entity}', {id}, '{operation}', {payload}, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+            END"#,
+                id = id_expr.replace("R.", &format!("{}.", row)),
+                payload = change_payload_sql(row, columns),
+            ));
+        }
+        
+        statements
+    }
+    
+    // calculation_date / time_period for a dimension key expression
+    fn statistics_period(dimension: &str, key: &str) -> (String, &'static str) {
+        if dimension == "day" {
+            (key.to_string(), "'day'")
+        } else {
+            ("'all'".to_string(), "'all'")
+        }
+    }
+    
+    // Trigger bodies add NEW rows and subtract OLD rows; groups whose trade
+    // count drops to zero are removed.
+    fn statistics_trigger_sql() -> Vec<String> {
+        fn apply(row: &str, sign: &str) -> String {
+            let mut body = String::new();
+            for (dimension, key_expr) in STAT_DIMENSIONS {
+                let key = key_expr.replace("R.", &format!("{}.", row));
+                let (date, period) = statistics_period(dimension, &key);
+                
+                for (metric, value_expr) in STAT_METRICS {
+                    let value = value_expr.replace("R.", &format!("{}.", row));
+                    body.push_str(&format!(
+                        r#"
+                INSERT INTO trade_statistics (statistic_type, statistic_key, statistic_value, calculation_date, time_period)
+                SELECT '{dimension}:{metric}', {key}, {sign}({value}), {date}, {period}
+                WHERE {key} IS NOT NULL
+                ON CONFLICT(statistic_type, statistic_key, calculation_date, time_period)
+                DO UPDATE SET statistic_value = statistic_value + excluded.statistic_value;"#
+                    ));
+                }
+                
+                if sign == "-" {
+                    body.push_str(&format!(
+                        r#"
+                DELETE FROM trade_statistics
+                WHERE statistic_type LIKE '{dimension}:%' AND statistic_key = {key} AND calculation_date = {date}
+                  AND (SELECT statistic_value FROM trade_statistics
+                       WHERE statistic_type = '{dimension}:trade_count' AND statistic_key = {key} AND calculation_date = {date}) <= 0;"#
+                    ));
+                }
+            }
+            body
+        }
+        
+        vec![
+            "DROP TRIGGER IF EXISTS trg_trade_statistics_insert".to_string(),
+            "DROP TRIGGER IF EXISTS trg_trade_statistics_update".to_string(),
+            "DROP TRIGGER IF EXISTS trg_trade_statistics_delete".to_string(),
+            format!("CREATE TRIGGER trg_trade_statistics_insert AFTER INSERT ON trades BEGIN{}\n            END", apply("NEW", "+")),
+            format!(
+                "CREATE TRIGGER trg_trade_statistics_update AFTER UPDATE OF {} ON trades BEGIN{}{}\n            END",
+                STAT_SOURCE_COLUMNS, apply("OLD", "-"), apply("NEW", "+")
+            ),
+            format!("CREATE TRIGGER trg_trade_statistics_delete AFTER DELETE ON trades BEGIN{}\n            END", apply("OLD", "-")),
+        ]
+    }
+    
+    fn statistics_rebuild_sql(schema: &str) -> Vec<String> {
+        let mut statements = vec![
+            format!("DELETE FROM {}.trade_statistics WHERE time_period IN ('day', 'all')", schema),
+        ];
+        
+        for (dimension, key_expr) in STAT_DIMENSIONS {
+            let (date, period) = statistics_period(dimension, key_expr);
+            for (metric, value_expr) in STAT_METRICS {
+                statements.push(format!(
+                    r#"
+                    INSERT INTO {schema}.trade_statistics (statistic_type, statistic_key, statistic_value, calculation_date, time_period)
+                    SELECT '{dimension}:{metric}', {key_expr}, SUM({value_expr}), {date}, {period}
+                    FROM {schema}.trades AS R
+                    WHERE {key_expr} IS NOT NULL
+                    GROUP BY {key_expr}
+                    "#
+                ));
+            }
+        }
+        
+        statements
+    }
+    
+    // Bind a JSON value using the matching SQLite storage class
+    pub(crate) fn bind_json_value<'q>(
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+        value: serde_json::Value,
+    ) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        match value {
+            serde_json::Value::Null => query.bind(None::<String>),
+            serde_json::Value::Bool(b) => query.bind(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => query.bind(i),
+                None => query.bind(n.as_f64()),
+            },
+            serde_json::Value::String(s) => query.bind(s),
+            other => query.bind(other.to_string()),
+        }
+    }
+    
+    // Export for use in other modules
+    pub use Trade;
+    pub use NewTrade;
+    pub use TradeQuery;
+    pub use EntitySchema;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Fresh workspace under the temp dir for every test
+    async fn test_db() -> DatabaseState {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "journal-db-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let mut db = DatabaseState::open(WorkspacePaths::new(&root)).unwrap();
+        db.initialize().await.unwrap();
+        db
+    }
+
+    // Insert a trade row directly; `fields` override a plain open EURUSD buy
+    async fn insert_trade(db: &DatabaseState, fields: serde_json::Value) -> u32 {
+        let mut row = json!({
+            "symbol": "EURUSD",
+            "trade_type": "Buy",
+            "volume": 1.0,
+            "entry_price": 1.1,
+            "sl": 1.09,
+            "tp": 1.12,
+            "entry_time": "2024-01-02T10:00:00Z",
+            "created_at": "2024-01-02T10:00:00Z",
+            "updated_at": "2024-01-02T10:00:00Z",
+        });
+        row.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        let row = row.as_object().unwrap();
+
+        let columns: Vec<&str> = row.keys().map(String::as_str).collect();
+        let sql = format!(
+            "INSERT INTO trades ({}) VALUES ({})",
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for value in row.values() {
+            query = bind_json_value(query, value.clone());
+        }
+        query.execute(&db.pool).await.unwrap().last_insert_rowid() as u32
+    }
+
+    async fn store_hash(db: &DatabaseState, image_path: &str, hash: u64) {
+        let bands = image_hash::hash_bands(hash);
+        sqlx::query(
+            "INSERT INTO image_hashes (image_path, dhash, band0, band1, band2, band3, width, height, created_at) VALUES (?, ?, ?, ?, ?, ?, 1, 1, '2024-01-01T00:00:00Z')"
+        )
+        .bind(image_path)
+        .bind(image_hash::to_sql(hash))
+        .bind(bands[0])
+        .bind(bands[1])
+        .bind(bands[2])
+        .bind(bands[3])
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn naive_timestamps_are_normalized_once_on_startup() {
+        let mut db = test_db().await;
+        let legacy = insert_trade(&db, json!({ "entry_time": "2024-01-02 10:00:00", "exit_time": "2024-01-02 12:30:00" })).await;
+        let bad_exit = insert_trade(&db, json!({ "entry_time": "2024-01-03 10:00:00", "exit_time": "soon" })).await;
+        // Pretend the journal predates the migration
+        sqlx::query("DELETE FROM schema_versions").execute(&db.pool).await.unwrap();
+
+        db.initialize().await.unwrap();
+        let times = |id: u32| sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT entry_time, exit_time, source_timezone FROM trades WHERE id = ?"
+        ).bind(id);
+        let (entry, exit, zone) = times(legacy).fetch_one(&db.pool).await.unwrap();
+        assert_eq!((entry.as_str(), exit.as_deref(), zone.as_deref()), ("2024-01-02T10:00:00Z", Some("2024-01-02T12:30:00Z"), Some("UTC")));
+        // Neither time of a row with an unreadable exit is rewritten
+        let (entry, exit, zone) = times(bad_exit).fetch_one(&db.pool).await.unwrap();
+        assert_eq!((entry.as_str(), exit.as_deref(), zone), ("2024-01-03 10:00:00", Some("soon"), None));
+
+        // Recorded, so later starts leave new naive rows alone
+        let late = insert_trade(&db, json!({ "entry_time": "2024-01-04 10:00:00" })).await;
+        db.initialize().await.unwrap();
+        let (entry, _, _) = times(late).fetch_one(&db.pool).await.unwrap();
+        assert_eq!(entry, "2024-01-04 10:00:00");
+        let versions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_versions WHERE version = ?")
+            .bind(TIMESTAMP_MIGRATION_VERSION)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(versions, 1);
+    }
+
+    async fn statistics_rows(db: &DatabaseState) -> Vec<(String, String, f64, String)> {
+        sqlx::query_as("SELECT statistic_type, statistic_key, statistic_value, calculation_date FROM trade_statistics ORDER BY 1, 2, 4")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn statistics_triggers_agree_with_a_full_rebuild() {
+        let db = test_db().await;
+        let a = insert_trade(&db, json!({ "strategy_name": "Breakout", "is_win": true, "profit_loss_money": 120.5 })).await;
+        let b = insert_trade(&db, json!({ "symbol": "GBPUSD", "ict_pattern": "FVG", "is_win": false, "profit_loss_money": -40.25 })).await;
+        let c = insert_trade(&db, json!({ "strategy_name": "Breakout", "entry_time": "2024-01-03T23:30:00Z" })).await;
+        insert_trade(&db, json!({ "symbol": "XAUUSD", "strategy_name": "Range", "is_win": true, "profit_loss_money": 10.0 })).await;
+
+        for sql in [
+            format!("UPDATE trades SET is_win = 1, profit_loss_money = 75.0 WHERE id = {c}"),
+            format!("UPDATE trades SET symbol = 'EURUSD', entry_time = '2024-01-05T08:00:00Z', strategy_name = 'Range' WHERE id = {b}"),
+            format!("UPDATE trades SET notes = 'reviewed' WHERE id = {a}"),
+            format!("DELETE FROM trades WHERE id = {a}"),
+        ] {
+            sqlx::query(&sql).execute(&db.pool).await.unwrap();
+        }
+
+        let maintained = statistics_rows(&db).await;
+        db.rebuild_trade_statistics().await.unwrap();
+        assert_eq!(maintained, statistics_rows(&db).await);
+
+        let strategies = db.get_dimension_statistics("strategy", false).await.unwrap();
+        let breakout = strategies.iter().find(|s| s.key == "Breakout").unwrap();
+        assert_eq!((breakout.trade_count, breakout.win_count, breakout.net_profit), (1, 1, 75.0));
+        let range = strategies.iter().find(|s| s.key == "Range").unwrap();
+        assert_eq!((range.trade_count, range.loss_count, range.net_profit), (2, 1, -30.25));
+    }
+
+    #[tokio::test]
+    async fn day_statistics_follow_the_display_timezone() {
+        let mut db = test_db().await;
+        // 23:30 / 00:30 / 01:30 in Athens: winter UTC+2, summer UTC+3
+        insert_trade(&db, json!({ "entry_time": "2024-01-15T21:30:00Z" })).await;
+        insert_trade(&db, json!({ "entry_time": "2024-01-15T22:30:00Z" })).await;
+        insert_trade(&db, json!({ "entry_time": "2024-07-15T22:30:00Z" })).await;
+        let days = |stats: Vec<DimensionStatistics>| stats.into_iter().map(|s| (s.key, s.trade_count)).collect::<Vec<_>>();
+
+        assert_eq!(days(db.get_dimension_statistics("day", false).await.unwrap()), [
+            ("2024-01-15".to_string(), 2),
+            ("2024-07-15".to_string(), 1),
+        ]);
+
+        db.update_time_settings(TimeSettings {
+            broker_timezone: "UTC".to_string(),
+            display_timezone: "Europe/Athens".to_string(),
+        }).await.unwrap();
+        let expected = [
+            ("2024-01-15".to_string(), 1),
+            ("2024-01-16".to_string(), 1),
+            ("2024-07-16".to_string(), 1),
+        ];
+        assert_eq!(days(db.get_dimension_statistics("day", false).await.unwrap()), expected);
+
+        // Triggers use the same offsets as the rebuild
+        insert_trade(&db, json!({ "entry_time": "2024-07-16T20:59:00Z" })).await;
+        let stats = days(db.get_dimension_statistics("day", false).await.unwrap());
+        assert_eq!(stats.last().unwrap(), &("2024-07-16".to_string(), 2));
+    }
+
+    #[tokio::test]
+    async fn similar_charts_find_a_nearest_match_that_shares_no_band() {
+        let db = test_db().await;
+        store_hash(&db, "query.png", 0).await;
+        // One bit off in every band: distance 4 and no band in common
+        store_hash(&db, "nearest.png", 1 | 1 << 16 | 1 << 32 | 1 << 48).await;
+        // Same first band, far apart otherwise
+        store_hash(&db, "band.png", 0xFFFF_FFFF_FFFF_0000).await;
+        let nearest = insert_trade(&db, json!({ "entry_image": "nearest.png" })).await;
+        let band_hit = insert_trade(&db, json!({ "entry_image": "band.png" })).await;
+
+        let results = db.find_similar_charts(ChartQuery::Image("query.png".to_string()), 1).await.unwrap();
+        assert_eq!((results[0].trade_id, results[0].distance), (nearest, 4));
+
+        let results = db.find_similar_charts(ChartQuery::Image("query.png".to_string()), 2).await.unwrap();
+        let ids: Vec<u32> = results.iter().map(|c| c.trade_id).collect();
+        assert_eq!(ids, [nearest, band_hit]);
+    }
+
+    #[tokio::test]
+    async fn similar_charts_trust_band_matches_within_the_guaranteed_distance() {
+        let db = test_db().await;
+        store_hash(&db, "query.png", 0).await;
+        store_hash(&db, "close.png", 0b111).await;
+        store_hash(&db, "far.png", 1 | 1 << 16 | 1 << 32 | 1 << 48).await;
+        let close = insert_trade(&db, json!({ "entry_image": "close.png" })).await;
+        insert_trade(&db, json!({ "entry_image": "far.png" })).await;
+
+        let results = db.find_similar_charts(ChartQuery::Image("query.png".to_string()), 1).await.unwrap();
+        assert_eq!((results[0].trade_id, results[0].distance), (close, 3));
+    }
+
+    async fn trade_rows(db: &DatabaseState, include_archived: bool) -> Vec<serde_json::Map<String, serde_json::Value>> {
+        sqlx::query(&format!("SELECT * FROM {} ORDER BY id", trades_source(&db.pool, include_archived).await.unwrap()))
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(row_to_json)
+            .collect()
+    }
+
+    async fn trade_column_names(db: &DatabaseState, schema: &str) -> Vec<String> {
+        table_columns(&db.pool, schema, "trades").await.unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    async fn store_trade_schema(db: &DatabaseState, fields: serde_json::Value) {
+        let schema = json!({ "name": TRADE_SCHEMA, "fields": fields });
+        sqlx::query("INSERT OR REPLACE INTO entity_schemas (entity_name, schema_json, created_at, updated_at) VALUES (?, ?, 'now', 'now')")
+            .bind(TRADE_SCHEMA)
+            .bind(schema.to_string())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn builtin_trade_columns_match_the_table() {
+        let db = test_db().await;
+        assert_eq!(trade_column_names(&db, "main").await, BUILTIN_TRADE_COLUMNS);
+    }
+
+    #[tokio::test]
+    async fn custom_fields_get_columns_in_the_live_and_archived_tables() {
+        let db = test_db().await;
+        store_trade_schema(&db, json!([
+            { "name": "symbol", "data_type": "string" },
+            { "name": "setup_grade", "data_type": "string" },
+            { "name": "screen_time", "data_type": "integer" },
+        ])).await;
+        let mut conn = db.pool.acquire().await.unwrap();
+        assert_eq!(ensure_custom_columns(&mut conn).await.unwrap(), ["setup_grade", "screen_time"]);
+        // Already there the second time
+        ensure_custom_columns(&mut conn).await.unwrap();
+        drop(conn);
+
+        for schema in ["main", ARCHIVE_SCHEMA] {
+            let columns = table_columns(&db.pool, schema, "trades").await.unwrap();
+            assert!(columns.contains(&("setup_grade".to_string(), "TEXT".to_string())));
+            assert!(columns.contains(&("screen_time".to_string(), "INTEGER".to_string())));
+        }
+
+        let invalid = json!({ "fields": [{ "name": "Setup Grade", "data_type": "string" }] });
+        assert!(custom_trade_fields(&invalid).unwrap_err().contains("Setup Grade"));
+    }
+
+    fn import_row(row: usize, fields: serde_json::Value) -> ImportRow {
+        let mut trade = json!({
+            "symbol": "EURUSD", "trade_type": "Buy", "volume": 1.0, "entry_price": 1.1,
+            "entry_time": format!("2024-03-04T0{}:00:00Z", row),
+        });
+        let mut record = ImportedTrade::new(serde_json::from_value(trade.take()).unwrap());
+        if let Some(exit_price) = fields["exit_price"].as_f64() {
+            record.exit_price = Some(exit_price);
+            record.exit_time = Some("2024-03-04T10:00:00Z".to_string());
+        }
+        record.profit = fields["profit"].as_f64();
+        if let Some(custom) = fields["custom"].as_object() {
+            record.custom_fields = custom.clone().into_iter().collect();
+        }
+        ImportRow { row, record }
+    }
+
+    #[tokio::test]
+    async fn imported_exits_get_metrics_and_missing_levels_stay_null() {
+        let db = test_db().await;
+        let parsed = ParsedImport {
+            total_rows: 2,
+            rows: vec![
+                import_row(1, json!({ "exit_price": 1.105 })),
+                import_row(2, json!({ "exit_price": 1.095, "profit": -48.5 })),
+            ],
+            ..Default::default()
+        };
+        let report = db.import_trades(parsed, &ImportOptions { allow_partial: true, ..Default::default() }).await.unwrap();
+        assert_eq!(report.imported, 2, "{:?}", report.errors);
+
+        let rows = trade_rows(&db, false).await;
+        // No profit column: the result comes from the exit price
+        assert_eq!(rows[0]["is_win"], json!(1));
+        assert!(rows[0]["profit_loss_pips"].as_f64().unwrap() > 49.0);
+        assert!(rows[0]["profit_loss_money"].as_f64().unwrap() > 0.0);
+        // A reported profit is kept as the money result
+        assert_eq!(rows[1]["is_win"], json!(0));
+        assert_eq!(rows[1]["profit_loss_money"], json!(-48.5));
+
+        for row in &rows {
+            assert_eq!((&row["sl"], &row["tp"]), (&serde_json::Value::Null, &serde_json::Value::Null));
+            assert_eq!(row["risk_reward_ratio"], serde_json::Value::Null);
+        }
+    }
+
+    #[tokio::test]
+    async fn sell_profit_is_signed_by_outcome_and_passes_the_integrity_check() {
+        let db = test_db().await;
+        let mut sell = import_row(1, json!({ "exit_price": 1.095 }));
+        sell.record.trade.trade_type = "Sell".to_string();
+        let parsed = ParsedImport { total_rows: 1, rows: vec![sell], ..Default::default() };
+        assert_eq!(db.import_trades(parsed, &ImportOptions::default()).await.unwrap().imported, 1);
+
+        // Written before the fix, and a broker figure that was always signed
+        let closed = json!({ "trade_type": "Sell", "exit_price": 1.095, "exit_time": "2024-01-02T12:00:00Z", "is_win": 1 });
+        let mut legacy = closed.clone();
+        legacy.as_object_mut().unwrap().extend(json!({ "profit_loss_money": -500.0, "profit_loss_pips": -50.0 }).as_object().unwrap().clone());
+        let legacy = insert_trade(&db, legacy).await;
+        let mut broker = closed;
+        broker["profit_loss_money"] = json!(31.5);
+        let broker = insert_trade(&db, broker).await;
+
+        sqlx::query("DELETE FROM schema_versions WHERE version = ?").bind(SELL_PROFIT_SIGN_MIGRATION_VERSION).execute(&db.pool).await.unwrap();
+        db.run_data_migrations().await.unwrap();
+
+        let rows = table_rows(&db, "SELECT id, is_win, profit_loss_money, profit_loss_pips FROM trades ORDER BY id").await;
+        assert_eq!(rows[0]["is_win"], json!(1));
+        assert!((rows[0]["profit_loss_money"].as_f64().unwrap() - 500.0).abs() < 1e-6);
+        assert!((rows[0]["profit_loss_pips"].as_f64().unwrap() - 50.0).abs() < 1e-6);
+        assert_eq!((&rows[1]["id"], &rows[1]["profit_loss_money"], &rows[1]["profit_loss_pips"]), (&json!(legacy), &json!(500.0), &json!(50.0)));
+        assert_eq!((&rows[2]["id"], &rows[2]["profit_loss_money"]), (&json!(broker), &json!(31.5)));
+
+        let report = crate::integrity::IntegrityChecker::new(db.pool(), db.image_storage_path()).check(false).await.unwrap();
+        assert!(report.findings.iter().all(|f| f.category != crate::integrity::FindingCategory::Outcome), "{:?}", report.findings);
+    }
+
+    #[tokio::test]
+    async fn imported_custom_fields_fill_their_schema_columns() {
+        let db = test_db().await;
+        store_trade_schema(&db, json!([
+            { "name": "setup_grade", "data_type": "string" },
+            { "name": "screen_time", "data_type": "integer" },
+            { "name": "risk_score", "data_type": "number" },
+        ])).await;
+        let parsed = ParsedImport {
+            total_rows: 4,
+            rows: vec![
+                import_row(1, json!({ "custom": { "setup_grade": "0012", "screen_time": "45", "risk_score": "2.5" } })),
+                import_row(2, json!({ "custom": { "mood": "calm" } })),
+                import_row(3, json!({ "custom": { "setup_grade": "1e3", "screen_time": "yes" } })),
+                import_row(4, json!({ "custom": { "screen_time": "long" } })),
+            ],
+            ..Default::default()
+        };
+        let report = db.import_trades(parsed, &ImportOptions { allow_partial: true, ..Default::default() }).await.unwrap();
+
+        assert_eq!(report.imported, 2);
+        let errors: Vec<(usize, Option<&str>)> = report.errors.iter().map(|e| (e.row, e.field.as_deref())).collect();
+        assert_eq!(errors, [(2, Some("custom.mood")), (4, Some("custom.screen_time"))]);
+        let rows = table_rows(&db, "SELECT setup_grade, typeof(setup_grade) AS grade_type, screen_time, risk_score FROM trades ORDER BY id").await;
+        // Text fields keep what looks like a number as written
+        assert_eq!((&rows[0]["setup_grade"], &rows[0]["grade_type"]), (&json!("0012"), &json!("text")));
+        assert_eq!((&rows[0]["screen_time"], &rows[0]["risk_score"]), (&json!(45), &json!(2.5)));
+        assert_eq!((&rows[1]["setup_grade"], &rows[1]["grade_type"], &rows[1]["screen_time"]), (&json!("1e3"), &json!("text"), &json!(1)));
+        assert!(!trade_column_names(&db, "main").await.contains(&"mood".to_string()));
+    }
+
+    fn ticketed(row: usize, broker: &str, ticket: &str) -> ImportRow {
+        let mut imported = import_row(row, json!({}));
+        imported.record.external_id = Some(ticket.to_string());
+        imported.record.scope = TicketScope::new(broker, Some("1001".to_string()));
+        imported
+    }
+
+    fn fill(broker: &str, id: &str, position: &str) -> Execution {
+        Execution {
+            external_id: Some(id.to_string()),
+            position_id: Some(position.to_string()),
+            symbol: "EURUSD".to_string(),
+            side: "Buy".to_string(),
+            volume: 1.0,
+            price: 1.1,
+            executed_at: "2024-03-04T08:00:00Z".to_string(),
+            commission: None,
+            scope: TicketScope::new(broker, Some("1001".to_string())),
+            source_timezone: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn tickets_are_scoped_to_their_broker_and_account() {
+        let db = test_db().await;
+        // Same ticket, fill id and account number at two brokers
+        let parsed = ParsedImport {
+            total_rows: 2,
+            rows: vec![ticketed(1, "metatrader", "5002"), ticketed(2, "ctrader", "5002")],
+            executions: vec![(1, fill("metatrader", "9001", "5002")), (2, fill("ctrader", "9001", "5002"))],
+            ..Default::default()
+        };
+        let options = ImportOptions { on_duplicate: DuplicatePolicy::Import, ..Default::default() };
+        let report = db.import_trades(parsed, &options).await.unwrap();
+        assert_eq!((report.imported, report.executions_imported), (2, 2), "{:?}", report.errors);
+
+        let linked: Vec<(String, i64)> = sqlx::query_as("SELECT broker, trade_id FROM executions ORDER BY broker")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        let trade_of = |broker: &str| report.trade_ids[if broker == "metatrader" { 0 } else { 1 }] as i64;
+        assert_eq!(linked, [("ctrader".to_string(), trade_of("ctrader")), ("metatrader".to_string(), trade_of("metatrader"))]);
+
+        // A newer statement from either broker finds its own trade
+        let again = ParsedImport {
+            total_rows: 1,
+            rows: vec![ticketed(1, "ctrader", "5002")],
+            executions: vec![(1, fill("ctrader", "9001", "5002"))],
+            ..Default::default()
+        };
+        let report = db.import_trades(again, &ImportOptions::default()).await.unwrap();
+        assert_eq!((report.imported, report.executions_imported), (0, 0));
+        assert_eq!(report.duplicates[0].trade_id as i64, trade_of("ctrader"));
+
+        // Tickets imported before scoping still block a re-import
+        sqlx::query("UPDATE trades SET broker = NULL, account = NULL, external_id = '7001'").execute(&db.pool).await.unwrap();
+        let legacy = ParsedImport { total_rows: 1, rows: vec![ticketed(1, "metatrader", "7001")], ..Default::default() };
+        assert_eq!(db.import_trades(legacy, &ImportOptions::default()).await.unwrap().imported, 0);
+    }
+
+    #[tokio::test]
+    async fn closing_fills_close_a_trade_opened_by_an_earlier_file() {
+        let db = test_db().await;
+        let opened = ParsedImport { total_rows: 1, rows: vec![ticketed(1, "ibkr_flex", "5002")], ..Default::default() };
+        let trade_id = db.import_trades(opened, &ImportOptions::default()).await.unwrap().trade_ids[0];
+
+        let closing = |account: &str| {
+            let mut sell = fill("ibkr_flex", "9100", "unused");
+            sell.position_id = None;
+            sell.side = "Sell".to_string();
+            sell.price = 1.12;
+            sell.executed_at = "2024-03-05T15:00:00Z".to_string();
+            sell.commission = Some(-1.5);
+            sell.scope.account = Some(account.to_string());
+            ParsedImport {
+                total_rows: 1,
+                closes: vec![PositionClose {
+                    symbol: "EURUSD".to_string(),
+                    trade_type: "Buy".to_string(),
+                    fills: vec![(1, sell)],
+                    profit: Some(20.0),
+                }],
+                ..Default::default()
+            }
+        };
+
+        // Another account of the same broker has nothing to close
+        let report = db.import_trades(closing("2002"), &ImportOptions::default()).await.unwrap();
+        assert_eq!((report.updated, report.executions_imported, report.warnings.len()), (0, 1, 1));
+        sqlx::query("DELETE FROM executions").execute(&db.pool).await.unwrap();
+
+        let report = db.import_trades(closing("1001"), &ImportOptions::default()).await.unwrap();
+        assert_eq!((report.updated, report.executions_imported), (1, 1), "{:?}", report.warnings);
+        let (exit_price, exit_time, profit, commission): (f64, String, f64, f64) = sqlx::query_as(
+            "SELECT exit_price, exit_time, profit_loss_money, commission FROM trades WHERE id = ?"
+        )
+        .bind(trade_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        // The reported 20.0 is net of the closing commission
+        assert_eq!((exit_price, exit_time.as_str(), profit, commission), (1.12, "2024-03-05T15:00:00Z", 21.5, -1.5));
+        let linked: Option<i64> = sqlx::query_scalar("SELECT trade_id FROM executions").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(linked, Some(trade_id as i64));
+
+        // Re-importing the statement leaves the closed trade alone
+        let report = db.import_trades(closing("1001"), &ImportOptions::default()).await.unwrap();
+        assert_eq!((report.updated, report.executions_imported), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn positions_imported_open_get_metrics_once_a_file_shows_them_closed() {
+        let db = test_db().await;
+        let opened = ParsedImport {
+            total_rows: 2,
+            rows: vec![ticketed(1, "metatrader", "7001"), ticketed(2, "metatrader", "7002")],
+            ..Default::default()
+        };
+        assert_eq!(db.import_trades(opened, &ImportOptions::default()).await.unwrap().imported, 2);
+
+        let closed = |row: usize, ticket: &str, profit: Option<f64>| {
+            let mut closed = ticketed(row, "metatrader", ticket);
+            closed.record.exit_price = Some(1.105);
+            closed.record.exit_time = Some("2024-03-04T10:00:00Z".to_string());
+            closed.record.profit = profit;
+            closed
+        };
+        let parsed = ParsedImport { total_rows: 2, rows: vec![closed(1, "7001", None), closed(2, "7002", Some(-12.5))], ..Default::default() };
+        let report = db.import_trades(parsed, &ImportOptions::default()).await.unwrap();
+        assert_eq!((report.imported, report.updated), (0, 2), "{:?}", report.errors);
+
+        let rows = table_rows(&db, "SELECT is_win, profit_loss_pips, profit_loss_money FROM trades ORDER BY id").await;
+        // No profit in the file: derived from the stored entry and the new exit
+        assert_eq!(rows[0]["is_win"], json!(1));
+        assert!((rows[0]["profit_loss_pips"].as_f64().unwrap() - 50.0).abs() < 1e-6);
+        assert!((rows[0]["profit_loss_money"].as_f64().unwrap() - 500.0).abs() < 1e-6);
+        // A reported profit still wins over the estimate
+        assert_eq!((&rows[1]["is_win"], &rows[1]["profit_loss_money"]), (&json!(0), &json!(-12.5)));
+        assert!((rows[1]["profit_loss_pips"].as_f64().unwrap() - 50.0).abs() < 1e-6);
+    }
+
+    async fn table_rows(db: &DatabaseState, sql: &str) -> Vec<serde_json::Map<String, serde_json::Value>> {
+        sqlx::query(sql).fetch_all(&db.pool).await.unwrap().iter().map(row_to_json).collect()
+    }
+
+    #[tokio::test]
+    async fn journal_export_imports_into_an_empty_journal_unchanged() {
+        let source = test_db().await;
+        store_trade_schema(&source, json!([{ "name": "setup_grade", "data_type": "string" }])).await;
+        ensure_custom_columns(&mut source.pool.acquire().await.unwrap()).await.unwrap();
+        let image = b"chart bytes".to_vec();
+        std::fs::create_dir_all(source.image_storage_path.join("images")).unwrap();
+        std::fs::write(source.image_storage_path.join("images/chart.png"), &image).unwrap();
+        insert_trade(&source, json!({
+            "pattern_combination": r#"["FVG","Order Block"]"#, "entry_image": "images/chart.png", "setup_grade": "A",
+        })).await;
+        insert_trade(&source, json!({ "symbol": "GBPUSD", "exit_price": 1.27, "exit_time": "2024-01-02T12:00:00Z", "notes": "closed" })).await;
+        sqlx::query("INSERT INTO plugin_data (plugin_name, data_key, data_value, data_type, created_at, updated_at) VALUES ('sessions', 'settings', '{\"london\":8}', 'json', 'now', 'now')")
+            .execute(&source.pool)
+            .await
+            .unwrap();
+        let rate = FxRate { base: "USD".to_string(), quote: "EUR".to_string(), date: "2024-01-02".parse().unwrap(), rate: 0.91, source: Some("ecb".to_string()) };
+        source.set_fx_rates(&[rate]).await.unwrap();
+
+        let file = source.image_storage_path.join("journal.jsonl");
+        let options = JournalExportOptions { images: ImageMode::Embedded, ..Default::default() };
+        let exported = source.export_journal(&file, &options).await.unwrap();
+        assert_eq!(exported.counts["fx_rate"], 1);
+
+        let target = test_db().await;
+        let options = JournalImportOptions { on_conflict: ConflictPolicy::Replace, ..Default::default() };
+        let report = target.import_journal(&file, &options).await.unwrap();
+        assert!(report.committed && report.trade_id_map.is_empty(), "{:?}", report);
+
+        assert_eq!(trade_rows(&target, true).await, trade_rows(&source, true).await);
+        assert_eq!(std::fs::read(target.image_storage_path.join("images/chart.png")).unwrap(), image);
+        for sql in [
+            "SELECT entity_name, schema_json FROM entity_schemas ORDER BY entity_name",
+            "SELECT plugin_name, data_key, data_value, data_type FROM plugin_data ORDER BY plugin_name, data_key",
+            "SELECT * FROM fx_rates ORDER BY base_currency, quote_currency, rate_date",
+        ] {
+            assert_eq!(table_rows(&target, sql).await, table_rows(&source, sql).await, "{}", sql);
+        }
+    }
+
+    #[tokio::test]
+    async fn report_covers_its_range_and_scope_and_warns_about_missing_screenshots() {
+        let db = test_db().await;
+        std::fs::create_dir_all(db.image_storage_path.join("images")).unwrap();
+        std::fs::write(db.image_storage_path.join("images/entry.png"), b"png").unwrap();
+        let shown = insert_trade(&db, json!({
+            "entry_time": "2024-03-04T08:00:00Z", "exit_time": "2024-03-04T09:00:00Z", "exit_price": 1.11, "is_win": true,
+            "profit_loss_money": 100.0, "entry_image": "images/entry.png", "exit_image": "images/missing.png",
+        })).await;
+        // Another symbol, and a trade of the month before
+        insert_trade(&db, json!({ "symbol": "GBPUSD", "entry_time": "2024-03-05T08:00:00Z" })).await;
+        let earlier = insert_trade(&db, json!({ "entry_time": "2024-02-28T08:00:00Z" })).await;
+
+        let path = db.image_storage_path.join("report.html");
+        let range = ReportRange::Custom { from: "2024-03-01T00:00:00Z".parse().unwrap(), to: "2024-04-01T00:00:00Z".parse().unwrap() };
+        let scope = TradeQuery { symbol: Some(vec!["EURUSD".to_string()]), ..Default::default() };
+        let options = ReportOptions { screenshot_trade_ids: vec![shown, earlier], ..Default::default() };
+        let result = db.generate_report(&path, &range, &scope, &options).await.unwrap();
+
+        assert_eq!((result.range.as_str(), result.trades, result.screenshots), ("2024-03-01 to 2024-03-31", 1, 1));
+        assert_eq!(result.warnings, [
+            format!("Exit image of trade {} was not found: images/missing.png", shown),
+            format!("Trade {} is not in the report", earlier),
+        ]);
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.contains("data:image/png;base64,cG5n") && html.contains("2024-03-01 to 2024-03-31"));
+    }
+
+    #[tokio::test]
+    async fn vault_reexport_keeps_edited_notes_and_removes_notes_of_deleted_trades() {
+        let db = test_db().await;
+        let kept = insert_trade(&db, json!({ "strategy_name": "London: sweep", "entry_time": "2024-03-04T08:00:00Z" })).await;
+        let deleted = insert_trade(&db, json!({ "strategy_name": "London: sweep", "entry_time": "2024-03-05T08:00:00Z" })).await;
+        let vault = db.image_storage_path.join("vault");
+        let first = db.export_vault(&vault, &TradeQuery::default(), &VaultExportOptions::default()).await.unwrap();
+        // Two trade notes, two days, one strategy and the index
+        assert_eq!((first.notes, first.written), (6, 6));
+
+        let kept_note = format!("Trades/2024-03-04 EURUSD Buy {}.md", kept);
+        std::fs::write(vault.join(&kept_note), "edited in the vault").unwrap();
+        sqlx::query("UPDATE trades SET notes = 'reviewed' WHERE id = ?").bind(kept).execute(&db.pool).await.unwrap();
+        sqlx::query("DELETE FROM trades WHERE id = ?").bind(deleted).execute(&db.pool).await.unwrap();
+        let second = db.export_vault(&vault, &TradeQuery::default(), &VaultExportOptions::default()).await.unwrap();
+
+        assert_eq!(second.kept_edited.len(), 1);
+        assert_eq!(second.kept_edited[0], kept_note);
+        assert_eq!((second.written, second.unchanged, second.deleted), (2, 1, 2));
+        assert_eq!(std::fs::read_to_string(vault.join(&kept_note)).unwrap(), "edited in the vault");
+        assert!(!vault.join(format!("Trades/2024-03-05 EURUSD Buy {}.md", deleted)).exists());
+        assert!(!vault.join("Daily/2024-03-05.md").exists());
+        let strategy = std::fs::read_to_string(vault.join("Strategies/London- sweep.md")).unwrap();
+        assert!(strategy.contains("trades: 1\n"));
+
+        // The edit survives further exports until it is overwritten on request
+        let overwrite = VaultExportOptions { overwrite_edited: true, ..Default::default() };
+        let third = db.export_vault(&vault, &TradeQuery::default(), &overwrite).await.unwrap();
+        assert_eq!((third.written, third.kept_edited.len()), (1, 0));
+        assert!(std::fs::read_to_string(vault.join(&kept_note)).unwrap().contains("reviewed"));
+    }
+
+    async fn not_null_columns(db: &DatabaseState) -> Vec<String> {
+        sqlx::query("PRAGMA main.table_info(trades)")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap()
+            .iter()
+            .filter(|row| row.get::<i64, _>("notnull") == 1)
+            .map(|row| row.get("name"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn levels_migration_relaxes_not_null_and_keeps_the_rows() {
+        let mut db = test_db().await;
+        let kept = insert_trade(&db, json!({ "notes": "old" })).await;
+        let deleted = insert_trade(&db, json!({})).await;
+        sqlx::query("DELETE FROM trades WHERE id = ?").bind(deleted).execute(&db.pool).await.unwrap();
+
+        // Rebuild the table with the layout journals had before the migration
+        let definition: String = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'trades'")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let strict = definition
+            .replacen("sl REAL,", "sl REAL NOT NULL,", 1)
+            .replacen("tp REAL,", "tp REAL NOT NULL,", 1)
+            .replacen("CREATE TABLE trades", "CREATE TABLE trades_strict", 1);
+        let mut tx = db.pool.begin().await.unwrap();
+        for statement in [
+            strict.as_str(),
+            "INSERT INTO trades_strict SELECT * FROM trades",
+            "DROP TABLE trades",
+            "ALTER TABLE trades_strict RENAME TO trades",
+        ] {
+            sqlx::query(statement).execute(&mut *tx).await.unwrap();
+        }
+        sqlx::query("UPDATE sqlite_sequence SET seq = ? WHERE name = 'trades'").bind(deleted).execute(&mut *tx).await.unwrap();
+        tx.commit().await.unwrap();
+        let strict_columns = not_null_columns(&db).await;
+        assert!(strict_columns.contains(&"sl".to_string()) && strict_columns.contains(&"tp".to_string()));
+        sqlx::query("DELETE FROM schema_versions WHERE version = ?")
+            .bind(OPTIONAL_LEVELS_MIGRATION_VERSION)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        db.initialize().await.unwrap();
+        let relaxed = not_null_columns(&db).await;
+        assert!(!relaxed.contains(&"sl".to_string()) && !relaxed.contains(&"tp".to_string()));
+        let rows = trade_rows(&db, false).await;
+        assert_eq!((rows.len(), &rows[0]["id"], &rows[0]["notes"]), (1, &json!(kept), &json!("old")));
+        // The id of the deleted trade is not handed out again, and the
+        // statistics triggers came back with the table
+        assert!(insert_trade(&db, json!({ "sl": null, "tp": null, "is_win": true, "profit_loss_money": 5.0 })).await > deleted);
+        let wins: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM trade_statistics").fetch_one(&db.pool).await.unwrap();
+        assert!(wins > 0);
+    }
+
+    #[tokio::test]
+    async fn archived_trades_round_trip_through_the_archive() {
+        let db = test_db().await;
+        let old = insert_trade(&db, json!({
+            "entry_time": "2023-03-01T09:00:00Z", "exit_time": "2023-03-01T11:00:00Z",
+            "exit_price": 1.105, "commission": -2.5, "is_win": true, "profit_loss_money": 50.0,
+            "notes": "kept", "external_id": "T-1",
+        })).await;
+        let recent = insert_trade(&db, json!({
+            "entry_time": "2024-05-01T09:00:00Z", "exit_time": "2024-05-01T10:00:00Z",
+            "exit_price": 1.095, "is_win": false, "profit_loss_money": -50.0,
+        })).await;
+        // An archive created before a column existed gets it appended at the
+        // end, out of the live table's order
+        sqlx::query("DROP TABLE archive.trades").execute(&db.pool).await.unwrap();
+        let reordered = BUILTIN_TRADE_COLUMNS.iter()
+            .filter(|column| **column != "notes")
+            .chain(["notes"].iter())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!("CREATE TABLE archive.trades AS SELECT {} FROM main.trades WHERE 0", reordered))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        for trade_id in [old, recent] {
+            sqlx::query("INSERT INTO executions (trade_id, symbol, side, volume, price, executed_at, created_at) VALUES (?, 'EURUSD', 'Buy', 1, 1.1, '2023-03-01T09:00:00Z', 'now')")
+                .bind(trade_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let fills = "SELECT id, trade_id FROM main.executions ORDER BY id";
+        let before = trade_rows(&db, false).await;
+        let fills_before = table_rows(&db, fills).await;
+
+        let archived = db.archive_trades(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()).await.unwrap();
+        assert_eq!((archived.trades, archived.executions), (1, 1));
+        let live = trade_rows(&db, false).await;
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0]["id"], json!(recent));
+        assert_eq!(trade_rows(&db, true).await, before);
+        assert_eq!(table_rows(&db, fills).await, [fills_before[1].clone()]);
+        assert_eq!(table_rows(&db, "SELECT id, trade_id FROM archive.executions").await, [fills_before[0].clone()]);
+
+        let restored = db.unarchive_trades(Some(vec![old]), None).await.unwrap();
+        assert_eq!((restored.trades, restored.executions), (1, 1));
+        assert_eq!(trade_rows(&db, false).await, before);
+        assert_eq!(trade_rows(&db, true).await.len(), 2);
+        assert_eq!(table_rows(&db, fills).await, fills_before);
+    }
+
+    #[tokio::test]
+    async fn change_log_records_each_mutation_with_its_row() {
+        let db = test_db().await;
+        let since = db.changes_since(0, 1000).await.unwrap().last_seq;
+
+        let id = insert_trade(&db, json!({ "notes": "first" })).await;
+        sqlx::query("UPDATE trades SET notes = 'second', exit_price = 1.2 WHERE id = ?").bind(id).execute(&db.pool).await.unwrap();
+        sqlx::query("DELETE FROM trades WHERE id = ?").bind(id).execute(&db.pool).await.unwrap();
+        sqlx::query("INSERT INTO plugin_data (plugin_name, data_key, data_value, data_type, created_at, updated_at) VALUES ('alerts', 'level', '1.1', 'number', 'now', 'now')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let batch = db.changes_since(since, 1000).await.unwrap();
+        let trade_changes: Vec<&ChangeEntry> = batch.changes.iter().filter(|change| change.entity == "trades").collect();
+        let operations: Vec<&str> = trade_changes.iter().map(|change| change.operation.as_str()).collect();
+        assert_eq!(operations, ["insert", "update", "delete"]);
+        assert!(trade_changes.iter().all(|change| change.entity_id == id.to_string()));
+
+        assert_eq!(trade_changes[0].payload["notes"], json!("first"));
+        assert_eq!(trade_changes[0].payload["exit_price"], serde_json::Value::Null);
+        assert_eq!(trade_changes[1].payload["notes"], json!("second"));
+        assert_eq!(trade_changes[1].payload["exit_price"], json!(1.2));
+        // A delete carries the row as it was
+        assert_eq!(trade_changes[2].payload["notes"], json!("second"));
+        assert_eq!(trade_changes[2].payload["id"], json!(id));
+        let columns = table_columns(&db.pool, "main", "trades").await.unwrap();
+        assert_eq!(trade_changes[2].payload.as_object().unwrap().len(), columns.len());
+
+        let plugin_change = batch.changes.iter().find(|change| change.entity == "plugin_data").unwrap();
+        assert_eq!(plugin_change.entity_id, "alerts/level");
+        assert_eq!(plugin_change.payload["data_value"], json!("1.1"));
+
+        // Rates feed the tax report, so a sync peer must see them too
+        let rate = FxRate { base: "USD".to_string(), quote: "EUR".to_string(), date: "2024-01-02".parse().unwrap(), rate: 0.91, source: None };
+        db.set_fx_rates(std::slice::from_ref(&rate)).await.unwrap();
+        db.set_fx_rates(&[FxRate { rate: 0.92, ..rate }]).await.unwrap();
+        let batch = db.changes_since(batch.last_seq, 1000).await.unwrap();
+        let rate_changes: Vec<(&str, &str, &serde_json::Value)> = batch.changes.iter()
+            .map(|change| (change.entity.as_str(), change.operation.as_str(), &change.payload["rate"]))
+            .collect();
+        assert_eq!(rate_changes, [("fx_rates", "insert", &json!(0.91)), ("fx_rates", "update", &json!(0.92))]);
+        assert_eq!(batch.changes[0].entity_id, "USD/EUR/2024-01-02");
+    }
+
+    #[tokio::test]
+    async fn change_log_tells_archive_moves_from_deletes() {
+        let db = test_db().await;
+        let old = insert_trade(&db, json!({ "entry_time": "2023-03-01T09:00:00Z", "exit_time": "2023-03-01T11:00:00Z", "exit_price": 1.105 })).await;
+        let gone = insert_trade(&db, json!({})).await;
+        sqlx::query("INSERT INTO cash_flows (kind, amount, occurred_at, created_at) VALUES ('deposit', 1000, '2023-01-01T00:00:00Z', 'now')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO executions (trade_id, symbol, side, volume, price, executed_at, created_at) VALUES (?, 'EURUSD', 'Buy', 1, 1.1, '2023-03-01T09:00:00Z', 'now')")
+            .bind(old)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let since = db.changes_since(0, 1000).await.unwrap();
+        let logged: Vec<&str> = since.changes.iter().map(|change| change.entity.as_str()).collect();
+        assert!(logged.contains(&"cash_flows") && logged.contains(&"executions"));
+
+        sqlx::query("DELETE FROM trades WHERE id = ?").bind(gone).execute(&db.pool).await.unwrap();
+        db.archive_trades(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()).await.unwrap();
+        db.unarchive_trades(Some(vec![old]), None).await.unwrap();
+
+        let batch = db.changes_since(since.last_seq, 1000).await.unwrap();
+        let trade_changes: Vec<(String, &str, Option<&str>)> = batch.changes.iter()
+            .filter(|change| change.entity == "trades")
+            .map(|change| (change.entity_id.clone(), change.operation.as_str(), change.moved_to.as_deref()))
+            .collect();
+        assert_eq!(trade_changes, [
+            (gone.to_string(), "delete", None),
+            (old.to_string(), "delete", Some("archive")),
+            (old.to_string(), "insert", Some("main")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn change_payloads_of_wide_tables_keep_every_column() {
+        let db = test_db().await;
+        let columns: Vec<String> = (0..130).map(|i| format!("c{}", i)).collect();
+        sqlx::query(&format!("CREATE TABLE wide (id INTEGER PRIMARY KEY, {})", columns.join(", ")))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let mut all = vec!["id".to_string()];
+        all.extend(columns.iter().cloned());
+        for statement in change_log_trigger_sql("wide", "R.id", &all) {
+            sqlx::query(&statement).execute(&db.pool).await.unwrap();
+        }
+        let since = db.changes_since(0, 1000).await.unwrap().last_seq;
+
+        sqlx::query("INSERT INTO wide (id, c0, c129) VALUES (7, 'first', 'last')").execute(&db.pool).await.unwrap();
+
+        let change = db.changes_since(since, 10).await.unwrap().changes.remove(0);
+        assert_eq!((change.entity.as_str(), change.entity_id.as_str()), ("wide", "7"));
+        let payload = change.payload.as_object().unwrap();
+        assert_eq!(payload.len(), 131);
+        assert_eq!((&payload["c0"], &payload["c129"]), (&json!("first"), &json!("last")));
+    }
+
+    #[tokio::test]
+    async fn unreadable_change_payloads_are_reported() {
+        let db = test_db().await;
+        insert_trade(&db, json!({})).await;
+        sqlx::query("UPDATE change_log SET payload = '{\"symbol\": ' WHERE seq = (SELECT MAX(seq) FROM change_log)")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let error = db.changes_since(0, 1000).await.unwrap_err();
+        assert!(error.to_string().contains("unreadable payload"));
+    }
+
+    fn by_ids(ids: &[u32]) -> TradeQuery {
+        TradeQuery { ids: Some(ids.to_vec()), ..Default::default() }
+    }
+
+    fn patch(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn bulk_update_rejects_columns_outside_the_whitelist() {
+        let db = test_db().await;
+        let id = insert_trade(&db, json!({ "exit_price": 1.2, "profit_loss_money": 50.0, "is_win": true })).await;
+
+        for column in ["is_win", "profit_loss_money", "id", "version"] {
+            let error = db.bulk_update_trades(&by_ids(&[id]), patch(json!({ column: 1 })), false).await.unwrap_err();
+            assert_eq!(error.to_string(), format!("Field '{}' cannot be bulk updated", column));
+        }
+        let money: f64 = sqlx::query_scalar("SELECT profit_loss_money FROM trades").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(money, 50.0);
+    }
+
+    #[tokio::test]
+    async fn bulk_update_rolls_back_earlier_chunks_when_a_later_one_fails() {
+        let db = test_db().await;
+        let mut ids = Vec::new();
+        for _ in 0..BULK_CHUNK_SIZE + 10 {
+            ids.push(insert_trade(&db, json!({})).await);
+        }
+        let last = *ids.last().unwrap();
+        sqlx::query(&format!(
+            "CREATE TRIGGER fail_last BEFORE UPDATE ON trades WHEN OLD.id = {} BEGIN SELECT RAISE(ABORT, 'rejected'); END",
+            last
+        ))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let filter = TradeQuery { symbol: Some(vec!["EURUSD".to_string()]), ..Default::default() };
+        assert!(db.bulk_update_trades(&filter, patch(json!({ "notes": "reviewed" })), false).await.is_err());
+
+        let reviewed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM trades WHERE notes IS NOT NULL")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(reviewed, 0);
+    }
+
+    #[tokio::test]
+    async fn bulk_update_recalculates_metrics_when_prices_change() {
+        let db = test_db().await;
+        let closed = insert_trade(&db, json!({
+            "exit_price": 1.12, "exit_time": "2024-01-02T12:00:00Z",
+            "is_win": true, "profit_loss_money": 2000.0, "profit_loss_pips": 200.0, "risk_reward_ratio": 2.0,
+        })).await;
+
+        // Entry moved above the exit: the buy is now a loser
+        db.bulk_update_trades(&by_ids(&[closed]), patch(json!({ "entry_price": 1.13 })), false).await.unwrap();
+        let row = sqlx::query("SELECT is_win, profit_loss_pips, profit_loss_money, risk_reward_ratio FROM trades WHERE id = ?")
+            .bind(closed)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<Option<bool>, _>("is_win"), Some(false));
+        assert!((row.get::<f64, _>("profit_loss_pips") + 100.0).abs() < 1e-6);
+        assert!((row.get::<f64, _>("profit_loss_money") + 1000.0).abs() < 1e-6);
+        assert!((row.get::<f64, _>("risk_reward_ratio") - 0.01 / 0.04).abs() < 1e-6);
+
+        // Fees are not inputs of the gross P/L, so a broker figure survives
+        sqlx::query("UPDATE trades SET profit_loss_money = 987.5 WHERE id = ?").bind(closed).execute(&db.pool).await.unwrap();
+        db.bulk_update_trades(&by_ids(&[closed]), patch(json!({ "commission": -7.0 })), false).await.unwrap();
+        let money: f64 = sqlx::query_scalar("SELECT profit_loss_money FROM trades").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(money, 987.5);
+
+        // Still open: nothing to derive
+        let open = insert_trade(&db, json!({})).await;
+        db.bulk_update_trades(&by_ids(&[open]), patch(json!({ "volume": 2.0 })), false).await.unwrap();
+        let is_win: Option<bool> = sqlx::query_scalar("SELECT is_win FROM trades WHERE id = ?").bind(open).fetch_one(&db.pool).await.unwrap();
+        assert_eq!(is_win, None);
+    }
+
+    #[tokio::test]
+    async fn date_range_includes_trades_entered_at_either_bound() {
+        let db = test_db().await;
+        let at_start = insert_trade(&db, json!({ "entry_time": "2024-01-01T00:00:00Z" })).await;
+        let at_end = insert_trade(&db, json!({ "entry_time": "2024-01-02T10:00:00Z" })).await;
+        insert_trade(&db, json!({ "entry_time": "2024-01-02T10:00:01Z" })).await;
+
+        let parse = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+        let filter = TradeQuery {
+            date_range: Some((parse("2024-01-01T00:00:00Z"), parse("2024-01-02T10:00:00Z"))),
+            ..Default::default()
+        };
+        let matched = db.bulk_update_trades(&filter, patch(json!({ "notes": "x" })), true).await.unwrap();
+        assert_eq!(matched.trade_ids, [at_start, at_end]);
+    }
+
+    #[tokio::test]
+    async fn price_edits_recalculate_alike_in_both_paths_and_keep_broker_figures() {
+        let db = test_db().await;
+        let closed = json!({ "exit_price": 1.12, "exit_time": "2024-01-02T12:00:00Z", "is_win": true, "profit_loss_money": 2000.0 });
+        let bulk = insert_trade(&db, closed.clone()).await;
+        let single = insert_trade(&db, closed.clone()).await;
+        let mut imported = closed;
+        imported["external_id"] = json!("9001");
+        imported["profit_loss_money"] = json!(1987.25);
+        let imported = insert_trade(&db, imported).await;
+        let money = |id: u32| {
+            let pool = db.pool.clone();
+            async move {
+                sqlx::query_scalar::<_, f64>("SELECT profit_loss_money FROM trades WHERE id = ?").bind(id).fetch_one(&pool).await.unwrap()
+            }
+        };
+
+        db.bulk_update_trades(&by_ids(&[bulk, imported]), patch(json!({ "symbol": "EURJPY", "entry_price": 1.13 })), false).await.unwrap();
+        db.update_trade(single, patch(json!({ "symbol": "EURJPY", "entry_price": 1.13 }))).await.unwrap();
+        assert!((money(bulk).await + 1000.0).abs() < 1e-6);
+        assert_eq!(money(single).await, money(bulk).await);
+        assert_eq!(money(imported).await, 1987.25);
+
+        db.update_trade(imported, patch(json!({ "volume": 2.0 }))).await.unwrap();
+        assert_eq!(money(imported).await, 1987.25);
+
+        // A P/L set in the same edit is taken as given
+        db.update_trade(single, patch(json!({ "exit_price": 1.2, "profit_loss_money": 15.0 }))).await.unwrap();
+        assert_eq!(money(single).await, 15.0);
+    }
+
+    #[tokio::test]
+    async fn merge_moves_fills_and_screenshots_to_the_primary() {
+        let db = test_db().await;
+        let primary = insert_trade(&db, json!({ "notes": "Entered on the sweep", "entry_image": "a.png" })).await;
+        let duplicate = insert_trade(&db, json!({
+            "notes": "Imported from MT5", "entry_image": "b.png", "exit_image": "b-exit.png",
+        })).await;
+        sqlx::query("INSERT INTO executions (trade_id, symbol, side, volume, price, executed_at, created_at) VALUES (?, 'EURUSD', 'Buy', 1, 1.1, '2024-01-02T10:00:00Z', '2024-01-02T10:00:00Z')")
+            .bind(duplicate)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        store_hash(&db, "a.png", 0).await;
+        store_hash(&db, "b.png", u64::MAX).await;
+
+        let merged = db.merge_trades(primary, duplicate, HashMap::new()).await.unwrap();
+        assert_eq!(merged.new_trade.notes.as_deref(), Some("Entered on the sweep\n\nImported from MT5"));
+        assert_eq!(merged.new_trade.entry_image.as_deref(), Some("a.png"));
+        assert_eq!(merged.new_trade.exit_image.as_deref(), Some("b-exit.png"));
+
+        let fill_owner: i64 = sqlx::query_scalar("SELECT trade_id FROM executions").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(fill_owner, primary as i64);
+
+        let sources: String = sqlx::query_scalar("SELECT field_sources_json FROM trade_merges").fetch_one(&db.pool).await.unwrap();
+        let sources: HashMap<String, MergeSource> = serde_json::from_str(&sources).unwrap();
+        assert_eq!(sources["notes"], MergeSource::Merged);
+        assert_eq!(sources["exit_image"], MergeSource::Duplicate);
+        assert_eq!(sources["entry_image"], MergeSource::Primary);
+
+        // The duplicate's replaced entry chart still finds the merged trade
+        store_hash(&db, "query.png", u64::MAX).await;
+        let results = db.find_similar_charts(ChartQuery::Image("query.png".to_string()), 1).await.unwrap();
+        assert_eq!((results[0].trade_id, results[0].image_path.as_str(), results[0].distance), (primary, "b.png", 0));
+    }
+
+    #[tokio::test]
+    async fn merge_only_combines_notes() {
+        let db = test_db().await;
+        let primary = insert_trade(&db, json!({ "notes": "same" })).await;
+        let duplicate = insert_trade(&db, json!({ "notes": "same " })).await;
+
+        let sources = HashMap::from([("symbol".to_string(), MergeSource::Merged)]);
+        let error = db.merge_trades(primary, duplicate, sources).await.unwrap_err();
+        assert_eq!(error.to_string(), "Field 'symbol' cannot combine both values");
+
+        // Notes that only differ in whitespace are not repeated
+        let merged = db.merge_trades(primary, duplicate, HashMap::new()).await.unwrap();
+        assert_eq!(merged.new_trade.notes.as_deref(), Some("same"));
+        let sources: String = sqlx::query_scalar("SELECT field_sources_json FROM trade_merges").fetch_one(&db.pool).await.unwrap();
+        assert!(sources.contains(r#""notes":"primary""#));
+    }
+
+    #[tokio::test]
+    async fn imported_rows_are_checked_for_duplicates_against_the_journal() {
+        let db = test_db().await;
+        let existing = insert_trade(&db, json!({ "entry_time": "2024-03-04T01:00:30Z" })).await;
+        let parsed = ParsedImport { total_rows: 2, rows: vec![import_row(1, json!({})), import_row(2, json!({}))], ..Default::default() };
+
+        let report = db.import_trades(parsed, &ImportOptions::default()).await.unwrap();
+        let duplicates: Vec<(usize, u32)> = report.duplicates.iter().map(|d| (d.row, d.trade_id)).collect();
+        assert_eq!(duplicates, [(1, existing)]);
+        assert_eq!(report.imported, 1);
+    }
+}
@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{SqlitePool, Row, Error as SqlxError};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use crate::database::{parse_trade_time, NewTrade};
+
+// Duplicate detection configuration
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DuplicateConfig {
+    pub time_tolerance_seconds: i64,
+    pub price_tolerance_ratio: f64,
+    pub volume_tolerance: f64,
+    pub min_score: f64,
+}
+
+impl Default for DuplicateConfig {
+    fn default() -> Self {
+        Self {
+            time_tolerance_seconds: 120,
+            price_tolerance_ratio: 0.0005, // 5 basis points
+            volume_tolerance: 0.001,
+            min_score: 0.8,
+        }
+    }
+}
+
+// Scoring weights; symbol and direction are hard requirements
+const BASE_WEIGHT: f64 = 0.15;
+const TIME_WEIGHT: f64 = 0.35;
+const VOLUME_WEIGHT: f64 = 0.25;
+const PRICE_WEIGHT: f64 = 0.25;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateCandidate {
+    pub trade_id: u32,
+    pub duplicate_id: u32,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateMatch {
+    pub trade_id: u32,
+    pub score: f64,
+}
+
+// Lightweight projection of the fields used for matching
+#[derive(Debug, Clone)]
+pub struct TradeFingerprint {
+    pub trade_id: u32,
+    pub symbol: String,
+    pub trade_type: String,
+    pub volume: f64,
+    pub entry_price: f64,
+    pub entry_time: DateTime<Utc>,
+}
+
+impl TradeFingerprint {
+    pub fn from_new_trade(trade_id: u32, trade: &NewTrade) -> Option<Self> {
+        Some(Self {
+            trade_id,
+            symbol: trade.symbol.to_uppercase(),
+            trade_type: trade.trade_type.clone(),
+            volume: trade.volume,
+            entry_price: trade.entry_price,
+            entry_time: parse_trade_time(&trade.entry_time)?,
+        })
+    }
+}
+
+// Score how likely two trades are the same execution (0.0 - 1.0)
+pub fn score_pair(
+    a: &TradeFingerprint,
+    b: &TradeFingerprint,
+    config: &DuplicateConfig,
+) -> Option<(f64, Vec<String>)> {
+    if a.symbol != b.symbol || a.trade_type != b.trade_type {
+        return None;
+    }
+
+    let time_diff = (a.entry_time - b.entry_time).num_seconds().abs();
+    if time_diff > config.time_tolerance_seconds {
+        return None;
+    }
+
+    let mut score = BASE_WEIGHT;
+    let mut reasons = vec![format!("same symbol and direction ({} {})", a.symbol, a.trade_type)];
+
+    let time_tolerance = config.time_tolerance_seconds.max(1) as f64;
+    score += TIME_WEIGHT * (1.0 - time_diff as f64 / time_tolerance);
+    reasons.push(format!("entry times {}s apart", time_diff));
+
+    let volume_diff = (a.volume - b.volume).abs();
+    if volume_diff <= config.volume_tolerance {
+        score += VOLUME_WEIGHT;
+        reasons.push("same volume".to_string());
+    } else {
+        let larger = a.volume.abs().max(b.volume.abs());
+        if larger > 0.0 {
+            score += VOLUME_WEIGHT * (1.0 - volume_diff / larger).max(0.0) * 0.5;
+        }
+    }
+
+    let price_tolerance = a.entry_price.abs().max(b.entry_price.abs()) * config.price_tolerance_ratio;
+    let price_diff = (a.entry_price - b.entry_price).abs();
+    if price_tolerance > 0.0 && price_diff <= price_tolerance {
+        score += PRICE_WEIGHT * (1.0 - price_diff / price_tolerance);
+        reasons.push(format!("entry prices within {:.5}", price_diff));
+    } else if price_diff == 0.0 {
+        score += PRICE_WEIGHT;
+        reasons.push("same entry price".to_string());
+    }
+
+    Some((score.min(1.0), reasons))
+}
+
+// In-memory index of fingerprints grouped by symbol and direction,
+// sorted by entry time so candidates can be found with a binary search
+#[derive(Debug, Default)]
+pub struct DuplicateIndex {
+    groups: HashMap<(String, String), Vec<TradeFingerprint>>,
+}
+
+impl DuplicateIndex {
+    pub fn insert(&mut self, fingerprint: TradeFingerprint) {
+        let group = self.groups
+            .entry((fingerprint.symbol.clone(), fingerprint.trade_type.clone()))
+            .or_default();
+        let position = group.partition_point(|f| f.entry_time <= fingerprint.entry_time);
+        group.insert(position, fingerprint);
+    }
+
+    fn neighbours<'a>(
+        &'a self,
+        fingerprint: &TradeFingerprint,
+        config: &DuplicateConfig,
+    ) -> impl Iterator<Item = &'a TradeFingerprint> {
+        let window = chrono::Duration::seconds(config.time_tolerance_seconds);
+        let group = self.groups
+            .get(&(fingerprint.symbol.clone(), fingerprint.trade_type.clone()))
+            .map(|g| g.as_slice())
+            .unwrap_or(&[]);
+
+        let start = group.partition_point(|f| f.entry_time < fingerprint.entry_time - window);
+        let end = group.partition_point(|f| f.entry_time <= fingerprint.entry_time + window);
+        group[start..end].iter()
+    }
+
+    pub fn best_match(
+        &self,
+        fingerprint: &TradeFingerprint,
+        config: &DuplicateConfig,
+    ) -> Option<DuplicateMatch> {
+        self.neighbours(fingerprint, config)
+            .filter(|other| other.trade_id != fingerprint.trade_id)
+            .filter_map(|other| {
+                score_pair(fingerprint, other, config)
+                    .map(|(score, _)| DuplicateMatch { trade_id: other.trade_id, score })
+            })
+            .filter(|m| m.score >= config.min_score)
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    pub fn all_pairs(&self, config: &DuplicateConfig) -> Vec<DuplicateCandidate> {
+        let mut candidates = Vec::new();
+
+        for group in self.groups.values() {
+            for (i, a) in group.iter().enumerate() {
+                for b in group[i + 1..].iter() {
+                    if (b.entry_time - a.entry_time).num_seconds() > config.time_tolerance_seconds {
+                        break;
+                    }
+
+                    if let Some((score, reasons)) = score_pair(a, b, config) {
+                        if score >= config.min_score {
+                            candidates.push(DuplicateCandidate {
+                                trade_id: a.trade_id.min(b.trade_id),
+                                duplicate_id: a.trade_id.max(b.trade_id),
+                                score,
+                                reasons,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        candidates
+    }
+}
+
+// Duplicate detector
+pub struct DuplicateDetector {
+    pool: SqlitePool,
+    config: DuplicateConfig,
+}
+
+impl DuplicateDetector {
+    pub fn new(pool: SqlitePool, config: DuplicateConfig) -> Self {
+        Self { pool, config }
+    }
+
+    pub fn config(&self) -> &DuplicateConfig {
+        &self.config
+    }
+
+    // Load fingerprints of all existing trades
+    pub async fn build_index(&self) -> Result<DuplicateIndex, SqlxError> {
+        let rows = sqlx::query(
+            "SELECT id, symbol, trade_type, volume, entry_price, entry_time FROM trades"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut index = DuplicateIndex::default();
+        for row in rows {
+            let entry_time: String = row.get("entry_time");
+            if let Some(entry_time) = parse_trade_time(&entry_time) {
+                index.insert(TradeFingerprint {
+                    trade_id: row.get::<i64, _>("id") as u32,
+                    symbol: row.get::<String, _>("symbol").to_uppercase(),
+                    trade_type: row.get("trade_type"),
+                    volume: row.get("volume"),
+                    entry_price: row.get("entry_price"),
+                    entry_time,
+                });
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub async fn find_duplicates(&self) -> Result<Vec<DuplicateCandidate>, SqlxError> {
+        let index = self.build_index().await?;
+        Ok(index.all_pairs(&self.config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(trade_id: u32, seconds: i64, volume: f64, price: f64) -> TradeFingerprint {
+        TradeFingerprint {
+            trade_id,
+            symbol: "XAUUSD".to_string(),
+            trade_type: "Sell".to_string(),
+            volume,
+            entry_price: price,
+            entry_time: parse_trade_time("2024-03-01T08:00:00Z").unwrap() + chrono::Duration::seconds(seconds),
+        }
+    }
+
+    #[test]
+    fn test_identical_trades_score_one() {
+        let config = DuplicateConfig::default();
+        let (score, _) = score_pair(&fingerprint(1, 0, 0.5, 2031.4), &fingerprint(2, 0, 0.5, 2031.4), &config).unwrap();
+        assert!((score - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_direction_and_time_are_hard_limits() {
+        let config = DuplicateConfig::default();
+        let mut buy = fingerprint(2, 0, 0.5, 2031.4);
+        buy.trade_type = "Buy".to_string();
+        assert!(score_pair(&fingerprint(1, 0, 0.5, 2031.4), &buy, &config).is_none());
+        assert!(score_pair(&fingerprint(1, 0, 0.5, 2031.4), &fingerprint(2, 600, 0.5, 2031.4), &config).is_none());
+    }
+
+    #[test]
+    fn test_index_finds_close_match_only() {
+        let config = DuplicateConfig::default();
+        let mut index = DuplicateIndex::default();
+        index.insert(fingerprint(1, 0, 0.5, 2031.4));
+        index.insert(fingerprint(2, 30, 0.5, 2031.5));
+        index.insert(fingerprint(3, 3600, 0.5, 2031.4));
+
+        let incoming = fingerprint(0, 25, 0.5, 2031.5);
+        assert_eq!(index.best_match(&incoming, &config).map(|m| m.trade_id), Some(2));
+
+        let pairs = index.all_pairs(&config);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].trade_id, pairs[0].duplicate_id), (1, 2));
+    }
+}
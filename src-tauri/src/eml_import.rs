@@ -0,0 +1,824 @@
+// Broker confirmation emails saved as .eml (MIME) files
+//
+// The message is decoded (multipart, quoted-printable, base64, encoded
+// headers, forwarded messages, declared charsets) into its plain-text and
+// HTML bodies; HTML is reduced to text. An email template then picks the
+// values out of a body: each field is a regex whose first capture group is
+// the value. When a field matches several times the email holds several
+// fills: the n-th match of each field belongs to the n-th fill, and fields
+// that match once apply to all.
+//
+// Templates decide whether a fill becomes an execution (linked to its trade
+// through the position id) or a trade. Fills without a ticket are identified
+// by the email's Message-ID, so importing the same message twice is a no-op.
+// Tickets are scoped to the template (and the account, when a template
+// extracts one), so two brokers' confirmations never collide.
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::database::{NewTrade, TRADE_TIME_FORMATS};
+use crate::importer::{
+    BrokerImporter, Execution, ImportContext, ImportFile, ImportRow, ImportRowError, ImportedTrade, ParsedImport, TicketScope,
+};
+use crate::mt_statement::decode_entities;
+use crate::paths;
+use crate::vault_export::content_hash;
+
+const TEMPLATES_FILE: &str = "email_templates.json";
+
+// Fields a template can extract; the first four are required
+pub const TEMPLATE_FIELDS: [&str; 15] = [
+    "symbol", "side", "volume", "price", "time", "ticket", "position", "commission", "swap",
+    "sl", "tp", "exit_price", "exit_time", "profit", "account",
+];
+const REQUIRED_FIELDS: [&str; 4] = ["symbol", "side", "volume", "price"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailOutput {
+    // One execution per fill, e.g. MT5 deal confirmations
+    #[default]
+    Execution,
+    // One trade per fill; exit fields close a trade imported while open
+    Trade,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailTemplate {
+    // Shown to the user and unique among saved templates
+    pub name: String,
+    // Case-insensitive substrings; every one that is set must match
+    #[serde(default)]
+    pub from_contains: Option<String>,
+    #[serde(default)]
+    pub subject_contains: Option<String>,
+    #[serde(default)]
+    pub body_contains: Option<String>,
+    // Field -> regex with a capture group
+    pub fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub output: EmailOutput,
+    // chrono format of the time fields; RFC 2822/3339 and the usual trade
+    // formats are tried when unset. Without a time field the Date header is used.
+    #[serde(default)]
+    pub time_format: Option<String>,
+    // Zone of times without an offset; the import's timezone when unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl EmailTemplate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Template name is required".to_string());
+        }
+        self.compile().map(|_| ())
+    }
+
+    fn compile(&self) -> Result<HashMap<&'static str, Regex>, String> {
+        for field in REQUIRED_FIELDS {
+            if !self.fields.contains_key(field) {
+                return Err(format!("Template '{}' has no pattern for {}", self.name, field));
+            }
+        }
+
+        let mut compiled = HashMap::new();
+        for (field, pattern) in &self.fields {
+            let field = TEMPLATE_FIELDS.iter()
+                .find(|known| **known == field.as_str())
+                .ok_or_else(|| format!("Unknown template field '{}'", field))?;
+            let regex = Regex::new(pattern).map_err(|e| format!("Pattern for {} is invalid: {}", field, e))?;
+            if regex.captures_len() < 2 {
+                return Err(format!("Pattern for {} needs a capture group around the value", field));
+            }
+            compiled.insert(*field, regex);
+        }
+        if let Some(zone) = &self.timezone {
+            crate::timezone::parse_timezone(zone)?;
+        }
+        Ok(compiled)
+    }
+
+    fn matches(&self, email: &Email) -> bool {
+        let contains = |haystack: &str, needle: &Option<String>| {
+            match needle {
+                Some(needle) => haystack.to_lowercase().contains(&needle.to_lowercase()),
+                None => true,
+            }
+        };
+        contains(&email.from, &self.from_contains)
+            && contains(&email.subject, &self.subject_contains)
+            && (self.body_contains.is_none() || email.bodies.iter().any(|body| contains(body, &self.body_contains)))
+    }
+}
+
+// A decoded message: headers we use and its text bodies, plain text first
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Email {
+    pub from: String,
+    pub subject: String,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub message_id: Option<String>,
+    pub bodies: Vec<String>,
+}
+
+struct Entity<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl Entity<'_> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    // "text/html; charset=utf-8" -> ("text/html", {"charset": "utf-8"})
+    fn content_type(&self) -> (String, HashMap<String, String>) {
+        let value = self.header("Content-Type").unwrap_or("text/plain");
+        let mut parts = value.split(';');
+        let mime = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let params = parts
+            .filter_map(|part| part.split_once('='))
+            .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+            .collect();
+        (mime, params)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|window| window == needle).map(|at| at + from)
+}
+
+fn split_entity(data: &[u8]) -> Entity<'_> {
+    // Headers end at the first empty line
+    let (head, body) = match (find(data, b"\r\n\r\n", 0), find(data, b"\n\n", 0)) {
+        (Some(crlf), Some(lf)) if crlf < lf => (&data[..crlf], &data[crlf + 4..]),
+        (_, Some(lf)) => (&data[..lf], &data[lf + 2..]),
+        (Some(crlf), None) => (&data[..crlf], &data[crlf + 4..]),
+        (None, None) => (data, &data[data.len()..]),
+    };
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        // Folded continuation of the previous header
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Entity { headers, body }
+}
+
+// Any charset a mail client may declare (WHATWG labels: windows-1251,
+// koi8-r, gb2312, shift_jis, ...); undeclared or unknown ones are read as
+// UTF-8, the bytes that are not replaced
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset.and_then(|label| Encoding::for_label(label.trim().as_bytes())).unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+fn decode_quoted_printable(data: &[u8], underscore_is_space: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'=' => {
+                let rest = &data[i + 1..];
+                if rest.starts_with(b"\r\n") {
+                    i += 3;
+                } else if rest.starts_with(b"\n") {
+                    i += 2;
+                } else if let Some(byte) = rest.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+                    decoded.push(byte);
+                    i += 3;
+                } else {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if underscore_is_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_transfer(entity: &Entity) -> Vec<u8> {
+    match entity.header("Content-Transfer-Encoding").map(|e| e.trim().to_ascii_lowercase()).as_deref() {
+        Some("base64") => {
+            let compact: Vec<u8> = entity.body.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+            BASE64.decode(compact).unwrap_or_else(|_| entity.body.to_vec())
+        }
+        Some("quoted-printable") => decode_quoted_printable(entity.body, false),
+        _ => entity.body.to_vec(),
+    }
+}
+
+// RFC 2047 encoded words: =?charset?B|Q?text?=
+fn decode_header(value: &str) -> String {
+    static WORD: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    let word = WORD.get_or_init(|| Regex::new(r"=\?([^?\s]+)\?([bBqQ])\?([^?\s]*)\?=").unwrap());
+
+    let mut decoded = String::new();
+    let mut last = 0;
+    for captures in word.captures_iter(value) {
+        let whole = captures.get(0).unwrap();
+        // Whitespace between two encoded words is not part of the text
+        let between = &value[last..whole.start()];
+        if last == 0 || !between.trim().is_empty() {
+            decoded.push_str(between);
+        }
+        let bytes = match &captures[2] {
+            "B" | "b" => BASE64.decode(&captures[3]).unwrap_or_default(),
+            _ => decode_quoted_printable(captures[3].as_bytes(), true),
+        };
+        decoded.push_str(&decode_charset(&bytes, Some(&captures[1])));
+        last = whole.end();
+    }
+    decoded.push_str(&value[last..]);
+    decoded
+}
+
+pub fn html_to_text(html: &str) -> String {
+    static PATTERNS: std::sync::OnceLock<(Regex, Regex, Regex, Regex)> = std::sync::OnceLock::new();
+    let (hidden, line_break, cell, tag) = PATTERNS.get_or_init(|| (
+        Regex::new(r"(?is)<(style|script|head)\b.*?</(style|script|head)\s*>").unwrap(),
+        Regex::new(r"(?i)<br\s*/?>|</(p|div|tr|li|h[1-6]|table)\s*>").unwrap(),
+        Regex::new(r"(?i)</t[dh]\s*>").unwrap(),
+        Regex::new(r"(?s)<[^>]*>").unwrap(),
+    ));
+
+    let text = hidden.replace_all(html, "");
+    let text = line_break.replace_all(&text, "\n");
+    let text = cell.replace_all(&text, " ");
+    let text = tag.replace_all(&text, "");
+    text.lines()
+        .map(decode_entities)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collect_bodies(entity: &Entity, plain: &mut Vec<String>, html: &mut Vec<String>, depth: usize) {
+    let (mime, params) = entity.content_type();
+    let attachment = entity.header("Content-Disposition").is_some_and(|d| d.to_ascii_lowercase().starts_with("attachment"));
+
+    if mime.starts_with("multipart/") && depth < 8 {
+        let Some(boundary) = params.get("boundary") else { return };
+        let delimiter = format!("--{}", boundary).into_bytes();
+        let mut at = find(entity.body, &delimiter, 0);
+        while let Some(start) = at {
+            let content = start + delimiter.len();
+            if entity.body[content..].starts_with(b"--") {
+                break;
+            }
+            let content = find(entity.body, b"\n", content).map_or(entity.body.len(), |newline| newline + 1);
+            at = find(entity.body, &delimiter, content);
+            let end = at.unwrap_or(entity.body.len());
+            // The line break before the delimiter belongs to it
+            let part = entity.body[content..end].strip_suffix(b"\n").unwrap_or(&entity.body[content..end]);
+            let part = part.strip_suffix(b"\r").unwrap_or(part);
+            collect_bodies(&split_entity(part), plain, html, depth + 1);
+        }
+    } else if mime == "message/rfc822" && depth < 8 {
+        // A forwarded confirmation
+        let decoded = decode_transfer(entity);
+        collect_bodies(&split_entity(&decoded), plain, html, depth + 1);
+    } else if (mime == "text/plain" || mime == "text/html") && !attachment {
+        let text = decode_charset(&decode_transfer(entity), params.get("charset").map(String::as_str));
+        if mime == "text/html" {
+            html.push(html_to_text(&text));
+        } else {
+            plain.push(text.replace("\r\n", "\n"));
+        }
+    }
+}
+
+pub fn parse_email(data: &[u8]) -> Email {
+    let entity = split_entity(data);
+    let mut plain = Vec::new();
+    let mut html = Vec::new();
+    collect_bodies(&entity, &mut plain, &mut html, 0);
+    plain.extend(html);
+
+    Email {
+        from: entity.header("From").map(decode_header).unwrap_or_default(),
+        subject: entity.header("Subject").map(decode_header).unwrap_or_default(),
+        date: entity.header("Date").and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok()),
+        message_id: entity.header("Message-ID").map(|id| id.trim().trim_matches(['<', '>']).to_string()).filter(|id| !id.is_empty()),
+        bodies: plain.into_iter().filter(|body| !body.trim().is_empty()).collect(),
+    }
+}
+
+// Values of one fill, by field
+type Fill = HashMap<&'static str, String>;
+
+// The fills of the first body in which every required field matches
+fn extract(regexes: &HashMap<&'static str, Regex>, email: &Email) -> Result<Vec<Fill>, String> {
+    let mut last_error = "The email has no text body".to_string();
+    for body in &email.bodies {
+        let matches: HashMap<&'static str, Vec<String>> = regexes.iter()
+            .map(|(field, regex)| {
+                let values = regex.captures_iter(body)
+                    .filter_map(|captures| captures.iter().skip(1).flatten().next().map(|m| m.as_str().trim().to_string()))
+                    .collect();
+                (*field, values)
+            })
+            .collect();
+
+        if let Some(missing) = REQUIRED_FIELDS.iter().find(|field| matches[**field].is_empty()) {
+            last_error = format!("No {} found in the email", missing);
+            continue;
+        }
+
+        let count = matches.values().map(Vec::len).max().unwrap_or_default();
+        if let Some((field, values)) = matches.iter().find(|(_, values)| values.len() > 1 && values.len() != count) {
+            return Err(format!("{} matched {} times but the email has {} fills", field, values.len(), count));
+        }
+        return Ok((0..count)
+            .map(|index| {
+                matches.iter()
+                    .filter_map(|(field, values)| {
+                        let value = if values.len() == 1 { values.first() } else { values.get(index) };
+                        value.map(|value| (*field, value.clone()))
+                    })
+                    .collect()
+            })
+            .collect());
+    }
+    Err(last_error)
+}
+
+// "1,234.50", "1 234,50" and "0.5 lots" all read as numbers. With both
+// separators the last one is the decimal mark; a lone comma is one too.
+fn number(value: &str) -> Option<f64> {
+    let cleaned: String = value.chars().filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-')).collect();
+    let cleaned = match (cleaned.rfind('.'), cleaned.rfind(',')) {
+        (Some(dot), Some(comma)) if comma > dot => cleaned.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => cleaned.replace(',', ""),
+        (None, Some(_)) if cleaned.matches(',').count() == 1 => cleaned.replace(',', "."),
+        _ => cleaned.replace(',', ""),
+    };
+    cleaned.parse().ok()
+}
+
+fn side(value: &str) -> Option<&'static str> {
+    let lower = value.to_lowercase();
+    if ["buy", "bought", "long"].iter().any(|word| lower.starts_with(word)) {
+        Some("Buy")
+    } else if ["sell", "sold", "short"].iter().any(|word| lower.starts_with(word)) {
+        Some("Sell")
+    } else {
+        None
+    }
+}
+
+// A stored time: explicit offsets are kept as RFC 3339, naive values are left
+// for the import to read in the source timezone
+fn time(value: &str, format: Option<&str>) -> Option<String> {
+    if let Some(format) = format {
+        return NaiveDateTime::parse_from_str(value, format).ok().map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+    DateTime::parse_from_rfc2822(value).or_else(|_| DateTime::parse_from_rfc3339(value)).ok()
+        .map(|t| t.to_rfc3339())
+        .or_else(|| {
+            TRADE_TIME_FORMATS.iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        })
+}
+
+// The fills of one email as executions or trades
+pub fn parse_message(
+    data: &[u8],
+    templates: &[EmailTemplate],
+    context: &ImportContext,
+) -> Result<(Email, ParsedImport), String> {
+    let email = parse_email(data);
+    let template = templates.iter()
+        .find(|template| template.matches(&email))
+        .ok_or_else(|| format!("No email template matches the message from '{}' with subject '{}'", email.from, email.subject))?;
+    let regexes = template.compile()?;
+    let fills = extract(&regexes, &email)?;
+
+    // Stable identity for fills without a ticket
+    let message_key = email.message_id.clone().unwrap_or_else(|| content_hash(data));
+    let timezone = template.timezone.clone().or_else(|| context.timezone.clone());
+    let mut parsed = ParsedImport { total_rows: fills.len(), ..Default::default() };
+
+    for (index, fill) in fills.iter().enumerate() {
+        let row = index + 1;
+        let error = |field: &str, message: String| ImportRowError { row, field: Some(field.to_string()), message };
+        let text = |field: &str| fill.get(field).map(String::as_str).filter(|value| !value.is_empty());
+        let amount = |field: &str| -> Result<Option<f64>, ImportRowError> {
+            text(field)
+                .map(|value| number(value).ok_or_else(|| error(field, format!("'{}' is not a number", value))))
+                .transpose()
+        };
+        let when = |field: &str| -> Result<Option<String>, ImportRowError> {
+            text(field)
+                .map(|value| time(value, template.time_format.as_deref()).ok_or_else(|| error(field, format!("Unreadable time '{}'", value))))
+                .transpose()
+        };
+
+        let fill_result = (|| {
+            let symbol = context.map_symbol(text("symbol").unwrap_or_default());
+            let side = side(text("side").unwrap_or_default())
+                .ok_or_else(|| error("side", format!("Unknown side '{}'", text("side").unwrap_or_default())))?;
+            let volume = amount("volume")?.map(f64::abs).filter(|v| *v > 0.0).ok_or_else(|| error("volume", "Missing volume".to_string()))?;
+            let price = amount("price")?.ok_or_else(|| error("price", "Missing price".to_string()))?;
+            let executed_at = match when("time")? {
+                Some(time) => time,
+                None => email.date.map(|date| date.to_rfc3339()).ok_or_else(|| error("time", "No time in the email".to_string()))?,
+            };
+            let id = text("ticket").map(str::to_string).unwrap_or_else(|| format!("{}#{}", message_key, row));
+            Ok::<_, ImportRowError>((symbol, side, volume, price, executed_at, id))
+        })();
+        let scope = TicketScope::new(&format!("email:{}", template.name), text("account").map(str::to_string));
+        let (symbol, side, volume, price, executed_at, id) = match fill_result {
+            Ok(values) => values,
+            Err(e) => {
+                parsed.errors.push(e);
+                continue;
+            }
+        };
+
+        match template.output {
+            EmailOutput::Execution => {
+                let commission = match amount("commission") {
+                    Ok(commission) => commission,
+                    Err(e) => {
+                        parsed.errors.push(e);
+                        continue;
+                    }
+                };
+                parsed.executions.push((row, Execution {
+                    external_id: Some(id),
+                    position_id: text("position").map(str::to_string),
+                    symbol,
+                    side: side.to_string(),
+                    volume,
+                    price,
+                    executed_at,
+                    commission,
+                    scope,
+                    source_timezone: timezone.clone(),
+                }));
+            }
+            EmailOutput::Trade => {
+                let extra = (|| Ok::<_, ImportRowError>((
+                    amount("sl")?, amount("tp")?, amount("commission")?, amount("swap")?,
+                    amount("exit_price")?, when("exit_time")?, amount("profit")?,
+                )))();
+                let (sl, tp, commission, swap, exit_price, exit_time, profit) = match extra {
+                    Ok(values) => values,
+                    Err(e) => {
+                        parsed.errors.push(e);
+                        continue;
+                    }
+                };
+                let trade = NewTrade {
+                    symbol,
+                    trade_type: side.to_string(),
+                    volume,
+                    entry_price: price,
+                    sl,
+                    tp,
+                    entry_time: executed_at,
+                    notes: None,
+                    commission,
+                    swap,
+                    ict_pattern: None,
+                    pattern_type: None,
+                    pattern_size: None,
+                    pattern_timeframe: None,
+                    pattern_combination: None,
+                    chart_explanation: None,
+                    strategy_name: None,
+                    emotion: None,
+                    confidence_level: None,
+                    market_condition: None,
+                    session: None,
+                    entry_image: None,
+                    exit_image: None,
+                    analysis_image: None,
+                    rsi: None,
+                    macd: None,
+                    moving_average: None,
+                    support_level: None,
+                    resistance_level: None,
+                    source_timezone: timezone.clone(),
+                };
+                let mut record = ImportedTrade::new(trade);
+                record.exit_price = exit_price;
+                record.exit_time = exit_time.or_else(|| exit_price.and(email.date.map(|date| date.to_rfc3339())));
+                record.profit = profit;
+                // The position ticket identifies the trade across its open and close emails
+                record.external_id = Some(text("position").map(str::to_string).unwrap_or(id));
+                record.scope = scope;
+                parsed.rows.push(ImportRow { row, record });
+            }
+        }
+    }
+
+    Ok((email, parsed))
+}
+
+// Many emails imported as one batch. Rows are numbered by file and messages
+// name the file they came from.
+pub fn parse_batch(files: &[ImportFile], templates: &[EmailTemplate], context: &ImportContext) -> ParsedImport {
+    let mut batch = ParsedImport::default();
+    for (index, file) in files.iter().enumerate() {
+        let row = index + 1;
+        match parse_message(&file.data, templates, context) {
+            Ok((_, parsed)) => {
+                batch.total_rows += parsed.total_rows;
+                batch.rows.extend(parsed.rows.into_iter().map(|r| ImportRow { row, ..r }));
+                batch.executions.extend(parsed.executions.into_iter().map(|(_, execution)| (row, execution)));
+                batch.errors.extend(parsed.errors.into_iter().map(|e| ImportRowError { row, message: format!("{}: {}", file.name, e.message), ..e }));
+                batch.warnings.extend(parsed.warnings.into_iter().map(|w| format!("{}: {}", file.name, w)));
+            }
+            Err(message) => {
+                batch.total_rows += 1;
+                batch.errors.push(ImportRowError { row, field: None, message: format!("{}: {}", file.name, message) });
+            }
+        }
+    }
+    batch
+}
+
+// What a template makes of one email, for writing templates
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailPreview {
+    pub email: Email,
+    pub template: Option<String>,
+    pub executions: Vec<Execution>,
+    pub trades: Vec<ImportedTrade>,
+    pub errors: Vec<ImportRowError>,
+    // Why nothing could be read at all
+    pub error: Option<String>,
+}
+
+pub fn preview(data: &[u8], templates: &[EmailTemplate], context: &ImportContext) -> EmailPreview {
+    let email = parse_email(data);
+    let template = templates.iter().find(|template| template.matches(&email)).map(|template| template.name.clone());
+    let mut preview = EmailPreview { email, template, executions: Vec::new(), trades: Vec::new(), errors: Vec::new(), error: None };
+
+    match parse_message(data, templates, context) {
+        Ok((_, parsed)) => {
+            preview.executions = parsed.executions.into_iter().map(|(_, execution)| execution).collect();
+            preview.trades = parsed.rows.into_iter().map(|row| row.record).collect();
+            preview.errors = parsed.errors;
+        }
+        Err(message) => preview.error = Some(message),
+    }
+    preview
+}
+
+// The registry's entry for single .eml files; templates are the saved ones
+pub struct EmailImporter {
+    templates: Vec<EmailTemplate>,
+}
+
+impl EmailImporter {
+    pub fn new(templates: Vec<EmailTemplate>) -> Self {
+        Self { templates }
+    }
+}
+
+impl BrokerImporter for EmailImporter {
+    fn id(&self) -> &str {
+        "email"
+    }
+
+    fn name(&self) -> &str {
+        "Broker confirmation email (.eml)"
+    }
+
+    fn detect(&self, file: &ImportFile) -> u8 {
+        if file.extension() == "eml" {
+            return 100;
+        }
+        let head: String = file.text().chars().take(4096).collect::<String>().to_ascii_lowercase();
+        if (head.contains("mime-version:") && head.contains("\nfrom:")) || (head.starts_with("from:") && head.contains("\nsubject:")) {
+            60
+        } else {
+            0
+        }
+    }
+
+    fn parse(&self, file: &ImportFile, context: &ImportContext) -> Result<ParsedImport, String> {
+        if self.templates.is_empty() {
+            return Err("No email templates saved; add one for your broker first".to_string());
+        }
+        parse_message(&file.data, &self.templates, context).map(|(_, parsed)| parsed)
+    }
+}
+
+// Saved templates are app-level like import profiles
+fn templates_path() -> PathBuf {
+    paths::config_dir().join(TEMPLATES_FILE)
+}
+
+pub async fn load_templates() -> Result<Vec<EmailTemplate>, Box<dyn std::error::Error>> {
+    match tokio::fs::read(templates_path()).await {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn write_templates(templates: &[EmailTemplate]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = templates_path();
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, serde_json::to_vec_pretty(templates)?).await?;
+    Ok(())
+}
+
+// Insert or replace by name
+pub async fn save_template(template: EmailTemplate) -> Result<(), Box<dyn std::error::Error>> {
+    template.validate()?;
+
+    let mut templates = load_templates().await?;
+    match templates.iter_mut().find(|t| t.name == template.name) {
+        Some(existing) => *existing = template,
+        None => templates.push(template),
+    }
+
+    write_templates(&templates).await
+}
+
+pub async fn delete_template(name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut templates = load_templates().await?;
+    let before = templates.len();
+    templates.retain(|t| t.name != name);
+
+    if templates.len() == before {
+        return Ok(false);
+    }
+    write_templates(&templates).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTIPART: &str = "From: =?UTF-8?Q?Acme_Br=C3=B6ker?= <confirm@acme.example>\r\n\
+Subject: =?utf-8?B?RXhlY3V0aW9u?= =?utf-8?B?IGNvbmZpcm1hdGlvbg==?=\r\n\
+Date: Mon, 4 Mar 2024 09:00:12 +0100\r\n\
+Message-ID: <abc123@acme.example>\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/alternative;\r\n\
+\tboundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<html><head><style>td{color:red}</style></head><body><table>\r\n\
+<tr><th>Deal</th><th>Symbol</th><th>Side</th><th>Volume</th><th>Price</th><th>Position</th></tr>\r\n\
+<tr><td>9001</td><td>XAUUSD</td><td>Sell</td><td>0.50</td><td>2 031.40</td><td>77</td></tr>\r\n\
+<tr><td>9002</td><td>XAUUSD</td><td>Buy</td><td>0.50</td><td>2 018.00</td><td>77</td></tr>\r\n\
+</table><p>Commission&nbsp;=E2=82=AC -3.50</p></body></html>\r\n\
+--b1--\r\n";
+
+    fn acme() -> EmailTemplate {
+        let fields = [
+            ("ticket", r"(?m)^(\d+) \w+ (?:Buy|Sell)"),
+            ("symbol", r"(?m)^\d+ (\w+) (?:Buy|Sell)"),
+            ("side", r"(?m)^\d+ \w+ (Buy|Sell)"),
+            ("volume", r"(?m)^\d+ \w+ (?:Buy|Sell) ([\d.]+)"),
+            ("price", r"(?m)^\d+ \w+ (?:Buy|Sell) [\d.]+ ([\d .]+?) \d+$"),
+            ("position", r"(?m) (\d+)$"),
+            ("commission", r"Commission € (-?[\d.]+)"),
+        ];
+        EmailTemplate {
+            name: "Acme".to_string(),
+            from_contains: Some("ACME.example".to_string()),
+            subject_contains: Some("confirmation".to_string()),
+            body_contains: None,
+            fields: fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            output: EmailOutput::Execution,
+            time_format: None,
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn decodes_mime_headers_and_html_bodies() {
+        let email = parse_email(MULTIPART.as_bytes());
+        assert_eq!(email.from, "Acme Bröker <confirm@acme.example>");
+        assert_eq!(email.subject, "Execution confirmation");
+        assert_eq!(email.message_id.as_deref(), Some("abc123@acme.example"));
+        assert_eq!(email.date.unwrap().to_rfc3339(), "2024-03-04T09:00:12+01:00");
+        assert_eq!(email.bodies, ["Deal Symbol Side Volume Price Position\n9001 XAUUSD Sell 0.50 2 031.40 77\n9002 XAUUSD Buy 0.50 2 018.00 77\nCommission € -3.50"]);
+
+    }
+
+    #[test]
+    fn bodies_and_headers_are_decoded_by_their_declared_charset() {
+        let latin = b"From: a@b.example\nSubject: Fill\nContent-Type: text/plain; charset=iso-8859-1\nContent-Transfer-Encoding: base64\n\nU29sZCAxIGxvdCDgIDEwMA==\n";
+        assert_eq!(parse_email(latin).bodies, ["Sold 1 lot \u{e0} 100"]);
+
+        let cyrillic = "From: confirm@broker.example\n\
+Subject: =?koi8-r?B?6dPQz8zOxc7JxQ==?= 9001\n\
+Content-Type: multipart/mixed; boundary=x\n\
+\n\
+--x\n\
+Content-Type: text/plain; charset=\"windows-1251\"\n\
+Content-Transfer-Encoding: quoted-printable\n\
+\n\
+=CF=F0=EE=E4=E0=ED=EE 1 =EB=EE=F2 =EF=EE 100\n\
+--x\n\
+Content-Type: text/plain; charset=GB2312\n\
+Content-Transfer-Encoding: base64\n\
+\n\
+wvSz9iAxIMrWILzbuPEgMjAzMS40\n\
+--x\n\
+Content-Type: text/plain; charset=x-unknown\n\
+\n\
+Caf\u{e9} 2 lots\n\
+--x--\n";
+        let email = parse_email(cyrillic.as_bytes());
+        assert_eq!(email.subject, "Исполнение 9001");
+        assert_eq!(email.bodies, ["Продано 1 лот по 100", "卖出 1 手 价格 2031.4", "Café 2 lots"]);
+
+        // Templates match the decoded text
+        let mut template = acme();
+        template.from_contains = None;
+        template.subject_contains = Some("исполнение".to_string());
+        assert!(template.matches(&email));
+        template.body_contains = Some("卖出".to_string());
+        assert!(template.matches(&email));
+        template.body_contains = Some("Sold".to_string());
+        assert!(!template.matches(&email));
+    }
+
+    #[test]
+    fn template_fields_become_one_execution_per_fill() {
+        let context = ImportContext { symbol_mapping: [("XAUUSD".to_string(), "GOLD".to_string())].into(), timezone: None };
+        let (_, parsed) = parse_message(MULTIPART.as_bytes(), &[acme()], &context).unwrap();
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+
+        let fills: Vec<_> = parsed.executions.iter()
+            .map(|(row, e)| (*row, e.external_id.as_deref().unwrap(), e.position_id.as_deref().unwrap(), e.symbol.as_str(), e.side.as_str(), e.price, e.commission))
+            .collect();
+        assert_eq!(fills, [
+            (1, "9001", "77", "GOLD", "Sell", 2031.4, Some(-3.5)),
+            (2, "9002", "77", "GOLD", "Buy", 2018.0, Some(-3.5)),
+        ]);
+        // No time field: the Date header, offset kept
+        assert_eq!(parsed.executions[0].1.executed_at, "2024-03-04T09:00:12+01:00");
+
+        let mut other = acme();
+        other.from_contains = Some("other.example".to_string());
+        let error = parse_message(MULTIPART.as_bytes(), &[other], &context).unwrap_err();
+        assert_eq!(error, "No email template matches the message from 'Acme Bröker <confirm@acme.example>' with subject 'Execution confirmation'");
+    }
+
+    #[test]
+    fn trade_templates_identify_fills_by_message_without_a_ticket() {
+        let mut template = acme();
+        template.fields.remove("ticket");
+        template.fields.remove("position");
+        template.fields.insert("sl".to_string(), r"SL (\S+)".to_string());
+        template.output = EmailOutput::Trade;
+        let single = MULTIPART.replace("<tr><td>9002</td><td>XAUUSD</td><td>Buy</td><td>0.50</td><td>2 018.00</td><td>77</td></tr>\r\n", "");
+
+        let files = [
+            ImportFile::new("a.eml", single.clone().into_bytes()),
+            ImportFile::new("copy of a.eml", single.into_bytes()),
+            ImportFile::new("bad.eml", MULTIPART.replace("XAUUSD", "").into_bytes()),
+        ];
+        let batch = parse_batch(&files, &[template], &ImportContext::default());
+
+        let ids: Vec<_> = batch.rows.iter().map(|r| (r.row, r.record.external_id.as_deref().unwrap())).collect();
+        assert_eq!(ids, [(1, "abc123@acme.example#1"), (2, "abc123@acme.example#1")]);
+        assert_eq!(batch.rows[0].record.trade.entry_price, 2031.4);
+        assert_eq!(batch.errors.len(), 1);
+        assert_eq!((batch.errors[0].row, batch.errors[0].message.as_str()), (3, "bad.eml: No symbol found in the email"));
+
+        assert_eq!(acme().validate(), Ok(()));
+        let mut broken = acme();
+        broken.fields.insert("price".to_string(), r"Price \d+".to_string());
+        assert_eq!(broken.validate(), Err("Pattern for price needs a capture group around the value".to_string()));
+    }
+}
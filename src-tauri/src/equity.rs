@@ -0,0 +1,228 @@
+// Equity curve and underwater series of closed trades
+//
+// Trades are taken in the order they were closed (exit time, entry time for
+// trades without one, then id). The running peak starts at the starting
+// balance, so losses before the first new high count as drawdown. Max
+// drawdown, recovery factor and Calmar ratio everywhere in the analysis are
+// read from this curve.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::TradeProjection;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EquityPoint {
+    pub trade_id: u32,
+    pub closed_at: DateTime<Utc>,
+    pub profit: f64,
+    pub cumulative_profit: f64,
+    pub balance: f64,
+    // Highest balance so far, the starting balance included
+    pub peak: f64,
+    // Below the peak, as an amount and as a percentage of the peak (0 while
+    // the peak is not positive)
+    pub drawdown: f64,
+    pub drawdown_percent: f64,
+    // Time since the peak was set; 0 at a new high
+    pub drawdown_minutes: i64,
+}
+
+// One stretch below a peak, from the trade that set the peak to the one that
+// got back to it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DrawdownPeriod {
+    pub started_at: DateTime<Utc>,
+    pub trough_at: DateTime<Utc>,
+    // None while the curve is still under water
+    pub recovered_at: Option<DateTime<Utc>>,
+    pub depth: f64,
+    pub depth_percent: f64,
+    // Until recovery, or until the last trade if there was none
+    pub duration_minutes: i64,
+    pub trades: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct EquityCurve {
+    pub starting_balance: f64,
+    pub points: Vec<EquityPoint>,
+    pub drawdowns: Vec<DrawdownPeriod>,
+    pub net_profit: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_percent: f64,
+    pub longest_drawdown_minutes: i64,
+    // Net profit over max drawdown
+    pub recovery_factor: f64,
+    // Annualized net profit (not compounded) over max drawdown; 0 until the
+    // trades span a month, shorter histories annualize to noise
+    pub calmar_ratio: f64,
+}
+
+const DAYS_PER_YEAR: f64 = 365.25;
+const CALMAR_MIN_DAYS: f64 = 30.0;
+
+fn percent(drawdown: f64, peak: f64) -> f64 {
+    if peak > 0.0 { drawdown / peak * 100.0 } else { 0.0 }
+}
+
+// Closed trades only; open trades are skipped
+pub fn build<'a>(trades: impl IntoIterator<Item = &'a TradeProjection>, starting_balance: f64) -> EquityCurve {
+    let mut closed: Vec<(DateTime<Utc>, DateTime<Utc>, u32, f64)> = trades.into_iter()
+        .filter(|t| t.is_win.is_some())
+        .filter_map(|t| {
+            let closed_at = t.exit_time.or(t.entry_time)?;
+            Some((closed_at, t.entry_time.unwrap_or(closed_at), t.id, t.profit_loss_money.unwrap_or(0.0)))
+        })
+        .collect();
+    closed.sort_by_key(|(closed_at, _, id, _)| (*closed_at, *id));
+
+    let mut curve = EquityCurve { starting_balance, ..Default::default() };
+    let Some((first_close, first_entry, ..)) = closed.first().copied() else { return curve };
+    // Before the first trade the starting balance is the peak, set when that trade opened
+    let opened_at = first_entry.min(first_close);
+
+    let mut balance = starting_balance;
+    let mut peak = starting_balance;
+    let mut peak_at = opened_at;
+    let mut current: Option<DrawdownPeriod> = None;
+
+    for (closed_at, _, id, profit) in &closed {
+        balance += profit;
+        if balance >= peak {
+            peak = balance;
+            peak_at = *closed_at;
+            if let Some(mut period) = current.take() {
+                period.recovered_at = Some(*closed_at);
+                period.duration_minutes = (*closed_at - period.started_at).num_minutes();
+                period.trades += 1;
+                curve.drawdowns.push(period);
+            }
+        } else {
+            let drawdown = peak - balance;
+            let period = current.get_or_insert(DrawdownPeriod {
+                started_at: peak_at,
+                trough_at: *closed_at,
+                recovered_at: None,
+                depth: 0.0,
+                depth_percent: 0.0,
+                duration_minutes: 0,
+                trades: 0,
+            });
+            period.trades += 1;
+            if drawdown > period.depth {
+                period.depth = drawdown;
+                period.depth_percent = percent(drawdown, peak);
+                period.trough_at = *closed_at;
+            }
+        }
+
+        let drawdown = peak - balance;
+        curve.points.push(EquityPoint {
+            trade_id: *id,
+            closed_at: *closed_at,
+            profit: *profit,
+            cumulative_profit: balance - starting_balance,
+            balance,
+            peak,
+            drawdown,
+            drawdown_percent: percent(drawdown, peak),
+            drawdown_minutes: (*closed_at - peak_at).num_minutes(),
+        });
+    }
+
+    let last_at = closed[closed.len() - 1].0;
+    if let Some(mut period) = current {
+        period.duration_minutes = (last_at - period.started_at).num_minutes();
+        curve.drawdowns.push(period);
+    }
+
+    curve.net_profit = balance - starting_balance;
+    curve.max_drawdown = curve.drawdowns.iter().map(|p| p.depth).fold(0.0, f64::max);
+    curve.max_drawdown_percent = curve.drawdowns.iter().map(|p| p.depth_percent).fold(0.0, f64::max);
+    curve.longest_drawdown_minutes = curve.drawdowns.iter().map(|p| p.duration_minutes).max().unwrap_or(0);
+    if curve.max_drawdown > 0.0 {
+        curve.recovery_factor = curve.net_profit / curve.max_drawdown;
+        let years = (last_at - opened_at).num_minutes() as f64 / (DAYS_PER_YEAR * 24.0 * 60.0);
+        if years * DAYS_PER_YEAR >= CALMAR_MIN_DAYS {
+            curve.calmar_ratio = curve.net_profit / years / curve.max_drawdown;
+        }
+    }
+    curve
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(id: u32, closed_at: &str, profit: f64) -> TradeProjection {
+        let closed_at = DateTime::parse_from_rfc3339(closed_at).unwrap().with_timezone(&Utc);
+        TradeProjection {
+            id,
+            entry_time: Some(closed_at - chrono::Duration::hours(1)),
+            exit_time: Some(closed_at),
+            is_win: Some(profit > 0.0),
+            profit_loss_money: Some(profit),
+            strategy_name: None,
+        }
+    }
+
+    #[test]
+    fn losses_before_the_first_high_are_drawdown() {
+        // The old calculation seeded the peak with the first return and
+        // reported no drawdown here
+        let trades = [trade(1, "2024-01-02T10:00:00Z", -100.0), trade(2, "2024-01-03T10:00:00Z", -50.0)];
+        let curve = build(&trades, 1000.0);
+
+        assert_eq!(curve.max_drawdown, 150.0);
+        assert_eq!(curve.max_drawdown_percent, 15.0);
+        let last = curve.points.last().unwrap();
+        assert_eq!((last.balance, last.peak, last.cumulative_profit), (850.0, 1000.0, -150.0));
+        assert_eq!(last.drawdown_minutes, 25 * 60);
+        assert_eq!(curve.drawdowns[0].recovered_at, None);
+        assert_eq!(curve.recovery_factor, -1.0);
+    }
+
+    #[test]
+    fn trades_are_ordered_by_close_time() {
+        // Listed by entry; the loss closes before the win
+        let mut late_exit = trade(1, "2024-01-05T10:00:00Z", 300.0);
+        late_exit.entry_time = Some(DateTime::parse_from_rfc3339("2024-01-01T09:00:00Z").unwrap().with_timezone(&Utc));
+        let trades = [late_exit, trade(2, "2024-01-02T10:00:00Z", -200.0), trade(3, "2024-01-03T10:00:00Z", 100.0)];
+        let curve = build(&trades, 0.0);
+
+        let order: Vec<(u32, f64, f64)> = curve.points.iter().map(|p| (p.trade_id, p.balance, p.drawdown)).collect();
+        assert_eq!(order, [(2, -200.0, 200.0), (3, -100.0, 100.0), (1, 200.0, 0.0)]);
+        assert_eq!(curve.max_drawdown, 200.0);
+        // No positive peak, so no percentage
+        assert_eq!(curve.max_drawdown_percent, 0.0);
+    }
+
+    #[test]
+    fn drawdown_periods_run_from_peak_to_recovery() {
+        let trades = [
+            trade(1, "2024-01-01T10:00:00Z", 500.0),
+            trade(2, "2024-01-02T10:00:00Z", -300.0),
+            trade(3, "2024-01-03T10:00:00Z", -100.0),
+            trade(4, "2024-01-05T10:00:00Z", 450.0),
+            trade(5, "2024-01-06T10:00:00Z", -50.0),
+            trade(6, "2024-12-31T10:00:00Z", 100.0),
+        ];
+        let curve = build(trades.iter().rev(), 10_000.0);
+
+        assert_eq!(curve.drawdowns.len(), 2);
+        let first = &curve.drawdowns[0];
+        assert_eq!(first.started_at.to_rfc3339(), "2024-01-01T10:00:00+00:00");
+        assert_eq!(first.trough_at.to_rfc3339(), "2024-01-03T10:00:00+00:00");
+        assert_eq!(first.recovered_at.unwrap().to_rfc3339(), "2024-01-05T10:00:00+00:00");
+        assert_eq!((first.depth, first.duration_minutes, first.trades), (400.0, 4 * 24 * 60, 3));
+        assert!((first.depth_percent - 400.0 / 10_500.0 * 100.0).abs() < 1e-9);
+
+        assert_eq!(curve.max_drawdown, 400.0);
+        assert_eq!(curve.longest_drawdown_minutes, 361 * 24 * 60);
+        assert_eq!(curve.net_profit, 600.0);
+        assert_eq!(curve.recovery_factor, 1.5);
+        // A year from the first entry (09:00) to the last close
+        let years = (365.0 * 24.0 * 60.0 + 60.0) / (DAYS_PER_YEAR * 24.0 * 60.0);
+        assert!((curve.calmar_ratio - 600.0 / years / 400.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,97 @@
+use image::{imageops::FilterType, DynamicImage};
+
+// Perceptual hashing for chart screenshots.
+//
+// A dHash compares the brightness of horizontally adjacent pixels on a
+// 9x8 grayscale thumbnail, which keeps the overall shape of a chart
+// (candles, swings, drawn zones) while ignoring resolution, compression
+// and small colour differences between platforms.
+
+pub const HASH_BITS: u32 = 64;
+
+// Number of 16-bit bands the hash is split into for indexed lookup.
+// Two hashes within distance `HASH_BANDS - 1` always share at least one
+// identical band (pigeonhole), so band equality is a cheap pre-filter.
+pub const HASH_BANDS: usize = 4;
+
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash <<= 1;
+            if left > right {
+                hash |= 1;
+            }
+        }
+    }
+
+    hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// 1.0 for identical hashes, 0.0 for completely inverted ones
+pub fn similarity(distance: u32) -> f64 {
+    1.0 - distance as f64 / HASH_BITS as f64
+}
+
+pub fn hash_bands(hash: u64) -> [i64; HASH_BANDS] {
+    let mut bands = [0i64; HASH_BANDS];
+    for (i, band) in bands.iter_mut().enumerate() {
+        *band = ((hash >> (i * 16)) & 0xFFFF) as i64;
+    }
+    bands
+}
+
+// SQLite stores integers as signed 64-bit values
+pub fn to_sql(hash: u64) -> i64 {
+    hash as i64
+}
+
+pub fn from_sql(value: i64) -> u64 {
+    value as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn gradient(width: u32, height: u32, reversed: bool) -> DynamicImage {
+        let image = GrayImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            Luma([if reversed { 255 - value } else { value }])
+        });
+        DynamicImage::ImageLuma8(image)
+    }
+
+    #[test]
+    fn test_dhash_is_resolution_independent() {
+        let small = dhash(&gradient(90, 80, false));
+        let large = dhash(&gradient(1920, 1080, false));
+        assert!(hamming_distance(small, large) <= 2);
+    }
+
+    #[test]
+    fn test_dhash_separates_opposite_images() {
+        let light_to_dark = dhash(&gradient(200, 100, true));
+        let dark_to_light = dhash(&gradient(200, 100, false));
+        assert!(hamming_distance(light_to_dark, dark_to_light) > 32);
+    }
+
+    #[test]
+    fn test_hash_bands_round_trip() {
+        let hash = 0xDEAD_BEEF_0123_4567u64;
+        let bands = hash_bands(hash);
+        let rebuilt = bands.iter().enumerate()
+            .fold(0u64, |acc, (i, band)| acc | ((*band as u64) << (i * 16)));
+        assert_eq!(rebuilt, hash);
+        assert_eq!(from_sql(to_sql(hash)), hash);
+    }
+}
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::broker_formats;
+use crate::database::NewTrade;
+use crate::duplicates::DuplicateConfig;
+
+// Where a ticket comes from. Brokers number their tickets independently, so an
+// external id only identifies a row together with its broker and account.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct TicketScope {
+    // Importer id or platform, e.g. "metatrader", "ibkr_flex"
+    pub broker: Option<String>,
+    // Account number when the file states it
+    pub account: Option<String>,
+}
+
+impl TicketScope {
+    pub fn new(broker: &str, account: Option<String>) -> Self {
+        Self { broker: Some(broker.to_string()), account }
+    }
+}
+
+// A trade produced by any importer, before it is written. Exit data and the
+// broker's own id live outside NewTrade because manual entry never sets them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportedTrade {
+    pub trade: NewTrade,
+    pub exit_price: Option<f64>,
+    pub exit_time: Option<String>,
+    // Realized P/L as reported by the broker; wins are derived from it
+    pub profit: Option<f64>,
+    // Ticket / order id used to skip trades that were already imported
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub scope: TicketScope,
+    // Values of custom schema fields, converted to the field's type when
+    // they are written
+    #[serde(default)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
+}
+
+impl ImportedTrade {
+    pub fn new(trade: NewTrade) -> Self {
+        Self {
+            trade,
+            exit_price: None,
+            exit_time: None,
+            profit: None,
+            external_id: None,
+            scope: TicketScope::default(),
+            custom_fields: HashMap::new(),
+        }
+    }
+}
+
+// Deposits, withdrawals, credit and other balance operations
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CashFlow {
+    pub kind: String,
+    pub amount: f64,
+    pub occurred_at: String,
+    pub comment: Option<String>,
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub scope: TicketScope,
+    pub source_timezone: Option<String>,
+}
+
+// A single fill. Brokers that report fills rather than round trips (IBKR)
+// also group them into trades; position_id is that trade's ticket.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Execution {
+    pub external_id: Option<String>,
+    pub position_id: Option<String>,
+    pub symbol: String,
+    // "Buy" or "Sell"
+    pub side: String,
+    pub volume: f64,
+    pub price: f64,
+    pub executed_at: String,
+    pub commission: Option<f64>,
+    #[serde(default)]
+    pub scope: TicketScope,
+    pub source_timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    // Leave the existing trade alone and report the row
+    #[default]
+    Skip,
+    // Import anyway; the pair can be merged later
+    Import,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ImportOptions {
+    pub on_duplicate: DuplicatePolicy,
+    pub duplicates: DuplicateConfig,
+    // Commit the good rows even when some rows failed
+    pub allow_partial: bool,
+    // Run the whole import, report, then roll back
+    pub dry_run: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            on_duplicate: DuplicatePolicy::Skip,
+            duplicates: DuplicateConfig::default(),
+            allow_partial: false,
+            dry_run: false,
+        }
+    }
+}
+
+// Closing fills of a position opened in an earlier file. Brokers that report
+// fills (IBKR) cannot tie them to a ticket, so the import closes the open
+// trade of the same instrument and broker account with them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionClose {
+    pub symbol: String,
+    // Side of the trade being closed
+    pub trade_type: String,
+    pub fills: Vec<(usize, Execution)>,
+    // Realized P/L of these fills as the broker reports it, net of the
+    // trade's commissions; it is stored gross like ImportedTrade::profit
+    pub profit: Option<f64>,
+}
+
+// Rows are numbered as the user sees them in the source file (1-based)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportDuplicate {
+    pub row: usize,
+    pub trade_id: u32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ImportReport {
+    pub total_rows: usize,
+    pub imported: usize,
+    // Previously imported open positions that the file now shows closed
+    pub updated: usize,
+    pub trade_ids: Vec<u32>,
+    pub duplicates: Vec<ImportDuplicate>,
+    pub errors: Vec<ImportRowError>,
+    pub cash_flows_imported: usize,
+    pub executions_imported: usize,
+    // Things the importer could not map but did not treat as row errors
+    pub warnings: Vec<String>,
+    // Id of the importer that read the file
+    pub format: Option<String>,
+    pub committed: bool,
+    pub dry_run: bool,
+}
+
+// A parsed row tagged with its position in the source file
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    pub row: usize,
+    pub record: ImportedTrade,
+}
+
+// Output of an importer: rows ready to write plus rows it could not parse
+#[derive(Debug, Clone, Default)]
+pub struct ParsedImport {
+    pub total_rows: usize,
+    pub rows: Vec<ImportRow>,
+    pub errors: Vec<ImportRowError>,
+    // Balance operations with their source row
+    pub cash_flows: Vec<(usize, CashFlow)>,
+    pub executions: Vec<(usize, Execution)>,
+    pub closes: Vec<PositionClose>,
+    pub warnings: Vec<String>,
+}
+
+impl ParsedImport {
+    // Tag every record with the file's broker and account; records that
+    // already carry a scope keep it
+    pub fn set_scope(&mut self, scope: &TicketScope) {
+        let rows = self.rows.iter_mut().map(|row| &mut row.record.scope);
+        let flows = self.cash_flows.iter_mut().map(|(_, flow)| &mut flow.scope);
+        let executions = self.executions.iter_mut()
+            .chain(self.closes.iter_mut().flat_map(|close| close.fills.iter_mut()))
+            .map(|(_, execution)| &mut execution.scope);
+        for slot in rows.chain(flows).chain(executions) {
+            if slot.broker.is_none() {
+                *slot = scope.clone();
+            }
+        }
+    }
+}
+
+// MT5 saves reports as UTF-16LE; MT4 uses the system code page, which reads
+// fine as lossy UTF-8 for the numeric and ASCII content we need
+pub fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], little_endian: bool| {
+        let units: Vec<u16> = bytes.chunks_exact(2)
+            .map(|pair| if little_endian { u16::from_le_bytes([pair[0], pair[1]]) } else { u16::from_be_bytes([pair[0], pair[1]]) })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    match bytes {
+        [0xff, 0xfe, rest @ ..] => utf16(rest, true),
+        [0xfe, 0xff, rest @ ..] => utf16(rest, false),
+        [b'<', 0, ..] => utf16(bytes, true),
+        _ => String::from_utf8_lossy(bytes).trim_start_matches('\u{feff}').to_string(),
+    }
+}
+
+// A file handed to the importers. The text is decoded once and shared by
+// every importer asked to detect it.
+pub struct ImportFile {
+    pub name: String,
+    pub data: Vec<u8>,
+    text: OnceLock<String>,
+}
+
+impl ImportFile {
+    pub fn new(name: impl Into<String>, data: Vec<u8>) -> Self {
+        Self { name: name.into(), data, text: OnceLock::new() }
+    }
+
+    pub async fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let data = tokio::fs::read(path).await?;
+        let name = path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        Ok(Self::new(name, data))
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| decode_text(&self.data))
+    }
+
+    pub fn extension(&self) -> String {
+        Path::new(&self.name).extension()
+            .map_or_else(String::new, |e| e.to_string_lossy().to_ascii_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportContext {
+    // Broker symbol -> journal symbol, from MTConnectionConfig.symbol_mapping
+    pub symbol_mapping: HashMap<String, String>,
+    // Zone the file's times are in when the format does not say; the broker
+    // timezone when unset
+    pub timezone: Option<String>,
+}
+
+impl ImportContext {
+    pub fn map_symbol(&self, symbol: &str) -> String {
+        self.symbol_mapping.iter()
+            .find(|(broker, _)| broker.eq_ignore_ascii_case(symbol))
+            .map_or_else(|| symbol.to_string(), |(_, journal)| journal.clone())
+    }
+}
+
+// One broker export format. Importers only parse; writing, dedupe and
+// rollback are shared through DatabaseState::import_trades.
+pub trait BrokerImporter: Send + Sync {
+    // Stable id used to pick the importer explicitly, e.g. "ctrader"
+    fn id(&self) -> &str;
+    fn name(&self) -> &str;
+    // Confidence that the file is in this format: 0 rejects it, and the
+    // highest score wins when several importers accept the same file
+    fn detect(&self, file: &ImportFile) -> u8;
+    fn parse(&self, file: &ImportFile, context: &ImportContext) -> Result<ParsedImport, String>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", content = "name", rename_all = "lowercase")]
+pub enum ImporterSource {
+    Builtin,
+    // A saved CSV import profile
+    Profile,
+    Plugin(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImporterInfo {
+    pub id: String,
+    pub name: String,
+    pub source: ImporterSource,
+}
+
+#[derive(Clone, Default)]
+pub struct ImporterRegistry {
+    importers: Vec<(ImporterSource, Arc<dyn BrokerImporter>)>,
+}
+
+impl ImporterRegistry {
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        for importer in broker_formats::builtin() {
+            registry.register(ImporterSource::Builtin, importer);
+        }
+        registry
+    }
+
+    // A later registration with the same id replaces the earlier one, so a
+    // plugin can ship a fixed version of a built-in format
+    pub fn register(&mut self, source: ImporterSource, importer: Arc<dyn BrokerImporter>) {
+        match self.importers.iter_mut().find(|(_, existing)| existing.id() == importer.id()) {
+            Some(slot) => {
+                log::info!("Importer '{}' replaced by {:?}", importer.id(), source);
+                *slot = (source, importer);
+            }
+            None => self.importers.push((source, importer)),
+        }
+    }
+
+    pub fn list(&self) -> Vec<ImporterInfo> {
+        self.importers.iter()
+            .map(|(source, importer)| ImporterInfo {
+                id: importer.id().to_string(),
+                name: importer.name().to_string(),
+                source: source.clone(),
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn BrokerImporter>> {
+        self.importers.iter()
+            .find(|(_, importer)| importer.id() == id)
+            .map(|(_, importer)| importer.clone())
+    }
+
+    // Best match for the file; ties go to the importer registered first
+    pub fn detect(&self, file: &ImportFile) -> Option<Arc<dyn BrokerImporter>> {
+        let mut best: Option<(u8, &Arc<dyn BrokerImporter>)> = None;
+        for (_, importer) in &self.importers {
+            let score = importer.detect(file);
+            if score > 0 && best.map_or(true, |(top, _)| score > top) {
+                best = Some((score, importer));
+            }
+        }
+        best.map(|(_, importer)| importer.clone())
+    }
+}
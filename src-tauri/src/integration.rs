@@ -2,7 +2,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::net::{TcpListener, TcpStream};
 use std::io::{Read, Write, BufReader, BufWriter};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, SystemTime};
 use tokio::sync::{Mutex, RwLock};
 use tokio::net::TcpListener as AsyncTcpListener;
@@ -13,6 +15,10 @@ use websocket::message::Message as WsMessage;
 use reqwest::Client as HttpClient;
 use serde_json::Value;
 
+use crate::paths;
+
+const CONFIG_FILE: &str = "mt_integration.json";
+
 // MetaTrader integration structures
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MTConnectionConfig {
@@ -116,6 +122,8 @@ pub struct MetaTraderIntegration {
     market_data_cache: Arc<RwLock<HashMap<String, MTMarketData>>>,
     positions_cache: Arc<RwLock<Vec<MTPosition>>>,
     account_info_cache: Arc<RwLock<Option<MTAccountInfo>>>,
+    // Cleared by shutdown; the background tasks stop at their next tick
+    running: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Clone)]
@@ -147,6 +155,7 @@ impl MetaTraderIntegration {
             market_data_cache: Arc::new(RwLock::new(HashMap::new())),
             positions_cache: Arc::new(RwLock::new(Vec::new())),
             account_info_cache: Arc::new(RwLock::new(None)),
+            running: Arc::new(AtomicBool::new(true)),
         }
     }
     
@@ -174,10 +183,25 @@ impl MetaTraderIntegration {
         Ok(())
     }
     
+    // Stop the connection, market data and positions tasks
+    pub fn shutdown(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+    
+    pub fn config(&self) -> &MTConnectionConfig {
+        &self.config
+    }
+    
+    // Broker connections are shared by every workspace, so the config lives
+    // in the application config directory rather than a workspace
+    fn config_path() -> PathBuf {
+        paths::config_dir().join(CONFIG_FILE)
+    }
+    
     async fn load_config(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = "config/mt_integration.json";
+        let config_path = Self::config_path();
         
-        if let Ok(config_data) = tokio::fs::read(config_path).await {
+        if let Ok(config_data) = tokio::fs::read(&config_path).await {
             self.config = serde_json::from_slice(&config_data)?;
             log::info!("Loaded MetaTrader integration configuration");
         } else {
@@ -190,10 +214,10 @@ impl MetaTraderIntegration {
     }
     
     async fn save_config(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = "config/mt_integration.json";
+        let config_path = Self::config_path();
         let config_data = serde_json::to_vec_pretty(&self.config)?;
         
-        if let Some(parent) = Path::new(config_path).parent() {
+        if let Some(parent) = config_path.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
         
@@ -204,6 +228,7 @@ impl MetaTraderIntegration {
     // Connection management
     async fn start_connection_manager(&self) -> Result<(), Box<dyn std::error::Error>> {
         let state = self.connection_state.clone();
+        let running = self.running.clone();
         let config = self.config.clone();
         
         tokio::spawn(async move {
@@ -211,6 +236,9 @@ impl MetaTraderIntegration {
             
             loop {
                 interval.tick().await;
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
                 
                 let mut current_state = state.write().await;
                 
@@ -263,12 +291,16 @@ impl MetaTraderIntegration {
     async fn start_market_data_updater(&self) -> Result<(), Box<dyn std::error::Error>> {
         let market_data_cache = self.market_data_cache.clone();
         let state = self.connection_state.clone();
+        let running = self.running.clone();
         
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(1));
             
             loop {
                 interval.tick().await;
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
                 
                 let current_state = state.read().await;
                 if !current_state.connected {
@@ -332,12 +364,16 @@ impl MetaTraderIntegration {
         let positions_cache = self.positions_cache.clone();
         let account_info_cache = self.account_info_cache.clone();
         let state = self.connection_state.clone();
+        let running = self.running.clone();
         
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(5));
             
             loop {
                 interval.tick().await;
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
                 
                 let current_state = state.read().await;
                 if !current_state.connected {
@@ -415,4 +451,5 @@ impl MetaTraderIntegration {
             currency: "USD".to_string(),
             server: "Demo Server".to_string(),
         })
-    }
\ No newline at end of file
+    }
+}
@@ -0,0 +1,737 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{SqlitePool, Row, Error as SqlxError};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::database::{bind_json_value, parse_trade_time, EntitySchema};
+
+// Finding classification
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingCategory {
+    StopLoss,
+    TakeProfit,
+    Timestamps,
+    // Exit time without exit price or the other way round
+    IncompleteExit,
+    Outcome,
+    MissingImage,
+    OrphanImage,
+    Schema,
+    PluginData,
+    Database,
+}
+
+// A repair the checker knows how to apply safely
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AutoFix {
+    SetTradeField { trade_id: u32, field: String, value: serde_json::Value },
+    ClearTradeField { trade_id: u32, field: String },
+    DeleteImageHash { image_path: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IntegrityFinding {
+    pub category: FindingCategory,
+    pub severity: Severity,
+    pub entity: String,
+    pub entity_id: Option<String>,
+    pub message: String,
+    pub fix: Option<AutoFix>,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IntegrityReport {
+    pub checked_at: DateTime<Utc>,
+    pub trades_scanned: u32,
+    pub findings: Vec<IntegrityFinding>,
+    pub fixes_applied: u32,
+    pub severity_counts: HashMap<Severity, u32>,
+}
+
+// Journal integrity checker
+pub struct IntegrityChecker {
+    pool: SqlitePool,
+    image_root: PathBuf,
+}
+
+impl IntegrityChecker {
+    pub fn new(pool: SqlitePool, image_root: impl AsRef<Path>) -> Self {
+        Self {
+            pool,
+            image_root: image_root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub async fn check(&self, apply_fixes: bool) -> Result<IntegrityReport, Box<dyn std::error::Error>> {
+        log::info!("Running journal integrity check...");
+
+        let mut findings = Vec::new();
+
+        self.check_database(&mut findings).await?;
+        let (trades_scanned, mut referenced_images) = self.check_trades(&mut findings).await?;
+        referenced_images.extend(self.archived_images().await?);
+        self.check_image_directories(&referenced_images, &mut findings).await?;
+        self.check_image_hashes(&referenced_images, &mut findings).await?;
+        self.check_schemas(&mut findings).await?;
+        self.check_plugin_data(&mut findings).await?;
+
+        let fixes_applied = if apply_fixes {
+            self.apply_fixes(&mut findings).await?
+        } else {
+            0
+        };
+
+        findings.sort_by(|a, b| b.severity.cmp(&a.severity));
+
+        let mut severity_counts = HashMap::new();
+        for finding in &findings {
+            *severity_counts.entry(finding.severity).or_insert(0) += 1;
+        }
+
+        log::info!("Integrity check completed: {} findings, {} fixes applied", findings.len(), fixes_applied);
+
+        Ok(IntegrityReport {
+            checked_at: Utc::now(),
+            trades_scanned,
+            findings,
+            fixes_applied,
+            severity_counts,
+        })
+    }
+
+    // SQLite-level checks
+    async fn check_database(&self, findings: &mut Vec<IntegrityFinding>) -> Result<(), SqlxError> {
+        let rows = sqlx::query("PRAGMA integrity_check").fetch_all(&self.pool).await?;
+        for row in rows {
+            let message: String = row.get(0);
+            if message != "ok" {
+                findings.push(finding(FindingCategory::Database, Severity::Critical, "database", None, message, None));
+            }
+        }
+
+        let rows = sqlx::query("PRAGMA foreign_key_check").fetch_all(&self.pool).await?;
+        for row in rows {
+            let table: String = row.get(0);
+            let rowid: Option<i64> = row.get(1);
+            let parent: String = row.get(2);
+            findings.push(finding(
+                FindingCategory::Database,
+                Severity::Error,
+                &table,
+                rowid.map(|id| id.to_string()),
+                format!("Row references a missing {} record", parent),
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Row-level trade checks; returns the scanned count and referenced image paths
+    async fn check_trades(
+        &self,
+        findings: &mut Vec<IntegrityFinding>,
+    ) -> Result<(u32, HashSet<String>), SqlxError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, trade_type, entry_price, sl, tp, entry_time, exit_time, exit_price,
+                   is_win, profit_loss_money, entry_image, exit_image, analysis_image
+            FROM trades
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut referenced_images = HashSet::new();
+        let trades_scanned = rows.len() as u32;
+
+        for row in rows {
+            let trade_id = row.get::<i64, _>("id") as u32;
+            let id = Some(trade_id.to_string());
+            let trade_type: String = row.get("trade_type");
+            let entry_price: f64 = row.get("entry_price");
+            let sl: f64 = row.get::<Option<f64>, _>("sl").unwrap_or(0.0);
+            let tp: f64 = row.get::<Option<f64>, _>("tp").unwrap_or(0.0);
+            let is_buy = trade_type == "Buy";
+
+            // No stop or target was set when the level is NULL (or 0 in older rows)
+            if sl != 0.0 && ((is_buy && sl >= entry_price) || (!is_buy && sl <= entry_price)) {
+                findings.push(finding(
+                    FindingCategory::StopLoss,
+                    Severity::Error,
+                    "trades",
+                    id.clone(),
+                    format!("{} stop loss {} is on the wrong side of entry {}", trade_type, sl, entry_price),
+                    None,
+                ));
+            }
+
+            if tp != 0.0 && ((is_buy && tp <= entry_price) || (!is_buy && tp >= entry_price)) {
+                findings.push(finding(
+                    FindingCategory::TakeProfit,
+                    Severity::Warning,
+                    "trades",
+                    id.clone(),
+                    format!("{} take profit {} is on the wrong side of entry {}", trade_type, tp, entry_price),
+                    None,
+                ));
+            }
+
+            let entry_time: String = row.get("entry_time");
+            let exit_time: Option<String> = row.get("exit_time");
+            let parsed_entry = parse_trade_time(&entry_time);
+
+            if parsed_entry.is_none() {
+                findings.push(finding(
+                    FindingCategory::Timestamps,
+                    Severity::Warning,
+                    "trades",
+                    id.clone(),
+                    format!("Entry time '{}' is not a recognised timestamp", entry_time),
+                    None,
+                ));
+            }
+
+            if let Some(exit_time) = &exit_time {
+                match (parsed_entry, parse_trade_time(exit_time)) {
+                    (Some(entry), Some(exit)) if exit < entry => {
+                        findings.push(finding(
+                            FindingCategory::Timestamps,
+                            Severity::Error,
+                            "trades",
+                            id.clone(),
+                            format!("Exit time {} precedes entry time {}", exit_time, entry_time),
+                            None,
+                        ));
+                    }
+                    (_, None) => {
+                        findings.push(finding(
+                            FindingCategory::Timestamps,
+                            Severity::Warning,
+                            "trades",
+                            id.clone(),
+                            format!("Exit time '{}' is not a recognised timestamp", exit_time),
+                            None,
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+
+            let exit_price: Option<f64> = row.get("exit_price");
+            if exit_time.is_some() != exit_price.is_some() {
+                findings.push(finding(
+                    FindingCategory::IncompleteExit,
+                    Severity::Warning,
+                    "trades",
+                    id.clone(),
+                    "Trade has only one of exit time and exit price".to_string(),
+                    None,
+                ));
+            }
+
+            let is_win: Option<bool> = row.get("is_win");
+            let profit_loss: Option<f64> = row.get("profit_loss_money");
+            if let (Some(is_win), Some(profit_loss)) = (is_win, profit_loss) {
+                // P/L is positive for a winner in either direction. Break-even
+                // trades are left alone, and nothing is fixed automatically
+                // since either value may be the wrong one.
+                if profit_loss != 0.0 && is_win != (profit_loss > 0.0) {
+                    findings.push(finding(
+                        FindingCategory::Outcome,
+                        Severity::Error,
+                        "trades",
+                        id.clone(),
+                        format!("is_win is {} but P/L is {:.2}", is_win, profit_loss),
+                        None,
+                    ));
+                }
+            }
+
+            for field in ["entry_image", "exit_image", "analysis_image"] {
+                if let Some(image_path) = row.get::<Option<String>, _>(field) {
+                    if !self.image_root.join(&image_path).exists() {
+                        findings.push(finding(
+                            FindingCategory::MissingImage,
+                            Severity::Warning,
+                            "trades",
+                            id.clone(),
+                            format!("{} points at missing file {}", field, image_path),
+                            Some(AutoFix::ClearTradeField {
+                                trade_id,
+                                field: field.to_string(),
+                            }),
+                        ));
+                    }
+                    referenced_images.insert(image_path);
+                }
+            }
+        }
+
+        Ok((trades_scanned, referenced_images))
+    }
+
+    // Archived trades are not validated, but still own their images
+    async fn archived_images(&self) -> Result<HashSet<String>, SqlxError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT entry_image AS image_path FROM archive.trades WHERE entry_image IS NOT NULL
+            UNION SELECT exit_image FROM archive.trades WHERE exit_image IS NOT NULL
+            UNION SELECT analysis_image FROM archive.trades WHERE analysis_image IS NOT NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|row| row.get("image_path")).collect())
+    }
+
+    // Files on disk that no trade references
+    async fn check_image_directories(
+        &self,
+        referenced_images: &HashSet<String>,
+        findings: &mut Vec<IntegrityFinding>,
+    ) -> Result<(), std::io::Error> {
+        let images_dir = self.image_root.join("images");
+        let thumbnails_dir = self.image_root.join("thumbnails");
+
+        if images_dir.exists() {
+            let mut entries = tokio::fs::read_dir(&images_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let relative_path = format!("images/{}", file_name);
+
+                if !referenced_images.contains(&relative_path) {
+                    findings.push(finding(
+                        FindingCategory::OrphanImage,
+                        Severity::Info,
+                        "images",
+                        Some(relative_path),
+                        "Image file is not referenced by any trade".to_string(),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        if thumbnails_dir.exists() {
+            let mut entries = tokio::fs::read_dir(&thumbnails_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+
+                if !images_dir.join(&file_name).exists() {
+                    findings.push(finding(
+                        FindingCategory::OrphanImage,
+                        Severity::Info,
+                        "thumbnails",
+                        Some(format!("thumbnails/{}", file_name)),
+                        "Thumbnail has no original image".to_string(),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check_image_hashes(
+        &self,
+        referenced_images: &HashSet<String>,
+        findings: &mut Vec<IntegrityFinding>,
+    ) -> Result<(), SqlxError> {
+        let rows = sqlx::query("SELECT image_path FROM image_hashes")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in rows {
+            let image_path: String = row.get("image_path");
+            if !referenced_images.contains(&image_path) && !self.image_root.join(&image_path).exists() {
+                findings.push(finding(
+                    FindingCategory::OrphanImage,
+                    Severity::Info,
+                    "image_hashes",
+                    Some(image_path.clone()),
+                    "Hash entry for an image that no longer exists".to_string(),
+                    Some(AutoFix::DeleteImageHash { image_path }),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check_schemas(&self, findings: &mut Vec<IntegrityFinding>) -> Result<(), SqlxError> {
+        let rows = sqlx::query("SELECT entity_name, schema_json FROM entity_schemas")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in rows {
+            let entity_name: String = row.get("entity_name");
+            let schema_json: String = row.get("schema_json");
+
+            match serde_json::from_str::<EntitySchema>(&schema_json) {
+                Ok(schema) => {
+                    if schema.name != entity_name {
+                        findings.push(finding(
+                            FindingCategory::Schema,
+                            Severity::Warning,
+                            "entity_schemas",
+                            Some(entity_name.clone()),
+                            format!("Stored under '{}' but declares name '{}'", entity_name, schema.name),
+                            None,
+                        ));
+                    }
+
+                    let mut seen = HashSet::new();
+                    for field in &schema.fields {
+                        if !seen.insert(field.name.as_str()) {
+                            findings.push(finding(
+                                FindingCategory::Schema,
+                                Severity::Error,
+                                "entity_schemas",
+                                Some(entity_name.clone()),
+                                format!("Field '{}' is declared more than once", field.name),
+                                None,
+                            ));
+                        }
+                    }
+                }
+                Err(e) => {
+                    findings.push(finding(
+                        FindingCategory::Schema,
+                        Severity::Error,
+                        "entity_schemas",
+                        Some(entity_name),
+                        format!("Schema JSON cannot be parsed: {}", e),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check_plugin_data(&self, findings: &mut Vec<IntegrityFinding>) -> Result<(), SqlxError> {
+        let rows = sqlx::query("SELECT id, plugin_name, data_key, data_value, data_type FROM plugin_data")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in rows {
+            let id: i64 = row.get("id");
+            let plugin_name: String = row.get("plugin_name");
+            let data_key: String = row.get("data_key");
+            let data_value: String = row.get("data_value");
+            let data_type: String = row.get("data_type");
+
+            let valid = match data_type.as_str() {
+                "json" | "object" | "array" => serde_json::from_str::<serde_json::Value>(&data_value).is_ok(),
+                "integer" => data_value.parse::<i64>().is_ok(),
+                "number" | "float" => data_value.parse::<f64>().is_ok(),
+                "boolean" => data_value == "true" || data_value == "false",
+                _ => true,
+            };
+
+            if !valid {
+                findings.push(finding(
+                    FindingCategory::PluginData,
+                    Severity::Warning,
+                    "plugin_data",
+                    Some(id.to_string()),
+                    format!("{}/{} is not a valid {} value", plugin_name, data_key, data_type),
+                    None,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Apply all available fixes in one transaction
+    async fn apply_fixes(&self, findings: &mut [IntegrityFinding]) -> Result<u32, SqlxError> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+        let mut applied = 0;
+
+        for finding in findings.iter_mut() {
+            let fix = match &finding.fix {
+                Some(fix) => fix,
+                None => continue,
+            };
+
+            match fix {
+                AutoFix::SetTradeField { trade_id, field, value } => {
+                    let sql = format!("UPDATE trades SET {} = ?, updated_at = ?, version = version + 1 WHERE id = ?", field);
+                    bind_json_value(sqlx::query(&sql), value.clone())
+                        .bind(&now)
+                        .bind(*trade_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                AutoFix::ClearTradeField { trade_id, field } => {
+                    let sql = format!("UPDATE trades SET {} = NULL, updated_at = ?, version = version + 1 WHERE id = ?", field);
+                    sqlx::query(&sql)
+                        .bind(&now)
+                        .bind(*trade_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                AutoFix::DeleteImageHash { image_path } => {
+                    sqlx::query("DELETE FROM image_hashes WHERE image_path = ?")
+                        .bind(image_path)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+
+            finding.fixed = true;
+            applied += 1;
+        }
+
+        tx.commit().await?;
+        Ok(applied)
+    }
+}
+
+fn finding(
+    category: FindingCategory,
+    severity: Severity,
+    entity: &str,
+    entity_id: Option<String>,
+    message: String,
+    fix: Option<AutoFix>,
+) -> IntegrityFinding {
+    IntegrityFinding {
+        category,
+        severity,
+        entity: entity.to_string(),
+        entity_id,
+        message,
+        fix,
+        fixed: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // In-memory journal with the columns the checker reads, plus an image
+    // root on disk
+    async fn checker() -> (IntegrityChecker, PathBuf) {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let image_root = std::env::temp_dir().join(format!(
+            "journal-integrity-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_dir_all(&image_root);
+        std::fs::create_dir_all(image_root.join("images")).unwrap();
+        std::fs::create_dir_all(image_root.join("thumbnails")).unwrap();
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for sql in [
+            "ATTACH DATABASE ':memory:' AS archive",
+            "CREATE TABLE trades (
+                id INTEGER PRIMARY KEY, trade_type TEXT NOT NULL, entry_price REAL NOT NULL, sl REAL NOT NULL,
+                tp REAL NOT NULL, entry_time TEXT NOT NULL, exit_time TEXT, exit_price REAL, is_win INTEGER,
+                profit_loss_money REAL, entry_image TEXT, exit_image TEXT, analysis_image TEXT, notes TEXT,
+                updated_at TEXT, version INTEGER NOT NULL DEFAULT 1
+            )",
+            "CREATE TABLE archive.trades (entry_image TEXT, exit_image TEXT, analysis_image TEXT)",
+            "CREATE TABLE image_hashes (image_path TEXT PRIMARY KEY)",
+            "CREATE TABLE entity_schemas (entity_name TEXT NOT NULL, schema_json TEXT NOT NULL)",
+            "CREATE TABLE plugin_data (id INTEGER PRIMARY KEY, plugin_name TEXT, data_key TEXT, data_value TEXT, data_type TEXT)",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        (IntegrityChecker::new(pool, &image_root), image_root)
+    }
+
+    // A sound closed buy; `columns` overrides it as "name = value" SQL
+    async fn insert_trade(checker: &IntegrityChecker, id: i64, columns: &str) {
+        sqlx::query(
+            "INSERT INTO trades (id, trade_type, entry_price, sl, tp, entry_time, exit_time, exit_price, is_win, profit_loss_money)
+             VALUES (?, 'Buy', 1.1, 1.09, 1.12, '2024-01-02T10:00:00Z', '2024-01-02T12:00:00Z', 1.11, 1, 100.0)"
+        )
+        .bind(id)
+        .execute(&checker.pool)
+        .await
+        .unwrap();
+        if !columns.is_empty() {
+            sqlx::query(&format!("UPDATE trades SET {} WHERE id = ?", columns))
+                .bind(id)
+                .execute(&checker.pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    fn categories(report: &IntegrityReport, entity_id: &str) -> Vec<FindingCategory> {
+        report.findings.iter()
+            .filter(|f| f.entity_id.as_deref() == Some(entity_id))
+            .map(|f| f.category)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn trade_checks_flag_each_inconsistency() {
+        let (checker, _) = checker().await;
+        insert_trade(&checker, 1, "").await;
+        insert_trade(&checker, 2, "sl = 1.15").await;
+        insert_trade(&checker, 3, "tp = 1.05").await;
+        insert_trade(&checker, 4, "entry_time = 'yesterday'").await;
+        insert_trade(&checker, 5, "exit_time = '2024-01-01T10:00:00Z'").await;
+        insert_trade(&checker, 6, "exit_time = 'later'").await;
+        insert_trade(&checker, 7, "exit_price = NULL").await;
+        insert_trade(&checker, 8, "is_win = 0").await;
+        // No stop or target set, and a break-even trade marked as a loss
+        insert_trade(&checker, 9, "sl = 0, tp = 0, is_win = 0, profit_loss_money = 0").await;
+
+        let report = checker.check(false).await.unwrap();
+        assert_eq!(report.trades_scanned, 9);
+        assert_eq!(categories(&report, "1"), []);
+        assert_eq!(categories(&report, "2"), [FindingCategory::StopLoss]);
+        assert_eq!(categories(&report, "3"), [FindingCategory::TakeProfit]);
+        assert_eq!(categories(&report, "4"), [FindingCategory::Timestamps]);
+        assert_eq!(categories(&report, "5"), [FindingCategory::Timestamps]);
+        assert_eq!(categories(&report, "6"), [FindingCategory::Timestamps]);
+        assert_eq!(categories(&report, "7"), [FindingCategory::IncompleteExit]);
+        assert_eq!(categories(&report, "8"), [FindingCategory::Outcome]);
+        assert_eq!(categories(&report, "9"), []);
+        assert_eq!(report.severity_counts[&Severity::Error], 3);
+        assert_eq!(report.fixes_applied, 0);
+    }
+
+    #[tokio::test]
+    async fn image_checks_find_missing_and_orphaned_files() {
+        let (checker, image_root) = checker().await;
+        std::fs::write(image_root.join("images/kept.png"), b"png").unwrap();
+        std::fs::write(image_root.join("images/archived.png"), b"png").unwrap();
+        std::fs::write(image_root.join("images/orphan.png"), b"png").unwrap();
+        std::fs::write(image_root.join("thumbnails/kept.png"), b"png").unwrap();
+        std::fs::write(image_root.join("thumbnails/gone.png"), b"png").unwrap();
+        insert_trade(&checker, 1, "entry_image = 'images/kept.png', exit_image = 'images/missing.png'").await;
+        for sql in [
+            "INSERT INTO archive.trades (entry_image) VALUES ('images/archived.png')",
+            "INSERT INTO image_hashes VALUES ('images/kept.png'), ('images/deleted.png')",
+        ] {
+            sqlx::query(sql).execute(&checker.pool).await.unwrap();
+        }
+
+        let report = checker.check(false).await.unwrap();
+        assert_eq!(categories(&report, "1"), [FindingCategory::MissingImage]);
+        assert_eq!(categories(&report, "images/orphan.png"), [FindingCategory::OrphanImage]);
+        assert_eq!(categories(&report, "thumbnails/gone.png"), [FindingCategory::OrphanImage]);
+        assert_eq!(categories(&report, "images/deleted.png"), [FindingCategory::OrphanImage]);
+        for kept in ["images/kept.png", "images/archived.png", "thumbnails/kept.png"] {
+            assert_eq!(categories(&report, kept), []);
+        }
+    }
+
+    #[tokio::test]
+    async fn schema_database_and_plugin_checks() {
+        let (checker, _) = checker().await;
+        let field = r#"{"name":"symbol","data_type":"string","constraints":[],"ui":{"label":"Symbol","component":"text","order":1,"col_span":1,"hidden":false,"readonly":false}}"#;
+        let schema = |name: &str, fields: &str| format!(r#"{{"name":"{}","fields":[{}],"indexes":[],"relationships":[]}}"#, name, fields);
+        for (entity, json) in [
+            ("Trade", schema("Trade", field)),
+            ("Renamed", schema("Trade", field)),
+            ("Twice", schema("Twice", &format!("{},{}", field, field))),
+            ("Broken", "{".to_string()),
+        ] {
+            sqlx::query("INSERT INTO entity_schemas VALUES (?, ?)").bind(entity).bind(json).execute(&checker.pool).await.unwrap();
+        }
+        for sql in [
+            "INSERT INTO plugin_data VALUES (1, 'notes', 'count', '12', 'integer'), (2, 'notes', 'limit', 'many', 'integer'), (3, 'notes', 'state', '{', 'json')",
+            "CREATE TABLE screenshots (trade_id INTEGER REFERENCES trades(id))",
+            // A row left behind while enforcement was off
+            "PRAGMA foreign_keys = OFF",
+            "INSERT INTO screenshots VALUES (42)",
+        ] {
+            sqlx::query(sql).execute(&checker.pool).await.unwrap();
+        }
+
+        let report = checker.check(false).await.unwrap();
+        assert_eq!(categories(&report, "Trade"), []);
+        assert_eq!(categories(&report, "Renamed"), [FindingCategory::Schema]);
+        assert_eq!(categories(&report, "Twice"), [FindingCategory::Schema]);
+        assert_eq!(categories(&report, "Broken"), [FindingCategory::Schema]);
+        assert_eq!(categories(&report, "1"), [FindingCategory::Database]);
+        assert_eq!(categories(&report, "2"), [FindingCategory::PluginData]);
+        assert_eq!(categories(&report, "3"), [FindingCategory::PluginData]);
+        assert!(report.findings.iter().all(|f| f.entity != "database"));
+    }
+
+    #[tokio::test]
+    async fn fixes_write_values_of_their_own_type() {
+        let (checker, image_root) = checker().await;
+        insert_trade(&checker, 1, "is_win = 0, exit_image = 'images/missing.png', analysis_image = 'images/gone.png'").await;
+        sqlx::query("INSERT INTO image_hashes VALUES ('images/deleted.png')").execute(&checker.pool).await.unwrap();
+
+        let report = checker.check(true).await.unwrap();
+        assert_eq!(report.fixes_applied, 3);
+        assert!(report.findings.iter().filter(|f| f.fix.is_some()).all(|f| f.fixed));
+        // The outcome mismatch is reported but left to the user
+        assert!(report.findings.iter().any(|f| f.category == FindingCategory::Outcome && f.fix.is_none()));
+        let row = sqlx::query("SELECT is_win, exit_image, analysis_image, version FROM trades WHERE id = 1").fetch_one(&checker.pool).await.unwrap();
+        assert_eq!(row.get::<Option<bool>, _>("is_win"), Some(false));
+        assert_eq!(row.get::<Option<String>, _>("exit_image"), None);
+        assert_eq!(row.get::<Option<String>, _>("analysis_image"), None);
+        assert_eq!(row.get::<i64, _>("version"), 3);
+        let hashes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM image_hashes").fetch_one(&checker.pool).await.unwrap();
+        assert_eq!(hashes, 0);
+        assert!(image_root.join("images").exists());
+
+        // Numbers, text and null keep their type instead of becoming NULL
+        let mut findings: Vec<IntegrityFinding> = [
+            ("entry_price", serde_json::json!(1.2)),
+            ("sl", serde_json::json!(1)),
+            ("notes", serde_json::json!("checked")),
+            ("exit_image", serde_json::Value::Null),
+        ]
+        .into_iter()
+        .map(|(field, value)| finding(
+            FindingCategory::Outcome,
+            Severity::Info,
+            "trades",
+            Some("1".to_string()),
+            String::new(),
+            Some(AutoFix::SetTradeField { trade_id: 1, field: field.to_string(), value }),
+        ))
+        .collect();
+        assert_eq!(checker.apply_fixes(&mut findings).await.unwrap(), 4);
+
+        let row = sqlx::query("SELECT entry_price, sl, typeof(sl) AS sl_type, notes, exit_image FROM trades WHERE id = 1")
+            .fetch_one(&checker.pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<f64, _>("entry_price"), 1.2);
+        assert_eq!((row.get::<f64, _>("sl"), row.get::<String, _>("sl_type").as_str()), (1.0, "real"));
+        assert_eq!(row.get::<String, _>("notes"), "checked");
+        assert_eq!(row.get::<Option<String>, _>("exit_image"), None);
+    }
+}
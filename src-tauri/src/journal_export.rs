@@ -0,0 +1,304 @@
+// Journal export format
+//
+// A journal is written as JSON Lines (UTF-8, one object per line):
+//
+//   {"format":"deep-journal","version":1,"exported_at":"2024-03-01T08:00:00Z","images":"reference"}
+//   {"type":"schema","data":{...}}
+//   ...
+//   {"type":"end","counts":{"schema":3,"trade":120,...}}
+//
+// The first line is the header and the last line the trailer; a file without
+// the trailer was cut short and is rejected. Every other line is a record
+// whose `data` holds one row with its columns exactly as stored: timestamps
+// in UTC, JSON columns (pattern_combination tags) as their text, custom
+// fields in their own columns. Records from cold storage carry "archived":
+// true. Record types, in the order they are written:
+//
+//   schema       entity_schemas row
+//   plugin_data  plugin_data row
+//   image        {"path": "images/<file>", "content": <base64>}; content only
+//                when images are embedded
+//   image_hash   image_hashes row
+//   trade        trades row
+//   trade_merge  trade_merges row; trade ids refer to trade records
+//   cash_flow    cash_flows row
+//   execution    executions row; trade_id refers to a trade record
+//   fx_rate      fx_rates row
+//
+// Readers skip record types and columns they do not know, so additions stay
+// within a version; anything else bumps FORMAT_VERSION.
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+pub const FORMAT_NAME: &str = "deep-journal";
+pub const FORMAT_VERSION: u32 = 1;
+pub const END_RECORD: &str = "end";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageMode {
+    // Only the stored path; the files travel separately (or not at all)
+    #[default]
+    Reference,
+    // File contents inline, base64 encoded
+    Embedded,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalHeader {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub images: ImageMode,
+}
+
+impl JournalHeader {
+    pub fn new(exported_at: String, images: ImageMode) -> Self {
+        Self { format: FORMAT_NAME.to_string(), version: FORMAT_VERSION, exported_at, images }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let header: JournalHeader = serde_json::from_str(line)
+            .map_err(|_| "Not a journal export: the first line is not a header".to_string())?;
+
+        if header.format != FORMAT_NAME {
+            return Err(format!("Not a journal export (format '{}')", header.format));
+        }
+        if header.version == 0 || header.version > FORMAT_VERSION {
+            return Err(format!(
+                "Journal export version {} is not supported (this app reads up to {})",
+                header.version, FORMAT_VERSION
+            ));
+        }
+        Ok(header)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RecordKind {
+    Schema,
+    PluginData,
+    Image,
+    ImageHash,
+    Trade,
+    TradeMerge,
+    CashFlow,
+    Execution,
+    FxRate,
+}
+
+impl RecordKind {
+    // Write order; references always point at records written earlier
+    pub const ALL: [RecordKind; 9] = [
+        RecordKind::Schema,
+        RecordKind::PluginData,
+        RecordKind::Image,
+        RecordKind::ImageHash,
+        RecordKind::Trade,
+        RecordKind::TradeMerge,
+        RecordKind::CashFlow,
+        RecordKind::Execution,
+        RecordKind::FxRate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RecordKind::Schema => "schema",
+            RecordKind::PluginData => "plugin_data",
+            RecordKind::Image => "image",
+            RecordKind::ImageHash => "image_hash",
+            RecordKind::Trade => "trade",
+            RecordKind::TradeMerge => "trade_merge",
+            RecordKind::CashFlow => "cash_flow",
+            RecordKind::Execution => "execution",
+            RecordKind::FxRate => "fx_rate",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    // Backing table; images are files, not rows
+    pub fn table(self) -> Option<&'static str> {
+        match self {
+            RecordKind::Schema => Some("entity_schemas"),
+            RecordKind::PluginData => Some("plugin_data"),
+            RecordKind::Image => None,
+            RecordKind::ImageHash => Some("image_hashes"),
+            RecordKind::Trade => Some("trades"),
+            RecordKind::TradeMerge => Some("trade_merges"),
+            RecordKind::CashFlow => Some("cash_flows"),
+            RecordKind::Execution => Some("executions"),
+            RecordKind::FxRate => Some("fx_rates"),
+        }
+    }
+
+    // The table enforces the natural key, so two copies cannot coexist
+    pub fn is_unique(self) -> bool {
+        matches!(self, RecordKind::Schema | RecordKind::PluginData | RecordKind::ImageHash | RecordKind::FxRate)
+    }
+
+    // Columns that identify the same entity in another journal. Local ids
+    // never do: they are only meaningful inside the journal that made them.
+    pub fn natural_key(self, data: &Map<String, Value>) -> &'static [&'static str] {
+        let has_external_id = data.get("external_id").map_or(false, |v| !v.is_null());
+        match self {
+            RecordKind::Schema => &["entity_name"],
+            RecordKind::PluginData => &["plugin_name", "data_key"],
+            RecordKind::Image => &[],
+            RecordKind::ImageHash => &["image_path"],
+            // Broker tickets are only unique within their broker account
+            RecordKind::Trade | RecordKind::CashFlow | RecordKind::Execution if has_external_id => &["external_id", "broker", "account"],
+            RecordKind::Trade => &["symbol", "trade_type", "entry_time", "entry_price", "volume"],
+            RecordKind::TradeMerge => &["primary_trade_id", "merged_trade_id", "merged_at"],
+            RecordKind::CashFlow => &["kind", "amount", "occurred_at"],
+            RecordKind::Execution => &["trade_id", "side", "volume", "price", "executed_at"],
+            RecordKind::FxRate => &["base_currency", "quote_currency", "rate_date"],
+        }
+    }
+
+    // Columns holding trade ids from the exporting journal
+    pub fn trade_references(self) -> &'static [&'static str] {
+        match self {
+            RecordKind::TradeMerge => &["primary_trade_id", "merged_trade_id"],
+            RecordKind::Execution => &["trade_id"],
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalRecord {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    #[serde(default)]
+    pub data: Map<String, Value>,
+    // Trailer only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counts: Option<BTreeMap<String, usize>>,
+}
+
+impl JournalRecord {
+    pub fn new(kind: RecordKind, archived: bool, data: Map<String, Value>) -> Self {
+        Self { kind: kind.name().to_string(), archived, data, counts: None }
+    }
+
+    pub fn end(counts: BTreeMap<String, usize>) -> Self {
+        Self { kind: END_RECORD.to_string(), archived: false, data: Map::new(), counts: Some(counts) }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    // Keep what is already in this journal
+    #[default]
+    Skip,
+    // Overwrite the local row with the file's version
+    Replace,
+    // Add the file's version next to the local one (as Skip for schemas,
+    // plugin data, image hashes and rates, which cannot be duplicated)
+    KeepBoth,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct JournalExportOptions {
+    pub images: ImageMode,
+    pub include_archived: bool,
+}
+
+impl Default for JournalExportOptions {
+    fn default() -> Self {
+        Self { images: ImageMode::Reference, include_archived: true }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct JournalImportOptions {
+    pub on_conflict: ConflictPolicy,
+    // Run the whole import, report, then roll back
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct JournalExportReport {
+    pub path: String,
+    pub counts: BTreeMap<String, usize>,
+    // Referenced images whose file was not found
+    pub missing_images: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct JournalImportReport {
+    pub version: u32,
+    pub inserted: BTreeMap<String, usize>,
+    pub replaced: BTreeMap<String, usize>,
+    pub skipped: BTreeMap<String, usize>,
+    // Trade ids of the file that map to a different id here
+    pub trade_id_map: BTreeMap<i64, i64>,
+    pub warnings: Vec<String>,
+    pub committed: bool,
+    pub dry_run: bool,
+}
+
+// Point trade references at the ids they received in this journal; ids not
+// in the map (merged trades that no longer exist) are kept as they are
+pub fn remap_trade_references(kind: RecordKind, data: &mut Map<String, Value>, trade_ids: &BTreeMap<i64, i64>) {
+    for column in kind.trade_references() {
+        if let Some(value) = data.get_mut(*column) {
+            if let Some(mapped) = value.as_i64().and_then(|id| trade_ids.get(&id)) {
+                *value = Value::from(*mapped);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn header_round_trips_and_rejects_other_versions() {
+        let header = JournalHeader::new("2024-03-01T08:00:00Z".to_string(), ImageMode::Embedded);
+        let line = serde_json::to_string(&header).unwrap();
+        let parsed = JournalHeader::parse(&line).unwrap();
+        assert_eq!(parsed.version, FORMAT_VERSION);
+        assert_eq!(parsed.images, ImageMode::Embedded);
+
+        let newer = line.replace(&format!("\"version\":{}", FORMAT_VERSION), "\"version\":99");
+        assert!(JournalHeader::parse(&newer).unwrap_err().contains("not supported"));
+        assert!(JournalHeader::parse(r#"{"type":"trade","data":{}}"#).is_err());
+    }
+
+    #[test]
+    fn records_serialize_compactly_and_unknown_types_are_ignorable() {
+        let mut data = Map::new();
+        data.insert("id".to_string(), json!(7));
+        let line = serde_json::to_string(&JournalRecord::new(RecordKind::Trade, false, data)).unwrap();
+        assert_eq!(line, r#"{"type":"trade","data":{"id":7}}"#);
+
+        let record: JournalRecord = serde_json::from_str(r#"{"type":"sticker","archived":true,"data":{}}"#).unwrap();
+        assert_eq!(RecordKind::parse(&record.kind), None);
+        assert!(RecordKind::ALL.iter().all(|kind| RecordKind::parse(kind.name()) == Some(*kind)));
+    }
+
+    #[test]
+    fn natural_keys_prefer_broker_ids_and_references_are_remapped() {
+        let mut trade = json!({"id": 3, "external_id": null, "symbol": "EURUSD"}).as_object().unwrap().clone();
+        assert_eq!(RecordKind::Trade.natural_key(&trade)[0], "symbol");
+        trade.insert("external_id".to_string(), json!("5002"));
+        assert_eq!(RecordKind::Trade.natural_key(&trade), &["external_id", "broker", "account"]);
+
+        let mut merge = json!({"primary_trade_id": 3, "merged_trade_id": 9}).as_object().unwrap().clone();
+        remap_trade_references(RecordKind::TradeMerge, &mut merge, &BTreeMap::from([(3, 41)]));
+        assert_eq!(merge["primary_trade_id"], json!(41));
+        assert_eq!(merge["merged_trade_id"], json!(9));
+    }
+}
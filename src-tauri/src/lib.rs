@@ -1,11 +1,33 @@
 pub mod database;
+pub mod image_hash;
+pub mod timezone;
+pub mod analysis;
+pub mod duplicates;
+pub mod workspace;
+pub mod paths;
+pub mod pagination;
+pub mod importer;
+pub mod csv_import;
+pub mod mt_statement;
+pub mod broker_formats;
+pub mod journal_export;
+pub mod trade_export;
+pub mod report;
+pub mod pdf_report;
+pub mod pdf_font;
+pub mod tax_report;
+pub mod vault_export;
+pub mod quick_entry;
+pub mod eml_import;
+pub mod equity;
 
-use database::{Database, NewTrade, Trade};
+use database::{DatabaseState, NewTrade, Trade};
+use workspace::WorkspaceManager;
 use tauri::State;
 use std::sync::Arc;
 
 pub struct AppState {
-    db: Arc<Database>,
+    db: Arc<DatabaseState>,
 }
 
 #[tauri::command]
@@ -43,10 +65,13 @@ pub fn run() {
         .setup(|app| {
             let app_handle = app.handle();
             tauri::async_runtime::spawn(async move {
-                // Initialize database
-                let database = Database::new("sqlite:trading_journal.db")
-                    .await
-                    .expect("Failed to initialize database");
+                // Initialize database of the last used workspace
+                let mut workspaces = WorkspaceManager::default();
+                workspaces.load().await.expect("Failed to load workspaces");
+                let workspace = workspaces.active().cloned().expect("No workspace is open");
+                let mut database = DatabaseState::open(workspaces.paths(&workspace))
+                    .expect("Failed to open database");
+                database.initialize().await.expect("Failed to initialize database");
                 app_handle.manage(AppState {
                     db: Arc::new(database),
                 });
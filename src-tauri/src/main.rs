@@ -6,11 +6,12 @@ use tauri::{
 use tauri_plugin_store::StoreBuilder;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap, 
+    collections::{BTreeMap, HashMap}, 
     sync::{Arc, Mutex}, 
     time::{Duration, SystemTime}
 };
 use tokio::time::interval;
+use chrono::{DateTime, Utc};
 
 // Application modules
 mod database;
@@ -20,10 +21,51 @@ mod analysis;
 mod backup;
 mod integration;
 mod utils;
+mod image_hash;
+mod duplicates;
+mod integrity;
+mod timezone;
+mod workspace;
+mod paths;
+mod plugin_storage;
+mod pagination;
+mod importer;
+mod csv_import;
+mod mt_statement;
+mod broker_formats;
+mod journal_export;
+mod trade_export;
+mod report;
+mod pdf_report;
+mod pdf_font;
+mod tax_report;
+mod vault_export;
+mod quick_entry;
+mod eml_import;
+mod equity;
 
 // Re-exports
-pub use database::{Trade, NewTrade, TradeQuery, DatabaseState};
+pub use database::{Trade, NewTrade, TradeQuery, DatabaseState, ChartQuery, SimilarChart, BulkResult, MergeSource, DimensionStatistics, ArchiveResult, ChangeBatch};
+pub use duplicates::{DuplicateDetector, DuplicateConfig, DuplicateCandidate};
+pub use integrity::{IntegrityChecker, IntegrityReport, IntegrityFinding};
+pub use timezone::TimeSettings;
+pub use workspace::{Workspace, WorkspaceManager, WorkspacePaths};
+pub use paths::{DataRoot, DataRootSource, MigrationReport};
 pub use plugins::{PluginManager, Plugin, PluginResult};
+pub use plugin_storage::{PluginValue, PluginEntry, StorageUsage, DataRetention};
+pub use pagination::TradePage;
+pub use importer::{ImportOptions, ImportReport, ImportContext, ImportFile, ImporterInfo, ImporterRegistry, ImporterSource, TicketScope};
+pub use csv_import::{CsvOptions, CsvPreview, ImportProfile};
+pub use mt_statement::{StatementFormat, StatementOptions};
+pub use journal_export::{ConflictPolicy, ImageMode, JournalExportOptions, JournalExportReport, JournalImportOptions, JournalImportReport};
+pub use trade_export::{ExportFormat, TradeExportOptions, TradeExportReport};
+pub use report::{ReportOptions, ReportRange, ReportResult};
+pub use pdf_report::PdfTemplate;
+pub use tax_report::{FxRate, TaxReportOptions, TaxReportResult};
+pub use vault_export::{VaultExportOptions, VaultExportReport};
+pub use quick_entry::{QuickEntryError, QuickEntryPreview};
+pub use eml_import::{EmailOutput, EmailPreview, EmailTemplate};
+pub use equity::{DrawdownPeriod, EquityCurve, EquityPoint};
 pub use trading::{TradingEngine, MarketData, Order, Position};
 pub use analysis::{Analyzer, TechnicalAnalysis, StatisticalAnalysis};
 pub use backup::{BackupManager, BackupConfig};
@@ -33,12 +75,14 @@ pub use utils::{Config, Logger, Error, Result};
 // Application state
 #[derive(Default)]
 struct AppState {
+    workspaces: WorkspaceManager,
     database: DatabaseState,
     plugins: PluginManager,
     trading_engine: TradingEngine,
     analyzer: Analyzer,
     backup_manager: BackupManager,
     mt_integration: MetaTraderIntegration,
+    importers: ImporterRegistry,
     config: Config,
     is_initialized: bool,
 }
@@ -90,159 +134,1487 @@ async fn get_all_trades(
         .map_err(|e| format!("Failed to fetch trades: {}", e))
 }
 
+#[tauri::command]
+async fn get_trades_page(
+    query: Option<TradeQuery>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<TradePage, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.get_trades_page(&query.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to fetch trades: {}", e))
+}
+
+// Pages through the matching trades in the background and emits each page as
+// a trades_batch event. Callers may pick the stream id so they can listen
+// before the first batch arrives; it is returned either way.
+#[tauri::command]
+async fn stream_trades(
+    query: Option<TradeQuery>,
+    batch_size: Option<u32>,
+    stream_id: Option<String>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let mut query = query.unwrap_or_default();
+    query.limit = batch_size.or(query.limit);
+    query.cursor = None;
+
+    // Fail fast on a bad query instead of in the background task
+    let first = state.database.get_trades_page(&query).await
+        .map_err(|e| format!("Failed to stream trades: {}", e))?;
+
+    let pool = state.database.pool();
+    let stream_id = stream_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let id = stream_id.clone();
+
+    tokio::spawn(async move {
+        let mut page = first;
+        let mut batch = 0u32;
+
+        loop {
+            let done = page.next_cursor.is_none();
+            if let Err(e) = app_handle.emit_all("trades_batch", serde_json::json!({
+                "stream_id": id,
+                "batch": batch,
+                "trades": page.trades,
+                "total_count": page.total_count,
+                "done": done,
+            })) {
+                log::error!("Failed to emit trades_batch event: {}", e);
+                break;
+            }
+
+            if done {
+                break;
+            }
+
+            query.cursor = page.next_cursor;
+            batch += 1;
+            page = match database::fetch_trade_page(&pool, &query).await {
+                Ok(page) => page,
+                Err(e) => {
+                    log::error!("Failed to stream trades: {}", e);
+                    if let Err(e) = app_handle.emit_all("trades_stream_error", serde_json::json!({
+                        "stream_id": id,
+                        "error": e.to_string(),
+                    })) {
+                        log::error!("Failed to emit trades_stream_error event: {}", e);
+                    }
+                    break;
+                }
+            };
+        }
+    });
+
+    Ok(stream_id)
+}
+
 #[tauri::command]
 async fn delete_trade(
     id: u32,
     state: State<'_, Arc<Mutex<AppState>>>,
     app_handle: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    match state.database.delete_trade(id).await {
+        Ok(()) => {
+            // Notify about deletion
+            if let Err(e) = app_handle.emit_all("trade_deleted", id) {
+                log::error!("Failed to emit trade_deleted event: {}", e);
+            }
+            
+            // Update analysis
+            tokio::spawn(async move {
+                if let Err(e) = update_analysis(&app_handle).await {
+                    log::error!("Failed to update analysis: {}", e);
+                }
+            });
+            
+            Ok(())
+        }
+        Err(e) => Err(format!("Failed to delete trade: {}", e)),
+    }
+}
+
+#[tauri::command]
+async fn update_trade(
+    id: u32,
+    updates: HashMap<String, serde_json::Value>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Trade, String> {
+    let mut state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    match state.database.update_trade(id, updates).await {
+        Ok(trade) => {
+            // Notify about update
+            if let Err(e) = app_handle.emit_all("trade_updated", &trade) {
+                log::error!("Failed to emit trade_updated event: {}", e);
+            }
+            
+            // Update analysis
+            tokio::spawn(async move {
+                if let Err(e) = update_analysis(&app_handle).await {
+                    log::error!("Failed to update analysis: {}", e);
+                }
+            });
+            
+            Ok(trade)
+        }
+        Err(e) => Err(format!("Failed to update trade: {}", e)),
+    }
+}
+
+// Bulk trade operations
+#[tauri::command]
+async fn bulk_update_trades(
+    filter: TradeQuery,
+    patch: HashMap<String, serde_json::Value>,
+    dry_run: Option<bool>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<BulkResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let result = state.database.bulk_update_trades(&filter, patch, dry_run.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to bulk update trades: {}", e))?;
+    
+    if result.affected > 0 {
+        // One batched event for the whole operation
+        if let Err(e) = app_handle.emit_all("trades_bulk_updated", &result) {
+            log::error!("Failed to emit trades_bulk_updated event: {}", e);
+        }
+        
+        // Recompute analysis once for the batch
+        tokio::spawn(async move {
+            if let Err(e) = update_analysis(&app_handle).await {
+                log::error!("Failed to update analysis: {}", e);
+            }
+        });
+    }
+    
+    Ok(result)
+}
+
+#[tauri::command]
+async fn bulk_delete_trades(
+    filter: TradeQuery,
+    dry_run: Option<bool>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<BulkResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let result = state.database.bulk_delete_trades(&filter, dry_run.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to bulk delete trades: {}", e))?;
+    
+    if result.affected > 0 {
+        if let Err(e) = app_handle.emit_all("trades_bulk_deleted", &result) {
+            log::error!("Failed to emit trades_bulk_deleted event: {}", e);
+        }
+        
+        tokio::spawn(async move {
+            if let Err(e) = update_analysis(&app_handle).await {
+                log::error!("Failed to update analysis: {}", e);
+            }
+        });
+    }
+    
+    Ok(result)
+}
+
+// Duplicate detection and merging
+#[tauri::command]
+async fn find_duplicate_trades(
+    config: Option<DuplicateConfig>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<DuplicateCandidate>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let detector = DuplicateDetector::new(state.database.pool(), config.unwrap_or_default());
+    detector.find_duplicates().await
+        .map_err(|e| format!("Failed to find duplicate trades: {}", e))
+}
+
+#[tauri::command]
+async fn merge_trades(
+    primary_id: u32,
+    duplicate_id: u32,
+    field_sources: Option<HashMap<String, MergeSource>>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Trade, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let trade = state.database.merge_trades(primary_id, duplicate_id, field_sources.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to merge trades: {}", e))?;
+    
+    if let Err(e) = app_handle.emit_all("trades_merged", serde_json::json!({
+        "primary_id": primary_id,
+        "duplicate_id": duplicate_id,
+    })) {
+        log::error!("Failed to emit trades_merged event: {}", e);
+    }
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+    
+    Ok(trade)
+}
+
+// Integrity commands
+#[tauri::command]
+async fn check_integrity(
+    apply_fixes: Option<bool>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<IntegrityReport, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let checker = IntegrityChecker::new(state.database.pool(), state.database.image_storage_path());
+    let report = checker.check(apply_fixes.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to check integrity: {}", e))?;
+    
+    if report.fixes_applied > 0 {
+        tokio::spawn(async move {
+            if let Err(e) = update_analysis(&app_handle).await {
+                log::error!("Failed to update analysis: {}", e);
+            }
+        });
+    }
+    
+    Ok(report)
+}
+
+// Schema management commands
+#[tauri::command]
+async fn get_schema(
+    entity_name: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<serde_json::Value, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.get_schema(&entity_name).await
+        .map_err(|e| format!("Failed to get schema: {}", e))
+}
+
+#[tauri::command]
+async fn update_schema(
+    schema: serde_json::Value,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.update_schema(schema).await
+        .map_err(|e| format!("Failed to update schema: {}", e))
+}
+
+// ICT Analysis commands
+#[tauri::command]
+async fn get_ict_win_rates(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<HashMap<String, serde_json::Value>>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.analyzer.calculate_ict_win_rates().await
+        .map_err(|e| format!("Failed to calculate ICT win rates: {}", e))
+}
+
+#[tauri::command]
+async fn get_ict_heatmap_data(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<HashMap<String, serde_json::Value>>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.analyzer.calculate_ict_heatmap().await
+        .map_err(|e| format!("Failed to calculate ICT heatmap: {}", e))
+}
+
+// Timezone commands
+#[tauri::command]
+async fn get_time_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<TimeSettings, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    Ok(state.database.time_settings().clone())
+}
+
+#[tauri::command]
+async fn update_time_settings(
+    settings: TimeSettings,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.update_time_settings(settings.clone()).await
+        .map_err(|e| format!("Failed to update time settings: {}", e))?;
+    
+    let display_timezone = state.database.time_settings().display_tz();
+    state.analyzer.set_display_timezone(display_timezone).await;
+    
+    if let Err(e) = app_handle.emit_all("time_settings_updated", &settings) {
+        log::error!("Failed to emit time_settings_updated event: {}", e);
+    }
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+    
+    Ok(())
+}
+
+#[tauri::command]
+async fn normalize_trade_timestamps(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<u32, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let normalized = state.database.normalize_trade_timestamps().await
+        .map_err(|e| format!("Failed to normalize timestamps: {}", e))?;
+    
+    if normalized > 0 {
+        tokio::spawn(async move {
+            if let Err(e) = update_analysis(&app_handle).await {
+                log::error!("Failed to update analysis: {}", e);
+            }
+        });
+    }
+    
+    Ok(normalized)
+}
+
+// Aggregate statistics commands
+#[tauri::command]
+async fn get_trade_statistics(
+    dimension: String,
+    include_archived: Option<bool>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<DimensionStatistics>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.get_dimension_statistics(&dimension, include_archived.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to get trade statistics: {}", e))
+}
+
+#[tauri::command]
+async fn rebuild_trade_statistics(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<u32, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let rows = state.database.rebuild_trade_statistics().await
+        .map_err(|e| format!("Failed to rebuild trade statistics: {}", e))?;
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+    
+    Ok(rows)
+}
+
+// Archive commands
+#[tauri::command]
+async fn archive_trades(
+    before: DateTime<Utc>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ArchiveResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let result = state.database.archive_trades(before).await
+        .map_err(|e| format!("Failed to archive trades: {}", e))?;
+    state.analyzer.invalidate_cache().await;
+    
+    if let Err(e) = app_handle.emit_all("trades_archived", &result) {
+        log::error!("Failed to emit trades_archived event: {}", e);
+    }
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+    
+    Ok(result)
+}
+
+#[tauri::command]
+async fn unarchive_trades(
+    ids: Option<Vec<u32>>,
+    since: Option<DateTime<Utc>>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ArchiveResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let result = state.database.unarchive_trades(ids, since).await
+        .map_err(|e| format!("Failed to restore archived trades: {}", e))?;
+    state.analyzer.invalidate_cache().await;
+    
+    if let Err(e) = app_handle.emit_all("trades_unarchived", &result) {
+        log::error!("Failed to emit trades_unarchived event: {}", e);
+    }
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+    
+    Ok(result)
+}
+
+#[tauri::command]
+async fn set_analysis_include_archived(
+    include_archived: bool,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.analyzer.set_include_archived(include_archived).await;
+    Ok(())
+}
+
+// Change log commands
+#[tauri::command]
+async fn get_changes_since(
+    since: i64,
+    limit: Option<u32>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<ChangeBatch, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.changes_since(since, limit.unwrap_or(1000)).await
+        .map_err(|e| format!("Failed to read change log: {}", e))
+}
+
+#[tauri::command]
+async fn compact_change_log(
+    before: DateTime<Utc>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<u32, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.compact_change_log(before).await
+        .map_err(|e| format!("Failed to compact change log: {}", e))
+}
+
+// Import commands
+#[tauri::command]
+async fn preview_csv_import(
+    path: String,
+    options: Option<CsvOptions>,
+) -> Result<CsvPreview, String> {
+    let data = csv_import::read_file(std::path::Path::new(&path)).await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    
+    csv_import::preview(&data, &options.unwrap_or_default())
+        .map_err(|e| format!("Failed to preview CSV: {}", e))
+}
+
+#[tauri::command]
+async fn list_import_profiles() -> Result<Vec<ImportProfile>, String> {
+    csv_import::load_profiles().await
+        .map_err(|e| format!("Failed to load import profiles: {}", e))
+}
+
+#[tauri::command]
+async fn save_import_profile(profile: ImportProfile) -> Result<(), String> {
+    csv_import::save_profile(profile).await
+        .map_err(|e| format!("Failed to save import profile: {}", e))
+}
+
+#[tauri::command]
+async fn delete_import_profile(name: String) -> Result<bool, String> {
+    csv_import::delete_profile(&name).await
+        .map_err(|e| format!("Failed to delete import profile: {}", e))
+}
+
+// Import a CSV with an inline profile or a saved one by name
+#[tauri::command]
+async fn import_csv(
+    path: String,
+    profile: Option<ImportProfile>,
+    profile_name: Option<String>,
+    options: Option<ImportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ImportReport, String> {
+    let profile = match (profile, profile_name) {
+        (Some(profile), _) => profile,
+        (None, Some(name)) => csv_import::find_profile(&name).await
+            .map_err(|e| format!("Failed to load import profiles: {}", e))?
+            .ok_or_else(|| format!("Import profile '{}' not found", name))?,
+        (None, None) => return Err("An import profile is required".to_string()),
+    };
+    
+    let data = csv_import::read_file(std::path::Path::new(&path)).await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut parsed = csv_import::parse(&data, &profile)
+        .map_err(|e| format!("Failed to import CSV: {}", e))?;
+    // Same scope as the profile's entry in import_file
+    parsed.set_scope(&TicketScope::new(&format!("profile:{}", profile.name), None));
+    
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let report = state.database.import_trades(parsed, &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to import trades: {}", e))?;
+    
+    notify_imported(&report, app_handle);
+    Ok(report)
+}
+
+// Import a MetaTrader 4 "Detailed Statement" or MetaTrader 5 "Report" saved
+// as HTML. Tickets make re-imports of newer statements add only new trades.
+#[tauri::command]
+async fn import_mt_statement(
+    path: String,
+    timezone: Option<String>,
+    options: Option<ImportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ImportReport, String> {
+    let bytes = tokio::fs::read(&path).await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let html = importer::decode_text(&bytes);
+    
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let statement_options = StatementOptions {
+        symbol_mapping: state.mt_integration.config().symbol_mapping.clone(),
+        timezone,
+    };
+    let parsed = mt_statement::parse(&html, &statement_options)
+        .map_err(|e| format!("Failed to import statement: {}", e))?;
+    
+    let report = state.database.import_trades(parsed, &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to import trades: {}", e))?;
+    
+    notify_imported(&report, app_handle);
+    Ok(report)
+}
+
+// Importers import_file can pick from, saved CSV profiles included
+#[tauri::command]
+async fn list_importers(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<ImporterInfo>, String> {
+    let registry = importer_registry(&state).await?;
+    Ok(registry.list())
+}
+
+// Import any supported broker export. The format is detected from the file
+// unless an importer id from list_importers is given.
+#[tauri::command]
+async fn import_file(
+    path: String,
+    importer: Option<String>,
+    timezone: Option<String>,
+    options: Option<ImportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ImportReport, String> {
+    let file = ImportFile::read(std::path::Path::new(&path)).await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let registry = importer_registry(&state).await?;
+    
+    let importer = match importer {
+        Some(id) => registry.get(&id).ok_or_else(|| format!("Importer '{}' not found", id))?,
+        None => registry.detect(&file).ok_or_else(|| format!("Unrecognized file format: {}", file.name))?,
+    };
+    
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let context = ImportContext {
+        symbol_mapping: state.mt_integration.config().symbol_mapping.clone(),
+        timezone,
+    };
+    let mut parsed = importer.parse(&file, &context)
+        .map_err(|e| format!("Failed to import {}: {}", importer.name(), e))?;
+    // Plugin importers may not say where their tickets come from
+    parsed.set_scope(&TicketScope::new(importer.id(), None));
+    
+    let mut report = state.database.import_trades(parsed, &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to import trades: {}", e))?;
+    report.format = Some(importer.id().to_string());
+    
+    notify_imported(&report, app_handle);
+    Ok(report)
+}
+
+// The app's registry plus the saved CSV profiles and email templates, which
+// can change at any time
+async fn importer_registry(state: &State<'_, Arc<Mutex<AppState>>>) -> Result<ImporterRegistry, String> {
+    let profiles = csv_import::load_profiles().await
+        .map_err(|e| format!("Failed to load import profiles: {}", e))?;
+    let templates = eml_import::load_templates().await
+        .map_err(|e| format!("Failed to load email templates: {}", e))?;
+    
+    let mut registry = state.lock().unwrap().importers.clone();
+    for profile in profiles {
+        let id = format!("profile:{}", profile.name);
+        registry.register(ImporterSource::Profile, Arc::new(csv_import::ProfileImporter::new(id, profile)));
+    }
+    registry.register(ImporterSource::Builtin, Arc::new(eml_import::EmailImporter::new(templates)));
+    Ok(registry)
+}
+
+// Email confirmation commands (eml_import.rs)
+#[tauri::command]
+async fn list_email_templates() -> Result<Vec<EmailTemplate>, String> {
+    eml_import::load_templates().await
+        .map_err(|e| format!("Failed to load email templates: {}", e))
+}
+
+#[tauri::command]
+async fn save_email_template(template: EmailTemplate) -> Result<(), String> {
+    eml_import::save_template(template).await
+        .map_err(|e| format!("Failed to save email template: {}", e))
+}
+
+#[tauri::command]
+async fn delete_email_template(name: String) -> Result<bool, String> {
+    eml_import::delete_template(&name).await
+        .map_err(|e| format!("Failed to delete email template: {}", e))
+}
+
+// Decode an email and show what a template (or the saved ones) extracts
+#[tauri::command]
+async fn preview_email(
+    path: String,
+    template: Option<EmailTemplate>,
+    timezone: Option<String>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<EmailPreview, String> {
+    let file = ImportFile::read(std::path::Path::new(&path)).await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let templates = match template {
+        Some(template) => vec![template],
+        None => eml_import::load_templates().await
+            .map_err(|e| format!("Failed to load email templates: {}", e))?,
+    };
+    
+    let state = state.lock().unwrap();
+    let context = ImportContext {
+        symbol_mapping: state.mt_integration.config().symbol_mapping.clone(),
+        timezone,
+    };
+    Ok(eml_import::preview(&file.data, &templates, &context))
+}
+
+// Import a batch of saved emails in one transaction. Fills already imported,
+// from an earlier batch or a duplicate file in this one, are skipped.
+#[tauri::command]
+async fn import_emails(
+    paths: Vec<String>,
+    timezone: Option<String>,
+    options: Option<ImportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<ImportReport, String> {
+    let mut files = Vec::new();
+    for path in &paths {
+        files.push(ImportFile::read(std::path::Path::new(path)).await
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?);
+    }
+    let templates = eml_import::load_templates().await
+        .map_err(|e| format!("Failed to load email templates: {}", e))?;
+    if templates.is_empty() {
+        return Err("No email templates saved; add one for your broker first".to_string());
+    }
+    
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let context = ImportContext {
+        symbol_mapping: state.mt_integration.config().symbol_mapping.clone(),
+        timezone,
+    };
+    let parsed = eml_import::parse_batch(&files, &templates, &context);
+    
+    let mut report = state.database.import_trades(parsed, &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to import trades: {}", e))?;
+    report.format = Some("email".to_string());
+    
+    notify_imported(&report, app_handle);
+    Ok(report)
+}
+
+// Write the whole journal to a JSON Lines file for another machine or a
+// colleague (format in journal_export.rs)
+#[tauri::command]
+async fn export_journal(
+    path: String,
+    options: Option<JournalExportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<JournalExportReport, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.export_journal(std::path::Path::new(&path), &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to export journal: {}", e))
+}
+
+#[tauri::command]
+async fn import_journal(
+    path: String,
+    options: Option<JournalImportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<JournalImportReport, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    let report = state.database.import_journal(std::path::Path::new(&path), &options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to import journal: {}", e))?;
+    
+    if report.committed {
+        if let Err(e) = app_handle.emit_all("journal_imported", &report) {
+            log::error!("Failed to emit journal_imported event: {}", e);
+        }
+        
+        tokio::spawn(async move {
+            if let Err(e) = update_analysis(&app_handle).await {
+                log::error!("Failed to update analysis: {}", e);
+            }
+        });
+    }
+    
+    Ok(report)
+}
+
+// Spreadsheet export of the trades matching a query, for reviewing in Excel
+// (columns, locales and the summary sheet in trade_export.rs)
+#[tauri::command]
+async fn export_trades(
+    path: String,
+    query: Option<TradeQuery>,
+    columns: Option<Vec<String>>,
+    format: Option<ExportFormat>,
+    options: Option<TradeExportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<TradeExportReport, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.export_trades(
+        std::path::Path::new(&path),
+        &query.unwrap_or_default(),
+        &columns.unwrap_or_default(),
+        format,
+        &options.unwrap_or_default(),
+    ).await
+        .map_err(|e| format!("Failed to export trades: {}", e))
+}
+
+// Balance, running peak and drawdown after each closed trade in the order
+// they were closed, with the drawdown periods (equity.rs)
+#[tauri::command]
+async fn equity_curve(
+    scope: Option<TradeQuery>,
+    starting_balance: Option<f64>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<EquityCurve, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.equity_curve(&scope.unwrap_or_default(), starting_balance.unwrap_or(0.0)).await
+        .map_err(|e| format!("Failed to build equity curve: {}", e))
+}
+
+// Offline HTML performance report for the trades in a range (layout in
+// report.rs); `scope` narrows the trades like any other trade query
+#[tauri::command]
+async fn generate_report(
+    path: String,
+    range: ReportRange,
+    scope: Option<TradeQuery>,
+    options: Option<ReportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<ReportResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.generate_report(
+        std::path::Path::new(&path),
+        &range,
+        &scope.unwrap_or_default(),
+        &options.unwrap_or_default(),
+    ).await
+        .map_err(|e| format!("Failed to generate report: {}", e))
+}
+
+// The same report as a paginated PDF; `template` picks the monthly
+// statement, strategy review or single-trade recap layout (pdf_report.rs)
+#[tauri::command]
+async fn generate_pdf_report(
+    path: String,
+    template: PdfTemplate,
+    range: ReportRange,
+    scope: Option<TradeQuery>,
+    options: Option<ReportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<ReportResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.generate_pdf_report(
+        std::path::Path::new(&path),
+        template,
+        &range,
+        &scope.unwrap_or_default(),
+        &options.unwrap_or_default(),
+    ).await
+        .map_err(|e| format!("Failed to generate PDF report: {}", e))
+}
+
+// Store daily exchange rates (e.g. from the central bank) for the tax report
+#[tauri::command]
+async fn set_fx_rates(
+    rates: Vec<FxRate>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<usize, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.set_fx_rates(&rates).await
+        .map_err(|e| format!("Failed to save exchange rates: {}", e))
+}
+
+#[tauri::command]
+async fn get_fx_rates(
+    base: String,
+    quote: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<FxRate>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.get_fx_rates(&base, &quote).await
+        .map_err(|e| format!("Failed to load exchange rates: {}", e))
+}
+
+// Realized P/L of one calendar year by instrument, converted to the tax
+// currency; writes a CSV of lots and a summary next to it (tax_report.rs)
+#[tauri::command]
+async fn generate_tax_report(
+    path: String,
+    options: TaxReportOptions,
+    scope: Option<TradeQuery>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<TaxReportResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.generate_tax_report(
+        std::path::Path::new(&path),
+        &scope.unwrap_or_default(),
+        &options,
+    ).await
+        .map_err(|e| format!("Failed to generate tax report: {}", e))
+}
+
+// One markdown note per trade plus daily and strategy pages, for Obsidian;
+// re-exporting into the same folder only rewrites what changed (vault_export.rs)
+#[tauri::command]
+async fn export_vault(
+    path: String,
+    scope: Option<TradeQuery>,
+    options: Option<VaultExportOptions>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<VaultExportReport, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.export_vault(
+        std::path::Path::new(&path),
+        &scope.unwrap_or_default(),
+        &options.unwrap_or_default(),
+    ).await
+        .map_err(|e| format!("Failed to export vault: {}", e))
+}
+
+// Quick entry commands (quick_entry.rs)
+#[tauri::command]
+async fn preview_quick_entry(
+    text: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<QuickEntryPreview, String> {
+    let aliases = quick_entry::load_aliases().await
+        .map_err(|e| format!("Failed to load instrument aliases: {}", e))?;
+    
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+    
+    state.database.preview_quick_entry(&text, &aliases).await
+        .map_err(|e| format!("Failed to preview quick entry: {}", e))
+}
+
+// Parse and save in one step; fails with the parse errors instead of saving
+// a partial trade
+#[tauri::command]
+async fn create_quick_trade(
+    text: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<u32, String> {
+    let preview = preview_quick_entry(text, state.clone()).await?;
+    if let Some(message) = preview.error_message() {
+        return Err(message);
+    }
+    let trade = preview.trade.ok_or("Nothing to save")?;
+    
+    create_trade(trade, state, app_handle).await
+}
+
+#[tauri::command]
+async fn get_instrument_aliases() -> Result<BTreeMap<String, String>, String> {
+    quick_entry::load_aliases().await
+        .map_err(|e| format!("Failed to load instrument aliases: {}", e))
+}
+
+#[tauri::command]
+async fn save_instrument_alias(alias: String, symbol: String) -> Result<(), String> {
+    quick_entry::save_alias(&alias, &symbol).await
+        .map_err(|e| format!("Failed to save instrument alias: {}", e))
+}
+
+#[tauri::command]
+async fn delete_instrument_alias(alias: String) -> Result<bool, String> {
+    quick_entry::delete_alias(&alias).await
+        .map_err(|e| format!("Failed to delete instrument alias: {}", e))
+}
+
+// Tell windows about committed imports and refresh the analysis
+fn notify_imported(report: &ImportReport, app_handle: AppHandle) {
+    if !report.committed || report.imported + report.updated == 0 {
+        return;
+    }
+    
+    if let Err(e) = app_handle.emit_all("trades_imported", serde_json::json!({
+        "imported": report.imported,
+        "updated": report.updated,
+        "trade_ids": report.trade_ids,
+    })) {
+        log::error!("Failed to emit trades_imported event: {}", e);
+    }
+    
+    tokio::spawn(async move {
+        if let Err(e) = update_analysis(&app_handle).await {
+            log::error!("Failed to update analysis: {}", e);
+        }
+    });
+}
+
+// Workspace commands
+#[tauri::command]
+async fn list_workspaces(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<Workspace>, String> {
+    let state = state.lock().unwrap();
+    Ok(state.workspaces.list())
+}
+
+#[tauri::command]
+async fn get_active_workspace(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Option<Workspace>, String> {
+    let state = state.lock().unwrap();
+    Ok(state.workspaces.active().cloned())
+}
+
+#[tauri::command]
+async fn create_workspace(
+    name: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Workspace, String> {
     let mut state = state.lock().unwrap();
     
-    if !state.is_initialized {
-        return Err("Application not initialized".to_string());
+    let workspace = state.workspaces.create(&name).await
+        .map_err(|e| format!("Failed to create workspace: {}", e))?;
+    
+    if let Err(e) = app_handle.emit_all("workspaces_updated", state.workspaces.list()) {
+        log::error!("Failed to emit workspaces_updated event: {}", e);
     }
+    
+    Ok(workspace)
+}
 
-    match state.database.delete_trade(id).await {
-        Ok(()) => {
-            // Notify about deletion
-            if let Err(e) = app_handle.emit_all("trade_deleted", id) {
-                log::error!("Failed to emit trade_deleted event: {}", e);
+#[tauri::command]
+async fn open_workspace(
+    id: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Workspace, String> {
+    let mut state = state.lock().unwrap();
+    
+    let workspace = state.workspaces.get(&id).cloned()
+        .ok_or_else(|| format!("Workspace not found: {}", id))?;
+    
+    // The current journal stays open until the new one is ready
+    let opened = open_workspace_state(&state.workspaces.paths(&workspace)).await
+        .map_err(|e| format!("Failed to open workspace: {}", e))?;
+    state.workspaces.set_active(Some(&id)).await
+        .map_err(|e| format!("Failed to open workspace: {}", e))?;
+    
+    install_workspace_state(&mut state, opened).await;
+    start_workspace_services(&mut state).await;
+    state.is_initialized = true;
+    
+    let workspace = state.workspaces.active().cloned()
+        .ok_or_else(|| "Workspace not found".to_string())?;
+    
+    if let Err(e) = app_handle.emit_all("workspace_changed", &workspace) {
+        log::error!("Failed to emit workspace_changed event: {}", e);
+    }
+    
+    Ok(workspace)
+}
+
+#[tauri::command]
+async fn rename_workspace(
+    id: String,
+    name: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Workspace, String> {
+    let mut state = state.lock().unwrap();
+    
+    let workspace = state.workspaces.rename(&id, &name).await
+        .map_err(|e| format!("Failed to rename workspace: {}", e))?;
+    
+    if let Err(e) = app_handle.emit_all("workspaces_updated", state.workspaces.list()) {
+        log::error!("Failed to emit workspaces_updated event: {}", e);
+    }
+    
+    Ok(workspace)
+}
+
+#[tauri::command]
+async fn duplicate_workspace(
+    id: String,
+    name: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Workspace, String> {
+    let mut state = state.lock().unwrap();
+    
+    let source = state.workspaces.get(&id).cloned()
+        .ok_or_else(|| format!("Workspace not found: {}", id))?;
+    let workspace = state.workspaces.duplicate(&id, &name).await
+        .map_err(|e| format!("Failed to duplicate workspace: {}", e))?;
+    
+    // The open journal is snapshotted through its pool; closed ones are plain files
+    let target = state.workspaces.paths(&workspace);
+    let is_active = state.is_initialized
+        && state.workspaces.active().map(|w| w.id == source.id).unwrap_or(false);
+    
+    if is_active {
+        state.database.snapshot_to(&target).await
+            .map_err(|e| format!("Failed to copy workspace database: {}", e))?;
+    } else {
+        let source_paths = state.workspaces.paths(&source);
+        for (from, to) in [
+            (source_paths.database_file(), target.database_file()),
+            (source_paths.archive_file(), target.archive_file()),
+        ] {
+            if from.exists() {
+                tokio::fs::copy(&from, &to).await
+                    .map_err(|e| format!("Failed to copy workspace database: {}", e))?;
             }
-            
-            // Update analysis
-            tokio::spawn(async move {
-                if let Err(e) = update_analysis(&app_handle).await {
-                    log::error!("Failed to update analysis: {}", e);
-                }
-            });
-            
-            Ok(())
         }
-        Err(e) => Err(format!("Failed to delete trade: {}", e)),
     }
+    
+    if let Err(e) = app_handle.emit_all("workspaces_updated", state.workspaces.list()) {
+        log::error!("Failed to emit workspaces_updated event: {}", e);
+    }
+    
+    Ok(workspace)
 }
 
 #[tauri::command]
-async fn update_trade(
-    id: u32,
-    updates: HashMap<String, serde_json::Value>,
+async fn close_workspace(
     state: State<'_, Arc<Mutex<AppState>>>,
     app_handle: AppHandle,
-) -> Result<Trade, String> {
+) -> Result<(), String> {
     let mut state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
+    
+    close_workspace_state(&mut state).await;
+    state.workspaces.set_active(None).await
+        .map_err(|e| format!("Failed to close workspace: {}", e))?;
+    
+    if let Err(e) = app_handle.emit_all("workspace_closed", ()) {
+        log::error!("Failed to emit workspace_closed event: {}", e);
+    }
+    
+    Ok(())
+}
 
-    match state.database.update_trade(id, updates).await {
-        Ok(trade) => {
-            // Notify about update
-            if let Err(e) = app_handle.emit_all("trade_updated", &trade) {
-                log::error!("Failed to emit trade_updated event: {}", e);
-            }
-            
-            // Update analysis
-            tokio::spawn(async move {
-                if let Err(e) = update_analysis(&app_handle).await {
-                    log::error!("Failed to update analysis: {}", e);
-                }
-            });
-            
-            Ok(trade)
-        }
-        Err(e) => Err(format!("Failed to update trade: {}", e)),
+// Data root commands
+#[tauri::command]
+async fn get_data_root() -> Result<DataRoot, String> {
+    Ok(paths::current())
+}
+
+#[tauri::command]
+async fn migrate_data_root(
+    target: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<MigrationReport, String> {
+    let mut state = state.lock().unwrap();
+    
+    let platform_dir = platform_data_dir()
+        .ok_or_else(|| "Failed to migrate data: no platform data directory".to_string())?;
+    
+    // Database files must be at rest while they are copied
+    let was_open = state.is_initialized;
+    if was_open {
+        close_workspace_state(&mut state).await;
+    }
+    
+    let result = paths::migrate(std::path::Path::new(&target), &platform_dir).await
+        .map_err(|e| format!("Failed to migrate data: {}", e));
+    
+    // Reopen from wherever the data lives now, also after a failed attempt
+    state.workspaces = WorkspaceManager::new(paths::data_root());
+    state.workspaces.load().await
+        .map_err(|e| format!("Failed to load workspaces: {}", e))?;
+    if was_open {
+        let workspace = state.workspaces.active().cloned()
+            .ok_or_else(|| "Failed to reopen workspace: no workspace is open".to_string())?;
+        let opened = open_workspace_state(&state.workspaces.paths(&workspace)).await
+            .map_err(|e| format!("Failed to reopen workspace: {}", e))?;
+        install_workspace_state(&mut state, opened).await;
+        start_workspace_services(&mut state).await;
+        state.is_initialized = true;
     }
+    
+    let report = result?;
+    
+    if let Err(e) = app_handle.emit_all("data_root_changed", paths::current()) {
+        log::error!("Failed to emit data_root_changed event: {}", e);
+    }
+    
+    Ok(report)
 }
 
-// Schema management commands
 #[tauri::command]
-async fn get_schema(
-    entity_name: String,
+async fn list_plugins(
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<Vec<String>, String> {
     let state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    state.database.get_schema(&entity_name).await
-        .map_err(|e| format!("Failed to get schema: {}", e))
+    Ok(state.plugins.list_plugins())
 }
 
 #[tauri::command]
-async fn update_schema(
-    schema: serde_json::Value,
+async fn execute_plugin(
+    name: String,
+    input: serde_json::Value,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<PluginResult, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.plugins.execute_plugin(&name, input)
+        .map_err(|e| format!("Failed to execute plugin: {}", e))
+}
+
+#[tauri::command]
+async fn uninstall_plugin(
+    name: String,
+    retention: Option<DataRetention>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<u32, String> {
     let mut state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    state.database.update_schema(schema).await
-        .map_err(|e| format!("Failed to update schema: {}", e))
+    state.plugins.uninstall_plugin(&name, retention.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to uninstall plugin: {}", e))
 }
 
-// ICT Analysis commands
+// Plugin storage commands
 #[tauri::command]
-async fn get_ict_win_rates(
+async fn plugin_storage_get(
+    plugin: String,
+    key: String,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<Vec<HashMap<String, serde_json::Value>>, String> {
+) -> Result<Option<PluginEntry>, String> {
     let state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    state.analyzer.calculate_ict_win_rates().await
-        .map_err(|e| format!("Failed to calculate ICT win rates: {}", e))
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.get(&key).await
+        .map_err(|e| format!("Failed to read plugin data: {}", e))
 }
 
 #[tauri::command]
-async fn get_ict_heatmap_data(
+async fn plugin_storage_set(
+    plugin: String,
+    key: String,
+    value: PluginValue,
+    ttl_seconds: Option<i64>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<Vec<HashMap<String, serde_json::Value>>, String> {
+) -> Result<(), String> {
     let state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    state.analyzer.calculate_ict_heatmap().await
-        .map_err(|e| format!("Failed to calculate ICT heatmap: {}", e))
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.set(&key, &value, ttl_seconds.map(chrono::Duration::seconds)).await
+        .map_err(|e| format!("Failed to write plugin data: {}", e))
 }
 
-// Plugin system commands
 #[tauri::command]
-async fn list_plugins(
+async fn plugin_storage_compare_and_set(
+    plugin: String,
+    key: String,
+    expected: Option<PluginValue>,
+    value: PluginValue,
+    ttl_seconds: Option<i64>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<Vec<String>, String> {
+) -> Result<bool, String> {
     let state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    Ok(state.plugins.list_plugins())
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.compare_and_set(&key, expected.as_ref(), &value, ttl_seconds.map(chrono::Duration::seconds)).await
+        .map_err(|e| format!("Failed to write plugin data: {}", e))
 }
 
 #[tauri::command]
-async fn execute_plugin(
-    name: String,
-    input: serde_json::Value,
+async fn plugin_storage_delete(
+    plugin: String,
+    key: String,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<PluginResult, String> {
+) -> Result<bool, String> {
     let state = state.lock().unwrap();
     
     if !state.is_initialized {
         return Err("Application not initialized".to_string());
     }
 
-    state.plugins.execute_plugin(&name, input)
-        .map_err(|e| format!("Failed to execute plugin: {}", e))
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.delete(&key).await
+        .map_err(|e| format!("Failed to delete plugin data: {}", e))
+}
+
+#[tauri::command]
+async fn plugin_storage_list(
+    plugin: String,
+    prefix: Option<String>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<PluginEntry>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.list(prefix.as_deref().unwrap_or("")).await
+        .map_err(|e| format!("Failed to list plugin data: {}", e))
+}
+
+#[tauri::command]
+async fn plugin_storage_usage(
+    plugin: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<StorageUsage, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    let store = state.plugins.storage(&plugin)
+        .map_err(|e| format!("Failed to open plugin storage: {}", e))?;
+    store.usage().await
+        .map_err(|e| format!("Failed to read plugin storage usage: {}", e))
 }
 
 // Backup and restore commands
@@ -291,6 +1663,37 @@ async fn save_image(
         .map_err(|e| format!("Failed to save image: {}", e))
 }
 
+// Chart similarity search
+#[tauri::command]
+async fn find_similar_charts(
+    image_or_trade_id: ChartQuery,
+    k: Option<u32>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<SimilarChart>, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.find_similar_charts(image_or_trade_id, k.unwrap_or(10)).await
+        .map_err(|e| format!("Failed to find similar charts: {}", e))
+}
+
+#[tauri::command]
+async fn reindex_chart_images(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<u32, String> {
+    let state = state.lock().unwrap();
+    
+    if !state.is_initialized {
+        return Err("Application not initialized".to_string());
+    }
+
+    state.database.reindex_image_hashes().await
+        .map_err(|e| format!("Failed to reindex chart images: {}", e))
+}
+
 // Dashboard data
 #[tauri::command]
 async fn get_dashboard_data(
@@ -329,41 +1732,112 @@ async fn update_analysis(app_handle: &AppHandle) -> Result<(), String> {
 async fn initialize_app(state: &mut AppState) -> Result<(), Box<dyn std::error::Error>> {
     log::info!("Initializing application...");
     
-    // Initialize database
-    state.database.initialize().await?;
-    log::info!("Database initialized");
+    // Open the last used workspace
+    state.workspaces.load().await?;
+    let workspace = state.workspaces.active().cloned()
+        .ok_or("No workspace is open")?;
+    let opened = open_workspace_state(&state.workspaces.paths(&workspace)).await?;
+    install_workspace_state(state, opened).await;
     
-    // Load plugins
-    state.plugins.load_plugins()?;
-    log::info!("Plugins loaded: {}", state.plugins.list_plugins().len());
+    // Plugins and MetaTrader
+    start_workspace_services(state).await;
     
     // Initialize trading engine
     state.trading_engine.initialize().await?;
     log::info!("Trading engine initialized");
     
+    // Load configuration
+    state.config.load().await?;
+    log::info!("Configuration loaded");
+    
+    state.is_initialized = true;
+    log::info!("Application initialization completed successfully");
+    
+    Ok(())
+}
+
+// Everything scoped to one workspace, opened in full before it replaces the
+// current one
+struct WorkspaceState {
+    database: DatabaseState,
+    analyzer: Analyzer,
+    backup_manager: BackupManager,
+}
+
+async fn open_workspace_state(paths: &WorkspacePaths) -> Result<WorkspaceState, Box<dyn std::error::Error>> {
+    log::info!("Opening workspace at {:?}", paths.root());
+    
+    // Initialize database
+    let mut database = DatabaseState::open(paths.clone())?;
+    database.initialize().await?;
+    log::info!("Database initialized");
+    
     // Initialize analyzer
-    state.analyzer.initialize().await?;
+    let mut analyzer = Analyzer::new(database.pool());
+    analyzer.set_display_timezone(database.time_settings().display_tz()).await;
+    analyzer.initialize().await?;
     log::info!("Analyzer initialized");
     
     // Initialize backup manager
-    state.backup_manager.initialize().await?;
+    let mut backup_manager = BackupManager::new(paths.root(), paths.backups_dir());
+    backup_manager.initialize().await?;
     log::info!("Backup manager initialized");
     
+    Ok(WorkspaceState { database, analyzer, backup_manager })
+}
+
+// Swap in an opened workspace and close the database it replaces
+async fn install_workspace_state(state: &mut AppState, opened: WorkspaceState) {
+    let previous = std::mem::replace(&mut state.database, opened.database);
+    state.analyzer = opened.analyzer;
+    state.backup_manager = opened.backup_manager;
+    
+    if let Err(e) = previous.close().await {
+        log::error!("Failed to close database: {}", e);
+    }
+}
+
+// (Re)start the services that hold on to the open workspace: plugins keep
+// their storage in its database and importers come partly from plugins, and
+// MetaTrader caches positions and account data of the journal it synced
+async fn start_workspace_services(state: &mut AppState) {
+    if let Err(e) = state.plugins.shutdown_all() {
+        log::error!("Failed to shut down plugins: {}", e);
+    }
+    state.plugins = PluginManager::new(paths::plugins_dir());
+    state.plugins.set_storage_pool(state.database.pool());
+    match state.plugins.load_plugins() {
+        Ok(()) => log::info!("Plugins loaded: {}", state.plugins.list_plugins().len()),
+        Err(e) => log::error!("Failed to load plugins: {}", e),
+    }
+    
+    // Built-in broker formats plus any a plugin brings
+    state.importers = ImporterRegistry::with_builtins();
+    for (plugin, importer) in state.plugins.importers() {
+        state.importers.register(ImporterSource::Plugin(plugin), importer);
+    }
+    
     // Try to connect to MetaTrader
+    state.mt_integration.shutdown();
+    state.mt_integration = MetaTraderIntegration::new();
     if let Err(e) = state.mt_integration.initialize().await {
         log::warn!("MetaTrader integration failed: {}", e);
     } else {
         log::info!("MetaTrader integration initialized");
     }
+}
+
+// OS data directory, e.g. ~/.local/share/Meta-Driven Trading Journal
+fn platform_data_dir() -> Option<std::path::PathBuf> {
+    tauri::api::path::data_dir().map(|dir| dir.join(paths::APP_DIR_NAME))
+}
+
+async fn close_workspace_state(state: &mut AppState) {
+    state.is_initialized = false;
     
-    // Load configuration
-    state.config.load().await?;
-    log::info!("Configuration loaded");
-    
-    state.is_initialized = true;
-    log::info!("Application initialization completed successfully");
-    
-    Ok(())
+    if let Err(e) = state.database.close().await {
+        log::error!("Failed to close database: {}", e);
+    }
 }
 
 // System tray configuration
@@ -614,6 +2088,11 @@ async fn start_background_tasks<R: Runtime>(app_handle: AppHandle<R>) -> Result<
                         log::warn!("Plugin {} health check failed: {}", plugin, e);
                     }
                 }
+                
+                // Drop plugin data whose TTL has passed
+                if let Err(e) = state.plugins.purge_expired_storage().await {
+                    log::warn!("Plugin storage cleanup failed: {}", e);
+                }
             }
         }
     });
@@ -653,6 +2132,11 @@ fn main() {
     // Initialize logger
     env_logger::init();
     
+    // Resolve the data root before anything touches the file system
+    let args: Vec<String> = std::env::args().collect();
+    paths::init(paths::resolve(&args, platform_data_dir()))
+        .expect("Failed to create data directory");
+    
     // Build Tauri application
     let app = Builder::default()
         .setup(|app| {
@@ -670,7 +2154,7 @@ fn main() {
             handle_window_events(&window);
             
             // Set window position and size from config
-            if let Ok(config) = std::fs::read_to_string("config/window.json") {
+            if let Ok(config) = std::fs::read_to_string(paths::config_dir().join("window.json")) {
                 if let Ok(window_config) = serde_json::from_str::<HashMap<String, serde_json::Value>>(&config) {
                     if let (Some(x), Some(y), Some(width), Some(height)) = (
                         window_config.get("x").and_then(|v| v.as_u64()),
@@ -691,17 +2175,80 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             create_trade,
             get_all_trades,
+            get_trades_page,
+            stream_trades,
             delete_trade,
             update_trade,
+            bulk_update_trades,
+            bulk_delete_trades,
+            find_duplicate_trades,
+            merge_trades,
             get_schema,
             update_schema,
             get_ict_win_rates,
             get_ict_heatmap_data,
             list_plugins,
             execute_plugin,
+            uninstall_plugin,
+            plugin_storage_get,
+            plugin_storage_set,
+            plugin_storage_compare_and_set,
+            plugin_storage_delete,
+            plugin_storage_list,
+            plugin_storage_usage,
             create_backup,
             restore_from_backup,
             save_image,
+            find_similar_charts,
+            check_integrity,
+            get_time_settings,
+            update_time_settings,
+            normalize_trade_timestamps,
+            get_trade_statistics,
+            rebuild_trade_statistics,
+            archive_trades,
+            unarchive_trades,
+            set_analysis_include_archived,
+            get_changes_since,
+            compact_change_log,
+            preview_csv_import,
+            list_import_profiles,
+            save_import_profile,
+            delete_import_profile,
+            import_csv,
+            import_mt_statement,
+            list_importers,
+            import_file,
+            export_journal,
+            import_journal,
+            export_trades,
+            generate_report,
+            generate_pdf_report,
+            set_fx_rates,
+            get_fx_rates,
+            generate_tax_report,
+            export_vault,
+            preview_quick_entry,
+            create_quick_trade,
+            get_instrument_aliases,
+            save_instrument_alias,
+            delete_instrument_alias,
+            list_email_templates,
+            save_email_template,
+            delete_email_template,
+            preview_email,
+            import_emails,
+            equity_curve,
+            list_workspaces,
+            get_active_workspace,
+            create_workspace,
+            open_workspace,
+            rename_workspace,
+            duplicate_workspace,
+            close_workspace,
+            get_data_root,
+            migrate_data_root,
+            reindex_chart_images,
             get_dashboard_data
         ])
         .build(tauri::generate_context!())
@@ -730,8 +2277,8 @@ fn main() {
                             });
                             
                             if let Ok(config_str) = serde_json::to_string_pretty(&window_config) {
-                                let _ = std::fs::create_dir_all("config");
-                                let _ = std::fs::write("config/window.json", config_str);
+                                let _ = std::fs::create_dir_all(paths::config_dir());
+                                let _ = std::fs::write(paths::config_dir().join("window.json"), config_str);
                             }
                         }
                     }
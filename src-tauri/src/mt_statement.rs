@@ -0,0 +1,552 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::database::NewTrade;
+use crate::importer::{
+    BrokerImporter, CashFlow, ImportContext, ImportFile, ImportRow, ImportRowError, ImportedTrade, ParsedImport, TicketScope,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    // "Detailed Statement" saved from the MT4 terminal
+    Mt4,
+    // "Report" (trade history) saved from the MT5 terminal
+    Mt5,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct StatementOptions {
+    // Broker symbol -> journal symbol, from MTConnectionConfig.symbol_mapping
+    pub symbol_mapping: HashMap<String, String>,
+    // Terminal server time zone; the broker timezone when unset
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Closed,
+    Open,
+    Deals,
+    Other,
+}
+
+// Section titles in both layouts, compared without a trailing colon
+const SECTIONS: &[(&str, Section)] = &[
+    ("closed transactions", Section::Closed),
+    ("open trades", Section::Open),
+    ("positions", Section::Closed),
+    ("open positions", Section::Open),
+    ("deals", Section::Deals),
+];
+
+// Broker of statement tickets, shared by MT4 and MT5
+pub const BROKER: &str = "metatrader";
+
+// Balance-type rows that move money without being a trade
+const CASH_FLOW_TYPES: &[&str] = &["balance", "credit", "bonus", "correction", "charge", "commission", "deposit", "withdrawal"];
+
+pub fn detect(html: &str) -> Option<StatementFormat> {
+    let lower = html.to_lowercase();
+
+    if lower.contains("closed transactions:") {
+        Some(StatementFormat::Mt4)
+    } else if lower.contains("trade history report") || (lower.contains(">positions<") && lower.contains(">deals<")) {
+        Some(StatementFormat::Mt5)
+    } else {
+        None
+    }
+}
+
+// Text of every <tr> as a list of cells. Cells spanning several columns are
+// padded with empty cells so data rows line up with their header.
+fn table_rows(html: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<(String, usize)> = None;
+    let mut rest = html;
+
+    fn finish_cell(row: &mut Option<Vec<String>>, cell: &mut Option<(String, usize)>) {
+        if let (Some(row), Some((text, span))) = (row.as_mut(), cell.take()) {
+            row.push(decode_entities(&text));
+            row.extend(std::iter::repeat(String::new()).take(span.saturating_sub(1)));
+        }
+    }
+
+    while let Some(start) = rest.find('<') {
+        if let Some((text, _)) = cell.as_mut() {
+            text.push_str(&rest[..start]);
+        }
+
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag.trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match (name.as_str(), closing) {
+            ("style" | "script", false) => {
+                let close = format!("</{}", name);
+                rest = rest.to_ascii_lowercase().find(&close).map_or("", |i| &rest[i..]);
+            }
+            ("tr", false) => {
+                finish_cell(&mut row, &mut cell);
+                rows.extend(row.replace(Vec::new()));
+            }
+            ("tr", true) | ("table", true) => {
+                finish_cell(&mut row, &mut cell);
+                rows.extend(row.take());
+            }
+            ("td" | "th", false) => {
+                finish_cell(&mut row, &mut cell);
+                cell = Some((String::new(), colspan(tag)));
+            }
+            ("td" | "th", true) => finish_cell(&mut row, &mut cell),
+            ("br", _) => {
+                if let Some((text, _)) = cell.as_mut() {
+                    text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    finish_cell(&mut row, &mut cell);
+    rows.extend(row);
+    rows
+}
+
+fn colspan(tag: &str) -> usize {
+    let lower = tag.to_lowercase();
+    lower.find("colspan")
+        .map(|i| &lower[i + "colspan".len()..])
+        .and_then(|rest| {
+            let digits: String = rest.trim_start_matches(|c: char| c == '=' || c == '"' || c == '\'' || c.is_whitespace())
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect();
+            digits.parse().ok()
+        })
+        .unwrap_or(1)
+        .clamp(1, 64)
+}
+
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let entity_end = rest[start..].find(';').filter(|end| *end <= 10);
+
+        let replacement = entity_end.and_then(|end| {
+            let entity = &rest[start + 1..start + end];
+            match entity {
+                "nbsp" => Some(' '),
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            }
+        });
+
+        match (replacement, entity_end) {
+            (Some(c), Some(end)) => {
+                decoded.push(c);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Column positions of a section header; repeated names (Time, Price) are
+// told apart by occurrence: the first is the open, the second the close
+struct Header {
+    names: Vec<String>,
+}
+
+impl Header {
+    fn new(cells: &[String]) -> Self {
+        Self {
+            names: cells.iter().map(|c| c.to_lowercase().replace(' ', "")).collect(),
+        }
+    }
+
+    fn find(&self, candidates: &[&str], occurrence: usize) -> Option<usize> {
+        self.names.iter()
+            .enumerate()
+            .filter(|(_, name)| candidates.contains(&name.as_str()))
+            .nth(occurrence)
+            .map(|(i, _)| i)
+    }
+}
+
+struct Row<'a> {
+    number: usize,
+    cells: &'a [String],
+    header: &'a Header,
+}
+
+impl<'a> Row<'a> {
+    fn text(&self, candidates: &[&str], occurrence: usize) -> Option<&'a str> {
+        self.header.find(candidates, occurrence)
+            .and_then(|i| self.cells.get(i))
+            .map(|cell| cell.trim())
+            .filter(|cell| !cell.is_empty())
+    }
+
+    fn number(&self, candidates: &[&str], occurrence: usize) -> Result<Option<f64>, ImportRowError> {
+        self.text(candidates, occurrence)
+            .map(|value| {
+                parse_number(value).ok_or_else(|| ImportRowError {
+                    row: self.number,
+                    field: Some(candidates[0].to_string()),
+                    message: format!("'{}' is not a number", value),
+                })
+            })
+            .transpose()
+    }
+
+    // Balance rows carry the amount under Profit; without that column it is
+    // the last number in the row, after a wide comment cell
+    fn amount(&self) -> Option<f64> {
+        self.text(&["profit"], 0)
+            .and_then(parse_number)
+            .or_else(|| self.cells.iter().rev().find_map(|c| parse_number(c)))
+    }
+}
+
+// "1 234.56", "0.10 / 0.10" (MT5 filled / requested volume)
+fn parse_number(value: &str) -> Option<f64> {
+    let first = value.split('/').next()?;
+    first.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+pub fn parse(html: &str, options: &StatementOptions) -> Result<ParsedImport, String> {
+    if detect(html).is_none() {
+        return Err("Not a MetaTrader 4 or 5 statement".to_string());
+    }
+
+    let rows = table_rows(html);
+    let mut parsed = ParsedImport::default();
+    let mut section = Section::Other;
+    let mut header: Option<Header> = None;
+
+    for (index, cells) in rows.iter().enumerate() {
+        let number = index + 1;
+        let filled: Vec<&String> = cells.iter().filter(|c| !c.trim().is_empty()).collect();
+
+        // A lone cell is a section title (or a note that ends the section)
+        if filled.len() == 1 {
+            let title = filled[0].trim().trim_end_matches(':').to_lowercase();
+            section = SECTIONS.iter()
+                .find(|(name, _)| *name == title)
+                .map_or(Section::Other, |(_, section)| *section);
+            header = None;
+            continue;
+        }
+        if section == Section::Other || filled.len() < 3 {
+            continue;
+        }
+
+        let header = match &header {
+            Some(header) => header,
+            None => {
+                header = Some(Header::new(cells));
+                continue;
+            }
+        };
+
+        let row = Row { number, cells, header };
+        let ticket = match row.text(&["ticket", "position", "deal"], 0) {
+            Some(ticket) if ticket.chars().all(|c| c.is_ascii_digit()) => ticket.to_string(),
+            // Totals and sub-headers have no ticket
+            _ => continue,
+        };
+        let kind = row.text(&["type"], 0).unwrap_or_default().to_lowercase();
+
+        if CASH_FLOW_TYPES.contains(&kind.as_str()) {
+            if let Some(flow) = cash_flow(&row, &ticket, &kind, options) {
+                parsed.cash_flows.push((number, flow));
+            }
+            continue;
+        }
+        // Pending orders (buy limit, sell stop...) never became trades, and
+        // MT5 deals are already covered by positions
+        if section == Section::Deals || (kind != "buy" && kind != "sell") {
+            continue;
+        }
+
+        parsed.total_rows += 1;
+        match trade(&row, ticket, &kind, section == Section::Closed, options) {
+            Ok(record) => parsed.rows.push(ImportRow { row: number, record }),
+            Err(error) => parsed.errors.push(error),
+        }
+    }
+
+    // Tickets are numbered per trade server; the account keeps two accounts'
+    // statements apart
+    parsed.set_scope(&TicketScope::new(BROKER, statement_account(&rows)));
+    Ok(parsed)
+}
+
+// Account number from the statement header: "Account: 1234567" in MT4, an
+// "Account:" cell followed by "1234567 (USD, Server, real)" in MT5
+fn statement_account(rows: &[Vec<String>]) -> Option<String> {
+    rows.iter().find_map(|cells| {
+        let cells: Vec<&str> = cells.iter().map(|c| c.trim()).filter(|c| !c.is_empty()).collect();
+        let at = cells.iter().position(|c| c.to_lowercase().starts_with("account:"))?;
+        let inline = cells[at]["account:".len()..].trim();
+        let value = if inline.is_empty() { cells.get(at + 1)? } else { inline };
+        let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+        (!digits.is_empty()).then_some(digits)
+    })
+}
+
+fn trade(
+    row: &Row,
+    ticket: String,
+    kind: &str,
+    closed: bool,
+    options: &StatementOptions,
+) -> Result<ImportedTrade, ImportRowError> {
+    let missing = |field: &str| ImportRowError {
+        row: row.number,
+        field: Some(field.to_string()),
+        message: format!("Missing {}", field),
+    };
+
+    let symbol = row.text(&["symbol", "item"], 0).ok_or_else(|| missing("symbol"))?.to_uppercase();
+    let symbol = options.symbol_mapping.iter()
+        .find(|(broker, _)| broker.eq_ignore_ascii_case(&symbol))
+        .map_or(symbol.clone(), |(_, journal)| journal.clone());
+    let entry_time = row.text(&["opentime", "time"], 0).ok_or_else(|| missing("entry_time"))?;
+
+    // Taxes (MT4) and fees (MT5) are charged like commission
+    let commission = [row.number(&["commission"], 0)?, row.number(&["taxes"], 0)?, row.number(&["fee"], 0)?]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a + b);
+
+    let trade = NewTrade {
+        symbol,
+        trade_type: if kind == "buy" { "Buy" } else { "Sell" }.to_string(),
+        volume: row.number(&["volume", "size"], 0)?.ok_or_else(|| missing("volume"))?,
+        entry_price: row.number(&["price"], 0)?.ok_or_else(|| missing("entry_price"))?,
+        // MetaTrader prints 0 for a level that was never set
+        sl: row.number(&["s/l"], 0)?.filter(|level| *level != 0.0),
+        tp: row.number(&["t/p"], 0)?.filter(|level| *level != 0.0),
+        entry_time: entry_time.to_string(),
+        notes: row.text(&["comment"], 0).map(str::to_string),
+        commission,
+        swap: row.number(&["swap"], 0)?,
+        ict_pattern: None,
+        pattern_type: None,
+        pattern_size: None,
+        pattern_timeframe: None,
+        pattern_combination: None,
+        chart_explanation: None,
+        strategy_name: None,
+        emotion: None,
+        confidence_level: None,
+        market_condition: None,
+        session: None,
+        entry_image: None,
+        exit_image: None,
+        analysis_image: None,
+        rsi: None,
+        macd: None,
+        moving_average: None,
+        support_level: None,
+        resistance_level: None,
+        source_timezone: options.timezone.clone(),
+    };
+
+    let mut record = ImportedTrade::new(trade);
+    record.external_id = Some(ticket);
+    if closed {
+        // Open MT4 rows carry the market price in the second Price column
+        record.exit_time = row.text(&["closetime", "time"], if row.header.find(&["closetime"], 0).is_some() { 0 } else { 1 })
+            .map(str::to_string);
+        record.exit_price = row.number(&["price"], 1)?;
+        record.profit = row.number(&["profit"], 0)?;
+    }
+
+    Ok(record)
+}
+
+fn cash_flow(row: &Row, ticket: &str, kind: &str, options: &StatementOptions) -> Option<CashFlow> {
+    let amount = row.amount()?;
+    let kind = match kind {
+        "balance" if amount >= 0.0 => "deposit",
+        "balance" => "withdrawal",
+        other => other,
+    };
+
+    // MT4 has no comment column; its comment is the first text cell after the type
+    let comment = row.text(&["comment"], 0)
+        .or_else(|| {
+            row.header.find(&["type"], 0).and_then(|i| {
+                row.cells.iter().skip(i + 1).map(|c| c.trim()).find(|c| !c.is_empty() && parse_number(c).is_none())
+            })
+        })
+        .map(str::to_string);
+
+    Some(CashFlow {
+        kind: kind.to_string(),
+        amount,
+        occurred_at: row.text(&["opentime", "time"], 0)?.to_string(),
+        comment,
+        external_id: Some(ticket.to_string()),
+        scope: TicketScope::default(),
+        source_timezone: options.timezone.clone(),
+    })
+}
+
+pub struct MetaTraderImporter;
+
+impl BrokerImporter for MetaTraderImporter {
+    fn id(&self) -> &str {
+        BROKER
+    }
+
+    fn name(&self) -> &str {
+        "MetaTrader 4/5 statement"
+    }
+
+    fn detect(&self, file: &ImportFile) -> u8 {
+        if detect(file.text()).is_some() { 90 } else { 0 }
+    }
+
+    fn parse(&self, file: &ImportFile, context: &ImportContext) -> Result<ParsedImport, String> {
+        let options = StatementOptions {
+            symbol_mapping: context.symbol_mapping.clone(),
+            timezone: context.timezone.clone(),
+        };
+        parse(file.text(), &options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MT4: &str = r##"<html><head><style>td { font: 8pt Tahoma; }</style></head><body>
+<table>
+<tr align=left><td colspan=2><b>Account: 2089123</b></td><td colspan=5><b>Name: Demo</b></td><td colspan=6><b>Currency: USD</b></td></tr>
+<tr align=left><td colspan=13><b>Closed Transactions:</b></td></tr>
+<tr align=center><td>Ticket</td><td nowrap>Open Time</td><td>Type</td><td>Size</td><td>Item</td><td>Price</td><td>S / L</td><td>T / P</td><td nowrap>Close Time</td><td>Price</td><td>Commission</td><td>Taxes</td><td>Swap</td><td>Profit</td></tr>
+<tr align=right><td>5001</td><td class=msdate>2024.03.01 08:00:00</td><td>balance</td><td colspan=10 align=left>Deposit&nbsp;#1</td><td class=mspt>10&nbsp;000.00</td></tr>
+<tr align=right><td title="#1">5002</td><td class=msdate>2024.03.01 09:15:22</td><td>sell</td><td class=mspt>0.50</td><td>xauusd.m</td><td>2031.40</td><td>2036.00</td><td>2018.00</td><td class=msdate>2024.03.01 11:02:10</td><td>2018.00</td><td>-3.50</td><td>0.00</td><td>-1.20</td><td class=mspt>670.00</td></tr>
+<tr align=right><td>5003</td><td>2024.03.01 10:00:00</td><td>buy limit</td><td>1.00</td><td>eurusd</td><td>1.0800</td><td>0.0000</td><td>0.0000</td><td>2024.03.01 12:00:00</td><td>1.0850</td><td colspan=4>cancelled</td></tr>
+<tr align=right><td colspan=10>&nbsp;</td><td>-3.50</td><td>0.00</td><td>-1.20</td><td>670.00</td></tr>
+<tr align=left><td colspan=13><b>Open Trades:</b></td></tr>
+<tr align=center><td>Ticket</td><td nowrap>Open Time</td><td>Type</td><td>Size</td><td>Item</td><td>Price</td><td>S / L</td><td>T / P</td><td nowrap>&nbsp;</td><td>Price</td><td>Commission</td><td>Taxes</td><td>Swap</td><td>Profit</td></tr>
+<tr align=right><td>5004</td><td>2024.03.02 14:00:00</td><td>buy</td><td>1.00</td><td>eurusd</td><td>1.0820</td><td>1.0790</td><td>1.0900</td><td>&nbsp;</td><td>1.0835</td><td>0.00</td><td>0.00</td><td>0.00</td><td>150.00</td></tr>
+<tr align=left><td colspan=13><b>Working Orders:</b></td></tr>
+</table></body></html>"##;
+
+    const MT5: &str = r#"<html><body><div><b>Trade History Report</b></div><table>
+<tr align="center"><th colspan="14"><div><b>Positions</b></div></th></tr>
+<tr align="center"><td>Time</td><td>Position</td><td>Symbol</td><td>Type</td><td>Volume</td><td>Price</td><td>S / L</td><td>T / P</td><td>Time</td><td>Price</td><td>Commission</td><td>Swap</td><td>Profit</td></tr>
+<tr align="right"><td>2024.03.04 07:30:00</td><td>7001</td><td>GBPUSD</td><td>buy</td><td>0.2 / 0.2</td><td>1.26500</td><td></td><td>1.27000</td><td>2024.03.04 09:00:00</td><td>1.26800</td><td>-1.40</td><td>0.00</td><td>60.00</td></tr>
+<tr align="right"><td>2024.03.04 08:00:00</td><td>7002</td><td>GBPUSD</td><td>sell</td><td>oops</td><td>1.26500</td><td></td><td></td><td>2024.03.04 09:00:00</td><td>1.26800</td><td>0</td><td>0</td><td>-60.00</td></tr>
+<tr align="center"><th colspan="14"><div><b>Deals</b></div></th></tr>
+<tr align="center"><td>Time</td><td>Deal</td><td>Symbol</td><td>Type</td><td>Direction</td><td>Volume</td><td>Price</td><td>Order</td><td>Commission</td><td>Fee</td><td>Swap</td><td>Profit</td><td>Balance</td><td>Comment</td></tr>
+<tr align="right"><td>2024.03.04 06:00:00</td><td>9001</td><td></td><td>balance</td><td></td><td></td><td></td><td></td><td>0.00</td><td>0.00</td><td>0.00</td><td>-250.00</td><td>9 750.00</td><td>Withdraw</td></tr>
+<tr align="right"><td>2024.03.04 07:30:00</td><td>9002</td><td>GBPUSD</td><td>buy</td><td>in</td><td>0.2</td><td>1.26500</td><td>8001</td><td>-0.70</td><td>0.00</td><td>0.00</td><td>0.00</td><td>9 749.30</td><td></td></tr>
+</table></body></html>"#;
+
+    #[test]
+    fn table_rows_decode_entities_and_expand_colspan() {
+        let rows = table_rows("<table><tr><td colspan=\"3\">A&amp;B&nbsp;&#67;</td><TD>x<br>y</TD></tr></table>");
+        assert_eq!(rows, vec![vec!["A&B C".to_string(), String::new(), String::new(), "x y".to_string()]]);
+
+        let utf16: Vec<u8> = [0xff, 0xfe].into_iter()
+            .chain("<html>".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(crate::importer::decode_text(&utf16), "<html>");
+    }
+
+    #[test]
+    fn mt4_statement_yields_closed_and_open_trades_and_deposits() {
+        let options = StatementOptions {
+            symbol_mapping: HashMap::from([("XAUUSD.M".to_string(), "XAUUSD".to_string())]),
+            timezone: Some("EET".to_string()),
+        };
+
+        assert_eq!(detect(MT4), Some(StatementFormat::Mt4));
+        let parsed = parse(MT4, &options).unwrap();
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.total_rows, 2);
+
+        let closed = &parsed.rows[0].record;
+        assert_eq!(closed.external_id.as_deref(), Some("5002"));
+        assert_eq!(closed.trade.symbol, "XAUUSD");
+        assert_eq!(closed.trade.trade_type, "Sell");
+        assert_eq!(closed.trade.entry_time, "2024.03.01 09:15:22");
+        assert_eq!(closed.trade.commission, Some(-3.5));
+        assert_eq!(closed.trade.swap, Some(-1.2));
+        assert_eq!(closed.trade.source_timezone.as_deref(), Some("EET"));
+        assert_eq!(closed.exit_time.as_deref(), Some("2024.03.01 11:02:10"));
+        assert_eq!((closed.exit_price, closed.profit), (Some(2018.0), Some(670.0)));
+        assert_eq!(closed.scope, TicketScope::new(BROKER, Some("2089123".to_string())));
+
+        let open = &parsed.rows[1].record;
+        assert_eq!(open.external_id.as_deref(), Some("5004"));
+        assert_eq!((open.exit_price, open.exit_time.as_deref()), (None, None));
+
+        let (_, deposit) = &parsed.cash_flows[0];
+        assert_eq!((deposit.kind.as_str(), deposit.amount), ("deposit", 10000.0));
+        assert_eq!(deposit.comment.as_deref(), Some("Deposit #1"));
+    }
+
+    #[test]
+    fn mt5_report_yields_positions_and_balance_deals() {
+        assert_eq!(detect(MT5), Some(StatementFormat::Mt5));
+        let parsed = parse(MT5, &StatementOptions::default()).unwrap();
+
+        assert_eq!(parsed.total_rows, 2);
+        assert_eq!(parsed.rows.len(), 1);
+        let position = &parsed.rows[0].record;
+        assert_eq!(position.external_id.as_deref(), Some("7001"));
+        assert_eq!(position.trade.volume, 0.2);
+        assert_eq!(position.trade.sl, None);
+        assert_eq!(position.exit_time.as_deref(), Some("2024.03.04 09:00:00"));
+        assert_eq!(position.profit, Some(60.0));
+
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].field.as_deref(), Some("volume"));
+
+        assert_eq!(parsed.cash_flows.len(), 1);
+        let (_, withdrawal) = &parsed.cash_flows[0];
+        assert_eq!(withdrawal.kind, "withdrawal");
+        assert_eq!(withdrawal.external_id.as_deref(), Some("9001"));
+        assert_eq!(withdrawal.comment.as_deref(), Some("Withdraw"));
+    }
+}
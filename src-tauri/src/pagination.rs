@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::Trade;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 200;
+pub const MAX_PAGE_SIZE: u32 = 5000;
+const CURSOR_VERSION: &str = "1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    // Newest first unless the caller asks otherwise
+    pub fn parse(order: Option<&str>) -> Option<Self> {
+        match order.map(|o| o.to_ascii_lowercase()).as_deref() {
+            None | Some("desc") => Some(SortDirection::Descending),
+            Some("asc") => Some(SortDirection::Ascending),
+            Some(_) => None,
+        }
+    }
+
+    pub fn sql(self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        }
+    }
+
+    fn comparison(self) -> &'static str {
+        match self {
+            SortDirection::Ascending => ">",
+            SortDirection::Descending => "<",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "a",
+            SortDirection::Descending => "d",
+        }
+    }
+}
+
+// Position after the last trade of a page, keyed on (entry_time, id) so rows
+// inserted between requests never shift the next page the way OFFSET does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeCursor {
+    pub entry_time: String,
+    pub id: u32,
+    pub direction: SortDirection,
+}
+
+impl TradeCursor {
+    pub fn after(trade: &Trade, direction: SortDirection) -> Self {
+        TradeCursor {
+            entry_time: trade.new_trade.entry_time.clone(),
+            id: trade.id,
+            direction,
+        }
+    }
+
+    // Opaque to the frontend; hex keeps it safe to pass around as-is
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}|{}|{}", CURSOR_VERSION, self.direction.tag(), self.id, self.entry_time);
+        raw.bytes().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        if value.len() % 2 != 0 {
+            return None;
+        }
+
+        let bytes = (0..value.len())
+            .step_by(2)
+            .map(|i| value.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+            .collect::<Option<Vec<u8>>>()?;
+        let raw = String::from_utf8(bytes).ok()?;
+
+        let mut parts = raw.splitn(4, '|');
+        if parts.next()? != CURSOR_VERSION {
+            return None;
+        }
+        let direction = match parts.next()? {
+            "a" => SortDirection::Ascending,
+            "d" => SortDirection::Descending,
+            _ => return None,
+        };
+        let id = parts.next()?.parse().ok()?;
+        let entry_time = parts.next()?.to_string();
+
+        Some(TradeCursor { entry_time, id, direction })
+    }
+
+    // Keyset predicate appended to a "WHERE 1=1..." trade filter
+    pub fn condition(&self) -> (String, Vec<String>) {
+        let op = self.direction.comparison();
+        (
+            format!(" AND (entry_time {op} ? OR (entry_time = ? AND id {op} ?))", op = op),
+            vec![self.entry_time.clone(), self.entry_time.clone(), self.id.to_string()],
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradePage {
+    pub trades: Vec<Trade>,
+    pub next_cursor: Option<String>,
+    pub total_count: i64,
+}
+
+pub fn page_size(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = TradeCursor {
+            entry_time: "2024-03-01T08:00:00Z".to_string(),
+            id: 4812,
+            direction: SortDirection::Descending,
+        };
+
+        let encoded = cursor.encode();
+        assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(TradeCursor::decode(&encoded), Some(cursor));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert_eq!(TradeCursor::decode(""), None);
+        assert_eq!(TradeCursor::decode("abc"), None);
+        assert_eq!(TradeCursor::decode("zz"), None);
+
+        let wrong_version: String = "9|d|1|2024-03-01T08:00:00Z".bytes().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(TradeCursor::decode(&wrong_version), None);
+    }
+
+    #[test]
+    fn sort_direction_and_page_size_defaults() {
+        assert_eq!(SortDirection::parse(None), Some(SortDirection::Descending));
+        assert_eq!(SortDirection::parse(Some("ASC")), Some(SortDirection::Ascending));
+        assert_eq!(SortDirection::parse(Some("sideways")), None);
+        assert_eq!(page_size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(0)), 1);
+        assert_eq!(page_size(Some(1_000_000)), MAX_PAGE_SIZE);
+    }
+}
@@ -0,0 +1,481 @@
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Row};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::fs;
+
+use crate::workspace::WorkspaceManager;
+
+pub const APP_DIR_NAME: &str = "Meta-Driven Trading Journal";
+const DATA_DIR_ARG: &str = "--data-dir";
+const PORTABLE_ARG: &str = "--portable";
+const PORTABLE_MARKER: &str = "portable";
+const PORTABLE_DIR: &str = "data";
+const LOCATION_FILE: &str = "data_location.json";
+
+static DATA_ROOT: RwLock<Option<DataRoot>> = RwLock::new(None);
+
+// Where the data root came from, highest precedence first
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataRootSource {
+    Argument,
+    Portable,
+    Migrated,
+    Platform,
+}
+
+// The single directory every module resolves its files against: workspaces,
+// app-level config, installed plugins and logs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DataRoot {
+    pub path: PathBuf,
+    pub source: DataRootSource,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationReport {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    pub workspaces_relocated: usize,
+    pub source_removed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedLocation {
+    path: PathBuf,
+}
+
+// Resolve the data root for this process. `platform_dir` is the OS default
+// (e.g. ~/.local/share/Meta-Driven Trading Journal); it also holds the
+// pointer written by `migrate` so a moved data set is found on next start.
+pub fn resolve(args: &[String], platform_dir: Option<PathBuf>) -> DataRoot {
+    let exe_dir = std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let marker = exe_dir.as_ref()
+        .map_or(false, |dir| dir.join(PORTABLE_MARKER).exists());
+    let platform_dir = platform_dir.unwrap_or_else(|| PathBuf::from("."));
+    let saved = read_saved_location(&platform_dir);
+
+    resolve_with(args, exe_dir.as_deref(), marker, saved, platform_dir)
+}
+
+fn resolve_with(
+    args: &[String],
+    exe_dir: Option<&Path>,
+    portable_marker: bool,
+    saved: Option<PathBuf>,
+    platform_dir: PathBuf,
+) -> DataRoot {
+    if let Some(path) = data_dir_arg(args) {
+        return DataRoot { path: absolute(&path), source: DataRootSource::Argument };
+    }
+
+    let portable = portable_marker || args.iter().any(|arg| arg == PORTABLE_ARG);
+    if let (true, Some(exe_dir)) = (portable, exe_dir) {
+        return DataRoot { path: exe_dir.join(PORTABLE_DIR), source: DataRootSource::Portable };
+    }
+
+    if let Some(path) = saved {
+        return DataRoot { path, source: DataRootSource::Migrated };
+    }
+
+    DataRoot { path: platform_dir, source: DataRootSource::Platform }
+}
+
+// Accepts both `--data-dir <path>` and `--data-dir=<path>`
+fn data_dir_arg(args: &[String]) -> Option<PathBuf> {
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_ARG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg.strip_prefix(DATA_DIR_ARG).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(PathBuf::from(value));
+        }
+    }
+
+    None
+}
+
+fn read_saved_location(platform_dir: &Path) -> Option<PathBuf> {
+    let data = std::fs::read(platform_dir.join(LOCATION_FILE)).ok()?;
+    serde_json::from_slice::<SavedLocation>(&data).ok().map(|saved| saved.path)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+pub fn init(root: DataRoot) -> Result<(), std::io::Error> {
+    std::fs::create_dir_all(&root.path)?;
+    log::info!("Using data root {:?} ({:?})", root.path, root.source);
+
+    *DATA_ROOT.write().unwrap() = Some(root);
+    Ok(())
+}
+
+// Falls back to the working directory when nothing was resolved (tools, benches)
+pub fn current() -> DataRoot {
+    DATA_ROOT.read().unwrap().clone().unwrap_or_else(|| DataRoot {
+        path: PathBuf::from("."),
+        source: DataRootSource::Platform,
+    })
+}
+
+pub fn data_root() -> PathBuf {
+    current().path
+}
+
+pub fn config_dir() -> PathBuf {
+    data_root().join("config")
+}
+
+pub fn plugins_dir() -> PathBuf {
+    data_root().join("plugins")
+}
+
+// Move the whole data set to `target` and make it the data root. The caller
+// must close the open workspace first so the database files are at rest.
+pub async fn migrate(
+    target: &Path,
+    platform_dir: &Path,
+) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+    let current = current();
+    let source = absolute(&current.path);
+    let target = absolute(target);
+
+    match current.source {
+        DataRootSource::Argument => {
+            return Err(format!("Data root is set with {}; change the argument instead", DATA_DIR_ARG).into());
+        }
+        DataRootSource::Portable => {
+            return Err("Data root is fixed in portable mode; remove the portable marker first".into());
+        }
+        DataRootSource::Migrated | DataRootSource::Platform => {}
+    }
+
+    if source == target || target.starts_with(&source) || source.starts_with(&target) {
+        return Err(format!("Cannot move data from {:?} into {:?}", source, target).into());
+    }
+    if target.exists() && fs::read_dir(&target).await?.next_entry().await?.is_some() {
+        return Err(format!("Target directory is not empty: {:?}", target).into());
+    }
+
+    log::info!("Migrating data root from {:?} to {:?}", source, target);
+
+    let copied = match copy_root(&source, &target).await {
+        Ok(copied) => copied,
+        Err(e) => {
+            // The target was empty, so all of it is the failed copy
+            if let Err(e) = fs::remove_dir_all(&target).await {
+                log::warn!("Failed to clean up incomplete copy {:?}: {}", target, e);
+            }
+            return Err(format!("Data was not moved, {:?} is still in use: {}", source, e).into());
+        }
+    };
+
+    // Journals living outside the old root (e.g. a pre-workspace data/ folder)
+    // are pulled into the new one so the data set is self-contained
+    let mut workspaces = WorkspaceManager::new(&target);
+    workspaces.load().await?;
+    let workspaces_relocated = workspaces.adopt_external().await?;
+
+    let pointer = SavedLocation { path: target.clone() };
+    fs::create_dir_all(platform_dir).await?;
+    fs::write(platform_dir.join(LOCATION_FILE), serde_json::to_vec_pretty(&pointer)?).await?;
+
+    *DATA_ROOT.write().unwrap() = Some(DataRoot {
+        path: target.clone(),
+        source: DataRootSource::Migrated,
+    });
+
+    let source_removed = match remove_source(&source).await {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Data copied, but the old root {:?} could not be removed: {}", source, e);
+            false
+        }
+    };
+
+    log::info!("Migrated {} files ({} bytes) to {:?}", copied.0, copied.1, target);
+
+    Ok(MigrationReport {
+        from: source,
+        to: target,
+        files_copied: copied.0,
+        bytes_copied: copied.1,
+        workspaces_relocated,
+        source_removed,
+    })
+}
+
+// Copy and verify every entry of the root except the location pointer
+async fn copy_root(source: &Path, target: &Path) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+    let mut copied = (0, 0);
+    let mut entries = fs::read_dir(source).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name() == LOCATION_FILE {
+            continue;
+        }
+        let to = target.join(entry.file_name());
+        let (files, bytes) = copy_tree(&entry.path(), &to).await?;
+        verify_copy(&entry.path(), &to).await?;
+        copied.0 += files;
+        copied.1 += bytes;
+    }
+
+    Ok(copied)
+}
+
+// The platform directory keeps the location pointer, so only its contents go
+async fn remove_source(source: &Path) -> Result<(), std::io::Error> {
+    let mut entries = fs::read_dir(source).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name() == LOCATION_FILE {
+            continue;
+        }
+        if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(entry.path()).await?;
+        } else {
+            fs::remove_file(entry.path()).await?;
+        }
+    }
+
+    let _ = fs::remove_dir(source).await;
+    Ok(())
+}
+
+// Recursive copy returning (files, bytes); a missing source copies nothing
+pub(crate) async fn copy_tree(from: &Path, to: &Path) -> Result<(u64, u64), std::io::Error> {
+    let metadata = match fs::metadata(from).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e),
+    };
+
+    if !metadata.is_dir() {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        return Ok((1, fs::copy(from, to).await?));
+    }
+
+    fs::create_dir_all(to).await?;
+
+    let mut copied = (0, 0);
+    let mut entries = fs::read_dir(from).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let (files, bytes) = Box::pin(copy_tree(&entry.path(), &to.join(entry.file_name()))).await?;
+        copied.0 += files;
+        copied.1 += bytes;
+    }
+
+    Ok(copied)
+}
+
+// Check a copy before its source may go: the same files with the same sizes,
+// and every SQLite database passing an integrity check with the same row
+// count in each table
+pub(crate) async fn verify_copy(from: &Path, to: &Path) -> Result<(), String> {
+    let expected = file_sizes(from).await.map_err(|e| format!("Failed to read {:?}: {}", from, e))?;
+    let actual = file_sizes(to).await.map_err(|e| format!("Failed to read {:?}: {}", to, e))?;
+
+    if let Some((file, size)) = expected.iter().find(|(file, size)| actual.get(*file) != Some(size)) {
+        return Err(format!("{:?} ({} bytes) was not copied intact", from.join(file), size));
+    }
+    if let Some(file) = actual.keys().find(|file| !expected.contains_key(*file)) {
+        return Err(format!("Unexpected file in the copy: {:?}", to.join(file)));
+    }
+
+    for file in expected.keys().filter(|file| file.extension().is_some_and(|ext| ext == "db")) {
+        let source_counts = table_row_counts(&from.join(file), false).await
+            .map_err(|e| format!("Failed to read database {:?}: {}", from.join(file), e))?;
+        let copy_counts = table_row_counts(&to.join(file), true).await
+            .map_err(|e| format!("Copied database {:?} is damaged: {}", to.join(file), e))?;
+        if source_counts != copy_counts {
+            return Err(format!("Copied database {:?} does not have the rows of the original", to.join(file)));
+        }
+    }
+
+    Ok(())
+}
+
+// Sizes of all files under `root` by relative path; a file root maps to ""
+async fn file_sizes(root: &Path) -> Result<BTreeMap<PathBuf, u64>, std::io::Error> {
+    let mut sizes = BTreeMap::new();
+    let metadata = match fs::metadata(root).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(sizes),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        sizes.insert(PathBuf::new(), metadata.len());
+        return Ok(sizes);
+    }
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(root.join(&dir)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let relative = dir.join(entry.file_name());
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(relative);
+            } else {
+                sizes.insert(relative, metadata.len());
+            }
+        }
+    }
+
+    Ok(sizes)
+}
+
+async fn table_row_counts(database: &Path, check_integrity: bool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let options = SqliteConnectOptions::new().filename(database).read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+
+    if check_integrity {
+        let result: String = sqlx::query_scalar("PRAGMA integrity_check").fetch_one(&mut connection).await?;
+        if result != "ok" {
+            return Err(sqlx::Error::Protocol(result));
+        }
+    }
+
+    let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+        .fetch_all(&mut connection)
+        .await?;
+    let mut counts = Vec::new();
+    for table in tables {
+        let row = sqlx::query(&format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")))
+            .fetch_one(&mut connection)
+            .await?;
+        counts.push((table, row.get::<i64, _>(0)));
+    }
+
+    connection.close().await?;
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("journal-paths-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    async fn journal_database(path: &Path, rows: usize) -> SqliteConnection {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+        let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("CREATE TABLE trades (id INTEGER PRIMARY KEY, symbol TEXT)").execute(&mut connection).await.unwrap();
+        for _ in 0..rows {
+            sqlx::query("INSERT INTO trades (symbol) VALUES ('EURUSD')").execute(&mut connection).await.unwrap();
+        }
+        connection
+    }
+
+    #[test]
+    fn data_dir_argument_accepts_both_forms() {
+        assert_eq!(data_dir_arg(&args(&["app", "--data-dir", "/srv/journal"])), Some(PathBuf::from("/srv/journal")));
+        assert_eq!(data_dir_arg(&args(&["app", "--data-dir=/srv/journal"])), Some(PathBuf::from("/srv/journal")));
+        assert_eq!(data_dir_arg(&args(&["app", "--data-directory=/x"])), None);
+        assert_eq!(data_dir_arg(&args(&["app", "--data-dir"])), None);
+    }
+
+    #[test]
+    fn resolution_follows_precedence() {
+        let exe = Path::new("/opt/journal");
+        let saved = Some(PathBuf::from("/mnt/journal"));
+        let platform = PathBuf::from("/home/me/.local/share/journal");
+
+        let root = resolve_with(&args(&["app", "--portable", "--data-dir", "/srv/j"]), Some(exe), true, saved.clone(), platform.clone());
+        assert_eq!(root, DataRoot { path: PathBuf::from("/srv/j"), source: DataRootSource::Argument });
+
+        let root = resolve_with(&args(&["app", "--portable"]), Some(exe), false, saved.clone(), platform.clone());
+        assert_eq!(root, DataRoot { path: PathBuf::from("/opt/journal/data"), source: DataRootSource::Portable });
+
+        let root = resolve_with(&args(&["app"]), Some(exe), true, saved.clone(), platform.clone());
+        assert_eq!(root.source, DataRootSource::Portable);
+
+        let root = resolve_with(&args(&["app"]), Some(exe), false, saved, platform.clone());
+        assert_eq!(root, DataRoot { path: PathBuf::from("/mnt/journal"), source: DataRootSource::Migrated });
+
+        let root = resolve_with(&args(&["app"]), Some(exe), false, None, platform.clone());
+        assert_eq!(root, DataRoot { path: platform, source: DataRootSource::Platform });
+    }
+
+    #[tokio::test]
+    async fn verify_copy_catches_changed_files_and_lost_rows() {
+        let root = temp_root("verify");
+        let (from, to) = (root.join("from"), root.join("to"));
+        journal_database(&from.join("data/trading_journal.db"), 3).await.close().await.unwrap();
+        std::fs::write(from.join("notes.txt"), "entry").unwrap();
+
+        copy_tree(&from, &to).await.unwrap();
+        verify_copy(&from, &to).await.unwrap();
+
+        // Deleting a row leaves the page, and so the file size, unchanged
+        let options = SqliteConnectOptions::new().filename(to.join("data/trading_journal.db"));
+        let mut copy = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("DELETE FROM trades WHERE id = 1").execute(&mut copy).await.unwrap();
+        copy.close().await.unwrap();
+        assert!(verify_copy(&from, &to).await.unwrap_err().contains("rows"));
+
+        copy_tree(&from, &to).await.unwrap();
+        std::fs::write(to.join("notes.txt"), "entr").unwrap();
+        assert!(verify_copy(&from, &to).await.unwrap_err().contains("notes.txt"));
+
+        std::fs::write(to.join("notes.txt"), "entry").unwrap();
+        std::fs::write(to.join("stray.txt"), "").unwrap();
+        assert!(verify_copy(&from, &to).await.unwrap_err().contains("stray.txt"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    // The only test that touches the process-wide data root
+    #[tokio::test]
+    async fn migrate_moves_the_data_set_and_points_to_it() {
+        let root = temp_root("migrate");
+        let (source, target) = (root.join("platform"), root.join("moved"));
+        journal_database(&source.join("data/trading_journal.db"), 5).await.close().await.unwrap();
+        std::fs::create_dir_all(source.join("data/images")).unwrap();
+        std::fs::write(source.join("data/images/chart.png"), [1u8; 64]).unwrap();
+
+        init(DataRoot { path: source.clone(), source: DataRootSource::Platform }).unwrap();
+        assert!(migrate(&source.join("inside"), &source).await.is_err());
+
+        let report = migrate(&target, &source).await.unwrap();
+        assert_eq!(report.files_copied, 2);
+        assert!(report.source_removed);
+        assert_eq!(current(), DataRoot { path: target.clone(), source: DataRootSource::Migrated });
+        assert_eq!(read_saved_location(&source), Some(target.clone()));
+        assert!(!source.join("data").exists());
+        assert_eq!(std::fs::read(target.join("data/images/chart.png")).unwrap(), [1u8; 64]);
+
+        let counts = table_row_counts(&target.join("data/trading_journal.db"), true).await.unwrap();
+        assert_eq!(counts, [("trades".to_string(), 5)]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
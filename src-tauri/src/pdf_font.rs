@@ -0,0 +1,306 @@
+// Fonts for PDF reports
+//
+// Report text is drawn with DejaVu Sans, embedded as TrueType so Cyrillic,
+// Greek, Arabic and Persian journal text prints as written. Characters it
+// lacks (Chinese, Japanese, Korean) come from a fallback font: the one named
+// in the report options, or else the first common system font found. Widths
+// are measured with each font's own advances, and right-to-left text is
+// shaped and put in visual order before it is measured or drawn, because PDF
+// text is always laid out left to right.
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use unicode_bidi::BidiInfo;
+
+const REGULAR: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+const BOLD: &[u8] = include_bytes!("../fonts/DejaVuSans-Bold.ttf");
+
+// Searched in order when no fallback font is configured
+const FALLBACK_CANDIDATES: [&str; 5] = [
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    "C:\\Windows\\Fonts\\ARIALUNI.TTF",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+];
+
+pub struct FontFace {
+    pub bytes: Cow<'static, [u8]>,
+    // Advance width of every mapped character, in em
+    advances: HashMap<char, f32>,
+}
+
+impl FontFace {
+    pub fn parse(bytes: Cow<'static, [u8]>) -> Result<Self, String> {
+        let face = ttf_parser::Face::parse(&bytes, 0).map_err(|e| e.to_string())?;
+        let em = face.units_per_em() as f32;
+        let mut advances = HashMap::new();
+        let subtables = face.tables().cmap.map(|cmap| cmap.subtables.into_iter().collect::<Vec<_>>()).unwrap_or_default();
+        for subtable in subtables.iter().filter(|subtable| subtable.is_unicode()) {
+            subtable.codepoints(|code| {
+                let glyph = subtable.glyph_index(code);
+                if let (Some(c), Some(glyph)) = (char::from_u32(code), glyph) {
+                    let advance = face.glyph_hor_advance(glyph).unwrap_or_default();
+                    advances.entry(c).or_insert(advance as f32 / em);
+                }
+            });
+        }
+        if advances.is_empty() {
+            return Err("the font maps no Unicode characters".to_string());
+        }
+        Ok(Self { bytes, advances })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::parse(Cow::Owned(bytes))
+    }
+
+    pub fn has(&self, c: char) -> bool {
+        self.advances.contains_key(&c)
+    }
+
+    pub fn advance(&self, c: char) -> f32 {
+        self.advances.get(&c).copied().unwrap_or(0.5)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaceKind {
+    Regular,
+    Bold,
+    Fallback,
+}
+
+// A stretch of visual-order text drawn with one font
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub face: FaceKind,
+    pub text: String,
+    // In mm at the requested size
+    pub width: f32,
+}
+
+pub struct PdfFonts {
+    pub regular: FontFace,
+    pub bold: FontFace,
+    fallback_path: Option<PathBuf>,
+    fallback: OnceCell<Option<FontFace>>,
+    missing: RefCell<BTreeSet<char>>,
+    warnings: RefCell<Vec<String>>,
+}
+
+impl PdfFonts {
+    // `fallback` replaces the search of system fonts when set
+    pub fn new(fallback: Option<&Path>) -> Self {
+        Self {
+            regular: FontFace::parse(Cow::Borrowed(REGULAR)).expect("bundled font is valid"),
+            bold: FontFace::parse(Cow::Borrowed(BOLD)).expect("bundled font is valid"),
+            fallback_path: fallback.map(Path::to_path_buf),
+            fallback: OnceCell::new(),
+            missing: RefCell::new(BTreeSet::new()),
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    // Loaded the first time a character is missing from the bundled fonts
+    pub fn fallback(&self) -> Option<&FontFace> {
+        self.fallback
+            .get_or_init(|| match &self.fallback_path {
+                Some(path) => FontFace::load(path)
+                    .map_err(|e| self.warn(format!("PDF font {} could not be loaded: {}", path.display(), e)))
+                    .ok(),
+                None => FALLBACK_CANDIDATES.iter().map(Path::new).filter(|path| path.exists()).find_map(|path| {
+                    FontFace::load(path)
+                        .map_err(|e| log::warn!("Skipping PDF fallback font {}: {}", path.display(), e))
+                        .ok()
+                }),
+            })
+            .as_ref()
+    }
+
+    pub fn face(&self, kind: FaceKind) -> Option<&FontFace> {
+        match kind {
+            FaceKind::Regular => Some(&self.regular),
+            FaceKind::Bold => Some(&self.bold),
+            FaceKind::Fallback => self.fallback(),
+        }
+    }
+
+    pub fn warn(&self, warning: String) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    // `text` in visual order, split where the font changes
+    pub fn runs(&self, text: &str, size: f32, bold: bool) -> Vec<Run> {
+        let primary = if bold { FaceKind::Bold } else { FaceKind::Regular };
+        let scale = size * crate::pdf_report::PT_TO_MM;
+        let mut runs: Vec<Run> = Vec::new();
+        for c in visual(text).chars() {
+            let kind = if c.is_whitespace() || c.is_control() || self.face(primary).is_some_and(|face| face.has(c)) {
+                primary
+            } else if self.fallback().is_some_and(|face| face.has(c)) {
+                FaceKind::Fallback
+            } else {
+                self.missing.borrow_mut().insert(c);
+                primary
+            };
+            let width = self.face(kind).map_or(0.5, |face| face.advance(c)) * scale;
+            match runs.last_mut() {
+                Some(run) if run.face == kind => {
+                    run.text.push(c);
+                    run.width += width;
+                }
+                _ => runs.push(Run { face: kind, text: c.to_string(), width }),
+            }
+        }
+        runs
+    }
+
+    pub fn width(&self, text: &str, size: f32, bold: bool) -> f32 {
+        self.runs(text, size, bold).iter().map(|run| run.width).sum()
+    }
+
+    // Load failures and characters no font could draw
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = self.warnings.borrow().clone();
+        let missing = self.missing.borrow();
+        if !missing.is_empty() {
+            let sample: String = missing.iter().take(10).collect();
+            warnings.push(format!(
+                "{} characters have no glyph in the PDF fonts and print as boxes (e.g. {}); set pdf_font to a TrueType font that covers them",
+                missing.len(),
+                sample,
+            ));
+        }
+        warnings
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Joining {
+    // Takes no connection on either side (hamza)
+    None,
+    // Connects only to the letter before it
+    Right,
+    // Connects on both sides
+    Dual,
+}
+
+// Arabic and Persian letters with their isolated presentation form; the
+// final, initial and medial forms follow it in that order
+const LETTERS: [(char, u32, Joining); 42] = [
+    ('\u{0621}', 0xFE80, Joining::None), ('\u{0622}', 0xFE81, Joining::Right), ('\u{0623}', 0xFE83, Joining::Right),
+    ('\u{0624}', 0xFE85, Joining::Right), ('\u{0625}', 0xFE87, Joining::Right), ('\u{0626}', 0xFE89, Joining::Dual),
+    ('\u{0627}', 0xFE8D, Joining::Right), ('\u{0628}', 0xFE8F, Joining::Dual), ('\u{0629}', 0xFE93, Joining::Right),
+    ('\u{062A}', 0xFE95, Joining::Dual), ('\u{062B}', 0xFE99, Joining::Dual), ('\u{062C}', 0xFE9D, Joining::Dual),
+    ('\u{062D}', 0xFEA1, Joining::Dual), ('\u{062E}', 0xFEA5, Joining::Dual), ('\u{062F}', 0xFEA9, Joining::Right),
+    ('\u{0630}', 0xFEAB, Joining::Right), ('\u{0631}', 0xFEAD, Joining::Right), ('\u{0632}', 0xFEAF, Joining::Right),
+    ('\u{0633}', 0xFEB1, Joining::Dual), ('\u{0634}', 0xFEB5, Joining::Dual), ('\u{0635}', 0xFEB9, Joining::Dual),
+    ('\u{0636}', 0xFEBD, Joining::Dual), ('\u{0637}', 0xFEC1, Joining::Dual), ('\u{0638}', 0xFEC5, Joining::Dual),
+    ('\u{0639}', 0xFEC9, Joining::Dual), ('\u{063A}', 0xFECD, Joining::Dual), ('\u{0641}', 0xFED1, Joining::Dual),
+    ('\u{0642}', 0xFED5, Joining::Dual), ('\u{0643}', 0xFED9, Joining::Dual), ('\u{0644}', 0xFEDD, Joining::Dual),
+    ('\u{0645}', 0xFEE1, Joining::Dual), ('\u{0646}', 0xFEE5, Joining::Dual), ('\u{0647}', 0xFEE9, Joining::Dual),
+    ('\u{0648}', 0xFEED, Joining::Right), ('\u{0649}', 0xFEEF, Joining::Right), ('\u{064A}', 0xFEF1, Joining::Dual),
+    ('\u{067E}', 0xFB56, Joining::Dual), ('\u{0686}', 0xFB7A, Joining::Dual), ('\u{0698}', 0xFB8A, Joining::Right),
+    ('\u{06A9}', 0xFB8E, Joining::Dual), ('\u{06AF}', 0xFB92, Joining::Dual), ('\u{06CC}', 0xFBFC, Joining::Dual),
+];
+
+const TATWEEL: char = '\u{0640}';
+const LAM: char = '\u{0644}';
+
+// Alef variants that merge with a preceding lam, and the isolated ligature
+const LAM_ALEF: [(char, u32); 4] = [('\u{0622}', 0xFEF5), ('\u{0623}', 0xFEF7), ('\u{0625}', 0xFEF9), ('\u{0627}', 0xFEFB)];
+
+fn letter(c: char) -> Option<(u32, Joining)> {
+    LETTERS.iter().find(|(letter, _, _)| *letter == c).map(|(_, form, joining)| (*form, *joining))
+}
+
+fn joining(c: char) -> Joining {
+    if c == TATWEEL { Joining::Dual } else { letter(c).map_or(Joining::None, |(_, joining)| joining) }
+}
+
+// Harakat and the superscript alef sit on a letter without breaking its joins
+fn is_transparent(c: char) -> bool {
+    matches!(c, '\u{064B}'..='\u{065F}' | '\u{0670}')
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+// Letters replaced by the presentation form their neighbours call for
+fn shape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let neighbour = |from: usize, forward: bool| {
+        let mut i = from;
+        loop {
+            i = if forward { i + 1 } else { i.checked_sub(1)? };
+            match chars.get(i) {
+                Some(c) if is_transparent(*c) => continue,
+                other => return other.copied(),
+            }
+        }
+    };
+
+    let mut shaped = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let joins_before = joining(c) != Joining::None && neighbour(i, false).is_some_and(|p| joining(p) == Joining::Dual);
+        if c == LAM {
+            if let Some((_, ligature)) = chars.get(i + 1).and_then(|next| LAM_ALEF.iter().find(|(alef, _)| alef == next)) {
+                shaped.extend(char::from_u32(ligature + joins_before as u32));
+                i += 2;
+                continue;
+            }
+        }
+        match letter(c) {
+            Some((isolated, kind)) => {
+                let joins_after = kind == Joining::Dual && neighbour(i, true).is_some_and(|n| joining(n) != Joining::None);
+                let offset = match (joins_before, joins_after) {
+                    (true, true) => 3,
+                    (false, true) => 2,
+                    (true, false) => 1,
+                    (false, false) => 0,
+                };
+                shaped.extend(char::from_u32(isolated + offset));
+            }
+            None => shaped.push(c),
+        }
+        i += 1;
+    }
+    shaped
+}
+
+// `text` as it is drawn left to right: Arabic script shaped and each
+// paragraph reordered by the Unicode bidirectional algorithm
+pub fn visual(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_rtl) {
+        return Cow::Borrowed(text);
+    }
+    let shaped = shape(text);
+    let info = BidiInfo::new(&shaped, None);
+    let visual: String = info.paragraphs.iter().map(|para| info.reorder_line(para, para.range.clone())).collect();
+    Cow::Owned(visual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arabic_script_is_shaped_and_put_in_visual_order() {
+        assert!(matches!(visual("XAUUSD London"), Cow::Borrowed("XAUUSD London")));
+        // Initial seen, lam-alef ligature in final form, isolated meem
+        assert_eq!(visual("سلام"), "\u{FEE1}\u{FEFC}\u{FEB3}");
+        // Persian peh, yeh and keheh: initial, medial, final
+        assert_eq!(visual("پیک"), "\u{FB8F}\u{FBFF}\u{FB58}");
+        // Harakat do not break a join
+        assert_eq!(visual("بَب"), "\u{FE90}\u{064E}\u{FE91}");
+        // A number after Arabic text belongs to the right-to-left run
+        assert_eq!(visual("Entry سلام 2 London"), "Entry 2 \u{FEE1}\u{FEFC}\u{FEB3} London");
+    }
+}
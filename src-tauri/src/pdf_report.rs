@@ -0,0 +1,862 @@
+// Paginated PDF performance reports
+//
+// Lays out the same ReportData as the HTML report on A4 pages without a
+// browser. Text uses the embedded TrueType fonts of pdf_font.rs so journal
+// notes in any script print as written. Charts are drawn with plain PDF
+// paths. Each template decides which sections appear and in what order.
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use printpdf::path::{PaintMode, WindingOrder};
+use printpdf::{
+    Color, Image, ImageTransform, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point, Polygon, Rect, Rgb,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::pdf_font::{FaceKind, PdfFonts};
+use crate::report::{self, ReportData, ReportScreenshot};
+use crate::trade_export::{self, Cell, ColumnType, ExportColumn, ExportLocale};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfTemplate {
+    // Key figures, equity, monthly returns and every trade of the range
+    MonthlyStatement,
+    // Breakdown by strategy, ICT pattern and session
+    StrategyReview,
+    // One page per trade in screenshot_trade_ids with its details, notes and charts
+    TradeRecap,
+}
+
+impl PdfTemplate {
+    fn heading(self) -> &'static str {
+        match self {
+            PdfTemplate::MonthlyStatement => "Monthly Statement",
+            PdfTemplate::StrategyReview => "Strategy Review",
+            PdfTemplate::TradeRecap => "Trade Recap",
+        }
+    }
+}
+
+pub struct PdfOutput {
+    pub bytes: Vec<u8>,
+    pub pages: usize,
+    // Screenshots that could not be decoded and text no font could draw
+    pub warnings: Vec<String>,
+}
+
+// A4 portrait, all measures in mm from the bottom left corner
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 16.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const CONTENT_TOP: f32 = PAGE_HEIGHT - 24.0;
+const CONTENT_BOTTOM: f32 = 18.0;
+const ROW_HEIGHT: f32 = 6.0;
+const TABLE_TEXT: f32 = 8.5;
+pub(crate) const PT_TO_MM: f32 = 0.3528;
+// Larger screenshots are scaled down before embedding
+const MAX_IMAGE_PIXELS: u32 = 1600;
+
+const TRADE_LIST_COLUMNS: [&str; 9] = [
+    "id", "exit_time", "symbol", "trade_type", "volume", "entry_price", "exit_price", "r_multiple", "profit_loss_money",
+];
+const RECAP_COLUMNS: [&str; 20] = [
+    "entry_time", "entry_price", "exit_time", "exit_price", "volume", "sl", "tp", "profit_loss_pips",
+    "profit_loss_money", "commission", "swap", "net_profit", "r_multiple", "holding_minutes", "ict_pattern",
+    "strategy_name", "session", "market_condition", "emotion", "confidence_level",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tone {
+    Normal,
+    Muted,
+    Positive,
+    Negative,
+}
+
+impl Tone {
+    fn of(value: f64) -> Self {
+        if value > 0.0 { Tone::Positive } else if value < 0.0 { Tone::Negative } else { Tone::Normal }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Tone::Normal => rgb(0.12, 0.14, 0.18),
+            Tone::Muted => rgb(0.42, 0.45, 0.5),
+            Tone::Positive => rgb(0.05, 0.5, 0.27),
+            Tone::Negative => rgb(0.75, 0.15, 0.15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+struct Column {
+    title: String,
+    weight: f32,
+    align: Align,
+}
+
+fn column(title: &str, weight: f32, align: Align) -> Column {
+    Column { title: title.to_string(), weight, align }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Text {
+    value: String,
+    tone: Tone,
+}
+
+fn plain(value: impl Into<String>) -> Text {
+    Text { value: value.into(), tone: Tone::Normal }
+}
+
+fn signed_money(value: f64) -> Text {
+    Text { value: report::money(value), tone: Tone::of(value) }
+}
+
+fn rgb(r: f32, g: f32, b: f32) -> Color {
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+fn ratio(value: f64) -> String {
+    if value.is_finite() { format!("{:.2}", value) } else { "n/a".to_string() }
+}
+
+// `text` shortened with an ellipsis to fit `width`
+fn fit(fonts: &PdfFonts, text: &str, width: f32, size: f32, bold: bool) -> String {
+    if fonts.width(text, size, bold) <= width {
+        return text.to_string();
+    }
+    let ellipsis = fonts.width("...", size, bold);
+    let mut fitted = String::new();
+    for c in text.chars() {
+        fitted.push(c);
+        if fonts.width(&fitted, size, bold) + ellipsis > width {
+            fitted.pop();
+            break;
+        }
+    }
+    format!("{}...", fitted.trim_end())
+}
+
+// Lines of at most `width`, breaking at spaces and keeping explicit line breaks
+fn wrap(fonts: &PdfFonts, text: &str, width: f32, size: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && fonts.width(&candidate, size, false) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn cell_text(cell: &Cell, column: &ExportColumn, locale: &ExportLocale, tz: Tz) -> Text {
+    match cell {
+        Cell::Empty => plain(""),
+        Cell::Text(text) => plain(text.clone()),
+        Cell::Number(value) if column.column_type == ColumnType::Money => signed_money(*value),
+        Cell::Number(value) if column.key == "r_multiple" => Text { value: format!("{:.2}R", value), tone: Tone::of(*value) },
+        Cell::Number(value) => plain(locale.format_number(*value, column.column_type)),
+        Cell::Boolean(value) => plain(if *value { "Yes" } else { "No" }),
+        Cell::DateTime(value) => plain(value.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string()),
+    }
+}
+
+// Page cursor over a document; pages are added as content runs past the bottom
+struct Layout {
+    doc: PdfDocumentReference,
+    pages: Vec<PdfLayerReference>,
+    fonts: PdfFonts,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    // Added to the document the first time a run needs it
+    fallback: OnceCell<Option<IndirectFontRef>>,
+    header: String,
+    subheader: String,
+    y: f32,
+}
+
+impl Layout {
+    fn new(title: &str, header: String, subheader: String, fonts: PdfFonts) -> Result<Self, printpdf::Error> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        let first = doc.get_page(page).get_layer(layer);
+        let regular = doc.add_external_font(&*fonts.regular.bytes)?;
+        let bold = doc.add_external_font(&*fonts.bold.bytes)?;
+
+        let mut layout = Layout {
+            doc,
+            pages: vec![first],
+            fonts,
+            regular,
+            bold,
+            fallback: OnceCell::new(),
+            header,
+            subheader,
+            y: CONTENT_TOP,
+        };
+        layout.decorate();
+        Ok(layout)
+    }
+
+    fn layer(&self) -> &PdfLayerReference {
+        self.pages.last().expect("layout always has a page")
+    }
+
+    fn decorate(&mut self) {
+        let top = PAGE_HEIGHT - 14.0;
+        self.text(&self.header.clone(), 10.0, MARGIN, top, true, Tone::Normal);
+        let width = self.fonts.width(&self.subheader, 8.5, false);
+        self.text(&self.subheader.clone(), 8.5, PAGE_WIDTH - MARGIN - width, top, false, Tone::Muted);
+        self.rule(PAGE_HEIGHT - 17.0, rgb(0.15, 0.39, 0.92), 0.8);
+        self.y = CONTENT_TOP;
+    }
+
+    fn add_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        self.pages.push(self.doc.get_page(page).get_layer(layer));
+        self.decorate();
+    }
+
+    // Start a new page unless `height` still fits on this one
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < CONTENT_BOTTOM {
+            self.add_page();
+        }
+    }
+
+    fn width(&self, text: &str, size: f32, bold: bool) -> f32 {
+        self.fonts.width(text, size, bold)
+    }
+
+    fn font(&self, face: FaceKind) -> &IndirectFontRef {
+        let fallback = match face {
+            FaceKind::Regular => return &self.regular,
+            FaceKind::Bold => return &self.bold,
+            FaceKind::Fallback => self.fallback.get_or_init(|| {
+                let bytes = &self.fonts.fallback()?.bytes;
+                self.doc.add_external_font(&**bytes)
+                    .map_err(|e| self.fonts.warn(format!("Fallback PDF font could not be embedded: {}", e)))
+                    .ok()
+            }),
+        };
+        fallback.as_ref().unwrap_or(&self.regular)
+    }
+
+    // Runs of the visual-order text, each in the font that has its glyphs
+    fn draw(&self, layer: &PdfLayerReference, text: &str, size: f32, x: f32, baseline: f32, bold: bool) {
+        let mut x = x;
+        for run in self.fonts.runs(text, size, bold) {
+            layer.use_text(run.text.as_str(), size, Mm(x), Mm(baseline), self.font(run.face));
+            x += run.width;
+        }
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, baseline: f32, bold: bool, tone: Tone) {
+        let layer = self.layer();
+        layer.set_fill_color(tone.color());
+        self.draw(layer, text, size, x, baseline, bold);
+    }
+
+    fn rule(&self, y: f32, color: Color, thickness: f32) {
+        let layer = self.layer();
+        layer.set_outline_color(color);
+        layer.set_outline_thickness(thickness);
+        layer.add_line(Line {
+            points: vec![(Point::new(Mm(MARGIN), Mm(y)), false), (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false)],
+            is_closed: false,
+        });
+    }
+
+    fn fill(&self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let layer = self.layer();
+        layer.set_fill_color(color);
+        layer.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + width), Mm(y + height)).with_mode(PaintMode::Fill));
+    }
+
+    fn title(&mut self, title: &str, meta: &str) {
+        self.text(title, 18.0, MARGIN, self.y - 7.0, true, Tone::Normal);
+        self.text(meta, 9.0, MARGIN, self.y - 13.0, false, Tone::Muted);
+        self.y -= 20.0;
+    }
+
+    // Section heading, kept on the same page as at least `keep` mm of its content
+    fn heading(&mut self, text: &str, keep: f32) {
+        self.ensure(10.0 + keep);
+        self.text(text, 12.5, MARGIN, self.y - 5.0, true, Tone::Normal);
+        self.y -= 9.0;
+    }
+
+    fn paragraph(&mut self, text: &str, size: f32, tone: Tone) {
+        let leading = size * PT_TO_MM * 1.45;
+        for line in wrap(&self.fonts, text, CONTENT_WIDTH, size) {
+            self.ensure(leading);
+            self.text(&line, size, MARGIN, self.y - leading + 1.0, false, tone);
+            self.y -= leading;
+        }
+        self.y -= 3.0;
+    }
+
+    // Label/value boxes, four to a row
+    fn key_figures(&mut self, figures: &[(&str, Text)]) {
+        let (gap, height) = (3.0, 15.0);
+        let width = (CONTENT_WIDTH - 3.0 * gap) / 4.0;
+        for row in figures.chunks(4) {
+            self.ensure(height + gap);
+            for (i, (label, value)) in row.iter().enumerate() {
+                let x = MARGIN + i as f32 * (width + gap);
+                self.fill(x, self.y - height, width, height, rgb(0.95, 0.96, 0.98));
+                self.text(label, 7.5, x + 3.0, self.y - 5.0, false, Tone::Muted);
+                self.text(&fit(&self.fonts, &value.value, width - 6.0, 12.0, true), 12.0, x + 3.0, self.y - 11.5, true, value.tone);
+            }
+            self.y -= height + gap;
+        }
+        self.y -= 3.0;
+    }
+
+    // Rows break across pages with the header repeated on each
+    fn table(&mut self, columns: &[Column], rows: &[Vec<Text>]) {
+        let total: f32 = columns.iter().map(|c| c.weight).sum();
+        let widths: Vec<f32> = columns.iter().map(|c| c.weight / total * CONTENT_WIDTH).collect();
+        let pad = 1.8;
+
+        let draw_row = |layout: &Layout, cells: Vec<(&str, Tone)>, bold: bool| {
+            let mut x = MARGIN;
+            for ((value, tone), (column, width)) in cells.into_iter().zip(columns.iter().zip(&widths)) {
+                let value = fit(&layout.fonts, value, width - 2.0 * pad, TABLE_TEXT, bold);
+                let left = match column.align {
+                    Align::Left => x + pad,
+                    Align::Right => x + width - pad - layout.width(&value, TABLE_TEXT, bold),
+                };
+                layout.text(&value, TABLE_TEXT, left, layout.y - ROW_HEIGHT + 1.9, bold, tone);
+                x += width;
+            }
+        };
+        let header = |layout: &mut Layout| {
+            layout.fill(MARGIN, layout.y - ROW_HEIGHT, CONTENT_WIDTH, ROW_HEIGHT, rgb(0.9, 0.92, 0.95));
+            draw_row(layout, columns.iter().map(|c| (c.title.as_str(), Tone::Muted)).collect(), true);
+            layout.y -= ROW_HEIGHT;
+        };
+
+        self.ensure(ROW_HEIGHT * 2.0);
+        header(self);
+        for (i, row) in rows.iter().enumerate() {
+            if self.y - ROW_HEIGHT < CONTENT_BOTTOM {
+                self.add_page();
+                header(self);
+            }
+            if i % 2 == 1 {
+                self.fill(MARGIN, self.y - ROW_HEIGHT, CONTENT_WIDTH, ROW_HEIGHT, rgb(0.97, 0.98, 0.99));
+            }
+            draw_row(self, row.iter().map(|t| (t.value.as_str(), t.tone)).collect(), false);
+            self.y -= ROW_HEIGHT;
+        }
+        self.y -= 5.0;
+    }
+
+    // Balance over time with three labelled grid lines
+    fn line_chart(&mut self, points: &[(DateTime<Utc>, f64)], tz: Tz) {
+        if points.len() < 2 {
+            self.paragraph("Not enough closed trades for an equity curve.", 9.0, Tone::Muted);
+            return;
+        }
+
+        let (height, axis) = (62.0, 24.0);
+        self.ensure(height + 4.0);
+        let (left, right, top, bottom) = (MARGIN + axis, PAGE_WIDTH - MARGIN, self.y - 2.0, self.y - height + 7.0);
+        let (start, end) = (points[0].0, points[points.len() - 1].0);
+        let span = ((end - start).num_seconds() as f64).max(1.0);
+        let mut low = points.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+        let mut high = points.iter().map(|(_, v)| *v).fold(f64::NEG_INFINITY, f64::max);
+        if high <= low {
+            low -= 1.0;
+            high += 1.0;
+        }
+        let x = |t: DateTime<Utc>| left + ((t - start).num_seconds() as f64 / span) as f32 * (right - left);
+        let y = |v: f64| bottom + ((v - low) / (high - low)) as f32 * (top - bottom);
+
+        let layer = self.layer().clone();
+        layer.set_outline_thickness(0.3);
+        layer.set_outline_color(rgb(0.88, 0.9, 0.93));
+        for value in [high, (high + low) / 2.0, low] {
+            layer.add_line(Line {
+                points: vec![(Point::new(Mm(left), Mm(y(value))), false), (Point::new(Mm(right), Mm(y(value))), false)],
+                is_closed: false,
+            });
+            let label = report::money(value);
+            self.text(&label, 7.0, left - 2.0 - self.width(&label, 7.0, false), y(value) - 1.0, false, Tone::Muted);
+        }
+
+        let line: Vec<(Point, bool)> = points.iter().map(|(t, v)| (Point::new(Mm(x(*t)), Mm(y(*v))), false)).collect();
+        let mut area = vec![(Point::new(Mm(x(start)), Mm(bottom)), false)];
+        area.extend(line.iter().cloned());
+        area.push((Point::new(Mm(x(end)), Mm(bottom)), false));
+        layer.set_fill_color(rgb(0.87, 0.92, 1.0));
+        layer.add_polygon(Polygon { rings: vec![area], mode: PaintMode::Fill, winding_order: WindingOrder::NonZero });
+        layer.set_outline_color(rgb(0.15, 0.39, 0.92));
+        layer.set_outline_thickness(1.0);
+        layer.add_line(Line { points: line, is_closed: false });
+
+        let first = start.with_timezone(&tz).date_naive().to_string();
+        let last = end.with_timezone(&tz).date_naive().to_string();
+        self.text(&first, 7.0, left, bottom - 5.0, false, Tone::Muted);
+        self.text(&last, 7.0, right - self.width(&last, 7.0, false), bottom - 5.0, false, Tone::Muted);
+        self.y -= height + 4.0;
+    }
+
+    // Horizontal bars; negative values grow left of a centred axis
+    fn bar_chart(&mut self, bars: &[(String, f64, String)]) {
+        if bars.is_empty() {
+            self.paragraph("No data in this range.", 9.0, Tone::Muted);
+            return;
+        }
+
+        let (label_width, value_width) = (42.0, 30.0);
+        let area = CONTENT_WIDTH - label_width - value_width;
+        let max = bars.iter().map(|(_, v, _)| v.abs()).fold(0.0, f64::max).max(f64::EPSILON);
+        let signed = bars.iter().any(|(_, v, _)| *v < 0.0);
+        let zero = MARGIN + label_width + if signed { area / 2.0 } else { 0.0 };
+        let scale = if signed { area / 2.0 } else { area };
+
+        for (name, value, caption) in bars {
+            self.ensure(ROW_HEIGHT);
+            let width = (value.abs() / max) as f32 * scale;
+            let start = if *value < 0.0 { zero - width } else { zero };
+            let baseline = self.y - ROW_HEIGHT + 1.9;
+            let name = fit(&self.fonts, name, label_width - 3.0, TABLE_TEXT, false);
+            self.text(&name, TABLE_TEXT, MARGIN + label_width - 3.0 - self.width(&name, TABLE_TEXT, false), baseline, false, Tone::Normal);
+            self.fill(start, self.y - ROW_HEIGHT + 1.2, width.max(0.3), ROW_HEIGHT - 2.4, Tone::of(*value).color());
+            self.text(caption, TABLE_TEXT, MARGIN + label_width + area + 3.0, baseline, false, Tone::Muted);
+            self.y -= ROW_HEIGHT;
+        }
+        self.y -= 5.0;
+    }
+
+    // Scaled to the content width, at most `max_height` tall
+    fn image(&mut self, caption: &str, bytes: &[u8], max_height: f32) -> Result<(), image::ImageError> {
+        let mut decoded = image::load_from_memory(bytes)?;
+        if decoded.width() > MAX_IMAGE_PIXELS || decoded.height() > MAX_IMAGE_PIXELS {
+            decoded = decoded.thumbnail(MAX_IMAGE_PIXELS, MAX_IMAGE_PIXELS);
+        }
+        let decoded = image::DynamicImage::ImageRgb8(decoded.to_rgb8());
+
+        let (pixels_wide, pixels_high) = (decoded.width() as f32, decoded.height() as f32);
+        let width = CONTENT_WIDTH.min(max_height * pixels_wide / pixels_high);
+        let height = width * pixels_high / pixels_wide;
+        self.ensure(height + 8.0);
+
+        Image::from_dynamic_image(&decoded).add_to_layer(self.layer().clone(), ImageTransform {
+            translate_x: Some(Mm(MARGIN)),
+            translate_y: Some(Mm(self.y - height)),
+            // Pixels per inch that make the image exactly `width` wide
+            dpi: Some(pixels_wide * 25.4 / width),
+            ..Default::default()
+        });
+        self.text(caption, 8.0, MARGIN, self.y - height - 4.5, false, Tone::Muted);
+        self.y -= height + 9.0;
+        Ok(())
+    }
+
+    // Footers need the page count, so they are written last
+    fn finish(self, footer: &str) -> Result<(Vec<u8>, usize, Vec<String>), printpdf::Error> {
+        let count = self.pages.len();
+        for (i, layer) in self.pages.iter().enumerate() {
+            let number = format!("Page {} of {}", i + 1, count);
+            layer.set_fill_color(Tone::Muted.color());
+            self.draw(layer, footer, 7.5, MARGIN, 10.0, false);
+            self.draw(layer, &number, 7.5, PAGE_WIDTH - MARGIN - self.width(&number, 7.5, false), 10.0, false);
+        }
+        Ok((self.doc.save_to_bytes()?, count, self.fonts.warnings()))
+    }
+}
+
+fn summary_figures(layout: &mut Layout, report: &ReportData) {
+    let summary = &report.summary;
+    layout.key_figures(&[
+        ("Net P/L", signed_money(summary.net_profit)),
+        ("Win rate", plain(format!("{:.1}%", summary.win_rate))),
+        ("Profit factor", plain(ratio(summary.profit_factor))),
+        ("Trades (W/L)", plain(format!("{} ({}/{})", summary.total_trades, summary.winning_trades, summary.losing_trades))),
+        ("Max drawdown", Text { value: report::money(summary.max_drawdown), tone: if summary.max_drawdown > 0.0 { Tone::Negative } else { Tone::Normal } }),
+        ("Expectancy", signed_money(summary.expectancy)),
+        ("Average trade", signed_money(summary.average_trade)),
+        ("Recovery factor", plain(ratio(summary.recovery_factor))),
+    ]);
+}
+
+fn equity_section(layout: &mut Layout, report: &ReportData) {
+    layout.heading(if report.starting_balance.is_some() { "Equity curve" } else { "Cumulative P/L" }, 60.0);
+    layout.line_chart(&report.equity, report.timezone);
+}
+
+fn monthly_section(layout: &mut Layout, report: &ReportData) {
+    layout.heading("Monthly returns", ROW_HEIGHT * 2.0);
+    if report.monthly.len() > 1 {
+        let bars: Vec<(String, f64, String)> = report.monthly.iter()
+            .map(|m| (format!("{} {}", &report::month_name(m.month)[..3], m.year), m.profit, report::money(m.profit)))
+            .collect();
+        layout.bar_chart(&bars);
+    }
+    let rows: Vec<Vec<Text>> = report.monthly.iter()
+        .map(|month| vec![
+            plain(format!("{} {}", report::month_name(month.month), month.year)),
+            plain(month.trades.to_string()),
+            signed_money(month.profit),
+            plain(if report.starting_balance.is_some() { format!("{:.2}%", month.return_percentage) } else { "-".to_string() }),
+        ])
+        .collect();
+    layout.table(
+        &[column("Month", 3.0, Align::Left), column("Trades", 1.0, Align::Right), column("P/L", 2.0, Align::Right), column("Return", 1.5, Align::Right)],
+        &rows,
+    );
+}
+
+fn trade_list_section(layout: &mut Layout, report: &ReportData) {
+    let keys: Vec<String> = TRADE_LIST_COLUMNS.iter().map(|key| key.to_string()).collect();
+    let columns = trade_export::resolve_columns(&keys, &HashMap::new()).expect("trade list columns are known");
+    let locale = ExportLocale::from_tag("en-GB");
+
+    layout.heading("Trades", ROW_HEIGHT * 2.0);
+    let rows: Vec<Vec<Text>> = report.trades.iter()
+        .map(|row| {
+            trade_export::row_cells(row, &columns, report.timezone).iter().zip(&columns)
+                .map(|(cell, column)| cell_text(cell, column, &locale, report.timezone))
+                .collect()
+        })
+        .collect();
+    let weights = [0.8, 2.2, 1.4, 0.9, 1.2, 1.5, 1.5, 1.4, 1.6];
+    let table: Vec<Column> = columns.iter().zip(weights)
+        .map(|(c, weight)| column(if c.key == "exit_time" { "Closed" } else { &c.label }, weight, match c.column_type {
+            ColumnType::Text | ColumnType::DateTime => Align::Left,
+            _ => Align::Right,
+        }))
+        .collect();
+    layout.table(&table, &rows);
+}
+
+fn strategy_section(layout: &mut Layout, report: &ReportData) {
+    layout.heading("Strategies", ROW_HEIGHT * 2.0);
+    if report.strategies.is_empty() {
+        layout.paragraph("No trades with a strategy in this range.", 9.0, Tone::Muted);
+        return;
+    }
+    let bars: Vec<(String, f64, String)> = report.strategies.iter()
+        .map(|s| (s.strategy.clone(), s.net_profit, report::money(s.net_profit)))
+        .collect();
+    layout.bar_chart(&bars);
+    let rows: Vec<Vec<Text>> = report.strategies.iter()
+        .map(|s| vec![
+            plain(s.strategy.clone()),
+            plain(s.total_trades.to_string()),
+            plain(format!("{:.1}%", s.win_rate)),
+            signed_money(s.net_profit),
+            plain(ratio(s.profit_factor)),
+            plain(report::money(s.max_drawdown)),
+        ])
+        .collect();
+    layout.table(
+        &[
+            column("Strategy", 3.0, Align::Left), column("Trades", 1.0, Align::Right), column("Win rate", 1.2, Align::Right),
+            column("Net P/L", 1.8, Align::Right), column("Profit factor", 1.4, Align::Right), column("Max drawdown", 1.8, Align::Right),
+        ],
+        &rows,
+    );
+}
+
+fn pattern_section(layout: &mut Layout, report: &ReportData) {
+    layout.heading("ICT pattern win rates", ROW_HEIGHT * 2.0);
+    if report.ict_win_rates.is_empty() {
+        layout.paragraph("No trades with an ICT pattern in this range.", 9.0, Tone::Muted);
+        return;
+    }
+    let number = |row: &HashMap<String, Value>, key: &str| row.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+    let pattern = |row: &HashMap<String, Value>| row.get("pattern").and_then(Value::as_str).unwrap_or_default().to_string();
+
+    let bars: Vec<(String, f64, String)> = report.ict_win_rates.iter()
+        .map(|row| (pattern(row), number(row, "win_rate"), format!("{:.1}% of {}", number(row, "win_rate"), number(row, "total_trades"))))
+        .collect();
+    layout.bar_chart(&bars);
+    let rows: Vec<Vec<Text>> = report.ict_win_rates.iter()
+        .map(|row| vec![
+            plain(pattern(row)),
+            plain(number(row, "total_trades").to_string()),
+            plain(number(row, "winning_trades").to_string()),
+            plain(format!("{:.1}%", number(row, "win_rate"))),
+            signed_money(number(row, "avg_win")),
+            signed_money(number(row, "avg_loss")),
+        ])
+        .collect();
+    layout.table(
+        &[
+            column("Pattern", 3.0, Align::Left), column("Trades", 1.0, Align::Right), column("Wins", 1.0, Align::Right),
+            column("Win rate", 1.2, Align::Right), column("Avg win", 1.6, Align::Right), column("Avg loss", 1.6, Align::Right),
+        ],
+        &rows,
+    );
+}
+
+fn session_section(layout: &mut Layout, report: &ReportData) {
+    let sessions = &report.sessions;
+    layout.heading("Sessions", ROW_HEIGHT * 2.0);
+    let rows: Vec<Vec<Text>> = [&sessions.asian_session, &sessions.london_session, &sessions.new_york_session, &sessions.overlap_sessions.london_new_york]
+        .into_iter()
+        .map(|s| vec![
+            plain(s.session.clone()),
+            plain(s.total_trades.to_string()),
+            plain(format!("{:.1}%", s.win_rate)),
+            signed_money(s.net_profit),
+            plain(format!("{:.1} h", s.average_holding)),
+        ])
+        .collect();
+    layout.table(
+        &[
+            column("Session", 3.0, Align::Left), column("Trades", 1.0, Align::Right), column("Win rate", 1.2, Align::Right),
+            column("Net P/L", 1.8, Align::Right), column("Avg holding", 1.4, Align::Right),
+        ],
+        &rows,
+    );
+}
+
+fn trade_title(row: &trade_export::TradeRow) -> String {
+    format!(
+        "#{} {} {}",
+        row.get("id").and_then(Value::as_i64).unwrap_or_default(),
+        report::text(row, "symbol").unwrap_or_default(),
+        report::text(row, "trade_type").unwrap_or_default(),
+    )
+}
+
+fn screenshot_images(layout: &mut Layout, shot: &ReportScreenshot, max_height: f32, warnings: &mut Vec<String>) {
+    for image in &shot.images {
+        if let Some(bytes) = &image.bytes {
+            if let Err(e) = layout.image(&format!("{} chart", image.label), bytes, max_height) {
+                let id = shot.trade.get("id").and_then(Value::as_i64).unwrap_or_default();
+                warnings.push(format!("{} image of trade {} could not be decoded: {}", image.label, id, e));
+            }
+        }
+    }
+}
+
+fn notes(layout: &mut Layout, row: &trade_export::TradeRow) {
+    for column in ["notes", "chart_explanation"] {
+        if let Some(text) = report::text(row, column) {
+            layout.paragraph(text, 9.5, Tone::Normal);
+        }
+    }
+}
+
+// Short review of each selected trade below the sections of a template
+fn review_section(layout: &mut Layout, report: &ReportData, warnings: &mut Vec<String>) {
+    if report.screenshots.is_empty() {
+        return;
+    }
+    layout.add_page();
+    layout.heading("Trade reviews", 0.0);
+    for shot in &report.screenshots {
+        let row = &shot.trade;
+        let mut title = trade_title(row);
+        if let Some(profit) = row.get("profit_loss_money").and_then(Value::as_f64) {
+            title = format!("{}  {}", title, report::money(profit));
+        }
+        layout.ensure(20.0);
+        layout.text(&title, 11.0, MARGIN, layout.y - 4.5, true, Tone::Normal);
+        layout.y -= 8.0;
+        let setup: Vec<&str> = ["ict_pattern", "strategy_name", "session"].iter().filter_map(|c| report::text(row, c)).collect();
+        if !setup.is_empty() {
+            layout.paragraph(&setup.join(" · "), 8.5, Tone::Muted);
+        }
+        notes(layout, row);
+        screenshot_images(layout, shot, 95.0, warnings);
+    }
+}
+
+fn recap(layout: &mut Layout, report: &ReportData, warnings: &mut Vec<String>) {
+    let keys: Vec<String> = RECAP_COLUMNS.iter().map(|key| key.to_string()).collect();
+    let columns = trade_export::resolve_columns(&keys, &HashMap::new()).expect("recap columns are known");
+    let locale = ExportLocale::from_tag("en-GB");
+
+    for (i, shot) in report.screenshots.iter().enumerate() {
+        if i > 0 {
+            layout.add_page();
+        }
+        let row = &shot.trade;
+        layout.heading(&trade_title(row), ROW_HEIGHT * 4.0);
+
+        let cells = trade_export::row_cells(row, &columns, report.timezone);
+        let details: Vec<Vec<Text>> = cells.iter().zip(&columns)
+            .filter(|(cell, _)| **cell != Cell::Empty)
+            .map(|(cell, column)| vec![Text { value: column.label.clone(), tone: Tone::Muted }, cell_text(cell, column, &locale, report.timezone)])
+            .collect();
+        layout.table(&[column("Field", 1.0, Align::Left), column("Value", 2.0, Align::Left)], &details);
+
+        if report::text(row, "notes").is_some() || report::text(row, "chart_explanation").is_some() {
+            layout.heading("Notes", 10.0);
+            notes(layout, row);
+        }
+        if shot.images.iter().any(|image| image.bytes.is_some()) {
+            layout.heading("Charts", 60.0);
+            screenshot_images(layout, shot, 120.0, warnings);
+        }
+    }
+}
+
+// `font` is a TrueType font for characters the bundled font lacks; common
+// system fonts are tried when it is None
+pub fn render_pdf(report: &ReportData, template: PdfTemplate, font: Option<&Path>) -> Result<PdfOutput, Box<dyn std::error::Error>> {
+    let tz = report.timezone;
+    let mut layout = Layout::new(
+        &report.title,
+        report.title.clone(),
+        format!("{} · {}", template.heading(), report.range_label),
+        PdfFonts::new(font),
+    )?;
+    let mut warnings = Vec::new();
+
+    layout.title(
+        template.heading(),
+        &format!("{} · {} trades · times in {}", report.range_label, report.trade_count, tz.name()),
+    );
+    match template {
+        PdfTemplate::MonthlyStatement => {
+            summary_figures(&mut layout, report);
+            equity_section(&mut layout, report);
+            monthly_section(&mut layout, report);
+            trade_list_section(&mut layout, report);
+            review_section(&mut layout, report, &mut warnings);
+        }
+        PdfTemplate::StrategyReview => {
+            summary_figures(&mut layout, report);
+            strategy_section(&mut layout, report);
+            pattern_section(&mut layout, report);
+            session_section(&mut layout, report);
+            equity_section(&mut layout, report);
+            review_section(&mut layout, report, &mut warnings);
+        }
+        PdfTemplate::TradeRecap => recap(&mut layout, report, &mut warnings),
+    }
+
+    let footer = format!("Generated {} · {}", report.generated_at.with_timezone(&tz).format("%Y-%m-%d %H:%M"), tz.name());
+    let (bytes, pages, font_warnings) = layout.finish(&footer)?;
+    warnings.extend(font_warnings);
+    Ok(PdfOutput { bytes, pages, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{ReportImage, ReportOptions};
+    use crate::trade_export::TradeRow;
+    use serde_json::json;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn rows(count: usize) -> Vec<TradeRow> {
+        (0..count)
+            .map(|i| {
+                let day = utc("2024-03-01T08:00:00Z") + chrono::Duration::hours(6 * i as i64);
+                let profit = if i % 3 == 0 { -120.0 } else { 85.5 };
+                json!({
+                    "id": i + 1, "symbol": "XAUUSD", "trade_type": if i % 2 == 0 { "Buy" } else { "Sell" }, "volume": 0.5,
+                    "entry_time": day.to_rfc3339(), "exit_time": (day + chrono::Duration::hours(2)).to_rfc3339(),
+                    "entry_price": 2031.4, "exit_price": 2040.0, "sl": 2026.0, "is_win": if profit > 0.0 { 1 } else { 0 },
+                    "profit_loss_money": profit, "strategy_name": if i % 2 == 0 { "London sweep" } else { "NY reversal" },
+                    "ict_pattern": "FVG", "notes": "Swept Asia high, entered on the retrace into the FVG.",
+                })
+                .as_object().unwrap().clone()
+            })
+            .collect()
+    }
+
+    fn recap_of(notes: &[&str]) -> ReportData {
+        let mut rows = rows(notes.len());
+        let mut report = ReportData::build(&rows, "All trades", &ReportOptions::default(), chrono_tz::UTC, utc("2024-04-01T08:00:00Z"));
+        for (row, note) in rows.iter_mut().zip(notes) {
+            row.insert("notes".to_string(), json!(note));
+            report.screenshots.push(ReportScreenshot { trade: row.clone(), images: Vec::new() });
+        }
+        report
+    }
+
+    #[test]
+    fn russian_and_persian_notes_are_embedded_and_measured_with_the_font() {
+        let report = recap_of(&["Сняли ликвидность азиатской сессии, вход на откате", "ورود پس از شکست سقف آسیا، حد ضرر زیر کف"]);
+        let recap = render_pdf(&report, PdfTemplate::TradeRecap, None).unwrap();
+        assert!(recap.warnings.is_empty(), "{:?}", recap.warnings);
+        assert!(recap.bytes.windows(10).any(|w| w == b"/FontFile2"));
+        assert!(!recap.bytes.windows(9).any(|w| w == b"Helvetica"));
+
+        let fonts = PdfFonts::new(None);
+        assert!(fonts.width("WWWW", 10.0, false) > 2.0 * fonts.width("iiii", 10.0, false));
+        assert!(fonts.width("Сделка", 10.0, true) > fonts.width("Сделка", 10.0, false));
+        let fitted = fit(&fonts, "Ликвидность лондонской сессии", 30.0, TABLE_TEXT, false);
+        assert!(fitted.starts_with("Ликвидность") && fitted.ends_with("..."), "{}", fitted);
+        assert!(fonts.width(&fitted, TABLE_TEXT, false) <= 30.0);
+        let lines = wrap(&fonts, "ورود پس از شکست سقف آسیا و برگشت به شکاف ارزش منصفانه\nخروج", 35.0, 10.0);
+        assert!(lines.len() > 2 && lines.last().unwrap() == "خروج");
+        assert!(lines.iter().all(|line| fonts.width(line, 10.0, false) <= 35.0));
+    }
+
+    #[test]
+    fn text_no_font_covers_is_reported() {
+        let report = recap_of(&["止损移到保本"]);
+        let missing = Path::new("/nonexistent/fonts/simhei.ttf");
+        let recap = render_pdf(&report, PdfTemplate::TradeRecap, Some(missing)).unwrap();
+        assert_eq!(recap.warnings.len(), 2, "{:?}", recap.warnings);
+        assert!(recap.warnings[0].starts_with("PDF font /nonexistent/fonts/simhei.ttf could not be loaded"));
+        assert!(recap.warnings[1].starts_with("6 characters have no glyph"), "{}", recap.warnings[1]);
+    }
+
+    #[test]
+    fn long_statements_break_onto_numbered_pages() {
+        let report = ReportData::build(&rows(120), "March 2024", &ReportOptions::default(), chrono_tz::UTC, utc("2024-04-01T08:00:00Z"));
+
+        let statement = render_pdf(&report, PdfTemplate::MonthlyStatement, None).unwrap();
+        assert!(statement.bytes.starts_with(b"%PDF"));
+        assert!(statement.pages >= 3, "{} pages", statement.pages);
+        assert!(statement.warnings.is_empty());
+
+        let review = render_pdf(&report, PdfTemplate::StrategyReview, None).unwrap();
+        assert!(review.bytes.starts_with(b"%PDF") && review.pages >= 1);
+    }
+
+    #[test]
+    fn recap_embeds_screenshots_and_reports_broken_ones() {
+        let rows = rows(2);
+        let mut report = ReportData::build(&rows, "All trades", &ReportOptions::default(), chrono_tz::UTC, utc("2024-04-01T08:00:00Z"));
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(40, 20).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        for row in &rows {
+            report.screenshots.push(ReportScreenshot {
+                trade: row.clone(),
+                images: vec![
+                    ReportImage { label: "Entry", path: "images/a.png".to_string(), bytes: Some(png.clone()) },
+                    ReportImage { label: "Exit", path: "images/b.png".to_string(), bytes: Some(b"not an image".to_vec()) },
+                ],
+            });
+        }
+
+        let recap = render_pdf(&report, PdfTemplate::TradeRecap, None).unwrap();
+        assert_eq!(recap.pages, 2);
+        assert_eq!(recap.warnings.len(), 2);
+        assert!(recap.warnings[0].starts_with("Exit image of trade 1 could not be decoded"));
+        assert!(recap.bytes.windows(6).any(|w| w == b"/Image"));
+    }
+}
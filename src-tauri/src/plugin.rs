@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use libloading::{Library, Symbol};
 use anyhow::{Result, anyhow};
+use sqlx::SqlitePool;
+
+use crate::importer::BrokerImporter;
+use crate::plugin_storage::{self, DataRetention, PluginStore, StorageQuota};
 
 // Plugin trait that all plugins must implement
 pub trait Plugin: Send + Sync {
@@ -22,6 +27,26 @@ pub trait Plugin: Send + Sync {
     fn set_config(&mut self, config: PluginConfig) -> Result<(), PluginError>;
 }
 
+// Native plugins are cast to `dyn Plugin` through create_plugin, so the trait
+// must not change. Anything newer is an optional exported entry point:
+//
+//   plugin_abi() -> *const c_char
+//       NUL-terminated PLUGIN_ABI the plugin was built with
+//   plugin_importers() -> *mut Vec<Arc<dyn BrokerImporter>>
+//       broker file formats the plugin can read, from Box::into_raw
+//   plugin_set_storage(store: *mut PluginStore)
+//       the plugin's key-value store, from Box::into_raw; called whenever a
+//       workspace is opened or the quota changes
+//
+// These pass Rust types, which only line up when the plugin was built by the
+// same compiler against the same version of the app, so they are used only
+// when plugin_abi matches exactly. Bump the leading number when one of them
+// or a type it passes changes.
+pub const PLUGIN_ABI: &str = concat!("1;", env!("CARGO_PKG_VERSION"), ";", env!("PLUGIN_RUSTC_VERSION"), "\0");
+
+type ImportersEntry = unsafe extern "C" fn() -> *mut Vec<Arc<dyn BrokerImporter>>;
+type SetStorageEntry = unsafe extern "C" fn(*mut PluginStore);
+
 // Plugin results and structures
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PluginResult {
@@ -143,6 +168,11 @@ pub struct PluginManager {
     plugins: Arc<Mutex<HashMap<String, Box<dyn Plugin>>>>,
     plugin_path: PathBuf,
     loaded_libraries: Vec<Library>,
+    storage_pool: Option<SqlitePool>,
+    storage_quotas: HashMap<String, StorageQuota>,
+    // From the optional entry points of native plugins, per plugin name
+    importers: HashMap<String, Vec<Arc<dyn BrokerImporter>>>,
+    storage_entries: HashMap<String, SetStorageEntry>,
 }
 
 impl PluginManager {
@@ -151,6 +181,10 @@ impl PluginManager {
             plugins: Arc::new(Mutex::new(HashMap::new())),
             plugin_path: plugin_path.as_ref().to_path_buf(),
             loaded_libraries: Vec::new(),
+            storage_pool: None,
+            storage_quotas: HashMap::new(),
+            importers: HashMap::new(),
+            storage_entries: HashMap::new(),
         }
     }
     
@@ -217,6 +251,8 @@ impl PluginManager {
                 .map_err(|e| PluginError::with_details("PLUGIN_INIT_FAILED", "Failed to initialize plugin", serde_json::to_value(e).unwrap()))?;
             
             let plugin_name = plugin.name().to_string();
+            self.load_entry_points(&lib, &plugin_name);
+            self.hand_storage(&plugin_name);
             
             // Store the plugin and library
             self.plugins.lock().unwrap().insert(plugin_name, plugin);
@@ -226,6 +262,34 @@ impl PluginManager {
         Ok(())
     }
     
+    // The optional entry points (see PLUGIN_ABI); a plugin built for another
+    // ABI, or before there was one, loads without them
+    unsafe fn load_entry_points(&mut self, lib: &Library, plugin_name: &str) {
+        let abi = lib.get::<unsafe extern "C" fn() -> *const c_char>(b"plugin_abi").ok()
+            .map(|abi| abi())
+            .filter(|abi| !abi.is_null())
+            .map(|abi| CStr::from_ptr(abi));
+        if abi.map(CStr::to_bytes_with_nul) != Some(PLUGIN_ABI.as_bytes()) {
+            if lib.get::<ImportersEntry>(b"plugin_importers").is_ok() || lib.get::<SetStorageEntry>(b"plugin_set_storage").is_ok() {
+                log::warn!(
+                    "Plugin {} was built for ABI {:?}, not {:?}; its importers and storage are not used",
+                    plugin_name, abi, PLUGIN_ABI
+                );
+            }
+            return;
+        }
+        
+        if let Ok(importers) = lib.get::<ImportersEntry>(b"plugin_importers") {
+            let importers = importers();
+            if !importers.is_null() {
+                self.importers.insert(plugin_name.to_string(), *Box::from_raw(importers));
+            }
+        }
+        if let Ok(set_storage) = lib.get::<SetStorageEntry>(b"plugin_set_storage") {
+            self.storage_entries.insert(plugin_name.to_string(), *set_storage);
+        }
+    }
+    
     fn load_script_plugins(&mut self) -> Result<(), PluginError> {
         let script_path = self.plugin_path.join("scripts");
         if !script_path.exists() {
@@ -260,6 +324,13 @@ impl PluginManager {
         self.plugins.lock().unwrap().keys().cloned().collect()
     }
     
+    // Importers contributed by loaded plugins, with the plugin's name
+    pub fn importers(&self) -> Vec<(String, Arc<dyn BrokerImporter>)> {
+        self.importers.iter()
+            .flat_map(|(name, importers)| importers.iter().map(move |importer| (name.clone(), importer.clone())))
+            .collect()
+    }
+    
     pub fn execute_plugin(
         &self, 
         plugin_name: &str, 
@@ -345,6 +416,8 @@ impl PluginManager {
     }
     
     pub fn unload_plugin(&mut self, plugin_name: &str) -> Result<(), PluginError> {
+        self.importers.remove(plugin_name);
+        self.storage_entries.remove(plugin_name);
         let mut plugins = self.plugins.lock().unwrap();
         
         if let Some(mut plugin) = plugins.remove(plugin_name) {
@@ -365,6 +438,26 @@ impl PluginManager {
         Ok(())
     }
     
+    // Unload a plugin and keep or delete its stored data
+    pub async fn uninstall_plugin(&mut self, plugin_name: &str, retention: DataRetention) -> Result<u32, PluginError> {
+        self.require_plugin(plugin_name)?;
+        // Checked up front so the plugin is not unloaded with its data left behind
+        if retention == DataRetention::Delete && self.storage_pool.is_none() {
+            return Err(PluginError::new("STORAGE_UNAVAILABLE", "Plugin data can only be deleted while a workspace is open"));
+        }
+        
+        let store = self.store_for(plugin_name);
+        self.unload_plugin(plugin_name)?;
+        
+        let removed = match (retention, store) {
+            (DataRetention::Delete, Some(store)) => store.clear().await?,
+            _ => 0,
+        };
+        
+        log::info!("Uninstalled plugin {} ({:?} data, {} keys removed)", plugin_name, retention, removed);
+        Ok(removed)
+    }
+    
     pub fn shutdown_all(&mut self) -> Result<(), PluginError> {
         log::info!("Shutting down all plugins...");
         
@@ -379,7 +472,9 @@ impl PluginManager {
             }
         }
         
-        // Unload all libraries
+        // Unload all libraries, after everything that points into them
+        self.importers.clear();
+        self.storage_entries.clear();
         self.loaded_libraries.clear();
         
         log::info!("All plugins shut down successfully");
@@ -387,6 +482,61 @@ impl PluginManager {
     }
 }
 
+// Plugin storage
+impl PluginManager {
+    // Point plugin storage at the open workspace's database and hand every
+    // loaded plugin its store
+    pub fn set_storage_pool(&mut self, pool: SqlitePool) {
+        self.storage_pool = Some(pool);
+        
+        let names: Vec<String> = self.storage_entries.keys().cloned().collect();
+        for name in names {
+            self.hand_storage(&name);
+        }
+    }
+    
+    pub fn set_storage_quota(&mut self, plugin_name: &str, quota: StorageQuota) {
+        self.storage_quotas.insert(plugin_name.to_string(), quota);
+        self.hand_storage(plugin_name);
+    }
+    
+    // Give a plugin exporting plugin_set_storage its store, which it owns from then on
+    fn hand_storage(&self, plugin_name: &str) {
+        if let (Some(set_storage), Some(store)) = (self.storage_entries.get(plugin_name), self.store_for(plugin_name)) {
+            unsafe { set_storage(Box::into_raw(Box::new(store))) };
+        }
+    }
+    
+    // Key-value store namespaced to one loaded plugin. Names come from the
+    // frontend, so only plugins that are actually installed get a store.
+    pub fn storage(&self, plugin_name: &str) -> Result<PluginStore, PluginError> {
+        self.require_plugin(plugin_name)?;
+        
+        self.store_for(plugin_name)
+            .ok_or_else(|| PluginError::new("STORAGE_UNAVAILABLE", "Plugin storage is not available until a workspace is open"))
+    }
+    
+    fn require_plugin(&self, plugin_name: &str) -> Result<(), PluginError> {
+        if !self.plugins.lock().unwrap().contains_key(plugin_name) {
+            return Err(PluginError::new("PLUGIN_NOT_FOUND", &format!("Plugin '{}' is not installed", plugin_name)));
+        }
+        Ok(())
+    }
+    
+    fn store_for(&self, plugin_name: &str) -> Option<PluginStore> {
+        let quota = self.storage_quotas.get(plugin_name).copied().unwrap_or_default();
+        self.storage_pool.clone().map(|pool| PluginStore::new(pool, plugin_name, quota))
+    }
+    
+    pub async fn purge_expired_storage(&self) -> Result<u32, PluginError> {
+        match &self.storage_pool {
+            Some(pool) => plugin_storage::purge_expired(pool).await
+                .map_err(|e| PluginError::new("STORAGE_FAILED", &format!("Failed to purge expired plugin data: {}", e))),
+            None => Ok(0),
+        }
+    }
+}
+
 // Default plugin implementations
 pub struct DefaultPlugin {
     name: String,
@@ -0,0 +1,489 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool, Error as SqlxError};
+
+use crate::plugins::PluginError;
+use crate::timezone;
+
+const MAX_KEY_LENGTH: usize = 256;
+
+// Value stored for a plugin; the variant is persisted in plugin_data.data_type
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum PluginValue {
+    String(String),
+    Integer(i64),
+    Number(f64),
+    Boolean(bool),
+    Json(serde_json::Value),
+}
+
+impl PluginValue {
+    // (data_value, data_type) as stored in plugin_data
+    fn encode(&self) -> (String, &'static str) {
+        match self {
+            PluginValue::String(value) => (value.clone(), "string"),
+            PluginValue::Integer(value) => (value.to_string(), "integer"),
+            PluginValue::Number(value) => (value.to_string(), "number"),
+            PluginValue::Boolean(value) => (value.to_string(), "boolean"),
+            PluginValue::Json(value) => (value.to_string(), "json"),
+        }
+    }
+
+    // Also accepts the type names older rows were written with
+    fn decode(data_value: &str, data_type: &str) -> Option<Self> {
+        match data_type {
+            "string" | "text" => Some(PluginValue::String(data_value.to_string())),
+            "integer" => data_value.parse().ok().map(PluginValue::Integer),
+            "number" | "float" => data_value.parse().ok().map(PluginValue::Number),
+            "boolean" => data_value.parse().ok().map(PluginValue::Boolean),
+            "json" | "object" | "array" => serde_json::from_str(data_value).ok().map(PluginValue::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PluginEntry {
+    pub key: String,
+    pub value: PluginValue,
+    pub created_at: String,
+    pub updated_at: String,
+    pub expires_at: Option<String>,
+}
+
+impl PluginEntry {
+    fn from_row(row: &SqliteRow) -> Result<Self, PluginError> {
+        let key: String = row.get("data_key");
+        let data_value: String = row.get("data_value");
+        let data_type: String = row.get("data_type");
+
+        let value = PluginValue::decode(&data_value, &data_type).ok_or_else(|| {
+            PluginError::new("STORAGE_CORRUPT", &format!("Stored value for '{}' is not a valid {}", key, data_type))
+        })?;
+
+        Ok(Self {
+            key,
+            value,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            expires_at: row.get("expires_at"),
+        })
+    }
+}
+
+// Per-plugin limits, counted over live (unexpired) keys
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct StorageQuota {
+    pub max_keys: u32,
+    pub max_bytes: u64,
+}
+
+impl Default for StorageQuota {
+    fn default() -> Self {
+        Self {
+            max_keys: 10_000,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct StorageUsage {
+    pub keys: u32,
+    pub bytes: u64,
+}
+
+impl StorageUsage {
+    fn fits(&self, quota: &StorageQuota) -> bool {
+        self.keys <= quota.max_keys && self.bytes <= quota.max_bytes
+    }
+}
+
+// What happens to a plugin's stored data when it is uninstalled
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DataRetention {
+    #[default]
+    Keep,
+    Delete,
+}
+
+// Key-value store of one plugin. Every statement is scoped to `plugin`, so a
+// plugin can never see or modify another plugin's keys.
+pub struct PluginStore {
+    pool: SqlitePool,
+    plugin: String,
+    quota: StorageQuota,
+}
+
+// Rows whose TTL has passed are invisible before they are purged
+const LIVE: &str = "(expires_at IS NULL OR expires_at > ?)";
+
+fn storage_error(e: SqlxError) -> PluginError {
+    PluginError::new("STORAGE_FAILED", &format!("Plugin storage error: {}", e))
+}
+
+fn validate_key(key: &str) -> Result<(), PluginError> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(PluginError::new(
+            "INVALID_KEY",
+            &format!("Storage keys must be 1 to {} bytes long", MAX_KEY_LENGTH),
+        ));
+    }
+    Ok(())
+}
+
+fn expiry(now: DateTime<Utc>, ttl: Option<Duration>) -> Option<String> {
+    ttl.map(|ttl| timezone::format_utc(now + ttl))
+}
+
+impl PluginStore {
+    pub fn new(pool: SqlitePool, plugin: &str, quota: StorageQuota) -> Self {
+        Self {
+            pool,
+            plugin: plugin.to_string(),
+            quota,
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<PluginEntry>, PluginError> {
+        let row = sqlx::query(&format!(
+            "SELECT * FROM plugin_data WHERE plugin_name = ? AND data_key = ? AND {}",
+            LIVE
+        ))
+        .bind(&self.plugin)
+        .bind(key)
+        .bind(timezone::format_utc(Utc::now()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(storage_error)?;
+
+        row.as_ref().map(PluginEntry::from_row).transpose()
+    }
+
+    // Keys starting with `prefix`, in key order. Compared byte-wise, unlike
+    // LIKE which would ignore case and treat % and _ as wildcards.
+    pub async fn list(&self, prefix: &str) -> Result<Vec<PluginEntry>, PluginError> {
+        let rows = sqlx::query(&format!(
+            "SELECT * FROM plugin_data WHERE plugin_name = ? AND substr(data_key, 1, length(?)) = ? AND {} ORDER BY data_key",
+            LIVE
+        ))
+        .bind(&self.plugin)
+        .bind(prefix)
+        .bind(prefix)
+        .bind(timezone::format_utc(Utc::now()))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(storage_error)?;
+
+        rows.iter().map(PluginEntry::from_row).collect()
+    }
+
+    pub async fn set(&self, key: &str, value: &PluginValue, ttl: Option<Duration>) -> Result<(), PluginError> {
+        self.write(key, None, value, ttl).await.map(|_| ())
+    }
+
+    // Write `value` only if the current value equals `expected`; `None` means
+    // the key must not exist (or has expired). Returns whether it was written.
+    pub async fn compare_and_set(
+        &self,
+        key: &str,
+        expected: Option<&PluginValue>,
+        value: &PluginValue,
+        ttl: Option<Duration>,
+    ) -> Result<bool, PluginError> {
+        self.write(key, Some(expected), value, ttl).await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<bool, PluginError> {
+        let result = sqlx::query("DELETE FROM plugin_data WHERE plugin_name = ? AND data_key = ?")
+            .bind(&self.plugin)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Remove every key of the plugin, used when it is uninstalled
+    pub async fn clear(&self) -> Result<u32, PluginError> {
+        let result = sqlx::query("DELETE FROM plugin_data WHERE plugin_name = ?")
+            .bind(&self.plugin)
+            .execute(&self.pool)
+            .await
+            .map_err(storage_error)?;
+
+        Ok(result.rows_affected() as u32)
+    }
+
+    pub async fn usage(&self) -> Result<StorageUsage, PluginError> {
+        let mut conn = self.pool.acquire().await.map_err(storage_error)?;
+        self.usage_excluding(&mut *conn, None, Utc::now()).await.map_err(storage_error)
+    }
+
+    async fn usage_excluding(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        excluded_key: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<StorageUsage, SqlxError> {
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT COUNT(*) AS keys,
+                   COALESCE(SUM(length(CAST(data_key AS BLOB)) + length(CAST(data_value AS BLOB))), 0) AS bytes
+            FROM plugin_data
+            WHERE plugin_name = ? AND data_key IS NOT ? AND {}
+            "#,
+            LIVE
+        ))
+        .bind(&self.plugin)
+        .bind(excluded_key)
+        .bind(timezone::format_utc(now))
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(StorageUsage {
+            keys: row.get::<i64, _>("keys") as u32,
+            bytes: row.get::<i64, _>("bytes") as u64,
+        })
+    }
+
+    // Shared by set and compare_and_set: the quota check and the write run in
+    // one transaction so concurrent writers cannot overshoot the quota.
+    // `expected` is None for an unconditional set.
+    async fn write(
+        &self,
+        key: &str,
+        expected: Option<Option<&PluginValue>>,
+        value: &PluginValue,
+        ttl: Option<Duration>,
+    ) -> Result<bool, PluginError> {
+        validate_key(key)?;
+
+        let now = Utc::now();
+        let now_text = timezone::format_utc(now);
+        let expires_at = expiry(now, ttl);
+        let (data_value, data_type) = value.encode();
+
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+
+        let mut usage = self.usage_excluding(&mut *tx, Some(key), now).await.map_err(storage_error)?;
+        usage.keys += 1;
+        usage.bytes += (key.len() + data_value.len()) as u64;
+        if !usage.fits(&self.quota) {
+            return Err(PluginError::with_details(
+                "QUOTA_EXCEEDED",
+                &format!("Storage quota exceeded for plugin '{}'", self.plugin),
+                serde_json::json!({ "usage": usage, "quota": self.quota }),
+            ));
+        }
+
+        let written = match expected {
+            Some(Some(expected)) => {
+                let (expected_value, expected_type) = expected.encode();
+                sqlx::query(&format!(
+                    r#"
+                    UPDATE plugin_data
+                    SET data_value = ?, data_type = ?, expires_at = ?, updated_at = ?
+                    WHERE plugin_name = ? AND data_key = ? AND data_value = ? AND data_type = ? AND {}
+                    "#,
+                    LIVE
+                ))
+                .bind(&data_value)
+                .bind(data_type)
+                .bind(&expires_at)
+                .bind(&now_text)
+                .bind(&self.plugin)
+                .bind(key)
+                .bind(expected_value)
+                .bind(expected_type)
+                .bind(&now_text)
+                .execute(&mut *tx)
+                .await
+                .map_err(storage_error)?
+                .rows_affected()
+            }
+            expected => {
+                // A conditional insert may only replace a row that has expired
+                let condition = if expected.is_some() {
+                    "WHERE plugin_data.expires_at IS NOT NULL AND plugin_data.expires_at <= excluded.updated_at"
+                } else {
+                    ""
+                };
+                sqlx::query(&format!(
+                    r#"
+                    INSERT INTO plugin_data (plugin_name, data_key, data_value, data_type, expires_at, created_at, updated_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(plugin_name, data_key) DO UPDATE SET
+                        data_value = excluded.data_value,
+                        data_type = excluded.data_type,
+                        expires_at = excluded.expires_at,
+                        updated_at = excluded.updated_at
+                    {}
+                    "#,
+                    condition
+                ))
+                .bind(&self.plugin)
+                .bind(key)
+                .bind(&data_value)
+                .bind(data_type)
+                .bind(&expires_at)
+                .bind(&now_text)
+                .bind(&now_text)
+                .execute(&mut *tx)
+                .await
+                .map_err(storage_error)?
+                .rows_affected()
+            }
+        };
+
+        tx.commit().await.map_err(storage_error)?;
+        Ok(written > 0)
+    }
+}
+
+// Drop expired keys of all plugins
+pub async fn purge_expired(pool: &SqlitePool) -> Result<u32, SqlxError> {
+    let result = sqlx::query("DELETE FROM plugin_data WHERE expires_at IS NOT NULL AND expires_at <= ?")
+        .bind(timezone::format_utc(Utc::now()))
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn storage_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE plugin_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, plugin_name TEXT NOT NULL, data_key TEXT NOT NULL,
+                data_value TEXT NOT NULL, data_type TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL,
+                expires_at TEXT, UNIQUE(plugin_name, data_key)
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    fn text(value: &str) -> PluginValue {
+        PluginValue::String(value.to_string())
+    }
+
+    fn expired() -> Option<Duration> {
+        Some(Duration::seconds(-1))
+    }
+
+    #[test]
+    fn values_round_trip_through_storage_encoding() {
+        let values = [
+            PluginValue::String("EURUSD".to_string()),
+            PluginValue::Integer(-42),
+            PluginValue::Number(0.1 + 0.2),
+            PluginValue::Boolean(true),
+            PluginValue::Json(serde_json::json!({ "levels": [1.1, 1.2] })),
+        ];
+
+        for value in values {
+            let (data_value, data_type) = value.encode();
+            assert_eq!(PluginValue::decode(&data_value, data_type), Some(value));
+        }
+    }
+
+    #[test]
+    fn decode_rejects_values_that_do_not_match_their_type() {
+        assert_eq!(PluginValue::decode("abc", "integer"), None);
+        assert_eq!(PluginValue::decode("yes", "boolean"), None);
+        assert_eq!(PluginValue::decode("1.5", "float"), Some(PluginValue::Number(1.5)));
+        assert_eq!(PluginValue::decode("x", "blob"), None);
+    }
+
+    #[tokio::test]
+    async fn compare_and_set_writes_only_over_the_expected_value() {
+        let store = PluginStore::new(storage_pool().await, "alerts", StorageQuota::default());
+
+        assert!(store.compare_and_set("lock", None, &text("a"), None).await.unwrap());
+        assert!(!store.compare_and_set("lock", None, &text("b"), None).await.unwrap());
+        assert!(!store.compare_and_set("lock", Some(&text("x")), &text("b"), None).await.unwrap());
+        // The stored text matches, the type does not
+        store.set("count", &PluginValue::Integer(1), None).await.unwrap();
+        assert!(!store.compare_and_set("count", Some(&text("1")), &PluginValue::Integer(2), None).await.unwrap());
+        assert!(store.compare_and_set("lock", Some(&text("a")), &text("b"), None).await.unwrap());
+
+        assert_eq!(store.get("lock").await.unwrap().unwrap().value, text("b"));
+        assert_eq!(store.get("count").await.unwrap().unwrap().value, PluginValue::Integer(1));
+        assert!(!store.compare_and_set("missing", Some(&text("a")), &text("b"), None).await.unwrap());
+        assert!(store.get("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_keys_are_invisible_and_purged() {
+        let pool = storage_pool().await;
+        let store = PluginStore::new(pool.clone(), "alerts", StorageQuota::default());
+
+        store.set("session", &text("old"), expired()).await.unwrap();
+        store.set("kept", &text("live"), Some(Duration::hours(1))).await.unwrap();
+
+        assert!(store.get("session").await.unwrap().is_none());
+        let keys: Vec<String> = store.list("").await.unwrap().into_iter().map(|entry| entry.key).collect();
+        assert_eq!(keys, ["kept"]);
+        assert_eq!(store.usage().await.unwrap().keys, 1);
+
+        // An expired key counts as absent for compare-and-set
+        assert!(!store.compare_and_set("session", Some(&text("old")), &text("new"), None).await.unwrap());
+        store.set("stale", &text("old"), expired()).await.unwrap();
+        assert!(store.compare_and_set("stale", None, &text("new"), None).await.unwrap());
+        assert_eq!(store.get("stale").await.unwrap().unwrap().expires_at, None);
+
+        assert_eq!(purge_expired(&pool).await.unwrap(), 1);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM plugin_data").fetch_one(&pool).await.unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn writes_beyond_the_quota_are_rejected() {
+        let pool = storage_pool().await;
+        let quota = StorageQuota { max_keys: 2, max_bytes: 16 };
+        let store = PluginStore::new(pool.clone(), "alerts", quota);
+
+        store.set("a", &text("1234"), None).await.unwrap();
+        store.set("b", &text("1234"), None).await.unwrap();
+        let error = store.set("c", &text("1"), None).await.unwrap_err();
+        assert_eq!(error.code, "QUOTA_EXCEEDED");
+
+        // Replacing a key only counts its new size
+        store.set("b", &text("123456"), None).await.unwrap();
+        assert_eq!(store.set("b", &text("123456789012"), None).await.unwrap_err().code, "QUOTA_EXCEEDED");
+        assert_eq!(store.get("b").await.unwrap().unwrap().value, text("123456"));
+
+        // Expired keys free their share, and other plugins have their own
+        store.set("a", &text("1234"), expired()).await.unwrap();
+        store.set("c", &text("1"), None).await.unwrap();
+        let other = PluginStore::new(pool, "journal-sync", quota);
+        other.set("a", &text("1234"), None).await.unwrap();
+        assert!(other.get("b").await.unwrap().is_none());
+        assert_eq!(store.usage().await.unwrap().keys, 2);
+    }
+
+    #[test]
+    fn quota_counts_keys_and_bytes() {
+        let quota = StorageQuota { max_keys: 2, max_bytes: 10 };
+        assert!(StorageUsage { keys: 2, bytes: 10 }.fits(&quota));
+        assert!(!StorageUsage { keys: 3, bytes: 1 }.fits(&quota));
+        assert!(!StorageUsage { keys: 1, bytes: 11 }.fits(&quota));
+    }
+}
@@ -0,0 +1,661 @@
+// Quick entry: one line of text instead of the trade form
+//
+//   XAUUSD sell 0.5 @2031.4 sl 2036 tp 2018 #FVG #london conf 8 "swept Asia high"
+//
+// Words may come in any order:
+//   <symbol>              first unlabelled word, resolved through the instrument aliases
+//   buy | sell            also long/short, b/s
+//   <number>              volume in lots
+//   @<price>              entry price ("@ 2031.4" works too)
+//   sl <price>, tp <price>
+//   #<tag>                a session (#london, #asia, #ny) or an ICT pattern; the
+//                         first pattern is the trade's pattern, all of them its combination
+//   conf <0-10>           confidence
+//   tf <timeframe>, strategy <name>, emotion <word>, market <word>, session <name>
+//   comm <amount>, swap <amount>
+//   time <HH:MM | YYYY-MM-DD HH:MM>   entry time in the display timezone, default now
+//   "text"                notes; several quoted parts become separate lines
+//
+// Values can be quoted ("strategy "London sweep""), labels can be glued to
+// their value ("sl2036", "tp=2018"). Numbers take a decimal point or comma
+// and thousands separators ("1,234.5", "1.234,5"). Every problem is reported with the
+// character range it covers so the entry box can underline it.
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::database::NewTrade;
+use crate::paths;
+use crate::timezone::{self, TradingSession};
+
+const ALIASES_FILE: &str = "instrument_aliases.json";
+
+// Aliases until the user saves their own list
+const DEFAULT_ALIASES: [(&str, &str); 16] = [
+    ("gold", "XAUUSD"),
+    ("xau", "XAUUSD"),
+    ("silver", "XAGUSD"),
+    ("xag", "XAGUSD"),
+    ("eu", "EURUSD"),
+    ("gu", "GBPUSD"),
+    ("uj", "USDJPY"),
+    ("au", "AUDUSD"),
+    ("nas", "NAS100"),
+    ("nq", "NAS100"),
+    ("us30", "US30"),
+    ("dow", "US30"),
+    ("spx", "SPX500"),
+    ("es", "SPX500"),
+    ("btc", "BTCUSD"),
+    ("oil", "USOIL"),
+];
+
+const VALUE_LABELS: [&str; 11] = ["sl", "tp", "conf", "tf", "strategy", "emotion", "market", "session", "comm", "swap", "time"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuickEntryError {
+    pub message: String,
+    // Character range in the input, end exclusive
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuickEntryPreview {
+    // None while the line has errors
+    pub trade: Option<NewTrade>,
+    // Price distance to the stop loss and the take profit
+    pub risk: Option<f64>,
+    pub reward: Option<f64>,
+    pub risk_reward: Option<f64>,
+    pub errors: Vec<QuickEntryError>,
+    pub warnings: Vec<String>,
+}
+
+impl QuickEntryPreview {
+    // The errors as one message, for commands that save
+    pub fn error_message(&self) -> Option<String> {
+        if self.errors.is_empty() {
+            return None;
+        }
+        Some(self.errors.iter().map(|e| format!("{} (column {})", e.message, e.start + 1)).collect::<Vec<_>>().join("; "))
+    }
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QuickEntryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        if matches!(chars[i], '"' | '“' | '”') {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(QuickEntryError { message: "Unclosed quote".to_string(), start, end: chars.len() }),
+                    Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some('"' | '“' | '”') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => {
+                        text.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token { text, quoted: true, start, end: i });
+        } else {
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '"' {
+                i += 1;
+            }
+            tokens.push(Token { text: chars[start..i].iter().collect(), quoted: false, start, end: i });
+        }
+    }
+
+    Ok(tokens)
+}
+
+// None unless `text` looks like a number. A point or comma that occurs once
+// and last is the decimal separator ("0,5", "1.234,5"); the other one, or
+// one that repeats, groups thousands ("1,000,000"). A lone comma before
+// exactly three digits ("1,000") could be either, so it is an error.
+fn number(text: &str) -> Option<Result<f64, String>> {
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let sign = if text.starts_with('-') { "-" } else { "" };
+    if !digits.chars().any(|c| c.is_ascii_digit()) || !digits.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',') {
+        return None;
+    }
+
+    let (whole, fraction, group) = match digits.rfind(['.', ',']) {
+        None => (digits, "", None),
+        Some(at) => {
+            let separator = digits[at..].chars().next().unwrap_or('.');
+            let other = if separator == '.' { ',' } else { '.' };
+            if digits.matches(separator).count() > 1 {
+                (digits, "", Some(separator))
+            } else {
+                let (whole, fraction) = (&digits[..at], &digits[at + 1..]);
+                let grouped = whole.contains(other);
+                if separator == ',' && !grouped && fraction.len() == 3 && !whole.is_empty() && !whole.starts_with('0') {
+                    return Some(Err(format!("'{}' is ambiguous; write {}{}{} or {}{}.{}", text, sign, whole, fraction, sign, whole, fraction)));
+                }
+                (whole, fraction, grouped.then_some(other))
+            }
+        }
+    };
+
+    let whole = match group {
+        Some(group) => {
+            let groups: Vec<&str> = whole.split(group).collect();
+            let valid = groups.iter().all(|g| g.chars().all(|c| c.is_ascii_digit()))
+                && (1..=3).contains(&groups[0].len())
+                && groups[1..].iter().all(|g| g.len() == 3);
+            if !valid {
+                return Some(Err(format!("'{}' is not a number", text)));
+            }
+            groups.concat()
+        }
+        None => whole.to_string(),
+    };
+    Some(format!("{}{}.{}", sign, whole, fraction).parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| format!("'{}' is not a number", text)))
+}
+
+fn session(tag: &str) -> Option<TradingSession> {
+    match tag.to_lowercase().replace(['-', '_'], "").as_str() {
+        "asia" | "asian" | "tokyo" => Some(TradingSession::Asian),
+        "london" | "ldn" | "lo" => Some(TradingSession::London),
+        "ny" | "newyork" | "nyc" => Some(TradingSession::NewYork),
+        _ => None,
+    }
+}
+
+// `label=value`, `label:value` and `sl2036` forms
+fn split_label(text: &str) -> Option<(String, Option<&str>)> {
+    let lower = text.to_lowercase();
+    if VALUE_LABELS.contains(&lower.as_str()) {
+        return Some((lower, None));
+    }
+    if let Some((label, value)) = text.split_once(['=', ':']) {
+        let label = label.to_lowercase();
+        if VALUE_LABELS.contains(&label.as_str()) {
+            return Some((label, Some(value)));
+        }
+    }
+    ["sl", "tp", "conf"].iter()
+        .find(|label| lower.starts_with(*label) && number(&text[label.len()..]).is_some())
+        .map(|label| (label.to_string(), Some(&text[label.len()..])))
+}
+
+pub fn resolve_symbol(word: &str, aliases: &BTreeMap<String, String>) -> Result<String, String> {
+    if let Some(symbol) = aliases.get(&word.to_lowercase()) {
+        return Ok(symbol.clone());
+    }
+    let valid = word.chars().all(|c| c.is_ascii_alphanumeric() || "._-/".contains(c))
+        && word.chars().any(|c| c.is_ascii_alphabetic());
+    if valid {
+        Ok(word.to_ascii_uppercase())
+    } else {
+        Err(format!("'{}' is not an instrument or alias", word))
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    symbol: Option<String>,
+    side: Option<&'static str>,
+    volume: Option<f64>,
+    entry_price: Option<f64>,
+    sl: Option<f64>,
+    tp: Option<f64>,
+    entry_time: Option<DateTime<Utc>>,
+    tags: Vec<String>,
+    session: Option<TradingSession>,
+    confidence: Option<f64>,
+    timeframe: Option<String>,
+    strategy: Option<String>,
+    emotion: Option<String>,
+    market: Option<String>,
+    commission: Option<f64>,
+    swap: Option<f64>,
+    notes: Vec<String>,
+}
+
+// Parse `input` into a trade. `known_symbols` are instruments already in the
+// journal; anything else is accepted with a warning so typos stand out.
+pub fn parse(
+    input: &str,
+    aliases: &BTreeMap<String, String>,
+    known_symbols: &[String],
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> QuickEntryPreview {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let tokens = match tokenize(input) {
+        Ok(tokens) => tokens,
+        Err(error) => return preview_error(vec![error]),
+    };
+
+    let mut fields = Fields::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+        let error = |message: String| QuickEntryError { message, start: token.start, end: token.end };
+
+        if token.quoted {
+            if !token.text.trim().is_empty() {
+                fields.notes.push(token.text.trim().to_string());
+            }
+            continue;
+        }
+
+        // Labels and `@` take their value from the same token or the next one
+        let labelled = if let Some(price) = token.text.strip_prefix('@') {
+            Some(("@".to_string(), (!price.is_empty()).then_some(price)))
+        } else if token.text.eq_ignore_ascii_case("at") {
+            Some(("@".to_string(), None))
+        } else {
+            split_label(&token.text)
+        };
+
+        if let Some((label, glued)) = labelled {
+            let (value, end) = match glued {
+                Some(value) => (value.to_string(), token.end),
+                None => match tokens.get(i) {
+                    Some(next) if next.quoted || split_label(&next.text).is_none() => {
+                        i += 1;
+                        (next.text.clone(), next.end)
+                    }
+                    _ => {
+                        let name = if label == "@" { "an entry price" } else { "a value" };
+                        errors.push(error(format!("Expected {} after '{}'", name, token.text)));
+                        continue;
+                    }
+                },
+            };
+            let span = |message: String| QuickEntryError { message, start: token.start, end };
+            if let Err(message) = apply_label(&mut fields, &label, &value, &tokens, &mut i, tz, now) {
+                errors.push(span(message));
+            }
+            continue;
+        }
+
+        if let Some(tag) = token.text.strip_prefix('#') {
+            let tag = tag.trim_matches(|c: char| c == ',' || c == ';');
+            match session(tag) {
+                _ if tag.is_empty() => errors.push(error("Empty tag".to_string())),
+                Some(found) => match fields.session {
+                    Some(existing) if existing != found => errors.push(error(format!("Session given twice ({} and {})", existing.name(), found.name()))),
+                    _ => fields.session = Some(found),
+                },
+                None if fields.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) => {}
+                None => fields.tags.push(tag.to_string()),
+            }
+            continue;
+        }
+
+        let side = match token.text.to_lowercase().as_str() {
+            "buy" | "long" | "b" => Some("Buy"),
+            "sell" | "short" | "s" => Some("Sell"),
+            _ => None,
+        };
+        if let Some(side) = side {
+            match fields.side {
+                Some(existing) if existing != side => errors.push(error(format!("Both {} and {} given", existing, side))),
+                _ => fields.side = Some(side),
+            }
+            continue;
+        }
+
+        if let Some(value) = number(&token.text) {
+            match (fields.volume, value) {
+                (_, Err(message)) => errors.push(error(message)),
+                (None, Ok(value)) if value > 0.0 => fields.volume = Some(value),
+                (None, Ok(_)) => errors.push(error("Volume must be greater than 0".to_string())),
+                (Some(_), Ok(_)) => errors.push(error(format!("Unexpected number {}; label it (sl, tp, conf, ...)", token.text))),
+            }
+            continue;
+        }
+
+        if fields.symbol.is_some() {
+            errors.push(error(format!("Unknown word '{}'", token.text)));
+            continue;
+        }
+        match resolve_symbol(&token.text, aliases) {
+            Ok(symbol) => fields.symbol = Some(symbol),
+            Err(message) => errors.push(error(message)),
+        }
+    }
+
+    let end = input.chars().count();
+    let missing = |message: &str| QuickEntryError { message: message.to_string(), start: end, end };
+    if input.trim().is_empty() {
+        return preview_error(vec![missing("Type a trade, e.g. XAUUSD sell 0.5 @2031.4 sl 2036 tp 2018")]);
+    }
+    if fields.symbol.is_none() {
+        errors.push(missing("Missing instrument"));
+    }
+    if fields.side.is_none() {
+        errors.push(missing("Missing direction (buy or sell)"));
+    }
+    if fields.volume.is_none() {
+        errors.push(missing("Missing volume"));
+    }
+    if fields.entry_price.is_none() {
+        errors.push(missing("Missing entry price (@price)"));
+    }
+
+    // Stops on the wrong side of the entry are almost always typos
+    if let (Some(side), Some(entry)) = (fields.side, fields.entry_price) {
+        let buy = side == "Buy";
+        for (label, level, below) in [("Stop loss", fields.sl, buy), ("Take profit", fields.tp, !buy)] {
+            let Some(level) = level else { continue };
+            if (below && level >= entry) || (!below && level <= entry) {
+                let side_of = if below { "below" } else { "above" };
+                errors.push(missing(&format!("{} {} must be {} the entry {} for a {}", label, level, side_of, entry, side)));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return preview_error(errors);
+    }
+
+    let symbol = fields.symbol.unwrap_or_default();
+    if !known_symbols.iter().any(|known| known.eq_ignore_ascii_case(&symbol)) && !aliases.values().any(|s| *s == symbol) {
+        warnings.push(format!("{} has not been traded before", symbol));
+    }
+    let entry_price = fields.entry_price.unwrap_or_default();
+    let risk = fields.sl.map(|sl| round((entry_price - sl).abs()));
+    let reward = fields.tp.map(|tp| round((tp - entry_price).abs()));
+    let risk_reward = match (risk, reward) {
+        (Some(risk), Some(reward)) if risk > 0.0 => Some((reward / risk * 100.0).round() / 100.0),
+        _ => None,
+    };
+    if fields.sl.is_none() {
+        warnings.push("No stop loss; R:R cannot be computed".to_string());
+    } else if fields.tp.is_none() {
+        warnings.push("No take profit; R:R cannot be computed".to_string());
+    }
+
+    let pattern_combination = (!fields.tags.is_empty()).then(|| fields.tags.clone());
+    let trade = NewTrade {
+        symbol,
+        trade_type: fields.side.unwrap_or_default().to_string(),
+        volume: fields.volume.unwrap_or_default(),
+        entry_price,
+        sl: fields.sl,
+        tp: fields.tp,
+        entry_time: timezone::format_utc(fields.entry_time.unwrap_or(now)),
+        notes: (!fields.notes.is_empty()).then(|| fields.notes.join("\n")),
+        commission: fields.commission,
+        swap: fields.swap,
+        ict_pattern: fields.tags.first().cloned(),
+        pattern_type: None,
+        pattern_size: None,
+        pattern_timeframe: fields.timeframe,
+        pattern_combination,
+        chart_explanation: None,
+        strategy_name: fields.strategy,
+        emotion: fields.emotion,
+        confidence_level: fields.confidence,
+        market_condition: fields.market,
+        session: fields.session.map(|session| session.name().to_string()),
+        entry_image: None,
+        exit_image: None,
+        analysis_image: None,
+        rsi: None,
+        macd: None,
+        moving_average: None,
+        support_level: None,
+        resistance_level: None,
+        source_timezone: Some(tz.name().to_string()),
+    };
+
+    QuickEntryPreview { trade: Some(trade), risk, reward, risk_reward, errors, warnings }
+}
+
+fn preview_error(errors: Vec<QuickEntryError>) -> QuickEntryPreview {
+    QuickEntryPreview { trade: None, risk: None, reward: None, risk_reward: None, errors, warnings: Vec::new() }
+}
+
+// Price distances without float noise (2031.4 - 2018 = 13.400000000000091)
+fn round(value: f64) -> f64 {
+    (value * 1e8).round() / 1e8
+}
+
+fn apply_label(
+    fields: &mut Fields,
+    label: &str,
+    value: &str,
+    tokens: &[Token],
+    next: &mut usize,
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let price = |name: &str| match number(value) {
+        Some(Ok(price)) if price > 0.0 => Ok(price),
+        Some(Err(message)) => Err(message),
+        _ => Err(format!("{} '{}' is not a price", name, value)),
+    };
+    let amount = |name: &str| number(value).unwrap_or_else(|| Err(format!("{} '{}' is not a number", name, value)));
+    let once = |slot: bool, name: &str| if slot { Err(format!("{} given twice", name)) } else { Ok(()) };
+    let word = |value: &str| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+
+    match label {
+        "@" => {
+            once(fields.entry_price.is_some(), "Entry price")?;
+            fields.entry_price = Some(price("Entry price")?);
+        }
+        "sl" => {
+            once(fields.sl.is_some(), "Stop loss")?;
+            fields.sl = Some(price("Stop loss")?);
+        }
+        "tp" => {
+            once(fields.tp.is_some(), "Take profit")?;
+            fields.tp = Some(price("Take profit")?);
+        }
+        "conf" => {
+            once(fields.confidence.is_some(), "Confidence")?;
+            let confidence = number(value).and_then(Result::ok).filter(|c| (0.0..=10.0).contains(c));
+            fields.confidence = Some(confidence.ok_or("Confidence must be a number from 0 to 10")?);
+        }
+        "comm" => fields.commission = Some(amount("Commission")?),
+        "swap" => fields.swap = Some(amount("Swap")?),
+        "tf" => fields.timeframe = Some(value.trim().to_uppercase()),
+        "strategy" => fields.strategy = word(value),
+        "emotion" => fields.emotion = word(value),
+        "market" => fields.market = word(value),
+        "session" => {
+            let found = session(value).ok_or_else(|| format!("Unknown session '{}' (asia, london or ny)", value))?;
+            fields.session = Some(found);
+        }
+        "time" => {
+            once(fields.entry_time.is_some(), "Time")?;
+            // A date may be followed by the time as its own word
+            let mut text = value.to_string();
+            if let Some(following) = tokens.get(*next).filter(|t| !t.quoted && NaiveTime::parse_from_str(&t.text, "%H:%M").is_ok()) {
+                if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+                    text = format!("{} {}", value, following.text);
+                    *next += 1;
+                }
+            }
+            fields.entry_time = Some(entry_time(&text, tz, now).ok_or_else(|| format!("'{}' is not a time (HH:MM or YYYY-MM-DD HH:MM)", text))?);
+        }
+        _ => unreachable!("unknown label {}", label),
+    }
+    Ok(())
+}
+
+// A bare time is today's in the display timezone
+fn entry_time(text: &str, tz: Tz, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    for format in ["%H:%M", "%H:%M:%S"] {
+        if let Ok(time) = NaiveTime::parse_from_str(text, format) {
+            let today = now.with_timezone(&tz).date_naive();
+            return Some(timezone::from_local(&today.and_time(time), tz));
+        }
+    }
+    timezone::parse_in_zone(text, tz)
+}
+
+// Aliases are app-level like import profiles, so every workspace shares them
+fn aliases_path() -> PathBuf {
+    paths::config_dir().join(ALIASES_FILE)
+}
+
+pub fn default_aliases() -> BTreeMap<String, String> {
+    DEFAULT_ALIASES.iter().map(|(alias, symbol)| (alias.to_string(), symbol.to_string())).collect()
+}
+
+pub async fn load_aliases() -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    match tokio::fs::read(aliases_path()).await {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(default_aliases()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn write_aliases(aliases: &BTreeMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = aliases_path();
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, serde_json::to_vec_pretty(aliases)?).await?;
+    Ok(())
+}
+
+// Insert or replace; aliases match case-insensitively
+pub async fn save_alias(alias: &str, symbol: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let alias = alias.trim().to_lowercase();
+    if alias.is_empty() || alias.chars().any(|c| c.is_whitespace() || "#@\"".contains(c)) {
+        return Err(format!("'{}' cannot be used as an alias", alias).into());
+    }
+    if VALUE_LABELS.contains(&alias.as_str()) || session(&alias).is_some() || matches!(alias.as_str(), "buy" | "sell" | "long" | "short" | "b" | "s" | "at") {
+        return Err(format!("'{}' is a quick entry keyword", alias).into());
+    }
+    let symbol = resolve_symbol(symbol.trim(), &BTreeMap::new())?;
+
+    let mut aliases = load_aliases().await?;
+    aliases.insert(alias, symbol);
+    write_aliases(&aliases).await
+}
+
+pub async fn delete_alias(alias: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut aliases = load_aliases().await?;
+    if aliases.remove(&alias.trim().to_lowercase()).is_none() {
+        return Ok(false);
+    }
+    write_aliases(&aliases).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-04T10:30:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn preview(input: &str) -> QuickEntryPreview {
+        parse(input, &default_aliases(), &["XAUUSD".to_string()], chrono_tz::Europe::Berlin, now())
+    }
+
+    #[test]
+    fn parses_a_full_line() {
+        let result = preview("gold sell 0.5 @2031.4 sl 2036 tp 2018 #FVG #london #OB conf 8 \"swept Asia high\" strategy \"London sweep\" time 09:15");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let trade = result.trade.unwrap();
+
+        assert_eq!((trade.symbol.as_str(), trade.trade_type.as_str(), trade.volume), ("XAUUSD", "Sell", 0.5));
+        assert_eq!((trade.entry_price, trade.sl, trade.tp), (2031.4, Some(2036.0), Some(2018.0)));
+        assert_eq!(trade.ict_pattern.as_deref(), Some("FVG"));
+        assert_eq!(trade.pattern_combination, Some(vec!["FVG".to_string(), "OB".to_string()]));
+        assert_eq!(trade.session.as_deref(), Some("London"));
+        assert_eq!(trade.confidence_level, Some(8.0));
+        assert_eq!(trade.notes.as_deref(), Some("swept Asia high"));
+        assert_eq!(trade.strategy_name.as_deref(), Some("London sweep"));
+        // 09:15 in Berlin
+        assert_eq!(trade.entry_time, "2024-03-04T08:15:00Z");
+
+        assert_eq!((result.risk, result.reward, result.risk_reward), (Some(4.6), Some(13.4), Some(2.91)));
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn accepts_glued_labels_and_any_order() {
+        let result = preview("#NY long sl=1.0850 tp1.0950 EURGBP @ 1.09 2 time 2024-03-01 14:00");
+        let trade = result.trade.unwrap();
+
+        assert_eq!((trade.symbol.as_str(), trade.trade_type.as_str(), trade.volume), ("EURGBP", "Buy", 2.0));
+        assert_eq!((trade.sl, trade.tp), (Some(1.085), Some(1.095)));
+        assert_eq!(trade.session.as_deref(), Some("New York"));
+        assert_eq!(trade.entry_time, "2024-03-01T13:00:00Z");
+        assert_eq!(result.risk_reward, Some(1.0));
+        assert_eq!(result.warnings, ["EURGBP has not been traded before"]);
+    }
+
+    #[test]
+    fn reports_errors_with_their_position() {
+        let result = preview("XAUUSD sell 0.5 @abc sl 2020 conf 12 foo");
+        assert!(result.trade.is_none());
+        let messages: Vec<(&str, usize, usize)> = result.errors.iter().map(|e| (e.message.as_str(), e.start, e.end)).collect();
+        assert_eq!(messages, [
+            ("Entry price 'abc' is not a price", 16, 20),
+            ("Confidence must be a number from 0 to 10", 29, 36),
+            ("Unknown word 'foo'", 37, 40),
+            ("Missing entry price (@price)", 40, 40),
+        ]);
+
+        let result = preview("XAUUSD sell 0.5 @2031.4 sl 2020 \"notes");
+        assert_eq!(result.errors[0].message, "Unclosed quote");
+
+        let result = preview("XAUUSD sell 0.5 @2031.4 sl 2020");
+        assert_eq!(result.errors[0].message, "Stop loss 2020 must be above the entry 2031.4 for a Sell");
+        assert_eq!(result.error_message().unwrap(), "Stop loss 2020 must be above the entry 2031.4 for a Sell (column 32)");
+    }
+
+    #[test]
+    fn thousands_separators_are_read_and_ambiguous_commas_rejected() {
+        let result = preview("nas buy 0,5 @18,250.5 sl 18.200,25 tp 18,300.0 comm -1,234.50");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let trade = result.trade.unwrap();
+        assert_eq!((trade.volume, trade.entry_price, trade.sl, trade.tp), (0.5, 18250.5, Some(18200.25), Some(18300.0)));
+        assert_eq!(trade.commission, Some(-1234.5));
+
+        // A point on its own is always the decimal point, repeated separators group
+        let trade = preview("XAUUSD buy 1.000 @2,031,400 sl 2.030.000").trade.unwrap();
+        assert_eq!((trade.volume, trade.entry_price, trade.sl), (1.0, 2031400.0, Some(2030000.0)));
+
+        let result = preview("XAUUSD buy 1,000 @2031,4 sl 2,0,30 swap -0,125");
+        let messages: Vec<(&str, usize, usize)> = result.errors.iter().map(|e| (e.message.as_str(), e.start, e.end)).collect();
+        assert_eq!(messages, [
+            ("'1,000' is ambiguous; write 1000 or 1.000", 11, 16),
+            ("'2,0,30' is not a number", 25, 34),
+            ("Missing volume", 46, 46),
+        ]);
+    }
+}
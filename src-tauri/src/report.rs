@@ -0,0 +1,616 @@
+// Self-contained HTML performance report
+//
+// One file with its CSS inline, charts drawn as inline SVG and screenshots
+// embedded as data URIs, so it opens offline and can be mailed as it is.
+// Figures are computed from the trades in the report's range and scope with
+// the same functions the analysis page uses.
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::analysis::{AnalysisSummary, Analyzer, MonthlyReturn, SessionAnalysis, SessionPerformance, StrategyPerformance, TradeProjection};
+use crate::database::DimensionStatistics;
+use crate::equity;
+use crate::timezone;
+use crate::trade_export::{self, TradeRow};
+
+pub const IMAGE_COLUMNS: [(&str, &str); 3] = [
+    ("entry_image", "Entry"),
+    ("exit_image", "Exit"),
+    ("analysis_image", "Analysis"),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ReportRange {
+    // "this_month", "last_month", "this_year", "last_year", "last_30_days" or "all"
+    Preset(String),
+    // `to` is exclusive
+    Custom { from: DateTime<Utc>, to: DateTime<Utc> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedRange {
+    pub bounds: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    pub label: String,
+}
+
+fn month_start(year: i32, month: u32, tz: Tz) -> DateTime<Utc> {
+    let date = NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(NaiveDate::MIN);
+    timezone::from_local(&date.and_time(NaiveTime::MIN), tz)
+}
+
+fn next_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 12 { (year + 1, 1) } else { (year, month + 1) }
+}
+
+pub fn month_name(month: u32) -> &'static str {
+    const NAMES: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ];
+    NAMES.get(month.wrapping_sub(1) as usize).copied().unwrap_or("")
+}
+
+impl ReportRange {
+    // Calendar presets follow the display timezone
+    pub fn resolve(&self, now: DateTime<Utc>, tz: Tz) -> Result<ResolvedRange, String> {
+        let local = now.with_timezone(&tz);
+        let (year, month) = (local.year(), local.month());
+
+        let month_range = |year: i32, month: u32| {
+            let (next_year, next) = next_month(year, month);
+            ResolvedRange {
+                bounds: Some((month_start(year, month, tz), month_start(next_year, next, tz))),
+                label: format!("{} {}", month_name(month), year),
+            }
+        };
+        let year_range = |year: i32| ResolvedRange {
+            bounds: Some((month_start(year, 1, tz), month_start(year + 1, 1, tz))),
+            label: year.to_string(),
+        };
+
+        match self {
+            ReportRange::Preset(preset) => match preset.as_str() {
+                "this_month" => Ok(month_range(year, month)),
+                "last_month" if month == 1 => Ok(month_range(year - 1, 12)),
+                "last_month" => Ok(month_range(year, month - 1)),
+                "this_year" => Ok(year_range(year)),
+                "last_year" => Ok(year_range(year - 1)),
+                "last_30_days" => Ok(ResolvedRange {
+                    bounds: Some((now - Duration::days(30), now)),
+                    label: "Last 30 days".to_string(),
+                }),
+                "all" => Ok(ResolvedRange { bounds: None, label: "All trades".to_string() }),
+                other => Err(format!("Unknown report range: {}", other)),
+            },
+            ReportRange::Custom { from, to } => {
+                if from >= to {
+                    return Err("The report range ends before it starts".to_string());
+                }
+                let last_day = (*to - Duration::seconds(1)).with_timezone(&tz).date_naive();
+                Ok(ResolvedRange {
+                    bounds: Some((*from, *to)),
+                    label: format!("{} to {}", from.with_timezone(&tz).date_naive(), last_day),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ReportOptions {
+    pub title: Option<String>,
+    // Trades of the report whose screenshots and notes are shown
+    pub screenshot_trade_ids: Vec<u32>,
+    // Balance at the start of the range; without it the equity curve is
+    // cumulative P/L and monthly returns have no percentage
+    pub starting_balance: Option<f64>,
+    // TrueType font for PDF text the bundled font cannot draw, e.g. Chinese;
+    // common system fonts are searched when unset
+    pub pdf_font: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReportResult {
+    pub path: String,
+    pub range: String,
+    pub trades: usize,
+    pub screenshots: usize,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportImage {
+    pub label: &'static str,
+    pub path: String,
+    // None when the file could not be read
+    pub bytes: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportScreenshot {
+    pub trade: TradeRow,
+    pub images: Vec<ReportImage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportData {
+    pub title: String,
+    pub range_label: String,
+    pub generated_at: DateTime<Utc>,
+    pub timezone: Tz,
+    pub starting_balance: Option<f64>,
+    pub trade_count: usize,
+    pub trades: Vec<TradeRow>,
+    pub summary: AnalysisSummary,
+    pub equity: Vec<(DateTime<Utc>, f64)>,
+    pub monthly: Vec<MonthlyReturn>,
+    pub ict_win_rates: Vec<HashMap<String, Value>>,
+    pub sessions: SessionAnalysis,
+    pub strategies: Vec<StrategyPerformance>,
+    pub screenshots: Vec<ReportScreenshot>,
+}
+
+// Balance after each closed trade, in the order trades were closed
+pub fn equity_points(trades: &[TradeProjection], starting_balance: f64) -> Vec<(DateTime<Utc>, f64)> {
+    let curve = equity::build(trades, starting_balance);
+    let mut points = Vec::with_capacity(curve.points.len() + 1);
+    if let Some(first) = curve.points.first() {
+        points.push((first.closed_at, starting_balance));
+    }
+    points.extend(curve.points.iter().map(|point| (point.closed_at, point.balance)));
+    points
+}
+
+pub fn text<'a>(row: &'a TradeRow, column: &str) -> Option<&'a str> {
+    row.get(column).and_then(Value::as_str).filter(|value| !value.trim().is_empty())
+}
+
+// Totals per value of a text column (ict_pattern, strategy_name)
+fn dimension_totals(rows: &[TradeRow], projections: &[TradeProjection], column: &str) -> Vec<DimensionStatistics> {
+    let mut totals: BTreeMap<&str, DimensionStatistics> = BTreeMap::new();
+    for (row, trade) in rows.iter().zip(projections) {
+        if let Some(key) = text(row, column) {
+            totals.entry(key)
+                .or_insert_with(|| DimensionStatistics { key: key.to_string(), ..Default::default() })
+                .record(trade.is_win, trade.profit_loss_money);
+        }
+    }
+    totals.into_values().collect()
+}
+
+impl ReportData {
+    // Everything but the screenshots, which need the image files
+    pub fn build(rows: &[TradeRow], range_label: &str, options: &ReportOptions, tz: Tz, now: DateTime<Utc>) -> Self {
+        let projections: Vec<TradeProjection> = rows.iter().map(trade_export::projection).collect();
+
+        ReportData {
+            title: options.title.clone().unwrap_or_else(|| "Trading Performance Report".to_string()),
+            range_label: range_label.to_string(),
+            generated_at: now,
+            timezone: tz,
+            starting_balance: options.starting_balance,
+            trade_count: rows.len(),
+            trades: rows.to_vec(),
+            summary: Analyzer::summarize(&projections),
+            equity: equity_points(&projections, options.starting_balance.unwrap_or(0.0)),
+            monthly: Analyzer::monthly_returns(&projections, tz, options.starting_balance),
+            ict_win_rates: Analyzer::ict_win_rates(dimension_totals(rows, &projections, "ict_pattern")),
+            sessions: Analyzer::session_breakdown(&projections),
+            strategies: Analyzer::strategy_breakdown(&projections, &dimension_totals(rows, &projections, "strategy_name")),
+            screenshots: Vec::new(),
+        }
+    }
+}
+
+pub fn data_uri(path: &str, bytes: &[u8]) -> String {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    let mime = match path.rsplit('.').next().map(|ext| ext.to_ascii_lowercase()).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    };
+    format!("data:{};base64,{}", mime, BASE64.encode(bytes))
+}
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn money(value: f64) -> String {
+    let text = format!("{:.2}", value.abs());
+    let (whole, decimals) = text.split_once('.').unwrap_or((&text, "00"));
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}.{}", if value < 0.0 { "-" } else { "" }, grouped, decimals)
+}
+
+fn ratio(value: f64) -> String {
+    if value.is_finite() { format!("{:.2}", value) } else { "&infin;".to_string() }
+}
+
+fn sign_class(value: f64) -> &'static str {
+    if value > 0.0 { "pos" } else if value < 0.0 { "neg" } else { "" }
+}
+
+const CHART_WIDTH: f64 = 760.0;
+
+// Line chart of the balance over time
+pub fn equity_svg(points: &[(DateTime<Utc>, f64)], tz: Tz) -> String {
+    if points.len() < 2 {
+        return "<p class=\"empty\">Not enough closed trades for an equity curve.</p>".to_string();
+    }
+
+    let (height, left, right, top, bottom) = (240.0, 78.0, 12.0, 12.0, 28.0);
+    let (start, end) = (points[0].0, points[points.len() - 1].0);
+    let span = ((end - start).num_seconds() as f64).max(1.0);
+    let mut low = points.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+    let mut high = points.iter().map(|(_, v)| *v).fold(f64::NEG_INFINITY, f64::max);
+    if high <= low {
+        low -= 1.0;
+        high += 1.0;
+    }
+
+    let x = |t: DateTime<Utc>| left + (t - start).num_seconds() as f64 / span * (CHART_WIDTH - left - right);
+    let y = |v: f64| top + (high - v) / (high - low) * (height - top - bottom);
+    let line: Vec<String> = points.iter().map(|(t, v)| format!("{:.1},{:.1}", x(*t), y(*v))).collect();
+
+    let mut svg = String::new();
+    let _ = write!(svg, r#"<svg class="chart" viewBox="0 0 {w} {h}" role="img" aria-label="Equity curve">"#, w = CHART_WIDTH, h = height);
+    for value in [high, (high + low) / 2.0, low] {
+        let _ = write!(
+            svg,
+            r#"<line class="grid" x1="{l}" x2="{r}" y1="{y:.1}" y2="{y:.1}"/><text class="axis" x="{tx}" y="{ty:.1}" text-anchor="end">{label}</text>"#,
+            l = left, r = CHART_WIDTH - right, y = y(value), tx = left - 6.0, ty = y(value) + 4.0, label = money(value),
+        );
+    }
+    let baseline = points[0].1;
+    let _ = write!(
+        svg,
+        r#"<line class="base" x1="{l}" x2="{r}" y1="{y:.1}" y2="{y:.1}"/>"#,
+        l = left, r = CHART_WIDTH - right, y = y(baseline),
+    );
+    let _ = write!(
+        svg,
+        r#"<polygon class="area" points="{x0:.1},{b:.1} {line} {x1:.1},{b:.1}"/><polyline class="line" points="{line}"/>"#,
+        x0 = x(start), x1 = x(end), b = y(low), line = line.join(" "),
+    );
+    let _ = write!(
+        svg,
+        r#"<text class="axis" x="{l}" y="{by}">{first}</text><text class="axis" x="{r}" y="{by}" text-anchor="end">{last}</text></svg>"#,
+        l = left, r = CHART_WIDTH - right, by = height - 8.0,
+        first = start.with_timezone(&tz).date_naive(), last = end.with_timezone(&tz).date_naive(),
+    );
+    svg
+}
+
+// Horizontal bars; negative values grow left of a centred axis
+pub fn bar_svg(label: &str, bars: &[(String, f64, String)]) -> String {
+    if bars.is_empty() {
+        return "<p class=\"empty\">No data in this range.</p>".to_string();
+    }
+
+    let (row, label_width, value_width) = (24.0, 170.0, 110.0);
+    let height = row * bars.len() as f64 + 8.0;
+    let area = CHART_WIDTH - label_width - value_width;
+    let max = bars.iter().map(|(_, v, _)| v.abs()).fold(0.0, f64::max).max(f64::EPSILON);
+    let signed = bars.iter().any(|(_, v, _)| *v < 0.0);
+    let zero = if signed { label_width + area / 2.0 } else { label_width };
+    let scale = if signed { area / 2.0 } else { area };
+
+    let mut svg = String::new();
+    let _ = write!(svg, r#"<svg class="chart" viewBox="0 0 {w} {h}" role="img" aria-label="{label}">"#, w = CHART_WIDTH, h = height, label = escape_html(label));
+    for (i, (name, value, caption)) in bars.iter().enumerate() {
+        let top = 4.0 + i as f64 * row;
+        let width = value.abs() / max * scale;
+        let start = if *value < 0.0 { zero - width } else { zero };
+        let _ = write!(
+            svg,
+            r#"<text class="axis" x="{lx}" y="{ty:.1}" text-anchor="end">{name}</text><rect class="bar {class}" x="{x:.1}" y="{y:.1}" width="{width:.1}" height="{h:.1}"/><text class="axis" x="{vx}" y="{ty:.1}">{caption}</text>"#,
+            lx = label_width - 8.0, ty = top + row / 2.0 + 4.0, name = escape_html(name),
+            class = if *value < 0.0 { "neg" } else { "pos" }, x = start, y = top + 3.0, h = row - 6.0,
+            vx = label_width + area + 8.0, caption = escape_html(caption),
+        );
+    }
+    if signed {
+        let _ = write!(svg, r#"<line class="base" x1="{z}" x2="{z}" y1="0" y2="{h}"/>"#, z = zero, h = height);
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Roboto, Helvetica, Arial, sans-serif; color: #1f2937; background: #f3f4f6; margin: 0; }
+main { max-width: 880px; margin: 0 auto; padding: 32px 24px 48px; background: #fff; }
+h1 { margin: 0 0 4px; font-size: 26px; }
+h2 { margin: 36px 0 12px; font-size: 18px; border-bottom: 1px solid #e5e7eb; padding-bottom: 6px; }
+.meta { color: #6b7280; font-size: 13px; }
+.cards { display: grid; grid-template-columns: repeat(4, 1fr); gap: 12px; margin-top: 24px; }
+.card { border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px; }
+.card .label { color: #6b7280; font-size: 12px; text-transform: uppercase; letter-spacing: .04em; }
+.card .value { font-size: 20px; font-weight: 600; margin-top: 4px; }
+table { width: 100%; border-collapse: collapse; font-size: 13px; margin-top: 12px; }
+th, td { padding: 6px 8px; border-bottom: 1px solid #e5e7eb; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+th { color: #6b7280; font-weight: 600; }
+.pos { color: #047857; fill: #10b981; }
+.neg { color: #b91c1c; fill: #ef4444; }
+.chart { width: 100%; height: auto; }
+.chart .axis { font-size: 11px; fill: #6b7280; }
+.chart .grid { stroke: #e5e7eb; }
+.chart .base { stroke: #9ca3af; stroke-dasharray: 4 3; }
+.chart .line { fill: none; stroke: #2563eb; stroke-width: 2; }
+.chart .area { fill: #dbeafe; opacity: .6; }
+.empty { color: #9ca3af; font-style: italic; }
+.shot { border: 1px solid #e5e7eb; border-radius: 8px; padding: 16px; margin-top: 16px; page-break-inside: avoid; }
+.shot h3 { margin: 0 0 8px; font-size: 15px; }
+.shot figure { margin: 12px 0 0; }
+.shot img { max-width: 100%; border-radius: 4px; border: 1px solid #e5e7eb; }
+.shot figcaption { color: #6b7280; font-size: 12px; margin-top: 4px; }
+.notes { white-space: pre-wrap; font-size: 14px; }
+footer { margin-top: 40px; color: #9ca3af; font-size: 12px; text-align: center; }
+@media print { body { background: #fff; } main { padding: 0; } }
+"#;
+
+fn card(html: &mut String, label: &str, value: &str, class: &str) {
+    let _ = write!(html, r#"<div class="card"><div class="label">{}</div><div class="value {}">{}</div></div>"#, label, class, value);
+}
+
+fn session_row(html: &mut String, session: &SessionPerformance) {
+    let _ = write!(
+        html,
+        r#"<tr><td>{}</td><td>{}</td><td>{:.1}%</td><td class="{}">{}</td><td>{:.1} h</td></tr>"#,
+        escape_html(&session.session), session.total_trades, session.win_rate,
+        sign_class(session.net_profit), money(session.net_profit), session.average_holding,
+    );
+}
+
+pub fn render_html(report: &ReportData) -> String {
+    let summary = &report.summary;
+    let tz = report.timezone;
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1"><title>{title}</title><style>{style}</style></head><body><main>"#,
+        title = escape_html(&report.title), style = STYLE,
+    );
+    let _ = write!(
+        html,
+        r#"<h1>{}</h1><div class="meta">{} &middot; {} trades &middot; times in {} &middot; generated {}</div>"#,
+        escape_html(&report.title), escape_html(&report.range_label), report.trade_count, tz.name(),
+        report.generated_at.with_timezone(&tz).format("%Y-%m-%d %H:%M"),
+    );
+
+    html.push_str(r#"<div class="cards">"#);
+    card(&mut html, "Net P/L", &money(summary.net_profit), sign_class(summary.net_profit));
+    card(&mut html, "Win rate", &format!("{:.1}%", summary.win_rate), "");
+    card(&mut html, "Profit factor", &ratio(summary.profit_factor), "");
+    card(&mut html, "Trades (W/L)", &format!("{} ({}/{})", summary.total_trades, summary.winning_trades, summary.losing_trades), "");
+    card(&mut html, "Max drawdown", &money(summary.max_drawdown), if summary.max_drawdown > 0.0 { "neg" } else { "" });
+    card(&mut html, "Expectancy", &money(summary.expectancy), sign_class(summary.expectancy));
+    card(&mut html, "Average trade", &money(summary.average_trade), sign_class(summary.average_trade));
+    card(&mut html, "Recovery factor", &ratio(summary.recovery_factor), "");
+    html.push_str("</div>");
+
+    let _ = write!(
+        html,
+        "<h2>{}</h2>{}",
+        if report.starting_balance.is_some() { "Equity curve" } else { "Cumulative P/L" },
+        equity_svg(&report.equity, tz),
+    );
+
+    html.push_str("<h2>Monthly returns</h2>");
+    let monthly_bars: Vec<(String, f64, String)> = report.monthly.iter()
+        .map(|m| (format!("{} {}", &month_name(m.month)[..3], m.year), m.profit, money(m.profit)))
+        .collect();
+    html.push_str(&bar_svg("Monthly P/L", &monthly_bars));
+    if !report.monthly.is_empty() {
+        html.push_str("<table><tr><th>Month</th><th>Trades</th><th>P/L</th><th>Return</th></tr>");
+        for month in &report.monthly {
+            let _ = write!(
+                html,
+                r#"<tr><td>{} {}</td><td>{}</td><td class="{}">{}</td><td>{}</td></tr>"#,
+                month_name(month.month), month.year, month.trades, sign_class(month.profit), money(month.profit),
+                if report.starting_balance.is_some() { format!("{:.2}%", month.return_percentage) } else { "&ndash;".to_string() },
+            );
+        }
+        html.push_str("</table>");
+    }
+
+    html.push_str("<h2>ICT pattern win rates</h2>");
+    let number = |row: &HashMap<String, Value>, key: &str| row.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+    let pattern_bars: Vec<(String, f64, String)> = report.ict_win_rates.iter()
+        .map(|row| {
+            let pattern = row.get("pattern").and_then(Value::as_str).unwrap_or_default().to_string();
+            let win_rate = number(row, "win_rate");
+            (pattern, win_rate, format!("{:.1}% of {}", win_rate, number(row, "total_trades")))
+        })
+        .collect();
+    html.push_str(&bar_svg("ICT pattern win rates", &pattern_bars));
+    if !report.ict_win_rates.is_empty() {
+        html.push_str("<table><tr><th>Pattern</th><th>Trades</th><th>Wins</th><th>Win rate</th><th>Avg win</th><th>Avg loss</th></tr>");
+        for row in &report.ict_win_rates {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td><td>{}</td><td>{}</td></tr>",
+                escape_html(row.get("pattern").and_then(Value::as_str).unwrap_or_default()),
+                number(row, "total_trades"), number(row, "winning_trades"), number(row, "win_rate"),
+                money(number(row, "avg_win")), money(number(row, "avg_loss")),
+            );
+        }
+        html.push_str("</table>");
+    }
+
+    let sessions = &report.sessions;
+    html.push_str("<h2>Sessions</h2><table><tr><th>Session</th><th>Trades</th><th>Win rate</th><th>Net P/L</th><th>Avg holding</th></tr>");
+    for session in [&sessions.asian_session, &sessions.london_session, &sessions.new_york_session, &sessions.overlap_sessions.london_new_york] {
+        session_row(&mut html, session);
+    }
+    html.push_str("</table>");
+
+    html.push_str("<h2>Strategies</h2>");
+    if report.strategies.is_empty() {
+        html.push_str(r#"<p class="empty">No trades with a strategy in this range.</p>"#);
+    } else {
+        html.push_str("<table><tr><th>Strategy</th><th>Trades</th><th>Win rate</th><th>Net P/L</th><th>Profit factor</th><th>Max drawdown</th></tr>");
+        for strategy in &report.strategies {
+            let _ = write!(
+                html,
+                r#"<tr><td>{}</td><td>{}</td><td>{:.1}%</td><td class="{}">{}</td><td>{}</td><td>{}</td></tr>"#,
+                escape_html(&strategy.strategy), strategy.total_trades, strategy.win_rate,
+                sign_class(strategy.net_profit), money(strategy.net_profit), ratio(strategy.profit_factor), money(strategy.max_drawdown),
+            );
+        }
+        html.push_str("</table>");
+    }
+
+    if !report.screenshots.is_empty() {
+        html.push_str("<h2>Trade reviews</h2>");
+    }
+    for shot in &report.screenshots {
+        let row = &shot.trade;
+        let entry = row.get("entry_time").and_then(Value::as_str).and_then(crate::database::parse_trade_time);
+        let profit = row.get("profit_loss_money").and_then(Value::as_f64);
+        let _ = write!(
+            html,
+            r#"<section class="shot"><h3>#{} {} {} &middot; {}{}</h3>"#,
+            row.get("id").and_then(Value::as_i64).unwrap_or_default(),
+            escape_html(text(row, "symbol").unwrap_or_default()),
+            escape_html(text(row, "trade_type").unwrap_or_default()),
+            entry.map(|t| t.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+            profit.map(|p| format!(r#" &middot; <span class="{}">{}</span>"#, sign_class(p), money(p))).unwrap_or_default(),
+        );
+        let setup: Vec<&str> = ["ict_pattern", "strategy_name", "session"].iter().filter_map(|c| text(row, c)).collect();
+        if !setup.is_empty() {
+            let _ = write!(html, r#"<div class="meta">{}</div>"#, escape_html(&setup.join(" · ")));
+        }
+        for column in ["notes", "chart_explanation"] {
+            if let Some(notes) = text(row, column) {
+                let _ = write!(html, r#"<p class="notes">{}</p>"#, escape_html(notes));
+            }
+        }
+        for image in &shot.images {
+            if let Some(bytes) = &image.bytes {
+                let _ = write!(
+                    html,
+                    r#"<figure><img src="{}" alt="{} chart"><figcaption>{}</figcaption></figure>"#,
+                    data_uri(&image.path, bytes), image.label, image.label,
+                );
+            }
+        }
+        html.push_str("</section>");
+    }
+
+    html.push_str("<footer>Figures cover closed trades in the range; open trades are counted but carry no P/L.</footer></main></body></html>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn rows(values: Vec<Value>) -> Vec<TradeRow> {
+        values.into_iter().map(|v| v.as_object().unwrap().clone()).collect()
+    }
+
+    #[test]
+    fn open_trades_are_counted_but_stay_out_of_the_figures() {
+        let rows = rows(vec![
+            json!({"id": 1, "symbol": "EURUSD", "entry_time": "2024-03-04T08:00:00Z", "exit_time": "2024-03-04T09:00:00Z", "is_win": 1, "profit_loss_money": 300.0}),
+            json!({"id": 2, "symbol": "EURUSD", "entry_time": "2024-03-05T08:00:00Z", "exit_time": "2024-03-05T09:00:00Z", "is_win": 0, "profit_loss_money": -100.0}),
+            json!({"id": 3, "symbol": "GBPUSD", "entry_time": "2024-03-06T08:00:00Z", "is_win": null, "profit_loss_money": null}),
+        ]);
+        let report = ReportData::build(&rows, "March 2024", &ReportOptions::default(), chrono_tz::UTC, utc("2024-04-01T08:00:00Z"));
+
+        assert_eq!((report.trade_count, report.summary.open_trades), (3, 1));
+        assert_eq!(report.summary.net_profit, 200.0);
+        // Cumulative P/L from zero, one point per closed trade
+        let balances: Vec<f64> = report.equity.iter().map(|(_, b)| *b).collect();
+        assert_eq!(balances, [0.0, 300.0, 200.0]);
+        assert_eq!(report.monthly.iter().map(|m| m.trades).sum::<u32>(), 2);
+
+        let html = render_html(&report);
+        assert!(html.contains("3 trades") && html.contains("<h2>Cumulative P/L</h2>"));
+        // No strategy on any trade, and no screenshots were asked for
+        assert!(html.contains("No trades with a strategy in this range."));
+        assert!(!html.contains("Trade reviews"));
+    }
+
+    #[test]
+    fn months_follow_the_display_timezone_and_compound_from_the_starting_balance() {
+        let athens = chrono_tz::Europe::Athens;
+        // Closed before midnight UTC on New Year's Eve, already January in Athens
+        let rows = rows(vec![
+            json!({"id": 1, "entry_time": "2023-12-15T08:00:00Z", "exit_time": "2023-12-15T10:00:00Z", "is_win": 1, "profit_loss_money": 500.0}),
+            json!({"id": 2, "entry_time": "2023-12-31T20:00:00Z", "exit_time": "2023-12-31T23:30:00Z", "is_win": 0, "profit_loss_money": -210.0}),
+        ]);
+        let options = ReportOptions { starting_balance: Some(10_000.0), ..Default::default() };
+        let report = ReportData::build(&rows, "Winter", &options, athens, utc("2024-02-01T08:00:00Z"));
+
+        let months: Vec<(i32, u32, f64)> = report.monthly.iter().map(|m| (m.year, m.month, m.return_percentage)).collect();
+        assert_eq!(months, [(2023, 12, 5.0), (2024, 1, -2.0)]);
+        let html = render_html(&report);
+        assert!(html.contains("<h2>Equity curve</h2>") && html.contains("times in Europe/Athens"));
+        assert!(html.contains("<td>December 2023</td>") && html.contains("<td>-2.00%</td>"));
+
+        // Without a balance there is nothing to take a percentage of
+        let report = ReportData::build(&rows, "Winter", &ReportOptions::default(), athens, utc("2024-02-01T08:00:00Z"));
+        assert!(render_html(&report).contains("<td>&ndash;</td>"));
+    }
+
+    #[test]
+    fn journal_text_is_escaped_and_only_readable_screenshots_are_inlined() {
+        let rows = rows(vec![
+            json!({"id": 1, "symbol": "XAUUSD", "trade_type": "Sell", "entry_time": "2024-03-04T08:00:00Z", "exit_time": "2024-03-04T09:30:00Z",
+                   "is_win": 1, "profit_loss_money": 460.0, "ict_pattern": "FVG & <OB>", "strategy_name": "London \"sweep\"",
+                   "notes": "<script>alert(1)</script>"}),
+        ]);
+        let mut report = ReportData::build(&rows, "March 2024", &ReportOptions::default(), chrono_tz::UTC, utc("2024-04-01T08:00:00Z"));
+        report.screenshots.push(ReportScreenshot {
+            trade: rows[0].clone(),
+            images: vec![
+                ReportImage { label: "Entry", path: "images/a.PNG".to_string(), bytes: Some(b"png".to_vec()) },
+                ReportImage { label: "Exit", path: "images/gone.webp".to_string(), bytes: None },
+            ],
+        });
+        let html = render_html(&report);
+
+        assert!(html.contains("FVG &amp; &lt;OB&gt;") && html.contains("London &quot;sweep&quot;"));
+        assert!(html.contains("&lt;script&gt;") && !html.contains("<script"));
+        assert!(html.contains(r#"<img src="data:image/png;base64,cG5n" alt="Entry chart">"#));
+        assert!(!html.contains("Exit chart"));
+        // Nothing is fetched when the file is opened
+        assert!(!html.contains("src=\"http") && !html.contains("<link"));
+    }
+}
@@ -0,0 +1,658 @@
+// Annual realized P/L for tax filing
+//
+// Closed positions are split into lots. Fills from the executions table, or a
+// trade's entry and exit when it has none, are matched first-in first-out
+// per instrument across trades, so a position closed in parts is realized in
+// the year of each closing fill at the price of the oldest open volume. Lot
+// P/L is valued like the closing trade's stored P/L, commission and swap are
+// spread by volume, and each lot is converted to the tax currency with the
+// stored rate for its close date.
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::database::parse_trade_time;
+use crate::report::money;
+use crate::trade_export::TradeRow;
+
+// Volumes below this are treated as flat
+const FLAT: f64 = 1e-9;
+
+// One unit of `base` is worth `rate` units of `quote` on `date`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FxRate {
+    pub base: String,
+    pub quote: String,
+    pub date: NaiveDate,
+    pub rate: f64,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl FxRate {
+    pub fn validate(&self) -> Result<(), String> {
+        for code in [&self.base, &self.quote] {
+            if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err(format!("'{}' is not a three-letter currency code", code));
+            }
+        }
+        if self.base == self.quote {
+            return Err(format!("{} cannot be converted to itself", self.base));
+        }
+        if !self.rate.is_finite() || self.rate <= 0.0 {
+            return Err(format!("{}/{} rate on {} must be positive", self.base, self.quote, self.date));
+        }
+        Ok(())
+    }
+}
+
+fn default_rate_age() -> u32 {
+    7
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaxReportOptions {
+    pub year: i32,
+    pub tax_currency: String,
+    // Currency of the P/L, commission and swap stored on trades
+    pub account_currency: String,
+    // How far back a rate may lie from the close date (weekends, holidays)
+    #[serde(default = "default_rate_age")]
+    pub max_rate_age_days: u32,
+}
+
+// Rates from one currency into another by date, inverted where only the
+// opposite pair is stored
+pub struct FxTable {
+    pair: String,
+    rates: BTreeMap<NaiveDate, f64>,
+    identity: bool,
+    max_age_days: i64,
+}
+
+impl FxTable {
+    pub fn new(from: &str, to: &str, rates: &[FxRate], max_age_days: u32) -> Self {
+        let mut table = BTreeMap::new();
+        // Inverted rates first so a directly stored rate wins on the same day
+        for rate in rates.iter().filter(|r| r.base == to && r.quote == from) {
+            table.insert(rate.date, 1.0 / rate.rate);
+        }
+        for rate in rates.iter().filter(|r| r.base == from && r.quote == to) {
+            table.insert(rate.date, rate.rate);
+        }
+        FxTable { pair: format!("{}/{}", from, to), rates: table, identity: from == to, max_age_days: max_age_days as i64 }
+    }
+
+    // The latest rate on or before `date` and the day it is from
+    pub fn rate_on(&self, date: NaiveDate) -> Option<(f64, NaiveDate)> {
+        if self.identity {
+            return Some((1.0, date));
+        }
+        self.rates.range(..=date).next_back()
+            .filter(|(day, _)| (date - **day).num_days() <= self.max_age_days)
+            .map(|(day, rate)| (*rate, *day))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub buy: bool,
+    pub volume: f64,
+    pub price: f64,
+    pub time: DateTime<Utc>,
+    pub commission: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedLot {
+    pub volume: f64,
+    pub open_price: f64,
+    pub opened_at: DateTime<Utc>,
+    pub close_price: f64,
+    pub closed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealizedLot {
+    // The trade whose fill closed the lot
+    pub trade_id: i64,
+    // The trade whose fill opened it
+    pub opened_by: i64,
+    pub symbol: String,
+    pub side: String,
+    pub lot: MatchedLot,
+    // In the account currency
+    pub profit: f64,
+    pub commission: f64,
+    pub swap: f64,
+}
+
+fn number(row: &TradeRow, column: &str) -> Option<f64> {
+    row.get(column).and_then(Value::as_f64)
+}
+
+fn time(row: &TradeRow, column: &str) -> Option<DateTime<Utc>> {
+    row.get(column).and_then(Value::as_str).and_then(parse_trade_time)
+}
+
+// A trade as the lot matcher sees it: its fills in execution order, ending
+// with the close at exit for whatever the fills leave open
+struct Position {
+    id: i64,
+    symbol: String,
+    side: String,
+    buy: bool,
+    // None while the trade is open
+    profit: Option<f64>,
+    commission: f64,
+    swap: f64,
+    fills: Vec<Fill>,
+}
+
+impl Position {
+    fn new(trade: &TradeRow, own_fills: &[Fill], warnings: &mut Vec<String>) -> Self {
+        let id = trade.get("id").and_then(Value::as_i64).unwrap_or_default();
+        let side = trade.get("trade_type").and_then(Value::as_str).unwrap_or("Buy").to_string();
+        let buy = !side.eq_ignore_ascii_case("sell");
+        let exit = match (time(trade, "exit_time"), number(trade, "exit_price"), number(trade, "profit_loss_money")) {
+            (Some(exit_time), Some(exit_price), Some(profit)) => Some((exit_time, exit_price, profit)),
+            _ => None,
+        };
+
+        let mut fills = Vec::new();
+        if own_fills.iter().any(|fill| fill.buy != buy) {
+            fills.extend_from_slice(own_fills);
+        } else if let (Some(volume), Some(price), Some(time)) = (number(trade, "volume"), number(trade, "entry_price"), time(trade, "entry_time")) {
+            fills.push(Fill { buy, volume, price, time, commission: None });
+        }
+
+        // Closing fills beyond the opened volume would eat other trades' lots
+        let mut open_volume: f64 = fills.iter().filter(|fill| fill.buy == buy).map(|fill| fill.volume).sum();
+        let mut excess = 0.0;
+        for fill in fills.iter_mut().filter(|fill| fill.buy != buy) {
+            let kept = fill.volume.min(open_volume);
+            excess += fill.volume - kept;
+            open_volume -= kept;
+            fill.volume = kept;
+        }
+        if excess > FLAT {
+            warnings.push(format!("Trade {} closes {} more than its fills opened; the excess is ignored", id, excess));
+        }
+        if let Some((exit_time, exit_price, _)) = exit {
+            if open_volume > FLAT {
+                fills.push(Fill { buy: !buy, volume: open_volume, price: exit_price, time: exit_time, commission: None });
+            }
+        }
+
+        // A fill without a commission simply had none
+        let commission = number(trade, "commission")
+            .unwrap_or_else(|| own_fills.iter().filter_map(|fill| fill.commission).sum());
+        Position {
+            id,
+            symbol: trade.get("symbol").and_then(Value::as_str).unwrap_or_default().to_string(),
+            side,
+            buy,
+            profit: exit.map(|(.., profit)| profit),
+            commission,
+            swap: number(trade, "swap").unwrap_or(0.0),
+            fills,
+        }
+    }
+
+    // Stored P/L per unit of price move and volume, which carries contract
+    // size and quote currency; None for scratch trades and open ones
+    fn value_per_point(&self) -> Option<f64> {
+        let direction = if self.buy { 1.0 } else { -1.0 };
+        let signed = |fill: &Fill| if fill.buy == self.buy { -fill.price * fill.volume } else { fill.price * fill.volume };
+        let moved = self.fills.iter().map(signed).sum::<f64>() * direction;
+        let gross: f64 = self.fills.iter().map(|fill| fill.price * fill.volume).sum();
+        self.profit.filter(|_| moved.abs() > FLAT * gross.max(1.0)).map(|profit| profit / moved)
+    }
+}
+
+// Volume not closed yet: (volume, price, opened_at, opening trade)
+type OpenLot = (f64, f64, DateTime<Utc>, i64);
+
+// Realized lots of all `trades`, with `fills` by trade id in execution
+// order. Positions in the same instrument and direction form one first-in
+// first-out queue across trades, so a closing fill realizes the oldest open
+// volume even when another trade opened it. Each lot's P/L is its price move
+// valued like the closing trade's stored P/L; commission and swap are spread
+// over the lots a trade closes by volume.
+pub fn realize(trades: &[TradeRow], fills: &HashMap<i64, Vec<Fill>>) -> (Vec<RealizedLot>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let positions: Vec<Position> = trades.iter()
+        .map(|trade| {
+            let id = trade.get("id").and_then(Value::as_i64).unwrap_or_default();
+            Position::new(trade, fills.get(&id).map(Vec::as_slice).unwrap_or_default(), &mut warnings)
+        })
+        .collect();
+
+    let mut events: Vec<(usize, &Fill)> = positions.iter().enumerate()
+        .flat_map(|(i, position)| position.fills.iter().map(move |fill| (i, fill)))
+        .collect();
+    events.sort_by_key(|(i, fill)| (fill.time, fill.buy != positions[*i].buy, positions[*i].id));
+
+    // Open volume per instrument and direction
+    let mut queues: HashMap<(&str, bool), VecDeque<OpenLot>> = HashMap::new();
+    let mut closed: Vec<Vec<(MatchedLot, i64)>> = vec![Vec::new(); positions.len()];
+    for (i, fill) in events {
+        let position = &positions[i];
+        let queue = queues.entry((position.symbol.as_str(), position.buy)).or_default();
+        if fill.buy == position.buy {
+            queue.push_back((fill.volume, fill.price, fill.time, position.id));
+            continue;
+        }
+        let mut remaining = fill.volume;
+        while remaining > FLAT {
+            let Some(front) = queue.front_mut() else { break };
+            let volume = front.0.min(remaining);
+            let lot = MatchedLot { volume, open_price: front.1, opened_at: front.2, close_price: fill.price, closed_at: fill.time };
+            closed[i].push((lot, front.3));
+            front.0 -= volume;
+            remaining -= volume;
+            if front.0 <= FLAT {
+                queue.pop_front();
+            }
+        }
+    }
+
+    let mut realized = Vec::new();
+    for (position, lots) in positions.iter().zip(closed) {
+        let Some(profit) = position.profit else {
+            if !lots.is_empty() {
+                warnings.push(format!(
+                    "Trade {} is still open; its {} closed lot(s) are left out until it is closed and has a P/L",
+                    position.id, lots.len()
+                ));
+            }
+            continue;
+        };
+        if lots.is_empty() {
+            continue;
+        }
+
+        let direction = if position.buy { 1.0 } else { -1.0 };
+        let volumes: Vec<f64> = lots.iter().map(|(lot, _)| lot.volume).collect();
+        // Without a net price move (scratch trades) P/L follows the volume
+        let profits = match position.value_per_point() {
+            Some(value) => lots.iter().map(|(lot, _)| (lot.close_price - lot.open_price) * lot.volume * direction * value).collect(),
+            None => spread(profit, &volumes),
+        };
+        let (commissions, swaps) = (spread(position.commission, &volumes), spread(position.swap, &volumes));
+        realized.extend(lots.into_iter().enumerate().map(|(i, (lot, opened_by))| RealizedLot {
+            trade_id: position.id,
+            opened_by,
+            symbol: position.symbol.clone(),
+            side: position.side.clone(),
+            profit: profits[i],
+            commission: commissions[i],
+            swap: swaps[i],
+            lot,
+        }));
+    }
+    (realized, warnings)
+}
+
+// `total` split in proportion to `weights`; the last part takes the rounding
+// so the parts add up to exactly the stored amount
+fn spread(total: f64, weights: &[f64]) -> Vec<f64> {
+    let sum: f64 = weights.iter().sum();
+    let mut parts: Vec<f64> = weights.iter().map(|weight| total * weight / sum).collect();
+    if let Some(last) = parts.len().checked_sub(1) {
+        parts[last] = total - parts[..last].iter().sum::<f64>();
+    }
+    parts
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxLine {
+    pub lot: RealizedLot,
+    pub rate: f64,
+    pub rate_date: NaiveDate,
+}
+
+impl TaxLine {
+    pub fn profit(&self) -> f64 {
+        self.lot.profit * self.rate
+    }
+
+    pub fn commission(&self) -> f64 {
+        self.lot.commission * self.rate
+    }
+
+    pub fn swap(&self) -> f64 {
+        self.lot.swap * self.rate
+    }
+}
+
+// Totals in the tax currency
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct InstrumentTotals {
+    pub symbol: String,
+    pub lots: usize,
+    pub volume: f64,
+    pub trading_profit: f64,
+    pub commission: f64,
+    pub swap: f64,
+    pub net: f64,
+}
+
+impl InstrumentTotals {
+    fn add(&mut self, line: &TaxLine) {
+        self.lots += 1;
+        self.volume += line.lot.lot.volume;
+        self.trading_profit += line.profit();
+        self.commission += line.commission();
+        self.swap += line.swap();
+        self.net = self.trading_profit + self.commission + self.swap;
+    }
+}
+
+pub struct TaxReport {
+    pub year: i32,
+    pub tax_currency: String,
+    pub account_currency: String,
+    pub timezone: Tz,
+    pub generated_at: DateTime<Utc>,
+    pub lines: Vec<TaxLine>,
+    pub instruments: Vec<InstrumentTotals>,
+    pub totals: InstrumentTotals,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaxReportResult {
+    pub path: String,
+    pub summary_path: String,
+    pub year: i32,
+    pub tax_currency: String,
+    pub lots: usize,
+    pub instruments: Vec<InstrumentTotals>,
+    pub totals: InstrumentTotals,
+    pub warnings: Vec<String>,
+}
+
+// Lots closed in `options.year` (in `tz`), converted and grouped. Fails when
+// a close date has no usable rate rather than reporting unconverted figures.
+pub fn build_report(
+    lots: Vec<RealizedLot>,
+    options: &TaxReportOptions,
+    fx: &FxTable,
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> Result<TaxReport, String> {
+    let mut lines = Vec::new();
+    let mut missing: Vec<NaiveDate> = Vec::new();
+
+    for lot in lots {
+        let closed_on = lot.lot.closed_at.with_timezone(&tz).date_naive();
+        if closed_on.year() != options.year {
+            continue;
+        }
+        match fx.rate_on(closed_on) {
+            Some((rate, rate_date)) => lines.push(TaxLine { lot, rate, rate_date }),
+            None => missing.push(closed_on),
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        return Err(format!(
+            "No {} rate within {} days before {}{}",
+            fx.pair,
+            options.max_rate_age_days,
+            missing[0],
+            if missing.len() > 1 { format!(" and {} other close date(s)", missing.len() - 1) } else { String::new() },
+        ));
+    }
+    lines.sort_by_key(|line| (line.lot.lot.closed_at, line.lot.trade_id));
+
+    let mut by_symbol: BTreeMap<String, InstrumentTotals> = BTreeMap::new();
+    let mut totals = InstrumentTotals { symbol: "Total".to_string(), ..Default::default() };
+    for line in &lines {
+        by_symbol.entry(line.lot.symbol.clone())
+            .or_insert_with(|| InstrumentTotals { symbol: line.lot.symbol.clone(), ..Default::default() })
+            .add(line);
+        totals.add(line);
+    }
+
+    Ok(TaxReport {
+        year: options.year,
+        tax_currency: options.tax_currency.clone(),
+        account_currency: options.account_currency.clone(),
+        timezone: tz,
+        generated_at: now,
+        lines,
+        instruments: by_symbol.into_values().collect(),
+        totals,
+    })
+}
+
+fn trim_decimals(value: f64) -> String {
+    let text = format!("{:.6}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// One row per realized lot, amounts in both currencies
+pub fn write_csv(path: &Path, report: &TaxReport) -> Result<(), Box<dyn std::error::Error>> {
+    let (account, tax) = (&report.account_currency, &report.tax_currency);
+    let time = |t: &DateTime<Utc>| t.with_timezone(&report.timezone).format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record([
+        "Trade ID".to_string(), "Opened By Trade".to_string(), "Symbol".to_string(), "Side".to_string(), "Volume".to_string(),
+        format!("Opened ({})", report.timezone.name()), format!("Closed ({})", report.timezone.name()),
+        "Open Price".to_string(), "Close Price".to_string(),
+        format!("Trading P/L ({})", account), format!("Commission ({})", account), format!("Swap ({})", account),
+        format!("Net ({})", account), format!("{}/{} Rate", account, tax), "Rate Date".to_string(),
+        format!("Trading P/L ({})", tax), format!("Commission ({})", tax), format!("Swap ({})", tax), format!("Net ({})", tax),
+    ])?;
+
+    for line in &report.lines {
+        let lot = &line.lot;
+        writer.write_record([
+            lot.trade_id.to_string(),
+            lot.opened_by.to_string(),
+            lot.symbol.clone(),
+            lot.side.clone(),
+            trim_decimals(lot.lot.volume),
+            time(&lot.lot.opened_at),
+            time(&lot.lot.closed_at),
+            trim_decimals(lot.lot.open_price),
+            trim_decimals(lot.lot.close_price),
+            format!("{:.2}", lot.profit),
+            format!("{:.2}", lot.commission),
+            format!("{:.2}", lot.swap),
+            format!("{:.2}", lot.profit + lot.commission + lot.swap),
+            trim_decimals(line.rate),
+            line.rate_date.to_string(),
+            format!("{:.2}", line.profit()),
+            format!("{:.2}", line.commission()),
+            format!("{:.2}", line.swap()),
+            format!("{:.2}", line.profit() + line.commission() + line.swap()),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// Plain-text summary per instrument, for reading or attaching to a filing
+pub fn render_summary(report: &TaxReport) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Realized P/L {} in {}", report.year, report.tax_currency);
+    let _ = writeln!(
+        text,
+        "Account currency {}; FIFO lot matching; close dates in {}; generated {}",
+        report.account_currency, report.timezone.name(),
+        report.generated_at.with_timezone(&report.timezone).format("%Y-%m-%d %H:%M"),
+    );
+    text.push('\n');
+
+    let header = ["Instrument", "Lots", "Volume", "Trading P/L", "Commission", "Swap", "Net"];
+    let row = |text: &mut String, cells: [String; 7]| {
+        let _ = writeln!(text, "{:<16}{:>6}{:>12}{:>16}{:>14}{:>14}{:>16}", cells[0], cells[1], cells[2], cells[3], cells[4], cells[5], cells[6]);
+    };
+    row(&mut text, header.map(str::to_string));
+    let totals_row = |text: &mut String, totals: &InstrumentTotals| {
+        row(text, [
+            totals.symbol.clone(), totals.lots.to_string(), trim_decimals(totals.volume), money(totals.trading_profit),
+            money(totals.commission), money(totals.swap), money(totals.net),
+        ]);
+    };
+    for instrument in &report.instruments {
+        totals_row(&mut text, instrument);
+    }
+    let _ = writeln!(text, "{}", "-".repeat(94));
+    totals_row(&mut text, &report.totals);
+
+    if report.tax_currency != report.account_currency {
+        if let (Some(first), Some(last)) = (report.lines.iter().map(|l| l.rate_date).min(), report.lines.iter().map(|l| l.rate_date).max()) {
+            let _ = writeln!(
+                text,
+                "\nConverted with {}/{} rates dated {} to {}, each lot at the rate for its close date.",
+                report.account_currency, report.tax_currency, first, last,
+            );
+        }
+    }
+    if report.lines.is_empty() {
+        let _ = writeln!(text, "\nNo positions were closed in {}.", report.year);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn fill(buy: bool, volume: f64, price: f64, at: &str, commission: Option<f64>) -> Fill {
+        Fill { buy, volume, price, time: utc(at), commission }
+    }
+
+    fn trade(id: i64, symbol: &str, side: &str, volume: f64, entry: (&str, f64), exit: Option<(&str, f64, f64)>) -> TradeRow {
+        json!({"id": id, "symbol": symbol, "trade_type": side, "volume": volume, "entry_time": entry.0, "entry_price": entry.1,
+               "exit_time": exit.map(|e| e.0), "exit_price": exit.map(|e| e.1), "profit_loss_money": exit.map(|e| e.2)})
+            .as_object().unwrap().clone()
+    }
+
+    fn options(year: i32) -> TaxReportOptions {
+        TaxReportOptions { year, tax_currency: "EUR".to_string(), account_currency: "USD".to_string(), max_rate_age_days: 7 }
+    }
+
+    fn rate(base: &str, quote: &str, date: &str, rate: f64) -> FxRate {
+        FxRate { base: base.to_string(), quote: quote.to_string(), date: date.parse().unwrap(), rate, source: None }
+    }
+
+    #[test]
+    fn closes_realize_the_oldest_open_volume_of_the_instrument_across_trades() {
+        // 100 USD per point per lot on every trade
+        let trades = [
+            trade(1, "XAUUSD", "Buy", 1.0, ("2023-06-01T10:00:00Z", 100.0), Some(("2024-02-01T10:00:00Z", 130.0, 3000.0))),
+            trade(2, "XAUUSD", "Buy", 1.0, ("2023-09-01T10:00:00Z", 120.0), Some(("2023-12-01T10:00:00Z", 125.0, 500.0))),
+            // A hedge in the other direction keeps its own queue
+            trade(3, "XAUUSD", "Sell", 1.0, ("2023-10-01T10:00:00Z", 118.0), Some(("2023-10-15T10:00:00Z", 110.0, 800.0))),
+            trade(4, "EURUSD", "Buy", 1.0, ("2023-05-01T10:00:00Z", 1.1), Some(("2023-12-20T10:00:00Z", 1.1, -2.0))),
+        ];
+        let (lots, warnings) = realize(&trades, &HashMap::new());
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let summary: Vec<(i64, i64, f64, f64)> = lots.iter().map(|l| (l.trade_id, l.opened_by, l.lot.open_price, l.profit)).collect();
+        assert_eq!(summary, [(1, 2, 120.0, 1000.0), (2, 1, 100.0, 2500.0), (3, 3, 118.0, 800.0), (4, 4, 1.1, -2.0)]);
+        // Nothing is lost once every position is closed
+        assert_eq!(lots.iter().map(|l| l.profit).sum::<f64>(), 3000.0 + 500.0 + 800.0 - 2.0);
+
+        let fx = FxTable::new("USD", "EUR", &[rate("USD", "EUR", "2023-12-01", 0.9), rate("EUR", "USD", "2023-12-20", 1.25), rate("USD", "EUR", "2023-10-13", 0.95)], 7);
+        let report = build_report(lots, &options(2023), &fx, chrono_tz::UTC, Utc::now()).unwrap();
+        assert_eq!(report.lines.len(), 3);
+        assert!((report.totals.trading_profit - (800.0 * 0.95 + 2500.0 * 0.9 - 2.0 * 0.8)).abs() < 1e-9);
+        let missing = FxTable::new("USD", "EUR", &[rate("USD", "EUR", "2023-10-01", 0.95)], 7);
+        let error = build_report(realize(&trades, &HashMap::new()).0, &options(2023), &missing, chrono_tz::UTC, Utc::now()).err().unwrap();
+        assert_eq!(error, "No USD/EUR rate within 7 days before 2023-10-15 and 2 other close date(s)");
+    }
+
+    #[test]
+    fn partial_closing_fills_are_realized_in_their_own_year_with_the_costs_they_carry() {
+        let mut row = trade(7, "XAUUSD", "Buy", 2.0, ("2023-12-20T10:00:00Z", 105.0), Some(("2024-01-05T10:00:00Z", 90.0, 150.0)));
+        row.insert("swap".to_string(), json!(-2.0));
+        // Fills with and without a commission; the trade row has none
+        let fills = HashMap::from([(7, vec![
+            fill(true, 1.0, 100.0, "2023-12-20T10:00:00Z", Some(-3.0)),
+            fill(true, 1.0, 110.0, "2023-12-21T10:00:00Z", None),
+            fill(false, 1.5, 120.0, "2023-12-28T10:00:00Z", Some(-1.0)),
+        ])]);
+
+        let (lots, warnings) = realize(&[row], &fills);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        // The rest closes at the exit; price moves 20, 5 and -20 share the 150
+        let summary: Vec<(f64, f64, f64, f64)> = lots.iter().map(|l| (l.lot.volume, l.lot.open_price, l.lot.close_price, l.profit)).collect();
+        assert_eq!(summary, [(1.0, 100.0, 120.0, 200.0), (0.5, 110.0, 120.0, 50.0), (0.5, 110.0, 90.0, -100.0)]);
+        assert_eq!(lots.iter().map(|l| l.commission).collect::<Vec<_>>(), [-2.0, -1.0, -1.0]);
+        assert_eq!(lots[2].swap, -0.5);
+        assert_eq!(lots[2].lot.closed_at, utc("2024-01-05T10:00:00Z"));
+    }
+
+    #[test]
+    fn open_trades_and_overclosing_fills_are_reported_not_realized() {
+        let open = trade(5, "GBPUSD", "Sell", 1.0, ("2024-05-01T08:00:00Z", 1.1), None);
+        let overclosed = trade(6, "EURUSD", "Buy", 1.0, ("2024-05-01T08:00:00Z", 1.1), Some(("2024-05-03T08:00:00Z", 1.2, 50.0)));
+        let fills = HashMap::from([
+            (5, vec![fill(false, 1.0, 1.1, "2024-05-01T08:00:00Z", None), fill(true, 0.4, 1.08, "2024-05-02T08:00:00Z", None)]),
+            (6, vec![fill(true, 1.0, 1.1, "2024-05-01T08:00:00Z", None), fill(false, 1.5, 1.2, "2024-05-03T08:00:00Z", None)]),
+        ]);
+        let (lots, warnings) = realize(&[open, overclosed], &fills);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0], "Trade 6 closes 0.5 more than its fills opened; the excess is ignored");
+        assert!(warnings[1].starts_with("Trade 5 is still open"));
+        assert_eq!(lots.len(), 1);
+        assert_eq!((lots[0].trade_id, lots[0].lot.volume, lots[0].profit), (6, 1.0, 50.0));
+    }
+
+    #[test]
+    fn rates_are_looked_up_backwards_inverted_and_bounded_in_age() {
+        let rates = [rate("EUR", "USD", "2024-03-01", 1.25), rate("USD", "EUR", "2024-03-04", 0.81)];
+        let fx = FxTable::new("USD", "EUR", &rates, 7);
+        assert_eq!(fx.rate_on("2024-03-03".parse().unwrap()), Some((0.8, "2024-03-01".parse().unwrap())));
+        assert_eq!(fx.rate_on("2024-03-04".parse().unwrap()).map(|(r, _)| r), Some(0.81));
+        assert_eq!(fx.rate_on("2024-02-29".parse().unwrap()), None);
+        assert_eq!(fx.rate_on("2024-03-20".parse().unwrap()), None);
+        assert_eq!(FxTable::new("EUR", "EUR", &[], 7).rate_on("2024-03-20".parse().unwrap()).map(|(r, _)| r), Some(1.0));
+
+        assert!(rate("usd", "EUR", "2024-03-01", 1.0).validate().is_err());
+        assert!(rate("USD", "EUR", "2024-03-01", 0.0).validate().is_err());
+    }
+
+    #[test]
+    fn totals_are_grouped_by_instrument_in_the_summary_and_csv() {
+        let mut trades = Vec::new();
+        for (id, symbol, exit, profit) in [(1, "EURUSD", "2024-05-01T12:00:00Z", 100.0), (2, "EURUSD", "2024-06-03T12:00:00Z", -40.0), (3, "GBPUSD", "2024-06-03T12:00:00Z", 60.0)] {
+            let mut row = trade(id, symbol, "Sell", 1.0, ("2024-05-01T08:00:00Z", 1.1), Some((exit, 1.09, profit)));
+            row.insert("commission".to_string(), json!(-3.0));
+            trades.push(row);
+        }
+        let fx = FxTable::new("USD", "USD", &[], 7);
+        let options = TaxReportOptions { tax_currency: "USD".to_string(), ..options(2024) };
+        let report = build_report(realize(&trades, &HashMap::new()).0, &options, &fx, chrono_tz::UTC, utc("2025-01-10T09:00:00Z")).unwrap();
+        let eurusd = &report.instruments[0];
+        assert_eq!((eurusd.symbol.as_str(), eurusd.lots, eurusd.commission, eurusd.net), ("EURUSD", 2, -6.0, 54.0));
+        assert_eq!(report.totals.net, 111.0);
+
+        let summary = render_summary(&report);
+        assert!(summary.starts_with("Realized P/L 2024 in USD"));
+        assert!(summary.lines().any(|line| line.starts_with("Total") && line.ends_with("111.00")));
+
+        let path = std::env::temp_dir().join(format!("tax-{}.csv", uuid::Uuid::new_v4()));
+        write_csv(&path, &report).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(csv.starts_with("Trade ID,Opened By Trade,Symbol,Side,Volume,Opened (UTC),Closed (UTC)"));
+        assert!(csv.lines().nth(1).unwrap().starts_with("1,1,EURUSD,Sell,1,2024-05-01 08:00:00,2024-05-01 12:00:00,1.1,1.09,100.00,-3.00"));
+    }
+}
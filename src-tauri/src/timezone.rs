@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDateTime, NaiveTime, Offset, SecondsFormat, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use std::path::Path;
+
+use crate::database::TRADE_TIME_FORMATS;
+
+const TIME_SETTINGS_FILE: &str = "time_settings.json";
+
+// Timezone configuration
+//
+// Trades are stored in UTC. `broker_timezone` is the zone naive timestamps
+// are assumed to be in when they arrive (MetaTrader servers usually run on
+// UTC+2/+3 with DST, i.e. "Europe/Athens"), `display_timezone` is the zone
+// hour/day analytics are bucketed in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TimeSettings {
+    pub broker_timezone: String,
+    pub display_timezone: String,
+}
+
+impl Default for TimeSettings {
+    fn default() -> Self {
+        Self {
+            broker_timezone: "UTC".to_string(),
+            display_timezone: "UTC".to_string(),
+        }
+    }
+}
+
+impl TimeSettings {
+    pub async fn load(config_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        match tokio::fs::read(config_dir.join(TIME_SETTINGS_FILE)).await {
+            Ok(data) => {
+                let settings: TimeSettings = serde_json::from_slice(&data)?;
+                settings.validate()?;
+                Ok(settings)
+            }
+            Err(_) => {
+                let settings = TimeSettings::default();
+                settings.save(config_dir).await?;
+                Ok(settings)
+            }
+        }
+    }
+
+    pub async fn save(&self, config_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        tokio::fs::create_dir_all(config_dir).await?;
+        tokio::fs::write(config_dir.join(TIME_SETTINGS_FILE), serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        parse_timezone(&self.broker_timezone)?;
+        parse_timezone(&self.display_timezone)?;
+        Ok(())
+    }
+
+    pub fn broker_tz(&self) -> Tz {
+        parse_timezone(&self.broker_timezone).unwrap_or(Tz::UTC)
+    }
+
+    pub fn display_tz(&self) -> Tz {
+        parse_timezone(&self.display_timezone).unwrap_or(Tz::UTC)
+    }
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>().map_err(|_| format!("Unknown timezone: {}", name))
+}
+
+// Resolve a wall-clock time in `tz`. When clocks go back the earlier of the
+// two instants is used; times inside a spring-forward gap are moved past it.
+pub fn from_local(naive: &NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    match tz.from_local_datetime(naive) {
+        LocalResult::Single(dt) => dt.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        LocalResult::None => tz.from_local_datetime(&(*naive + Duration::hours(1)))
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(naive)),
+    }
+}
+
+// Parse a trade timestamp. Explicit offsets are honoured, naive values are
+// read as wall-clock time in `tz`.
+pub fn parse_in_zone(value: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+
+    TRADE_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|naive| from_local(&naive, tz))
+}
+
+// Storage format: sorts correctly as text and is understood by SQLite's
+// date functions, e.g. "2024-03-01T08:00:00Z"
+pub fn format_utc(instant: DateTime<Utc>) -> String {
+    instant.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn normalize_timestamp(value: &str, tz: Tz) -> Option<String> {
+    parse_in_zone(value, tz).map(format_utc)
+}
+
+// UTC bounds [start, end) of the local calendar day containing `instant`
+pub fn local_day_bounds(instant: DateTime<Utc>, tz: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let date = instant.with_timezone(&tz).date_naive();
+    let next = date.succ_opt().unwrap_or(date);
+
+    (
+        from_local(&date.and_time(NaiveTime::MIN), tz),
+        from_local(&next.and_time(NaiveTime::MIN), tz),
+    )
+}
+
+fn utc_offset_seconds(instant: DateTime<Utc>, tz: Tz) -> i32 {
+    tz.offset_from_utc_datetime(&instant.naive_utc()).fix().local_minus_utc()
+}
+
+// UTC offset of `tz` in [from, to): the offset at `from`, then every instant
+// it changes. Zones change at most once a day, so days are scanned and a
+// change is narrowed down to the second.
+pub fn offset_changes(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(DateTime<Utc>, i32)> {
+    let mut changes = vec![(from, utc_offset_seconds(from, tz))];
+    let mut day = from;
+
+    while day < to {
+        let next = day + Duration::days(1);
+        let current = changes[changes.len() - 1].1;
+        if utc_offset_seconds(next, tz) != current {
+            // First second after `day` on the new offset
+            let (mut low, mut high) = (day, next);
+            while high - low > Duration::seconds(1) {
+                let mid = low + (high - low) / 2;
+                if utc_offset_seconds(mid, tz) == current {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            changes.push((high, utc_offset_seconds(high, tz)));
+        }
+        day = next;
+    }
+
+    changes
+}
+
+// Trading sessions, defined on each market's own clock so their UTC hours
+// shift with that market's DST rules
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TradingSession {
+    Asian,
+    London,
+    NewYork,
+}
+
+impl TradingSession {
+    pub const ALL: [TradingSession; 3] = [TradingSession::Asian, TradingSession::London, TradingSession::NewYork];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TradingSession::Asian => "Asian",
+            TradingSession::London => "London",
+            TradingSession::NewYork => "New York",
+        }
+    }
+
+    // Market timezone and local opening hours [open, close)
+    fn market(&self) -> (Tz, u32, u32) {
+        match self {
+            TradingSession::Asian => (chrono_tz::Asia::Tokyo, 9, 18),
+            TradingSession::London => (chrono_tz::Europe::London, 8, 17),
+            TradingSession::NewYork => (chrono_tz::America::New_York, 8, 17),
+        }
+    }
+
+    pub fn contains(&self, instant: DateTime<Utc>) -> bool {
+        let (tz, open, close) = self.market();
+        let local = instant.with_timezone(&tz);
+
+        !matches!(local.weekday(), Weekday::Sat | Weekday::Sun)
+            && local.hour() >= open
+            && local.hour() < close
+    }
+}
+
+pub fn sessions_at(instant: DateTime<Utc>) -> Vec<TradingSession> {
+    TradingSession::ALL.iter()
+        .copied()
+        .filter(|session| session.contains(instant))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_broker_time_follows_dst() {
+        let athens = chrono_tz::Europe::Athens;
+        assert_eq!(normalize_timestamp("2024-01-15 10:00:00", athens).unwrap(), "2024-01-15T08:00:00Z");
+        assert_eq!(normalize_timestamp("2024.07.15 10:00", athens).unwrap(), "2024-07-15T07:00:00Z");
+        assert_eq!(normalize_timestamp("2024-07-15T10:00:00+01:00", athens).unwrap(), "2024-07-15T09:00:00Z");
+    }
+
+    #[test]
+    fn test_dst_gap_and_overlap() {
+        let athens = chrono_tz::Europe::Athens;
+        // 03:30 does not exist on 2024-03-31 in Athens; it resolves to 04:30 EEST
+        assert_eq!(normalize_timestamp("2024-03-31 03:30", athens).unwrap(), "2024-03-31T01:30:00Z");
+        // 03:30 happens twice on 2024-10-27; the first (EEST) occurrence is used
+        assert_eq!(normalize_timestamp("2024-10-27 03:30", athens).unwrap(), "2024-10-27T00:30:00Z");
+    }
+
+    #[test]
+    fn test_sessions_shift_with_market_dst() {
+        assert!(TradingSession::London.contains(utc("2024-01-15T08:30:00Z")));
+        assert!(TradingSession::London.contains(utc("2024-07-15T07:30:00Z")));
+        assert!(!TradingSession::London.contains(utc("2024-07-15T16:30:00Z")));
+        assert_eq!(sessions_at(utc("2024-07-15T13:00:00Z")), vec![TradingSession::London, TradingSession::NewYork]);
+        assert!(sessions_at(utc("2024-07-13T13:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn test_local_day_bounds() {
+        let (start, end) = local_day_bounds(utc("2024-07-15T22:30:00Z"), chrono_tz::Europe::Athens);
+        assert_eq!(format_utc(start), "2024-07-15T21:00:00Z");
+        assert_eq!(format_utc(end), "2024-07-16T21:00:00Z");
+    }
+
+    #[test]
+    fn test_offset_changes_at_dst_transitions() {
+        let changes: Vec<(String, i32)> = offset_changes(chrono_tz::Europe::Athens, utc("2024-01-01T00:00:00Z"), utc("2025-01-01T00:00:00Z"))
+            .into_iter()
+            .map(|(at, offset)| (format_utc(at), offset))
+            .collect();
+        assert_eq!(changes, [
+            ("2024-01-01T00:00:00Z".to_string(), 7200),
+            ("2024-03-31T01:00:00Z".to_string(), 10800),
+            ("2024-10-27T01:00:00Z".to_string(), 7200),
+        ]);
+        assert_eq!(offset_changes(Tz::UTC, utc("2024-01-01T00:00:00Z"), utc("2025-01-01T00:00:00Z")).len(), 1);
+    }
+}
@@ -0,0 +1,683 @@
+// Spreadsheet export of trades
+//
+// Columns are picked by key:
+//
+//   <trade column>   any stored trade column ("symbol", "profit_loss_money", ...)
+//   custom.<name>    a custom field of the Trade schema (its own column)
+//   <computed>       a value derived per trade, see COMPUTED_COLUMNS
+//
+// Headers use the Trade schema's FieldUI labels where the schema has one.
+// CSV cells are written as text in the chosen locale. XLSX cells keep their
+// type (numbers, dates, booleans) with a number format, so Excel shows them
+// in the reader's own locale; only the date layout follows the export locale.
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::analysis::{AnalysisSummary, Analyzer, TradeProjection};
+use crate::database::parse_trade_time;
+
+pub const TRADE_SCHEMA: &str = "Trade";
+pub const CUSTOM_PREFIX: &str = "custom.";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" | "txt" => Some(ExportFormat::Csv),
+            "xlsx" => Some(ExportFormat::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Number,
+    Price,
+    Money,
+    Boolean,
+    DateTime,
+}
+
+// Stored trade columns that can be exported, with a label for when the
+// schema has none
+pub const TRADE_COLUMNS: &[(&str, &str, ColumnType)] = &[
+    ("id", "ID", ColumnType::Integer),
+    ("external_id", "Broker Ticket", ColumnType::Text),
+    ("symbol", "Symbol", ColumnType::Text),
+    ("trade_type", "Type", ColumnType::Text),
+    ("volume", "Volume", ColumnType::Number),
+    ("entry_time", "Entry Time", ColumnType::DateTime),
+    ("entry_price", "Entry Price", ColumnType::Price),
+    ("sl", "Stop Loss", ColumnType::Price),
+    ("tp", "Take Profit", ColumnType::Price),
+    ("exit_time", "Exit Time", ColumnType::DateTime),
+    ("exit_price", "Exit Price", ColumnType::Price),
+    ("commission", "Commission", ColumnType::Money),
+    ("swap", "Swap", ColumnType::Money),
+    ("profit_loss_pips", "P/L (pips)", ColumnType::Number),
+    ("profit_loss_money", "P/L", ColumnType::Money),
+    ("risk_reward_ratio", "Risk/Reward", ColumnType::Number),
+    ("is_win", "Win", ColumnType::Boolean),
+    ("ict_pattern", "ICT Pattern", ColumnType::Text),
+    ("pattern_type", "Pattern Type", ColumnType::Text),
+    ("pattern_size", "Pattern Size", ColumnType::Number),
+    ("pattern_timeframe", "Pattern Timeframe", ColumnType::Text),
+    ("pattern_combination", "Pattern Combination", ColumnType::Text),
+    ("chart_explanation", "Chart Explanation", ColumnType::Text),
+    ("strategy_name", "Strategy", ColumnType::Text),
+    ("emotion", "Emotion", ColumnType::Text),
+    ("confidence_level", "Confidence", ColumnType::Number),
+    ("market_condition", "Market Condition", ColumnType::Text),
+    ("session", "Session", ColumnType::Text),
+    ("notes", "Notes", ColumnType::Text),
+    ("entry_image", "Entry Image", ColumnType::Text),
+    ("exit_image", "Exit Image", ColumnType::Text),
+    ("analysis_image", "Analysis Image", ColumnType::Text),
+    ("rsi", "RSI", ColumnType::Number),
+    ("macd", "MACD", ColumnType::Number),
+    ("moving_average", "Moving Average", ColumnType::Price),
+    ("support_level", "Support", ColumnType::Price),
+    ("resistance_level", "Resistance", ColumnType::Price),
+    ("source_timezone", "Source Timezone", ColumnType::Text),
+    ("created_at", "Created", ColumnType::DateTime),
+    ("updated_at", "Updated", ColumnType::DateTime),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Computed {
+    // P/L after commission and swap (both stored signed, costs negative)
+    NetProfit,
+    Outcome,
+    // Realized move in units of the initial risk (entry to stop loss)
+    RMultiple,
+    HoldingMinutes,
+    EntryWeekday,
+    EntryHour,
+}
+
+pub const COMPUTED_COLUMNS: &[(&str, &str, ColumnType, Computed)] = &[
+    ("net_profit", "Net P/L", ColumnType::Money, Computed::NetProfit),
+    ("outcome", "Outcome", ColumnType::Text, Computed::Outcome),
+    ("r_multiple", "R Multiple", ColumnType::Number, Computed::RMultiple),
+    ("holding_minutes", "Holding Time (min)", ColumnType::Number, Computed::HoldingMinutes),
+    ("entry_weekday", "Entry Weekday", ColumnType::Text, Computed::EntryWeekday),
+    ("entry_hour", "Entry Hour", ColumnType::Integer, Computed::EntryHour),
+];
+
+// Used when the caller does not pick columns
+pub const DEFAULT_COLUMNS: &[&str] = &[
+    "id", "symbol", "trade_type", "volume", "entry_time", "entry_price", "sl", "tp", "exit_time",
+    "exit_price", "profit_loss_money", "commission", "swap", "net_profit", "r_multiple", "outcome",
+    "ict_pattern", "strategy_name", "session", "emotion", "confidence_level", "notes",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnSource {
+    Stored,
+    Custom(String),
+    Computed(Computed),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportColumn {
+    pub key: String,
+    pub label: String,
+    pub column_type: ColumnType,
+    pub source: ColumnSource,
+}
+
+// Labels per field name from a stored EntitySchema (fields[].ui.label)
+pub fn schema_labels(schema: &Value) -> HashMap<String, String> {
+    schema.get("fields")
+        .and_then(Value::as_array)
+        .map(|fields| {
+            fields.iter()
+                .filter_map(|field| {
+                    let name = field.get("name")?.as_str()?;
+                    let label = field.get("ui")?.get("label")?.as_str()?.trim();
+                    (!label.is_empty()).then(|| (name.to_string(), label.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn resolve_columns(keys: &[String], labels: &HashMap<String, String>) -> Result<Vec<ExportColumn>, String> {
+    let keys: Vec<String> = if keys.is_empty() {
+        DEFAULT_COLUMNS.iter().map(|key| key.to_string()).collect()
+    } else {
+        keys.to_vec()
+    };
+
+    keys.into_iter()
+        .map(|key| {
+            let label = |fallback: &str| labels.get(&key).cloned().unwrap_or_else(|| fallback.to_string());
+
+            if let Some(name) = key.strip_prefix(CUSTOM_PREFIX).filter(|name| !name.is_empty()) {
+                return Ok(ExportColumn {
+                    label: labels.get(name).cloned().unwrap_or_else(|| name.to_string()),
+                    column_type: ColumnType::Text,
+                    source: ColumnSource::Custom(name.to_string()),
+                    key,
+                });
+            }
+            if let Some((_, fallback, column_type)) = TRADE_COLUMNS.iter().find(|(name, _, _)| *name == key) {
+                return Ok(ExportColumn { label: label(fallback), column_type: *column_type, source: ColumnSource::Stored, key });
+            }
+            if let Some((_, fallback, column_type, computed)) = COMPUTED_COLUMNS.iter().find(|(name, ..)| *name == key) {
+                return Ok(ExportColumn {
+                    label: label(fallback),
+                    column_type: *column_type,
+                    source: ColumnSource::Computed(*computed),
+                    key,
+                });
+            }
+            Err(format!("Unknown export column: {}", key))
+        })
+        .collect()
+}
+
+// Date and number conventions of the people reading the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportLocale {
+    pub decimal_separator: char,
+    pub csv_delimiter: u8,
+    // chrono pattern for CSV, Excel number format for XLSX
+    pub date_format: &'static str,
+    pub excel_date_format: &'static str,
+}
+
+// (language, decimal comma, chrono date pattern, Excel date pattern);
+// regions only matter for English, see ExportLocale::from_tag
+const LOCALES: &[(&str, bool, &str, &str)] = &[
+    ("de", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("ru", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("tr", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("pl", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("cs", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("fi", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("nb", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("da", true, "%d.%m.%Y %H:%M", "dd.mm.yyyy hh:mm"),
+    ("fr", true, "%d/%m/%Y %H:%M", "dd/mm/yyyy hh:mm"),
+    ("es", true, "%d/%m/%Y %H:%M", "dd/mm/yyyy hh:mm"),
+    ("it", true, "%d/%m/%Y %H:%M", "dd/mm/yyyy hh:mm"),
+    ("pt", true, "%d/%m/%Y %H:%M", "dd/mm/yyyy hh:mm"),
+    ("nl", true, "%d-%m-%Y %H:%M", "dd-mm-yyyy hh:mm"),
+    ("sv", true, "%Y-%m-%d %H:%M", "yyyy-mm-dd hh:mm"),
+    ("fa", false, "%Y/%m/%d %H:%M", "yyyy/mm/dd hh:mm"),
+    ("ja", false, "%Y/%m/%d %H:%M", "yyyy/mm/dd hh:mm"),
+    ("zh", false, "%Y/%m/%d %H:%M", "yyyy/mm/dd hh:mm"),
+    ("ko", false, "%Y-%m-%d %H:%M", "yyyy-mm-dd hh:mm"),
+];
+
+impl Default for ExportLocale {
+    // ISO dates and a decimal point: unambiguous for any reader
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            csv_delimiter: b',',
+            date_format: "%Y-%m-%d %H:%M:%S",
+            excel_date_format: "yyyy-mm-dd hh:mm:ss",
+        }
+    }
+}
+
+impl ExportLocale {
+    // BCP 47 tag such as "en-US" or "de_DE"; unknown tags get the default
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let mut parts = tag.split('-');
+        let language = parts.next().unwrap_or_default();
+        let region = parts.next().unwrap_or_default();
+
+        if language == "en" {
+            let (date_format, excel_date_format) = match region {
+                "us" | "" => ("%m/%d/%Y %H:%M", "mm/dd/yyyy hh:mm"),
+                _ => ("%d/%m/%Y %H:%M", "dd/mm/yyyy hh:mm"),
+            };
+            return Self { date_format, excel_date_format, ..Self::default() };
+        }
+
+        match LOCALES.iter().find(|(name, ..)| *name == language) {
+            // A decimal comma makes Excel expect semicolon-separated CSV
+            Some((_, decimal_comma, date_format, excel_date_format)) => Self {
+                decimal_separator: if *decimal_comma { ',' } else { '.' },
+                csv_delimiter: if *decimal_comma { b';' } else { b',' },
+                date_format,
+                excel_date_format,
+            },
+            None => Self::default(),
+        }
+    }
+
+    // No digit grouping: spreadsheet apps read grouped CSV numbers as text
+    pub fn format_number(&self, value: f64, column_type: ColumnType) -> String {
+        let text = match column_type {
+            ColumnType::Money => format!("{:.2}", value),
+            ColumnType::Integer => format!("{:.0}", value),
+            ColumnType::Price => trim_decimals(format!("{:.5}", value)),
+            _ => trim_decimals(format!("{:.6}", value)),
+        };
+        if self.decimal_separator == '.' { text } else { text.replace('.', &self.decimal_separator.to_string()) }
+    }
+}
+
+fn trim_decimals(text: String) -> String {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TradeExportOptions {
+    // Locale tag for dates and decimals ("en-US", "de-DE", ...)
+    pub locale: Option<String>,
+    // Timezone dates are shown in; the display timezone when unset
+    pub timezone: Option<String>,
+    // Add a Summary sheet (a <name>.summary.csv beside a CSV export)
+    pub include_summary: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradeExportReport {
+    pub path: String,
+    pub format: ExportFormat,
+    pub rows: usize,
+    pub columns: Vec<String>,
+    pub summary_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+    Boolean(bool),
+    DateTime(DateTime<Utc>),
+}
+
+// A stored trade row (column -> value, see row_to_json in database.rs)
+pub type TradeRow = Map<String, Value>;
+
+fn number(row: &TradeRow, column: &str) -> Option<f64> {
+    row.get(column).and_then(Value::as_f64)
+}
+
+fn time(row: &TradeRow, column: &str) -> Option<DateTime<Utc>> {
+    row.get(column).and_then(Value::as_str).and_then(parse_trade_time)
+}
+
+fn is_win(row: &TradeRow) -> Option<bool> {
+    match row.get("is_win")? {
+        Value::Bool(value) => Some(*value),
+        value => value.as_i64().map(|value| value != 0),
+    }
+}
+
+// P/L after commission and swap; empty while the trade has no P/L
+fn net_profit(row: &TradeRow) -> Option<f64> {
+    number(row, "profit_loss_money")
+        .map(|profit| profit + number(row, "commission").unwrap_or(0.0) + number(row, "swap").unwrap_or(0.0))
+}
+
+fn json_cell(value: &Value, column_type: ColumnType) -> Cell {
+    match (value, column_type) {
+        (Value::Null, _) => Cell::Empty,
+        (Value::Bool(value), _) => Cell::Boolean(*value),
+        (Value::Number(n), ColumnType::Boolean) => Cell::Boolean(n.as_i64() != Some(0)),
+        (Value::Number(n), _) => n.as_f64().map(Cell::Number).unwrap_or(Cell::Empty),
+        (Value::String(s), ColumnType::DateTime) => parse_trade_time(s).map(Cell::DateTime).unwrap_or_else(|| Cell::Text(s.clone())),
+        (Value::String(s), _) if s.is_empty() => Cell::Empty,
+        (Value::String(s), _) => match serde_json::from_str::<Vec<String>>(s) {
+            // pattern_combination and similar tag lists are stored as JSON
+            Ok(list) if s.starts_with('[') => Cell::Text(list.join(", ")),
+            _ => Cell::Text(s.clone()),
+        },
+        (Value::Array(items), _) => Cell::Text(
+            items.iter()
+                .map(|item| item.as_str().map(str::to_string).unwrap_or_else(|| item.to_string()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        (value, _) => Cell::Text(value.to_string()),
+    }
+}
+
+fn computed_cell(row: &TradeRow, computed: Computed, tz: Tz) -> Cell {
+    let value = match computed {
+        Computed::NetProfit => net_profit(row),
+        Computed::Outcome => {
+            return Cell::Text(match is_win(row) {
+                Some(true) => "Win",
+                Some(false) => "Loss",
+                None => "Open",
+            }.to_string());
+        }
+        Computed::RMultiple => {
+            let (entry, sl, exit) = (number(row, "entry_price"), number(row, "sl"), number(row, "exit_price"));
+            let sell = row.get("trade_type").and_then(Value::as_str).map_or(false, |t| t.to_ascii_lowercase().starts_with("sell"));
+            match (entry, sl, exit) {
+                (Some(entry), Some(sl), Some(exit)) if sl != 0.0 && entry != sl => {
+                    let moved = if sell { entry - exit } else { exit - entry };
+                    Some(moved / (entry - sl).abs())
+                }
+                _ => None,
+            }
+        }
+        Computed::HoldingMinutes => match (time(row, "entry_time"), time(row, "exit_time")) {
+            (Some(entry), Some(exit)) => Some((exit - entry).num_seconds() as f64 / 60.0),
+            _ => None,
+        },
+        Computed::EntryWeekday => {
+            return time(row, "entry_time")
+                .map(|entry| Cell::Text(entry.with_timezone(&tz).weekday().to_string()))
+                .unwrap_or(Cell::Empty);
+        }
+        Computed::EntryHour => time(row, "entry_time").map(|entry| entry.with_timezone(&tz).hour() as f64),
+    };
+    value.map(Cell::Number).unwrap_or(Cell::Empty)
+}
+
+pub fn row_cells(row: &TradeRow, columns: &[ExportColumn], tz: Tz) -> Vec<Cell> {
+    columns.iter()
+        .map(|column| match &column.source {
+            ColumnSource::Stored => row.get(&column.key).map(|value| json_cell(value, column.column_type)).unwrap_or(Cell::Empty),
+            ColumnSource::Custom(name) => row.get(name)
+                .map(|value| json_cell(value, column.column_type))
+                .unwrap_or(Cell::Empty),
+            ColumnSource::Computed(computed) => computed_cell(row, *computed, tz),
+        })
+        .collect()
+}
+
+pub fn projection(row: &TradeRow) -> TradeProjection {
+    TradeProjection {
+        id: row.get("id").and_then(Value::as_i64).unwrap_or_default() as u32,
+        entry_time: time(row, "entry_time"),
+        exit_time: time(row, "exit_time"),
+        is_win: is_win(row),
+        profit_loss_money: number(row, "profit_loss_money"),
+        strategy_name: row.get("strategy_name").and_then(Value::as_str).map(Arc::from),
+    }
+}
+
+pub fn summary_rows(summary: &AnalysisSummary) -> Vec<(&'static str, f64, ColumnType)> {
+    vec![
+        ("Total trades", summary.total_trades as f64, ColumnType::Integer),
+        ("Winning trades", summary.winning_trades as f64, ColumnType::Integer),
+        ("Losing trades", summary.losing_trades as f64, ColumnType::Integer),
+        ("Open trades", summary.open_trades as f64, ColumnType::Integer),
+        ("Win rate (%)", summary.win_rate, ColumnType::Money),
+        ("Net profit", summary.net_profit, ColumnType::Money),
+        ("Gross profit", summary.gross_profit, ColumnType::Money),
+        ("Gross loss", summary.gross_loss, ColumnType::Money),
+        ("Profit factor", summary.profit_factor, ColumnType::Number),
+        ("Expectancy", summary.expectancy, ColumnType::Money),
+        ("Average trade", summary.average_trade, ColumnType::Money),
+        ("Sharpe ratio", summary.sharpe_ratio, ColumnType::Number),
+        ("Max drawdown", summary.max_drawdown, ColumnType::Money),
+        ("Recovery factor", summary.recovery_factor, ColumnType::Number),
+        ("Calmar ratio", summary.calmar_ratio, ColumnType::Number),
+        ("Risk of ruin", summary.risk_of_ruin, ColumnType::Number),
+    ]
+}
+
+// Header text; dates name the timezone they are shown in
+fn header(column: &ExportColumn, tz: Tz) -> String {
+    if column.column_type == ColumnType::DateTime {
+        format!("{} ({})", column.label, tz.name())
+    } else {
+        column.label.clone()
+    }
+}
+
+pub fn write_csv(
+    path: &Path,
+    columns: &[ExportColumn],
+    rows: &[TradeRow],
+    locale: &ExportLocale,
+    tz: Tz,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(locale.csv_delimiter).from_path(path)?;
+    writer.write_record(columns.iter().map(|column| header(column, tz)))?;
+
+    for row in rows {
+        let cells = row_cells(row, columns, tz);
+        writer.write_record(cells.iter().zip(columns).map(|(cell, column)| match cell {
+            Cell::Empty => String::new(),
+            Cell::Text(text) => text.clone(),
+            Cell::Number(value) => locale.format_number(*value, column.column_type),
+            Cell::Boolean(value) => if *value { "TRUE" } else { "FALSE" }.to_string(),
+            Cell::DateTime(value) => value.with_timezone(&tz).format(locale.date_format).to_string(),
+        }))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_summary_csv(
+    path: &Path,
+    summary: &AnalysisSummary,
+    locale: &ExportLocale,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(locale.csv_delimiter).from_path(path)?;
+    writer.write_record(["Metric", "Value"])?;
+    for (label, value, column_type) in summary_rows(summary) {
+        let value = if value.is_finite() { locale.format_number(value, column_type) } else { String::new() };
+        writer.write_record([label, value.as_str()])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn number_format(column_type: ColumnType, locale: &ExportLocale) -> Format {
+    match column_type {
+        ColumnType::Money => Format::new().set_num_format("#,##0.00"),
+        ColumnType::Price => Format::new().set_num_format("0.00###"),
+        ColumnType::Integer => Format::new().set_num_format("0"),
+        ColumnType::DateTime => Format::new().set_num_format(locale.excel_date_format),
+        _ => Format::new(),
+    }
+}
+
+fn excel_datetime(value: &DateTime<Utc>, tz: Tz) -> Result<ExcelDateTime, rust_xlsxwriter::XlsxError> {
+    let local = value.with_timezone(&tz);
+    ExcelDateTime::from_ymd(local.year() as u16, local.month() as u8, local.day() as u8)?
+        .and_hms(local.hour() as u16, local.minute() as u8, local.second())
+}
+
+pub fn write_xlsx(
+    path: &Path,
+    columns: &[ExportColumn],
+    rows: &[TradeRow],
+    summary: Option<&AnalysisSummary>,
+    locale: &ExportLocale,
+    tz: Tz,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let formats: Vec<Format> = columns.iter().map(|column| number_format(column.column_type, locale)).collect();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Trades")?;
+    for (col, column) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, header(column, tz), &bold)?;
+    }
+
+    for (index, row) in rows.iter().enumerate() {
+        let r = index as u32 + 1;
+        for (col, cell) in row_cells(row, columns, tz).into_iter().enumerate() {
+            let c = col as u16;
+            match cell {
+                Cell::Empty => {}
+                Cell::Text(text) => { sheet.write_string(r, c, text)?; }
+                Cell::Number(value) => { sheet.write_number_with_format(r, c, value, &formats[col])?; }
+                Cell::Boolean(value) => { sheet.write_boolean(r, c, value)?; }
+                Cell::DateTime(value) => { sheet.write_datetime_with_format(r, c, excel_datetime(&value, tz)?, &formats[col])?; }
+            }
+        }
+    }
+
+    if !columns.is_empty() {
+        sheet.set_freeze_panes(1, 0)?;
+        sheet.autofilter(0, 0, rows.len() as u32, columns.len() as u16 - 1)?;
+    }
+    sheet.autofit();
+
+    if let Some(summary) = summary {
+        write_summary_sheet(workbook.add_worksheet(), summary, &bold)?;
+    }
+
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn write_summary_sheet(sheet: &mut Worksheet, summary: &AnalysisSummary, bold: &Format) -> Result<(), rust_xlsxwriter::XlsxError> {
+    sheet.set_name("Summary")?;
+    sheet.write_string_with_format(0, 0, "Metric", bold)?;
+    sheet.write_string_with_format(0, 1, "Value", bold)?;
+
+    for (index, (label, value, column_type)) in summary_rows(summary).into_iter().enumerate() {
+        let r = index as u32 + 1;
+        sheet.write_string(r, 0, label)?;
+        // Profit factor is infinite without losses; Excel has no such number
+        if value.is_finite() {
+            sheet.write_number_with_format(r, 1, value, &number_format(column_type, &ExportLocale::default()))?;
+        }
+    }
+    sheet.autofit();
+    Ok(())
+}
+
+// Summary of the exported rows, as the analysis page would compute it but
+// on the Net P/L column, so its profit figures add up to that column
+pub fn summarize(rows: &[TradeRow]) -> AnalysisSummary {
+    let trades: Vec<TradeProjection> = rows.iter()
+        .map(|row| TradeProjection { profit_loss_money: net_profit(row), ..projection(row) })
+        .collect();
+    Analyzer::summarize(&trades)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> TradeRow {
+        value.as_object().unwrap().clone()
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("trade-export-test-{}-{}", std::process::id(), name))
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn german_csv_quotes_delimiters_and_leaves_open_trades_empty() {
+        let rows = [
+            row(json!({
+                "symbol": "EURUSD", "trade_type": "Buy", "entry_price": 1.085, "sl": null, "exit_price": 1.09,
+                "entry_time": "2024-03-04T08:00:00Z", "exit_time": "2024-03-04T09:30:00Z", "is_win": 1,
+                "profit_loss_money": 1234.5, "commission": -7.0, "swap": -1.5,
+                "pattern_combination": "[\"FVG\",\"OB\"]", "notes": "Retest; held \"partial\"",
+            })),
+            // Summer time, and past midnight in Berlin
+            row(json!({
+                "symbol": "XAUUSD", "trade_type": "Sell", "entry_price": 2031.4, "sl": 2036.0,
+                "entry_time": "2024-07-01T22:30:00Z", "is_win": null, "notes": "",
+            })),
+        ];
+        let columns = resolve_columns(
+            &keys(&["entry_time", "symbol", "entry_price", "profit_loss_money", "net_profit", "r_multiple", "outcome", "pattern_combination", "notes"]),
+            &HashMap::new(),
+        ).unwrap();
+        let path = temp_file("de.csv");
+        write_csv(&path, &columns, &rows, &ExportLocale::from_tag("de_DE"), chrono_tz::Europe::Berlin).unwrap();
+
+        assert_eq!(lines(&path), [
+            "Entry Time (Europe/Berlin);Symbol;Entry Price;P/L;Net P/L;R Multiple;Outcome;Pattern Combination;Notes",
+            "04.03.2024 09:00;EURUSD;1,085;1234,50;1226,00;;Win;FVG, OB;\"Retest; held \"\"partial\"\"\"",
+            "02.07.2024 00:30;XAUUSD;2031,4;;;;Open;;",
+        ]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn summary_nets_out_costs_and_blanks_an_infinite_profit_factor() {
+        let win = row(json!({
+            "id": 1, "entry_time": "2024-03-04T08:00:00Z", "is_win": 1,
+            "profit_loss_money": 100.0, "commission": -7.0, "swap": -3.0,
+        }));
+        let loss = row(json!({
+            "id": 2, "entry_time": "2024-03-05T08:00:00Z", "is_win": 0,
+            "profit_loss_money": -50.0, "commission": -7.0, "swap": null,
+        }));
+
+        // The summary agrees with the Net P/L column, not the gross P/L
+        let summary = summarize(&[win.clone(), loss]);
+        assert_eq!((summary.net_profit, summary.gross_profit, summary.gross_loss), (33.0, 90.0, 57.0));
+
+        let path = temp_file("summary.csv");
+        write_summary_csv(&path, &summarize(&[win]), &ExportLocale::default()).unwrap();
+        let lines = lines(&path);
+        assert!(lines.contains(&"Net profit,90.00".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"Profit factor,".to_string()), "{:?}", lines);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn schema_labels_name_stored_and_custom_columns() {
+        let schema = json!({"name": "Trade", "fields": [
+            {"name": "symbol", "ui": {"label": "Instrument"}},
+            {"name": "setup_grade", "ui": {"label": "Setup Grade"}},
+            {"name": "screen_time", "ui": {"label": "  "}},
+        ]});
+        let labels = schema_labels(&schema);
+        let columns = resolve_columns(&keys(&["symbol", "custom.setup_grade", "custom.screen_time", "exit_time"]), &labels).unwrap();
+        let rows = [
+            row(json!({"symbol": "NAS100", "setup_grade": "A", "screen_time": 25, "exit_time": "2024-01-02T15:00:00Z"})),
+            // A trade saved before the field existed
+            row(json!({"symbol": "US30", "exit_time": null})),
+        ];
+        let path = temp_file("labels.csv");
+        write_csv(&path, &columns, &rows, &ExportLocale::from_tag("en-US"), chrono_tz::America::New_York).unwrap();
+
+        assert_eq!(lines(&path), [
+            "Instrument,Setup Grade,screen_time,Exit Time (America/New_York)",
+            "NAS100,A,25,01/02/2024 10:00",
+            "US30,,,",
+        ]);
+        let _ = std::fs::remove_file(path);
+
+        // Keys are spliced into SQL column lists, so only known names pass
+        for key in ["custom.", "volume; DROP TABLE trades", "Symbol"] {
+            assert!(resolve_columns(&keys(&[key]), &labels).is_err(), "{}", key);
+        }
+    }
+}
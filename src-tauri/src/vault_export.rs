@@ -0,0 +1,526 @@
+// Markdown vault export for Obsidian and similar note apps
+//
+// Layout below the chosen folder:
+//   Trades/<date> <symbol> <side> <id>.md   one note per trade, YAML front-matter
+//   Attachments/trade-<id>-<kind>.<ext>     copied screenshots
+//   Daily/<date>.md                         trades entered that day
+//   Strategies/<strategy>.md                trades of one strategy
+//   Trading Journal.md                      links to every day and strategy
+//   .trading-journal.json                   manifest of what the last export wrote
+//
+// Notes link to each other with wiki-links ([[Trades/...|#12 XAUUSD Sell]]),
+// so backlinks and the graph view work without plugins. The manifest keeps a
+// hash per file: a re-export rewrites only files whose content changed,
+// removes files it wrote for trades that are gone, and leaves files that were
+// edited in the vault alone.
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::report::{self, IMAGE_COLUMNS};
+use crate::trade_export::{self, Cell, ColumnType, TradeRow, TRADE_COLUMNS};
+
+pub const MANIFEST_FILE: &str = ".trading-journal.json";
+pub const MANIFEST_VERSION: u32 = 1;
+const INDEX_NOTE: &str = "Trading Journal.md";
+
+// Stored columns that are not front-matter: the note body and the screenshots
+const BODY_COLUMNS: [&str; 5] = ["notes", "chart_explanation", "entry_image", "exit_image", "analysis_image"];
+const COMPUTED_PROPERTIES: [&str; 4] = ["net_profit", "outcome", "r_multiple", "holding_minutes"];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VaultExportOptions {
+    pub include_archived: bool,
+    // Also rewrite (or remove) files that were changed in the vault since
+    // the last export
+    pub overwrite_edited: bool,
+}
+
+impl Default for VaultExportOptions {
+    fn default() -> Self {
+        Self { include_archived: true, overwrite_edited: false }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VaultExportReport {
+    pub path: String,
+    pub notes: usize,
+    pub attachments: usize,
+    pub written: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+    // Vault paths left alone because they were edited after the last export
+    pub kept_edited: Vec<String>,
+    // Referenced images whose file was not found
+    pub missing_images: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Manifest {
+    pub version: u32,
+    pub exported_at: String,
+    // Vault path -> content hash
+    pub files: BTreeMap<String, String>,
+}
+
+// 64-bit FNV-1a; stable across builds, unlike the std hasher
+pub fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Unchanged,
+    Write,
+    KeepEdited,
+}
+
+// `previous` is the hash the last export recorded, `on_disk` the hash of the
+// file as it is now. A file that differs from both the new content and what
+// was exported was changed by someone else, including files we never wrote.
+pub fn file_action(previous: Option<&str>, on_disk: Option<&str>, new: &str, overwrite_edited: bool) -> FileAction {
+    match on_disk {
+        None => FileAction::Write,
+        Some(disk) if disk == new => FileAction::Unchanged,
+        Some(disk) if previous != Some(disk) && !overwrite_edited => FileAction::KeepEdited,
+        Some(_) => FileAction::Write,
+    }
+}
+
+pub struct VaultPlan {
+    // Vault path -> markdown
+    pub notes: Vec<(String, String)>,
+    // Vault path -> stored image path (relative to the image storage)
+    pub attachments: Vec<(String, String)>,
+}
+
+// Characters that break file names on some platform or wiki-link syntax
+pub fn note_name(value: &str) -> String {
+    let cleaned: String = value.chars()
+        .map(|c| if "\\/:*?\"<>|#^[]".contains(c) || c.is_control() { '-' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+    if cleaned.is_empty() { "Untitled".to_string() } else { cleaned.to_string() }
+}
+
+fn tag(value: &str) -> String {
+    value.trim().chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '-' }).collect()
+}
+
+fn signed(value: f64) -> String {
+    if value > 0.0 { format!("+{}", report::money(value)) } else { report::money(value) }
+}
+
+// JSON scalars, arrays and objects are valid YAML flow values
+fn yaml(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+struct TradeNote<'a> {
+    row: &'a TradeRow,
+    id: i64,
+    symbol: &'a str,
+    side: &'a str,
+    entry: Option<DateTime<Utc>>,
+    day: Option<NaiveDate>,
+    strategy: Option<&'a str>,
+    profit: Option<f64>,
+    is_win: Option<bool>,
+    // Without the .md extension, as used in links
+    path: String,
+}
+
+impl<'a> TradeNote<'a> {
+    fn new(row: &'a TradeRow, tz: Tz) -> Self {
+        let id = row.get("id").and_then(Value::as_i64).unwrap_or_default();
+        let symbol = report::text(row, "symbol").unwrap_or_default();
+        let side = report::text(row, "trade_type").unwrap_or_default();
+        let projection = trade_export::projection(row);
+        let day = projection.entry_time.map(|t| t.with_timezone(&tz).date_naive());
+        let name = format!("{} {} {} {}", day.map(|d| d.to_string()).unwrap_or_default(), symbol, side, id);
+
+        TradeNote {
+            row,
+            id,
+            symbol,
+            side,
+            entry: projection.entry_time,
+            day,
+            strategy: report::text(row, "strategy_name").map(str::trim),
+            profit: projection.profit_loss_money,
+            is_win: projection.is_win,
+            path: format!("Trades/{}", note_name(&name)),
+        }
+    }
+
+    fn link(&self) -> String {
+        format!("[[{}|#{} {} {}]]", self.path, self.id, self.symbol, self.side)
+    }
+
+    // One list line for index pages
+    fn line(&self, tz: Tz, with_date: bool) -> String {
+        let when = self.entry
+            .map(|t| t.with_timezone(&tz).format(if with_date { "%Y-%m-%d %H:%M" } else { "%H:%M" }).to_string())
+            .unwrap_or_default();
+        let mut line = format!("- {} {}", when, self.link());
+        if let Some(strategy) = self.strategy.filter(|_| !with_date) {
+            let _ = write!(line, " · {}", strategy);
+        }
+        match (self.is_win, self.profit) {
+            (Some(_), Some(profit)) => { let _ = write!(line, " · {}", signed(profit)); }
+            _ => line.push_str(" · open"),
+        }
+        line
+    }
+}
+
+fn daily_path(day: NaiveDate) -> String {
+    format!("Daily/{}", day)
+}
+
+// Strategy note per strategy name. Names that only differ in characters
+// note_name replaces, or in case (which Windows and macOS file names
+// ignore), would share one note; all but a name that needed no cleaning get
+// a suffix from the strategy's hash, so each keeps its note across exports.
+fn strategy_paths<'a>(strategies: impl Iterator<Item = &'a str>) -> HashMap<&'a str, String> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for strategy in strategies {
+        groups.entry(note_name(strategy).to_lowercase()).or_default().push(strategy);
+    }
+
+    let mut paths = HashMap::new();
+    for names in groups.into_values() {
+        let plain = names.iter().position(|name| note_name(name) == *name).unwrap_or(names.len());
+        for (i, name) in names.iter().enumerate() {
+            let path = if names.len() == 1 || i == plain {
+                format!("Strategies/{}", note_name(name))
+            } else {
+                format!("Strategies/{} {}", note_name(name), &content_hash(name.as_bytes())[..6])
+            };
+            paths.insert(*name, path);
+        }
+    }
+    paths
+}
+
+fn front_matter(note: &TradeNote, custom_fields: &[String], tz: Tz) -> String {
+    let keys: Vec<String> = TRADE_COLUMNS.iter()
+        .map(|(key, ..)| key.to_string())
+        .filter(|key| !BODY_COLUMNS.contains(&key.as_str()))
+        .chain(COMPUTED_PROPERTIES.iter().map(|key| key.to_string()))
+        .collect();
+    let columns = trade_export::resolve_columns(&keys, &HashMap::new()).expect("vault columns are known");
+
+    let mut yaml_text = String::from("---\n");
+    for (cell, column) in trade_export::row_cells(note.row, &columns, tz).into_iter().zip(&columns) {
+        let value = match cell {
+            Cell::Empty => continue,
+            // Tag lists are stored as JSON; keep them a list
+            Cell::Text(_) if column.key == "pattern_combination" => note.row.get("pattern_combination")
+                .and_then(Value::as_str)
+                .and_then(|text| serde_json::from_str::<Value>(text).ok())
+                .filter(Value::is_array)
+                .unwrap_or_else(|| Value::String(report::text(note.row, "pattern_combination").unwrap_or_default().to_string())),
+            Cell::Text(text) => Value::String(text),
+            Cell::Number(number) if column.column_type == ColumnType::Integer => Value::from(number as i64),
+            Cell::Number(number) => serde_json::Number::from_f64(number).map(Value::Number).unwrap_or(Value::Null),
+            Cell::Boolean(flag) => Value::Bool(flag),
+            Cell::DateTime(time) => Value::String(time.with_timezone(&tz).to_rfc3339()),
+        };
+        let _ = writeln!(yaml_text, "{}: {}", column.key, yaml(&value));
+    }
+
+    let custom: serde_json::Map<String, Value> = custom_fields.iter()
+        .filter_map(|name| note.row.get(name).filter(|value| !value.is_null()).map(|value| (name.clone(), value.clone())))
+        .collect();
+    if !custom.is_empty() {
+        let _ = writeln!(yaml_text, "custom_fields: {}", yaml(&Value::Object(custom)));
+    }
+
+    let mut tags = vec!["trade".to_string(), format!("symbol/{}", tag(note.symbol))];
+    if let Some(strategy) = note.strategy {
+        tags.push(format!("strategy/{}", tag(strategy)));
+    }
+    if let Some(pattern) = report::text(note.row, "ict_pattern") {
+        tags.push(format!("ict/{}", tag(pattern)));
+    }
+    let _ = writeln!(yaml_text, "tags: {}", yaml(&Value::from(tags)));
+    if let Some(day) = note.day {
+        let _ = writeln!(yaml_text, "day: {}", yaml(&Value::String(format!("[[{}]]", daily_path(day)))));
+    }
+    yaml_text.push_str("---\n");
+    yaml_text
+}
+
+fn trade_markdown(
+    note: &TradeNote,
+    related: &[(String, &TradeNote)],
+    attachments: &[(String, &str)],
+    custom_fields: &[String],
+    strategy_paths: &HashMap<&str, String>,
+    tz: Tz,
+) -> String {
+    let mut md = front_matter(note, custom_fields, tz);
+    let when = note.entry.map(|t| t.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+    let _ = writeln!(md, "\n# #{} {} {} · {}\n", note.id, note.symbol, note.side, when);
+
+    let result = match (note.is_win, note.profit) {
+        (Some(true), Some(profit)) => format!("Win · {}", signed(profit)),
+        (Some(false), Some(profit)) => format!("Loss · {}", signed(profit)),
+        _ => "Open".to_string(),
+    };
+    let mut context = vec![format!("**Result:** {}", result)];
+    if let Some(day) = note.day {
+        context.push(format!("**Day:** [[{}|{}]]", daily_path(day), day));
+    }
+    if let Some(strategy) = note.strategy {
+        context.push(format!("**Strategy:** [[{}|{}]]", strategy_paths[strategy], strategy));
+    }
+    let _ = writeln!(md, "{}", context.join(" · "));
+
+    for (column, heading) in [("notes", "Notes"), ("chart_explanation", "Chart explanation")] {
+        if let Some(text) = report::text(note.row, column) {
+            let _ = writeln!(md, "\n## {}\n\n{}", heading, text.trim_end());
+        }
+    }
+    if !attachments.is_empty() {
+        md.push_str("\n## Screenshots\n");
+        for (path, label) in attachments {
+            let _ = writeln!(md, "\n### {}\n\n![[{}]]", label, path);
+        }
+    }
+    if !related.is_empty() {
+        md.push_str("\n## Related\n\n");
+        for (relation, other) in related {
+            let _ = writeln!(md, "- {}: {}", relation, other.link());
+        }
+    }
+    md
+}
+
+fn outcome_counts(notes: &[&TradeNote]) -> (f64, usize, usize) {
+    let profit = notes.iter().filter(|n| n.is_win.is_some()).filter_map(|n| n.profit).sum();
+    let wins = notes.iter().filter(|n| n.is_win == Some(true)).count();
+    let losses = notes.iter().filter(|n| n.is_win == Some(false)).count();
+    (profit, wins, losses)
+}
+
+fn daily_markdown(day: NaiveDate, notes: &[&TradeNote], tz: Tz) -> String {
+    let (profit, wins, losses) = outcome_counts(notes);
+    let mut md = String::new();
+    let _ = writeln!(md, "---\ndate: {}\ntrades: {}\nnet_profit: {}\ntags: [\"trading-day\"]\n---\n", day, notes.len(), yaml(&Value::from((profit * 100.0).round() / 100.0)));
+    let _ = writeln!(md, "# Trading day {}\n", day);
+    let _ = writeln!(md, "Net P/L **{}** · {} wins · {} losses\n", signed(profit), wins, losses);
+    for note in notes {
+        let _ = writeln!(md, "{}", note.line(tz, false));
+    }
+    md
+}
+
+fn strategy_markdown(strategy: &str, notes: &[&TradeNote], tz: Tz) -> String {
+    let (profit, wins, losses) = outcome_counts(notes);
+    let win_rate = if wins + losses > 0 { wins as f64 / (wins + losses) as f64 * 100.0 } else { 0.0 };
+    let mut md = String::new();
+    let _ = writeln!(
+        md,
+        "---\nstrategy: {}\ntrades: {}\nwin_rate: {}\nnet_profit: {}\ntags: [\"strategy\"]\n---\n",
+        yaml(&Value::String(strategy.to_string())), notes.len(),
+        yaml(&Value::from((win_rate * 10.0).round() / 10.0)), yaml(&Value::from((profit * 100.0).round() / 100.0)),
+    );
+    let _ = writeln!(md, "# {}\n", strategy);
+    let _ = writeln!(md, "{} trades · {:.1}% win rate · net P/L **{}**\n", notes.len(), win_rate, signed(profit));
+    for note in notes {
+        let _ = writeln!(md, "{}", note.line(tz, true));
+    }
+    md
+}
+
+fn index_markdown(
+    days: &BTreeMap<NaiveDate, Vec<&TradeNote>>,
+    strategies: &BTreeMap<&str, Vec<&TradeNote>>,
+    strategy_paths: &HashMap<&str, String>,
+) -> String {
+    let mut md = String::from("# Trading journal\n");
+    if !strategies.is_empty() {
+        md.push_str("\n## Strategies\n\n");
+        for (strategy, notes) in strategies {
+            let (profit, ..) = outcome_counts(notes);
+            let _ = writeln!(md, "- [[{}|{}]] · {} trades · {}", strategy_paths[strategy], strategy, notes.len(), signed(profit));
+        }
+    }
+    let mut month = None;
+    for (day, notes) in days.iter().rev() {
+        if month != Some((day.year(), day.month())) {
+            month = Some((day.year(), day.month()));
+            let _ = writeln!(md, "\n## {} {}\n", report::month_name(day.month()), day.year());
+        }
+        let (profit, ..) = outcome_counts(notes);
+        let _ = writeln!(md, "- [[{}|{}]] · {} trades · {}", daily_path(*day), day, notes.len(), signed(profit));
+    }
+    md
+}
+
+// Every note and attachment of the export, in a stable order
+// `custom_fields` names the Trade schema's custom columns in the rows
+pub fn plan(rows: &[TradeRow], custom_fields: &[String], tz: Tz) -> VaultPlan {
+    let mut notes: Vec<TradeNote> = rows.iter().map(|row| TradeNote::new(row, tz)).collect();
+    notes.sort_by_key(|note| (note.entry, note.id));
+
+    let mut days: BTreeMap<NaiveDate, Vec<&TradeNote>> = BTreeMap::new();
+    let mut strategies: BTreeMap<&str, Vec<&TradeNote>> = BTreeMap::new();
+    for note in &notes {
+        if let Some(day) = note.day {
+            days.entry(day).or_default().push(note);
+        }
+        if let Some(strategy) = note.strategy {
+            strategies.entry(strategy).or_default().push(note);
+        }
+    }
+    let strategy_paths = strategy_paths(strategies.keys().copied());
+
+    let mut plan = VaultPlan { notes: Vec::new(), attachments: Vec::new() };
+    for note in &notes {
+        let mut related: Vec<(String, &TradeNote)> = Vec::new();
+        if let Some(strategy) = note.strategy {
+            let peers = &strategies[strategy];
+            let at = peers.iter().position(|peer| peer.id == note.id).unwrap_or_default();
+            if let Some(previous) = at.checked_sub(1).map(|i| peers[i]) {
+                related.push((format!("Previous {} trade", strategy), previous));
+            }
+            if let Some(next) = peers.get(at + 1) {
+                related.push((format!("Next {} trade", strategy), next));
+            }
+        }
+        if let Some(day) = note.day {
+            let same_symbol: Vec<&TradeNote> = days[&day].iter()
+                .filter(|other| other.id != note.id && other.symbol == note.symbol)
+                .filter(|other| !related.iter().any(|(_, listed)| listed.id == other.id))
+                .copied()
+                .collect();
+            for other in same_symbol {
+                related.push((format!("Same day on {}", note.symbol), other));
+            }
+        }
+
+        let mut attachments = Vec::new();
+        for (column, label) in IMAGE_COLUMNS {
+            if let Some(stored) = report::text(note.row, column) {
+                let extension = stored.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_else(|| "png".to_string());
+                let path = format!("Attachments/trade-{}-{}.{}", note.id, label.to_ascii_lowercase(), extension);
+                plan.attachments.push((path.clone(), stored.to_string()));
+                attachments.push((path, label));
+            }
+        }
+
+        plan.notes.push((format!("{}.md", note.path), trade_markdown(note, &related, &attachments, custom_fields, &strategy_paths, tz)));
+    }
+    for (day, notes) in &days {
+        plan.notes.push((format!("{}.md", daily_path(*day)), daily_markdown(*day, notes, tz)));
+    }
+    for (strategy, notes) in &strategies {
+        plan.notes.push((format!("{}.md", strategy_paths[strategy]), strategy_markdown(strategy, notes, tz)));
+    }
+    plan.notes.push((INDEX_NOTE.to_string(), index_markdown(&days, &strategies, &strategy_paths)));
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<TradeRow> {
+        [
+            json!({"id": 12, "symbol": "XAUUSD", "trade_type": "Sell", "volume": 0.5, "entry_price": 2031.4, "sl": 2036.0, "tp": 2018.0,
+                   "entry_time": "2024-03-04T08:00:00Z", "exit_time": "2024-03-04T09:30:00Z", "exit_price": 2018.0, "is_win": 1,
+                   "profit_loss_money": 670.0, "strategy_name": "London: sweep", "ict_pattern": "FVG", "pattern_combination": "[\"FVG\",\"OB\"]",
+                   "notes": "Swept \"Asia\" high", "entry_image": "images/abc.PNG", "setup_grade": "A"}),
+            json!({"id": 13, "symbol": "XAUUSD", "trade_type": "Buy", "volume": 0.5, "entry_price": 2020.0, "sl": 2015.0, "tp": 2030.0,
+                   "entry_time": "2024-03-04T13:00:00Z", "exit_time": "2024-03-04T14:00:00Z", "exit_price": 2015.0, "is_win": 0,
+                   "profit_loss_money": -250.0}),
+            json!({"id": 20, "symbol": "EURUSD", "trade_type": "Buy", "volume": 1.0, "entry_price": 1.09, "sl": 1.085, "tp": 1.1,
+                   "entry_time": "2024-04-02T07:00:00Z", "strategy_name": "London: sweep"}),
+        ].into_iter().map(|v| v.as_object().unwrap().clone()).collect()
+    }
+
+    #[test]
+    fn trade_notes_carry_front_matter_body_and_links() {
+        let plan = plan(&rows(), &["setup_grade".to_string()], chrono_tz::Europe::Berlin);
+        let (path, note) = &plan.notes[0];
+        assert_eq!(path, "Trades/2024-03-04 XAUUSD Sell 12.md");
+
+        assert!(note.starts_with("---\nid: 12\n"));
+        assert!(note.contains("entry_time: \"2024-03-04T09:00:00+01:00\"\n"));
+        assert!(note.contains("pattern_combination: [\"FVG\",\"OB\"]\n"));
+        assert!(note.contains("is_win: true\n") && note.contains("outcome: \"Win\"\n"));
+        assert!(note.contains("custom_fields: {\"setup_grade\":\"A\"}\n"));
+        assert!(note.contains("tags: [\"trade\",\"symbol/XAUUSD\",\"strategy/London--sweep\",\"ict/FVG\"]\n"));
+        assert!(!note.contains("entry_image:"));
+
+        assert!(note.contains("## Notes\n\nSwept \"Asia\" high\n"));
+        assert!(note.contains("![[Attachments/trade-12-entry.png]]"));
+        assert!(note.contains("**Strategy:** [[Strategies/London- sweep|London: sweep]]"));
+        assert!(note.contains("- Next London: sweep trade: [[Trades/2024-04-02 EURUSD Buy 20|#20 EURUSD Buy]]"));
+        assert!(note.contains("- Same day on XAUUSD: [[Trades/2024-03-04 XAUUSD Buy 13|#13 XAUUSD Buy]]"));
+        assert_eq!(plan.attachments, [("Attachments/trade-12-entry.png".to_string(), "images/abc.PNG".to_string())]);
+    }
+
+    #[test]
+    fn index_pages_group_trades_by_day_and_strategy() {
+        let plan = plan(&rows(), &[], chrono_tz::UTC);
+        let page = |path: &str| &plan.notes.iter().find(|(p, _)| p == path).unwrap().1;
+
+        let day = page("Daily/2024-03-04.md");
+        assert!(day.contains("trades: 2\nnet_profit: 420.0\n"));
+        assert!(day.contains("Net P/L **+420.00** · 1 wins · 1 losses"));
+        assert!(day.contains("- 08:00 [[Trades/2024-03-04 XAUUSD Sell 12|#12 XAUUSD Sell]] · London: sweep · +670.00"));
+
+        let strategy = page("Strategies/London- sweep.md");
+        assert!(strategy.contains("strategy: \"London: sweep\"\ntrades: 2\nwin_rate: 100.0\n"));
+        assert!(strategy.contains("- 2024-04-02 07:00 [[Trades/2024-04-02 EURUSD Buy 20|#20 EURUSD Buy]] · open"));
+
+        let index = page("Trading Journal.md");
+        assert!(index.find("## April 2024").unwrap() < index.find("## March 2024").unwrap());
+        assert!(index.contains("- [[Daily/2024-03-04|2024-03-04]] · 2 trades · +420.00"));
+    }
+
+    #[test]
+    fn strategies_whose_note_names_collide_get_their_own_notes() {
+        let mut rows = rows();
+        for (id, strategy) in [(30, "London/ sweep"), (31, "London- sweep"), (32, "scalp"), (33, "Scalp")] {
+            let mut row = rows[2].clone();
+            row.insert("id".to_string(), json!(id));
+            row.insert("strategy_name".to_string(), json!(strategy));
+            rows.push(row);
+        }
+        let plan = plan(&rows, &[], chrono_tz::UTC);
+        let strategy_notes: Vec<&str> = plan.notes.iter().map(|(path, _)| path.as_str()).filter(|path| path.starts_with("Strategies/")).collect();
+
+        // "London- sweep" needed no cleaning and keeps the plain name
+        let london = format!("Strategies/London- sweep {}", &content_hash("London: sweep".as_bytes())[..6]);
+        let slash = format!("Strategies/London- sweep {}", &content_hash("London/ sweep".as_bytes())[..6]);
+        let lower = format!("Strategies/scalp {}", &content_hash(b"scalp")[..6]);
+        assert_eq!(strategy_notes, [
+            "Strategies/London- sweep.md".to_string(), format!("{}.md", slash), format!("{}.md", london),
+            "Strategies/Scalp.md".to_string(), format!("{}.md", lower),
+        ]);
+        let mut folded: Vec<String> = strategy_notes.iter().map(|path| path.to_lowercase()).collect();
+        folded.sort();
+        folded.dedup();
+        assert_eq!(folded.len(), 5);
+
+        // Links follow the note each strategy was written to
+        let note = |path: &str| &plan.notes.iter().find(|(p, _)| p == path).unwrap().1;
+        assert!(note("Trades/2024-03-04 XAUUSD Sell 12.md").contains(&format!("**Strategy:** [[{}|London: sweep]]", london)));
+        assert!(note("Trades/2024-04-02 EURUSD Buy 33.md").contains("**Strategy:** [[Strategies/Scalp|Scalp]]"));
+        let index = note("Trading Journal.md");
+        assert!(index.contains(&format!("- [[{}|London/ sweep]] · 1 trades", slash)));
+        assert!(index.contains(&format!("- [[{}|scalp]] · 1 trades", lower)));
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::paths::{self, copy_tree, verify_copy};
+
+const WORKSPACES_DIR: &str = "workspaces";
+const REGISTRY_FILE: &str = "workspaces.json";
+const DATABASE_FILE: &str = "trading_journal.db";
+const ARCHIVE_FILE: &str = "archive.db";
+const LEGACY_DATABASE: &str = "data/trading_journal.db";
+pub const DEFAULT_WORKSPACE_ID: &str = "personal";
+
+// A named journal (personal, prop firm, student, ...). Every workspace owns
+// its own directory with database, images, plugin data and config, so
+// nothing is shared between journals. `path` is relative to the data root
+// unless the journal lives outside it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub last_opened_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct WorkspaceRegistry {
+    active: Option<String>,
+    workspaces: Vec<Workspace>,
+}
+
+// Directory layout of a single workspace
+#[derive(Debug, Clone)]
+pub struct WorkspacePaths {
+    root: PathBuf,
+}
+
+impl WorkspacePaths {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Database plus the images/ and thumbnails/ trees trade records point into
+    pub fn data_dir(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    pub fn database_file(&self) -> PathBuf {
+        self.data_dir().join(DATABASE_FILE)
+    }
+
+    // Cold storage for archived trades, attached to every connection
+    pub fn archive_file(&self) -> PathBuf {
+        self.data_dir().join(ARCHIVE_FILE)
+    }
+
+    pub fn database_url(&self) -> String {
+        format!("sqlite:{}", self.database_file().display())
+    }
+
+    pub fn plugins_data_dir(&self) -> PathBuf {
+        self.root.join("plugins-data")
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        self.root.join("config")
+    }
+
+    pub fn backups_dir(&self) -> PathBuf {
+        self.root.join("backups")
+    }
+
+    pub fn directories(&self) -> Vec<PathBuf> {
+        let data = self.data_dir();
+        vec![
+            data.join("images"),
+            data.join("thumbnails"),
+            data.join("exports"),
+            self.plugins_data_dir(),
+            self.config_dir(),
+            self.backups_dir(),
+            self.root.join("logs"),
+        ]
+    }
+
+    // Everything a journal owns, and nothing else that may share its folder
+    pub fn entries(&self) -> Vec<PathBuf> {
+        vec![
+            self.data_dir(),
+            self.plugins_data_dir(),
+            self.config_dir(),
+            self.backups_dir(),
+            self.root.join("logs"),
+        ]
+    }
+}
+
+// Registry of all workspaces and which one is open
+pub struct WorkspaceManager {
+    root: PathBuf,
+    base_dir: PathBuf,
+    registry: WorkspaceRegistry,
+}
+
+impl Default for WorkspaceManager {
+    fn default() -> Self {
+        Self::new(paths::data_root())
+    }
+}
+
+impl WorkspaceManager {
+    pub fn new(data_root: impl AsRef<Path>) -> Self {
+        let root = data_root.as_ref().to_path_buf();
+
+        Self {
+            base_dir: root.join(WORKSPACES_DIR),
+            root,
+            registry: WorkspaceRegistry::default(),
+        }
+    }
+
+    pub async fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let registry_path = self.base_dir.join(REGISTRY_FILE);
+
+        if let Ok(data) = fs::read(&registry_path).await {
+            self.registry = serde_json::from_slice(&data)?;
+        }
+
+        if self.registry.workspaces.is_empty() {
+            // Journals from before workspaces keep living where they are,
+            // either in the data root itself or the old working directory
+            let legacy_root = [self.root.clone(), PathBuf::from(".")]
+                .into_iter()
+                .find(|dir| dir.join(LEGACY_DATABASE).exists());
+            let path = match legacy_root {
+                Some(dir) => self.relative_to_root(&dir.canonicalize()?),
+                None => PathBuf::from(WORKSPACES_DIR).join(DEFAULT_WORKSPACE_ID),
+            };
+
+            self.registry.workspaces.push(Workspace {
+                id: DEFAULT_WORKSPACE_ID.to_string(),
+                name: "Personal".to_string(),
+                path,
+                created_at: Utc::now(),
+                last_opened_at: None,
+            });
+        }
+
+        // Reopen the most recently used journal if the last one was closed
+        if self.active().is_none() {
+            self.registry.active = self.registry.workspaces.iter()
+                .max_by_key(|w| w.last_opened_at)
+                .map(|w| w.id.clone());
+        }
+
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.base_dir).await?;
+        fs::write(
+            self.base_dir.join(REGISTRY_FILE),
+            serde_json::to_vec_pretty(&self.registry)?,
+        ).await?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Workspace> {
+        self.registry.workspaces.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Workspace> {
+        self.registry.workspaces.iter().find(|w| w.id == id)
+    }
+
+    pub fn active(&self) -> Option<&Workspace> {
+        self.registry.active.as_deref().and_then(|id| self.get(id))
+    }
+
+    pub fn paths(&self, workspace: &Workspace) -> WorkspacePaths {
+        WorkspacePaths::new(self.root.join(&workspace.path))
+    }
+
+    fn relative_to_root(&self, path: &Path) -> PathBuf {
+        let root = self.root.canonicalize().unwrap_or_else(|_| self.root.clone());
+        path.strip_prefix(&root)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    pub async fn create(&mut self, name: &str) -> Result<Workspace, Box<dyn std::error::Error>> {
+        let workspace = self.new_workspace(name)?;
+
+        for dir in self.paths(&workspace).directories() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        self.registry.workspaces.push(workspace.clone());
+        self.save().await?;
+
+        log::info!("Created workspace '{}' at {:?}", workspace.name, workspace.path);
+        Ok(workspace)
+    }
+
+    pub async fn rename(&mut self, id: &str, name: &str) -> Result<Workspace, Box<dyn std::error::Error>> {
+        let name = self.validate_name(name, Some(id))?;
+
+        let workspace = self.registry.workspaces.iter_mut()
+            .find(|w| w.id == id)
+            .ok_or_else(|| format!("Workspace not found: {}", id))?;
+        workspace.name = name;
+        let workspace = workspace.clone();
+
+        self.save().await?;
+        Ok(workspace)
+    }
+
+    // Copies everything except the database, which the caller copies so an
+    // open journal can be snapshotted consistently (see DatabaseState::snapshot_to)
+    pub async fn duplicate(&mut self, id: &str, name: &str) -> Result<Workspace, Box<dyn std::error::Error>> {
+        let source = self.get(id)
+            .cloned()
+            .ok_or_else(|| format!("Workspace not found: {}", id))?;
+        let workspace = self.new_workspace(name)?;
+
+        let source_paths = self.paths(&source);
+        let target_paths = self.paths(&workspace);
+
+        for dir in target_paths.directories() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        for (from, to) in [
+            (source_paths.data_dir().join("images"), target_paths.data_dir().join("images")),
+            (source_paths.data_dir().join("thumbnails"), target_paths.data_dir().join("thumbnails")),
+            (source_paths.plugins_data_dir(), target_paths.plugins_data_dir()),
+            (source_paths.config_dir(), target_paths.config_dir()),
+        ] {
+            copy_tree(&from, &to).await?;
+        }
+
+        self.registry.workspaces.push(workspace.clone());
+        self.save().await?;
+
+        log::info!("Duplicated workspace '{}' as '{}'", source.name, workspace.name);
+        Ok(workspace)
+    }
+
+    // Copy legacy journals registered outside the data root into it, so the
+    // root alone holds the full data set. Only the journal's own entries are
+    // copied; the folder around it (often the old working directory) is not.
+    // Returns how many were moved in.
+    pub async fn adopt_external(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut adopted = 0;
+
+        for index in 0..self.registry.workspaces.len() {
+            let workspace = self.registry.workspaces[index].clone();
+            if !workspace.path.is_absolute() {
+                continue;
+            }
+            if !workspace.path.join(LEGACY_DATABASE).exists() {
+                log::warn!("Workspace '{}' at {:?} is not a journal folder, leaving it in place", workspace.name, workspace.path);
+                continue;
+            }
+
+            let path = PathBuf::from(WORKSPACES_DIR).join(&workspace.id);
+            let source = WorkspacePaths::new(&workspace.path);
+            let target = WorkspacePaths::new(self.root.join(&path));
+            for (from, to) in source.entries().into_iter().zip(target.entries()) {
+                copy_tree(&from, &to).await?;
+                verify_copy(&from, &to).await?;
+            }
+            self.registry.workspaces[index].path = path;
+            adopted += 1;
+
+            log::info!("Copied workspace '{}' from {:?} into the data root", workspace.name, workspace.path);
+        }
+
+        self.save().await?;
+        Ok(adopted)
+    }
+
+    pub async fn set_active(&mut self, id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(id) = id {
+            let workspace = self.registry.workspaces.iter_mut()
+                .find(|w| w.id == id)
+                .ok_or_else(|| format!("Workspace not found: {}", id))?;
+            workspace.last_opened_at = Some(Utc::now());
+        }
+
+        self.registry.active = id.map(str::to_string);
+        self.save().await
+    }
+
+    fn new_workspace(&self, name: &str) -> Result<Workspace, String> {
+        let name = self.validate_name(name, None)?;
+        let id = unique_id(&slugify(&name), |candidate| self.get(candidate).is_some());
+
+        Ok(Workspace {
+            path: PathBuf::from(WORKSPACES_DIR).join(&id),
+            id,
+            name,
+            created_at: Utc::now(),
+            last_opened_at: None,
+        })
+    }
+
+    fn validate_name(&self, name: &str, exclude_id: Option<&str>) -> Result<String, String> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err("Workspace name cannot be empty".to_string());
+        }
+
+        let taken = self.registry.workspaces.iter()
+            .filter(|w| Some(w.id.as_str()) != exclude_id)
+            .any(|w| w.name.eq_ignore_ascii_case(name));
+        if taken {
+            return Err(format!("A workspace named '{}' already exists", name));
+        }
+
+        Ok(name.to_string())
+    }
+}
+
+// Directory-safe identifier derived from the display name
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "workspace".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+fn unique_id(base: &str, exists: impl Fn(&str) -> bool) -> String {
+    if !exists(base) {
+        return base.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !exists(candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_produces_directory_safe_ids() {
+        assert_eq!(slugify("Prop Firm #2"), "prop-firm-2");
+        assert_eq!(slugify("  Student -- Journal "), "student-journal");
+        assert_eq!(slugify("日本"), "workspace");
+    }
+
+    #[test]
+    fn unique_id_appends_counter_on_collision() {
+        let taken = ["prop", "prop-2"];
+        assert_eq!(unique_id("prop", |id| taken.contains(&id)), "prop-3");
+        assert_eq!(unique_id("student", |id| taken.contains(&id)), "student");
+    }
+
+    #[test]
+    fn paths_are_scoped_to_the_workspace_root() {
+        let paths = WorkspacePaths::new("workspaces/prop");
+        assert_eq!(paths.database_file(), PathBuf::from("workspaces/prop/data/trading_journal.db"));
+        assert_eq!(paths.config_dir(), PathBuf::from("workspaces/prop/config"));
+        assert!(paths.directories().iter().all(|dir| dir.starts_with("workspaces/prop")));
+    }
+
+    #[tokio::test]
+    async fn adopt_external_copies_only_legacy_journals() {
+        use sqlx::{sqlite::{SqliteConnectOptions, SqliteConnection}, Connection};
+
+        let temp = std::env::temp_dir().join(format!("journal-adopt-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&temp);
+        let (root, legacy, home) = (temp.join("root"), temp.join("legacy"), temp.join("home"));
+
+        std::fs::create_dir_all(legacy.join("data")).unwrap();
+        let options = SqliteConnectOptions::new().filename(legacy.join(LEGACY_DATABASE)).create_if_missing(true);
+        let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("CREATE TABLE trades (id INTEGER PRIMARY KEY)").execute(&mut connection).await.unwrap();
+        connection.close().await.unwrap();
+        std::fs::create_dir_all(legacy.join("config")).unwrap();
+        std::fs::write(legacy.join("config/time_settings.json"), "{}").unwrap();
+        std::fs::create_dir_all(legacy.join("Documents")).unwrap();
+        std::fs::write(legacy.join("Documents/secret.txt"), "private").unwrap();
+        std::fs::create_dir_all(home.join("Documents")).unwrap();
+
+        let mut manager = WorkspaceManager::new(&root);
+        manager.load().await.unwrap();
+        manager.registry.workspaces[0].path = legacy.clone();
+        let mut other = manager.registry.workspaces[0].clone();
+        other.id = "home".to_string();
+        other.path = home.clone();
+        manager.registry.workspaces.push(other);
+
+        assert_eq!(manager.adopt_external().await.unwrap(), 1);
+
+        let adopted = root.join(WORKSPACES_DIR).join(DEFAULT_WORKSPACE_ID);
+        assert_eq!(manager.registry.workspaces[0].path, PathBuf::from(WORKSPACES_DIR).join(DEFAULT_WORKSPACE_ID));
+        assert!(adopted.join(LEGACY_DATABASE).exists());
+        assert!(adopted.join("config/time_settings.json").exists());
+        assert!(!adopted.join("Documents").exists());
+        assert_eq!(manager.registry.workspaces[1].path, home);
+        assert!(!root.join(WORKSPACES_DIR).join("home").exists());
+
+        let _ = std::fs::remove_dir_all(&temp);
+    }
+}
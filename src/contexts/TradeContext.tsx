@@ -9,8 +9,8 @@ export interface Trade {
   trade_type: 'Buy' | 'Sell';
   volume: number;
   entry_price: number;
-  sl: number;
-  tp: number;
+  sl: number | null;
+  tp: number | null;
   entry_time: string;
   exit_time?: string;
   exit_price?: number;
@@ -1,9 +1,20 @@
-import React, { useEffect, useState } from 'react';
+import React, { useEffect, useRef, useState } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import { useNavigate } from 'react-router-dom';
 import DynamicTable from '../components/DynamicTable/DynamicTable';
 import { Trade } from '../types/trade';
 
+const STREAM_BATCH_SIZE = 500;
+
+interface TradeBatch {
+  stream_id: string;
+  batch: number;
+  trades: Trade[];
+  total_count: number;
+  done: boolean;
+}
+
 const TradeListPage: React.FC = () => {
   const [trades, setTrades] = useState<Trade[]>([]);
   const [loading, setLoading] = useState<boolean>(true);
@@ -13,24 +24,53 @@ const TradeListPage: React.FC = () => {
   const [filterResult, setFilterResult] = useState<string>('all');
   const [useDynamicTable, setUseDynamicTable] = useState(true);
   const [selectedTrades, setSelectedTrades] = useState<number[]>([]);
+  const [totalCount, setTotalCount] = useState<number>(0);
+  const streamRef = useRef<string | null>(null);
   const navigate = useNavigate();
 
+  // Trades arrive in batches so the table can render before the whole journal is loaded
   const fetchTrades = async () => {
+    const streamId = crypto.randomUUID();
+    streamRef.current = streamId;
+
     try {
       setLoading(true);
-      const fetchedTrades: Trade[] = await invoke('get_all_trades');
-      setTrades(fetchedTrades);
+      await invoke('stream_trades', { batchSize: STREAM_BATCH_SIZE, streamId });
       setError(null);
     } catch (err) {
       setError('Failed to fetch trades. Please try again.');
-      console.error(err);
-    } finally {
       setLoading(false);
+      console.error(err);
     }
   };
 
   useEffect(() => {
-    fetchTrades();
+    const unlistenBatch = listen<TradeBatch>('trades_batch', ({ payload }) => {
+      if (payload.stream_id !== streamRef.current) {
+        return;
+      }
+
+      setTrades(prev => (payload.batch === 0 ? payload.trades : [...prev, ...payload.trades]));
+      setTotalCount(payload.total_count);
+      if (payload.batch === 0 || payload.done) {
+        setLoading(false);
+      }
+    });
+
+    const unlistenError = listen<{ stream_id: string; error: string }>('trades_stream_error', ({ payload }) => {
+      if (payload.stream_id === streamRef.current) {
+        setError('Failed to fetch trades. Please try again.');
+        console.error(payload.error);
+      }
+    });
+
+    // Only start the stream once both listeners are registered
+    Promise.all([unlistenBatch, unlistenError]).then(() => fetchTrades());
+
+    return () => {
+      unlistenBatch.then(unlisten => unlisten());
+      unlistenError.then(unlisten => unlisten());
+    };
   }, []);
 
   const handleDelete = async (tradeId: number) => {
@@ -250,6 +290,9 @@ const TradeListPage: React.FC = () => {
         <div className="flex items-center space-x-4">
           <div className="text-sm text-gray-500 flex items-center space-x-2">
             <span>Total: {stats.total}</span>
+            {trades.length < totalCount && (
+              <span>Loaded {trades.length} of {totalCount}</span>
+            )}
             <span className="text-green-600">Wins: {stats.wins}</span>
             <span className="text-red-600">Losses: {stats.losses}</span>
             <span className="text-yellow-600">Open: {stats.open}</span>
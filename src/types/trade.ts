@@ -3,8 +3,8 @@ export interface NewTrade {
     trade_type: string;
     volume: number;
     entry_price: number;
-    sl: number;
-    tp: number;
+    sl: number | null;
+    tp: number | null;
     entry_time: string;
     notes?: string;
     ict_pattern?: string;